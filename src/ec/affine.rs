@@ -0,0 +1,415 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::CurveParams;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, Term};
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// A point, in affine coordinates, of a short Weierstrass curve over an emulated prime field.
+///
+/// The coordinates are residues: they have as many limbs as the modulus, each range checked, but
+/// are not necessarily smaller than the modulus. The point at infinity cannot be represented.
+#[derive(Clone)]
+pub struct EmulatedAffinePoint<Scalar: PrimeField> {
+    pub x: BigNat<Scalar>,
+    pub y: BigNat<Scalar>,
+    /// The reduced coordinates of the point (filled at witness-time)
+    pub value: Option<(BigInt, BigInt)>,
+    /// Parameters
+    pub params: CurveParams,
+}
+
+impl<Scalar: PrimeField> EmulatedAffinePoint<Scalar> {
+    pub(crate) fn from_coords(x: BigNat<Scalar>, y: BigNat<Scalar>, params: &CurveParams) -> Self {
+        let value = x.value.as_ref().and_then(|x| {
+            y.value
+                .as_ref()
+                .map(|y| (x.mod_floor(&params.p), y.mod_floor(&params.p)))
+        });
+        Self {
+            x,
+            y,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// Allocates a point with range checked coordinates. The point is not checked to be on the
+    /// curve, see `assert_on_curve`.
+    /// The value is provided by a closure returning the coordinates.
+    pub fn alloc_from_coords<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<(BigInt, BigInt), SynthesisError>,
+    {
+        let coords = f().ok();
+        let x = BigNat::alloc_from_nat(
+            cs.namespace(|| "x"),
+            || Ok(coords.grab()?.0.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        x.assert_well_formed(cs.namespace(|| "x rangecheck"))?;
+        let y = BigNat::alloc_from_nat(
+            cs.namespace(|| "y"),
+            || Ok(coords.grab()?.1.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        y.assert_well_formed(cs.namespace(|| "y rangecheck"))?;
+        Ok(Self::from_coords(x, y, params))
+    }
+
+    /// Creates a constant point.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        x: &BigInt,
+        y: &BigInt,
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self::from_coords(
+            params.constant::<Scalar, CS>(x)?,
+            params.constant::<Scalar, CS>(y)?,
+            params,
+        ))
+    }
+
+    /// The generator of the curve, as a constant.
+    pub fn generator<CS: ConstraintSystem<Scalar>>(
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.generator.0, &params.generator.1, params)
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!("Curves do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Constrain `self` to satisfy the curve equation `y^2 = x^3 + a*x + b`.
+    pub fn assert_on_curve<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let a = params.constant::<Scalar, CS>(&params.a)?;
+        let b = params.constant::<Scalar, CS>(&params.b)?;
+        let xx = BigNat::alloc_residue(
+            cs.namespace(|| "x^2"),
+            || Ok(self.x.value.grab()? * self.x.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x^2 check"),
+            &[Term::Product(&self.x, &self.x)],
+            &[Term::Single(&xx)],
+            &p,
+        )?;
+        let three_x = self.x.scale::<CS>(Scalar::from(3));
+        let mut lhs = vec![Term::Product(&self.y, &self.y)];
+        let mut rhs = vec![Term::Product(&xx, &self.x), Term::Single(&b)];
+        if params.a_is_minus_three() {
+            // y^2 + 3x = x^3 + b
+            lhs.push(Term::Single(&three_x));
+        } else if params.a != BigInt::from(0) {
+            rhs.push(Term::Product(&a, &self.x));
+        }
+        BigNat::assert_sums_congruent(cs.namespace(|| "curve equation"), &lhs, &rhs, &p)
+    }
+
+    /// Given the slope `lambda` of the line through `self` and `other` (or of the tangent at
+    /// `self` when doubling), computes the third point of intersection, negated.
+    fn chord<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+        lambda: &BigNat<Scalar>,
+        p: &BigNat<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        // x3 = lambda^2 - x1 - x2
+        let x = BigNat::alloc_residue(
+            cs.namespace(|| "x"),
+            || {
+                let l = lambda.value.grab()?;
+                Ok(l * l - self.x.value.grab()? - other.x.value.grab()?)
+            },
+            p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x check"),
+            &[Term::Product(lambda, lambda)],
+            &[
+                Term::Single(&x),
+                Term::Single(&self.x),
+                Term::Single(&other.x),
+            ],
+            p,
+        )?;
+        // y3 = lambda * (x1 - x3) - y1
+        let y = BigNat::alloc_residue(
+            cs.namespace(|| "y"),
+            || {
+                let l = lambda.value.grab()?;
+                Ok(l * (self.x.value.grab()? - x.value.grab()?) - self.y.value.grab()?)
+            },
+            p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "y check"),
+            &[Term::Product(lambda, &self.x)],
+            &[
+                Term::Product(lambda, &x),
+                Term::Single(&y),
+                Term::Single(&self.y),
+            ],
+            p,
+        )?;
+        Ok(Self::from_coords(x, y, &self.params))
+    }
+
+    /// Computes `self + other` with the incomplete addition formula. The x-coordinates of `self`
+    /// and `other` are constrained to be distinct, so neither doubling nor adding opposite points
+    /// is possible.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let p = self.params.modulus::<Scalar, CS>()?;
+        // x2 - x1 is invertible
+        let dx_inv = BigNat::alloc_residue(
+            cs.namespace(|| "dx inverse"),
+            || {
+                invert(
+                    &(other.x.value.grab()? - self.x.value.grab()?),
+                    &self.params.p,
+                )
+            },
+            &p,
+        )?;
+        let one = BigNat::one::<CS>(self.params.limb_width);
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "distinct x"),
+            &[Term::Product(&other.x, &dx_inv)],
+            &[Term::Product(&self.x, &dx_inv), Term::Single(&one)],
+            &p,
+        )?;
+        // lambda * (x2 - x1) = y2 - y1
+        let lambda = BigNat::alloc_residue(
+            cs.namespace(|| "lambda"),
+            || {
+                let dy = other.y.value.grab()? - self.y.value.grab()?;
+                Ok(dy * dx_inv.value.grab()?)
+            },
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "lambda check"),
+            &[Term::Product(&lambda, &other.x), Term::Single(&self.y)],
+            &[Term::Product(&lambda, &self.x), Term::Single(&other.y)],
+            &p,
+        )?;
+        self.chord(cs.namespace(|| "chord"), other, &lambda, &p)
+    }
+
+    /// Computes `2 * self`. The point must be on a curve without points of order two (e.g. one
+    /// of odd order), so that its y-coordinate is non-zero.
+    pub fn double<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let a = params.constant::<Scalar, CS>(&params.a)?;
+        // lambda * 2y = 3x^2 + a
+        let lambda = BigNat::alloc_residue(
+            cs.namespace(|| "lambda"),
+            || {
+                let x = self.x.value.grab()?;
+                let y = self.y.value.grab()?;
+                Ok((3 * x * x + &params.a) * invert(&(2 * y), &params.p)?)
+            },
+            &p,
+        )?;
+        let two_y = self.y.scale::<CS>(Scalar::from(2));
+        let three_x = self.x.scale::<CS>(Scalar::from(3));
+        let mut rhs = vec![Term::Product(&three_x, &self.x)];
+        if params.a != BigInt::from(0) {
+            rhs.push(Term::Single(&a));
+        }
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "lambda check"),
+            &[Term::Product(&lambda, &two_y)],
+            &rhs,
+            &p,
+        )?;
+        self.chord(cs.namespace(|| "chord"), self, &lambda, &p)
+    }
+
+    /// Computes `-self`.
+    pub fn negate<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let y = self.y.neg_mod(cs.namespace(|| "y"), &p)?;
+        Ok(Self::from_coords(self.x.clone(), y, &self.params))
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedAffinePoint<Scalar> {
+    type Scalar = Scalar;
+    type Value = (BigInt, BigInt);
+    type Params = CurveParams;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        Self::alloc_from_coords(cs, || Ok((*value.grab()?).clone()), params)
+    }
+    fn value(&self) -> Option<&(BigInt, BigInt)> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        self.x.limb_values.as_ref().and_then(|xs| {
+            self.y
+                .limb_values
+                .as_ref()
+                .map(|ys| xs.iter().chain(ys.iter()).cloned().collect())
+        })
+    }
+    fn params(&self) -> &CurveParams {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        let mut wires = self.x.limbs.clone();
+        wires.extend(self.y.limbs.iter().cloned());
+        wires
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedAffinePoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some((x, y)) => write!(f, "EmulatedAffinePoint({}, {})", x, y),
+            None => write!(f, "EmulatedAffinePoint(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedAffinePoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedAffinePoint")
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::NativePoint;
+    use crate::util::bit::Bit;
+    use crate::util::test_helpers::*;
+
+    pub struct AffineOpsInputs {
+        pub p: (BigInt, BigInt),
+        pub q: (BigInt, BigInt),
+        pub select: bool,
+    }
+
+    pub struct AffineOps {
+        inputs: Option<AffineOpsInputs>,
+        params: CurveParams,
+    }
+
+    fn assert_point<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        point: &EmulatedAffinePoint<Scalar>,
+        expected: NativePoint,
+    ) -> Result<(), SynthesisError> {
+        let (x, y) = expected.grab()?;
+        let expected = EmulatedAffinePoint::constant::<CS>(x, y, &point.params)?;
+        point.x.equal(cs.namespace(|| "x"), &expected.x)?;
+        point.y.equal(cs.namespace(|| "y"), &expected.y)
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for AffineOps {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let p = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "p"),
+                || Ok(inputs.p.clone()),
+                params,
+            )?;
+            let q = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "q"),
+                || Ok(inputs.q.clone()),
+                params,
+            )?;
+            p.assert_on_curve(cs.namespace(|| "p on curve"))?;
+            q.assert_on_curve(cs.namespace(|| "q on curve"))?;
+            let (np, nq) = (Some(inputs.p.clone()), Some(inputs.q.clone()));
+
+            let sum = p.add(cs.namespace(|| "p + q"), &q)?;
+            assert_point(cs.namespace(|| "sum"), &sum, params.add(&np, &nq))?;
+            sum.assert_on_curve(cs.namespace(|| "sum on curve"))?;
+
+            let double = p.double(cs.namespace(|| "2p"))?;
+            assert_point(cs.namespace(|| "double"), &double, params.double(&np))?;
+
+            let neg = q.negate(cs.namespace(|| "-q"))?;
+            assert_point(cs.namespace(|| "neg"), &neg, params.negate(&nq))?;
+
+            let select = Bit::alloc(cs.namespace(|| "select"), Some(inputs.select))?;
+            let chosen = Gadget::mux(cs.namespace(|| "mux"), &select, &p, &q)?;
+            let expected = if inputs.select { nq } else { np };
+            assert_point(cs.namespace(|| "chosen"), &chosen, expected)?;
+            Ok(())
+        }
+    }
+
+    fn affine_ops(params: CurveParams, p: u64, q: u64, select: bool) -> AffineOps {
+        let g = Some(params.generator.clone());
+        let p = params.mul(&BigInt::from(p), &g).unwrap();
+        let q = params.mul(&BigInt::from(q), &g).unwrap();
+        AffineOps {
+            inputs: Some(AffineOpsInputs { p, q, select }),
+            params,
+        }
+    }
+
+    fn off_curve(params: CurveParams) -> AffineOps {
+        let mut ops = affine_ops(params, 1, 2, false);
+        ops.inputs.as_mut().unwrap().p.1 += 1;
+        ops
+    }
+
+    circuit_tests! {
+        affine_secp256k1_ops: (affine_ops(CurveParams::secp256k1(), 1, 2, false), true),
+        affine_secp256k1_ops_select: (affine_ops(CurveParams::secp256k1(), 5, 1234567, true), true),
+        affine_p256_ops: (affine_ops(CurveParams::p256(), 7, 3, true), true),
+        affine_secp256k1_off_curve: (off_curve(CurveParams::secp256k1()), false),
+    }
+}
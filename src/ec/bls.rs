@@ -0,0 +1,472 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+use super::affine::EmulatedAffinePoint;
+use super::g2::{EmulatedG2Point, NativeG2Point};
+use super::hash_to_curve::{self, HashToCurveParams};
+use super::hash_to_g2::{self, HashToG2Params};
+use super::pairing::{assert_in_g1, assert_pairing_product_is_one, PairingParams};
+use super::projective::EmulatedProjectivePoint;
+use super::NativePoint;
+use crate::util::bit::Bitvector;
+use crate::util::gadget::Gadget;
+
+/// The domain separation tag of the minimal-pubkey-size ciphersuite with proofs of possession
+/// (draft-irtf-cfrg-bls-signature-05, section 4.2.3), with public keys in G1 and signatures in
+/// G2, as used by Ethereum.
+pub const MIN_PK_POP_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// The domain separation tag of the minimal-signature-size ciphersuite with proofs of
+/// possession, with public keys in G2 and signatures in G1.
+pub const MIN_SIG_POP_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Parameters of BLS signatures (Boneh, Lynn and Shacham) over a pairing-friendly curve: the
+/// pairing, and the hashes of messages to G1 and G2.
+///
+/// A signature of `msg` under the secret key `sk` is `sk * H(msg)`, checked against the public
+/// key `sk * g` with the pairing: in the minimal-pubkey-size variant ("min-pk"), public keys are
+/// in G1 and signatures in G2, and in the minimal-signature-size variant ("min-sig") the other
+/// way around.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlsParams {
+    pub pairing: PairingParams,
+    pub hash_g1: HashToCurveParams,
+    pub hash_g2: HashToG2Params,
+}
+
+impl BlsParams {
+    /// BLS signatures over BLS12-381.
+    pub fn bls12_381() -> Self {
+        BlsParams {
+            pairing: PairingParams::bls12_381(),
+            hash_g1: HashToCurveParams::bls12_381(),
+            hash_g2: HashToG2Params::bls12_381(),
+        }
+    }
+
+    /// Whether the product of the pairings of `(P, Q)`, for the points of G1 and G2 of `pairs`,
+    /// is one. Fails if any point is the identity.
+    fn pairing_product_is_one(&self, pairs: &[(NativePoint, NativeG2Point)]) -> bool {
+        let pairs: Option<Vec<_>> = pairs
+            .iter()
+            .map(|(p, q)| Some((p.clone()?, q.clone()?)))
+            .collect();
+        match pairs {
+            Some(pairs) => self.pairing.multi_pairing(&pairs) == self.pairing.fp12().from_int(1),
+            None => false,
+        }
+    }
+
+    /// The min-pk public key `sk * g1` of the secret key `sk`.
+    pub fn min_pk_public_key(&self, sk: &BigInt) -> NativePoint {
+        let g1 = &self.pairing.g1;
+        g1.mul(sk, &Some(g1.generator.clone()))
+    }
+
+    /// The min-pk signature `sk * H(msg)` of `msg`, in G2.
+    pub fn min_pk_sign(&self, sk: &BigInt, msg: &[u8], dst: &[u8]) -> NativeG2Point {
+        let g2 = &self.pairing.g2;
+        g2.mul(sk, &self.hash_g2.hash_to_curve(msg, dst))
+    }
+
+    /// Whether `signature` is a valid min-pk signature of `msg` under `public_key` (`Verify`):
+    /// the public key is a point of G1 and the signature a point of G2, neither the identity,
+    /// and `e(public_key, H(msg)) = e(g1, signature)`.
+    pub fn min_pk_verify(
+        &self,
+        public_key: &NativePoint,
+        msg: &[u8],
+        signature: &NativeG2Point,
+        dst: &[u8],
+    ) -> bool {
+        let (g1, g2) = (&self.pairing.g1, &self.pairing.g2);
+        if !self.pairing.is_in_g1(public_key)
+            || !g2.is_on_curve(signature)
+            || !g2.is_in_subgroup(signature)
+        {
+            return false;
+        }
+        let neg_g1 = g1.negate(&Some(g1.generator.clone()));
+        self.pairing_product_is_one(&[
+            (public_key.clone(), self.hash_g2.hash_to_curve(msg, dst)),
+            (neg_g1, signature.clone()),
+        ])
+    }
+
+    /// Whether `signature` is a valid min-pk signature of `msg` under all the public keys, which
+    /// must have been validated beforehand (`FastAggregateVerify`): a valid signature under
+    /// their sum.
+    pub fn min_pk_fast_aggregate_verify(
+        &self,
+        public_keys: &[NativePoint],
+        msg: &[u8],
+        signature: &NativeG2Point,
+        dst: &[u8],
+    ) -> bool {
+        let g1 = &self.pairing.g1;
+        let aggregate = public_keys.iter().fold(None, |acc, pk| g1.add(&acc, pk));
+        !public_keys.is_empty() && self.min_pk_verify(&aggregate, msg, signature, dst)
+    }
+
+    /// The min-sig public key `sk * g2` of the secret key `sk`.
+    pub fn min_sig_public_key(&self, sk: &BigInt) -> NativeG2Point {
+        let g2 = &self.pairing.g2;
+        g2.mul(sk, &Some(g2.generator.clone()))
+    }
+
+    /// The min-sig signature `sk * H(msg)` of `msg`, in G1.
+    pub fn min_sig_sign(&self, sk: &BigInt, msg: &[u8], dst: &[u8]) -> NativePoint {
+        let g1 = &self.pairing.g1;
+        g1.mul(sk, &self.hash_g1.hash_to_curve(msg, dst))
+    }
+
+    /// Whether `signature` is a valid min-sig signature of `msg` under `public_key` (`Verify`):
+    /// the public key is a point of G2 and the signature a point of G1, neither the identity,
+    /// and `e(H(msg), public_key) = e(signature, g2)`.
+    pub fn min_sig_verify(
+        &self,
+        public_key: &NativeG2Point,
+        msg: &[u8],
+        signature: &NativePoint,
+        dst: &[u8],
+    ) -> bool {
+        let g2 = &self.pairing.g2;
+        if !g2.is_on_curve(public_key)
+            || !g2.is_in_subgroup(public_key)
+            || !self.pairing.is_in_g1(signature)
+        {
+            return false;
+        }
+        let neg_g2 = g2.negate(&Some(g2.generator.clone()));
+        self.pairing_product_is_one(&[
+            (self.hash_g1.hash_to_curve(msg, dst), public_key.clone()),
+            (signature.clone(), neg_g2),
+        ])
+    }
+}
+
+/// Constrain the point `signature` of the twist to be a valid min-pk signature of the message
+/// `msg` under `public_key`, a point of G1 which is not the identity (see `BlsParams`):
+/// `signature` is constrained to be in G2, and `e(public_key, H(msg)) e(-g1, signature) = 1`.
+fn core_verify_min_pk<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &EmulatedAffinePoint<Scalar>,
+    msg: &[Boolean],
+    signature: &EmulatedG2Point<Scalar>,
+    dst: &[u8],
+    params: &BlsParams,
+) -> Result<(), SynthesisError> {
+    let g1 = &params.pairing.g1;
+    signature.assert_in_subgroup(cs.namespace(|| "signature in g2"))?;
+    let h = hash_to_g2::hash_to_curve(cs.namespace(|| "hash"), msg, dst, &params.hash_g2)?;
+    let neg_g1 = g1.negate(&Some(g1.generator.clone())).unwrap();
+    let neg_g1 = EmulatedAffinePoint::constant::<CS>(&neg_g1.0, &neg_g1.1, g1)?;
+    assert_pairing_product_is_one(
+        cs.namespace(|| "pairing check"),
+        &[(public_key, &h), (&neg_g1, signature)],
+        &params.pairing,
+    )
+}
+
+/// Constrain `signature` to be a valid min-pk signature of the message `msg`, given as the bits
+/// of its bytes, each byte's most significant bit first, under `public_key`, with the domain
+/// separation tag `dst` (see `BlsParams::min_pk_verify`).
+///
+/// The public key is constrained to be on the curve and in G1, and the signature, which must be
+/// on the twist (as allocated by `EmulatedG2Point::alloc_affine`), to be in G2. Neither can be
+/// the identity: the public key is affine, and the pairing check proves that the signature is
+/// not.
+pub fn verify_min_pk<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &EmulatedAffinePoint<Scalar>,
+    msg: &[Boolean],
+    signature: &EmulatedG2Point<Scalar>,
+    dst: &[u8],
+    params: &BlsParams,
+) -> Result<(), SynthesisError> {
+    public_key.assert_on_curve(cs.namespace(|| "public key on curve"))?;
+    assert_in_g1(
+        cs.namespace(|| "public key in g1"),
+        public_key,
+        &params.pairing,
+    )?;
+    core_verify_min_pk(
+        cs.namespace(|| "verify"),
+        public_key,
+        msg,
+        signature,
+        dst,
+        params,
+    )
+}
+
+/// Computes the sum of the public keys selected by the bits of `participants`, e.g. the
+/// participants of an Ethereum sync committee, with complete additions. The keys must be on the
+/// curve.
+pub fn aggregate_public_keys<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_keys: &[EmulatedAffinePoint<Scalar>],
+    participants: &Bitvector<Scalar>,
+    params: &BlsParams,
+) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+    if participants.bits.len() != public_keys.len() {
+        eprintln!(
+            "{} participation bits for {} public keys",
+            participants.bits.len(),
+            public_keys.len()
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let mut acc = EmulatedProjectivePoint::identity::<CS>(&params.pairing.g1)?;
+    for (i, public_key) in public_keys.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("key {}", i));
+        let sum = acc.add(
+            cs.namespace(|| "add"),
+            &EmulatedProjectivePoint::from_affine::<CS>(public_key)?,
+        )?;
+        let bit = participants.get(i).unwrap();
+        acc = Gadget::mux(cs.namespace(|| "select"), &bit, &acc, &sum)?;
+    }
+    Ok(acc)
+}
+
+/// Constrain `signature` to be a valid min-pk signature of the message `msg` under the public
+/// keys selected by `participants` (`FastAggregateVerify`): a signature under their sum, which
+/// is constrained to be in G1 and not the identity (so at least one key must be selected).
+///
+/// The keys are constrained to be on the curve, but not to be in G1: as in `FastAggregateVerify`,
+/// they must have been validated beforehand, with proofs of possession against rogue key attacks
+/// (in Ethereum, when the validators made their deposits).
+#[allow(clippy::too_many_arguments)]
+pub fn fast_aggregate_verify_min_pk<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_keys: &[EmulatedAffinePoint<Scalar>],
+    participants: &Bitvector<Scalar>,
+    msg: &[Boolean],
+    signature: &EmulatedG2Point<Scalar>,
+    dst: &[u8],
+    params: &BlsParams,
+) -> Result<(), SynthesisError> {
+    for (i, public_key) in public_keys.iter().enumerate() {
+        public_key.assert_on_curve(cs.namespace(|| format!("key {} on curve", i)))?;
+    }
+    let aggregate = aggregate_public_keys(
+        cs.namespace(|| "aggregate"),
+        public_keys,
+        participants,
+        params,
+    )?;
+    let aggregate = aggregate.to_affine(cs.namespace(|| "aggregate affine"))?;
+    assert_in_g1(
+        cs.namespace(|| "aggregate in g1"),
+        &aggregate,
+        &params.pairing,
+    )?;
+    core_verify_min_pk(
+        cs.namespace(|| "verify"),
+        &aggregate,
+        msg,
+        signature,
+        dst,
+        params,
+    )
+}
+
+/// Constrain `signature` to be a valid min-sig signature of the message `msg`, given as the bits
+/// of its bytes, each byte's most significant bit first, under `public_key`, with the domain
+/// separation tag `dst` (see `BlsParams::min_sig_verify`).
+///
+/// The public key, which must be on the twist (as allocated by `EmulatedG2Point::alloc_affine`),
+/// is constrained to be in G2, and the signature to be on the curve and in G1. Neither can be
+/// the identity: the signature is affine, and the pairing check proves that the public key is
+/// not.
+pub fn verify_min_sig<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &EmulatedG2Point<Scalar>,
+    msg: &[Boolean],
+    signature: &EmulatedAffinePoint<Scalar>,
+    dst: &[u8],
+    params: &BlsParams,
+) -> Result<(), SynthesisError> {
+    let g2 = &params.pairing.g2;
+    public_key.assert_in_subgroup(cs.namespace(|| "public key in g2"))?;
+    signature.assert_on_curve(cs.namespace(|| "signature on curve"))?;
+    assert_in_g1(
+        cs.namespace(|| "signature in g1"),
+        signature,
+        &params.pairing,
+    )?;
+    let h = hash_to_curve::hash_to_curve(cs.namespace(|| "hash"), msg, dst, &params.hash_g1)?;
+    let h = h.to_affine(cs.namespace(|| "hash affine"))?;
+    let neg_g2 = g2.to_projective(&g2.negate(&Some(g2.generator.clone())));
+    let neg_g2 = EmulatedG2Point::constant::<CS>(&neg_g2, g2)?;
+    assert_pairing_product_is_one(
+        cs.namespace(|| "pairing check"),
+        &[(&h, public_key), (signature, &neg_g2)],
+        &params.pairing,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::bit::Bit;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+
+    const SECRET_KEYS: [u64; 3] = [0x1234_5678_9abc_def0, 0x0fed_cba9_8765_4321, 42];
+
+    #[test]
+    fn bls_native() {
+        let params = BlsParams::bls12_381();
+        let sk = BigInt::from(SECRET_KEYS[0]);
+        let pk = params.min_pk_public_key(&sk);
+        let sig = params.min_pk_sign(&sk, b"hello", MIN_PK_POP_DST);
+        assert!(params.min_pk_verify(&pk, b"hello", &sig, MIN_PK_POP_DST));
+        assert!(!params.min_pk_verify(&pk, b"hellp", &sig, MIN_PK_POP_DST));
+        assert!(!params.min_pk_verify(&pk, b"hello", &sig, MIN_SIG_POP_DST));
+
+        let pk = params.min_sig_public_key(&sk);
+        let sig = params.min_sig_sign(&sk, b"hello", MIN_SIG_POP_DST);
+        assert!(params.min_sig_verify(&pk, b"hello", &sig, MIN_SIG_POP_DST));
+        let other = params.min_sig_public_key(&BigInt::from(SECRET_KEYS[1]));
+        assert!(!params.min_sig_verify(&other, b"hello", &sig, MIN_SIG_POP_DST));
+    }
+
+    #[test]
+    fn bls_native_fast_aggregate() {
+        let params = BlsParams::bls12_381();
+        let g2 = &params.pairing.g2;
+        let keys: Vec<_> = SECRET_KEYS.iter().map(|sk| BigInt::from(*sk)).collect();
+        let pks: Vec<_> = keys.iter().map(|sk| params.min_pk_public_key(sk)).collect();
+        let sig = keys.iter().fold(None, |acc, sk| {
+            g2.add(&acc, &params.min_pk_sign(sk, b"block root", MIN_PK_POP_DST))
+        });
+        assert!(params.min_pk_fast_aggregate_verify(&pks, b"block root", &sig, MIN_PK_POP_DST));
+        assert!(!params.min_pk_fast_aggregate_verify(
+            &pks[..2],
+            b"block root",
+            &sig,
+            MIN_PK_POP_DST
+        ));
+        assert!(!params.min_pk_fast_aggregate_verify(&[], b"block root", &None, MIN_PK_POP_DST));
+    }
+
+    fn alloc_g1<CS: ConstraintSystem<Fr>>(
+        mut cs: CS,
+        point: &NativePoint,
+        params: &BlsParams,
+    ) -> EmulatedAffinePoint<Fr> {
+        let point = point.clone().unwrap();
+        EmulatedAffinePoint::alloc_from_coords(
+            cs.namespace(|| "g1"),
+            || Ok(point),
+            &params.pairing.g1,
+        )
+        .unwrap()
+    }
+
+    fn alloc_g2<CS: ConstraintSystem<Fr>>(
+        mut cs: CS,
+        point: &NativeG2Point,
+        params: &BlsParams,
+    ) -> EmulatedG2Point<Fr> {
+        let point = point.clone().unwrap();
+        EmulatedG2Point::alloc_affine(cs.namespace(|| "g2"), || Ok(point), &params.pairing.g2)
+            .unwrap()
+    }
+
+    /// Verifies a min-pk signature of `signed` against the message `checked`.
+    fn min_pk(signed: &[u8], checked: &[u8]) -> bool {
+        let params = BlsParams::bls12_381();
+        let sk = BigInt::from(SECRET_KEYS[0]);
+        let pk = params.min_pk_public_key(&sk);
+        let sig = params.min_pk_sign(&sk, signed, MIN_PK_POP_DST);
+        is_satisfied_by(|cs| {
+            let pk = alloc_g1(cs.namespace(|| "pk"), &pk, &params);
+            let sig = alloc_g2(cs.namespace(|| "sig"), &sig, &params);
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(checked), checked.len())?;
+            verify_min_pk(
+                cs.namespace(|| "verify"),
+                &pk,
+                &msg,
+                &sig,
+                MIN_PK_POP_DST,
+                &params,
+            )
+        })
+    }
+
+    #[test]
+    fn bls_min_pk() {
+        assert!(min_pk(b"hello", b"hello"));
+    }
+
+    #[test]
+    fn bls_min_pk_wrong_message() {
+        assert!(!min_pk(b"hello", b"hellp"));
+    }
+
+    #[test]
+    fn bls_min_sig() {
+        let params = BlsParams::bls12_381();
+        let sk = BigInt::from(SECRET_KEYS[0]);
+        let pk = params.min_sig_public_key(&sk);
+        let sig = params.min_sig_sign(&sk, b"hello", MIN_SIG_POP_DST);
+        assert!(is_satisfied_by(|cs| {
+            let pk = alloc_g2(cs.namespace(|| "pk"), &pk, &params);
+            let sig = alloc_g1(cs.namespace(|| "sig"), &sig, &params);
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(b"hello"), 5)?;
+            verify_min_sig(
+                cs.namespace(|| "verify"),
+                &pk,
+                &msg,
+                &sig,
+                MIN_SIG_POP_DST,
+                &params,
+            )
+        }));
+    }
+
+    #[test]
+    fn bls_fast_aggregate_min_pk() {
+        let params = BlsParams::bls12_381();
+        let g2 = &params.pairing.g2;
+        let participants = [true, false, true];
+        let keys: Vec<_> = SECRET_KEYS.iter().map(|sk| BigInt::from(*sk)).collect();
+        let pks: Vec<_> = keys.iter().map(|sk| params.min_pk_public_key(sk)).collect();
+        let sig = keys
+            .iter()
+            .zip(participants)
+            .filter(|(_, p)| *p)
+            .fold(None, |acc, (sk, _)| {
+                g2.add(&acc, &params.min_pk_sign(sk, b"block root", MIN_PK_POP_DST))
+            });
+        assert!(is_satisfied_by(|cs| {
+            let pks: Vec<_> = pks
+                .iter()
+                .enumerate()
+                .map(|(i, pk)| alloc_g1(cs.namespace(|| format!("pk {}", i)), pk, &params))
+                .collect();
+            let bits = participants
+                .iter()
+                .enumerate()
+                .map(|(i, p)| Bit::alloc(cs.namespace(|| format!("participant {}", i)), Some(*p)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let sig = alloc_g2(cs.namespace(|| "sig"), &sig, &params);
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(b"block root"), 10)?;
+            fast_aggregate_verify_min_pk(
+                cs.namespace(|| "verify"),
+                &pks,
+                &Bitvector::from_bits(bits),
+                &msg,
+                &sig,
+                MIN_PK_POP_DST,
+                &params,
+            )
+        }));
+    }
+}
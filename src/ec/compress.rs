@@ -0,0 +1,447 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use super::affine::EmulatedAffinePoint;
+use super::edwards::{EdwardsParams, EmulatedEdwardsPoint};
+use super::CurveParams;
+use crate::mp::bignat::BigNat;
+use crate::util::bit::{Bit, Bitvector};
+use crate::util::convert::{f_to_nat, nat_to_f};
+use crate::util::num::Num;
+use crate::OptionExt;
+
+/// The integer encoded by `bytes` (given as bits, each byte's most significant bit first), read
+/// as a big-endian number, as a (little-endian) bit-vector.
+pub fn be_bytes_to_bits<Scalar: PrimeField>(bytes: &Bitvector<Scalar>) -> Bitvector<Scalar> {
+    let n_bits = bytes.bits.len();
+    Bitvector::from_bits(
+        (0..n_bits)
+            .map(|i| bytes.get(n_bits - 1 - i).unwrap())
+            .collect(),
+    )
+}
+
+/// The integer encoded by `bytes` (given as bits, each byte's most significant bit first), read
+/// as a little-endian number, as a (little-endian) bit-vector.
+pub fn le_bytes_to_bits<Scalar: PrimeField>(bytes: &Bitvector<Scalar>) -> Bitvector<Scalar> {
+    let n_bits = bytes.bits.len();
+    Bitvector::from_bits(
+        (0..n_bits)
+            .map(|i| bytes.get(i - i % 8 + 7 - i % 8).unwrap())
+            .collect(),
+    )
+}
+
+/// The number with (little-endian) bits `bits`, with `n_limbs` limbs of `limb_width` bits.
+fn from_bits<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    bits: &Bitvector<Scalar>,
+    limb_width: usize,
+    n_limbs: usize,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    if bits.bits.is_empty() || bits.bits.len() > limb_width * n_limbs {
+        eprintln!(
+            "{} bits do not fit in {} limbs of width {}",
+            bits.bits.len(),
+            n_limbs,
+            limb_width
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    Ok(BigNat::recompose(bits, limb_width).with_n_limbs::<CS>(n_limbs))
+}
+
+/// Constrain the parity of the well formed number `n` to be `odd`.
+pub(crate) fn assert_parity<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    n: &BigNat<Scalar>,
+    odd: &Bit<Scalar>,
+) -> Result<(), SynthesisError> {
+    // The lowest limb is 2 h + odd, for h < 2^(limb_width - 1)
+    let half = Num::alloc(cs.namespace(|| "half"), || {
+        let limb = f_to_nat(&n.limb_values.grab()?[0]);
+        Ok(nat_to_f(&(limb >> 1u32)).unwrap())
+    })?;
+    half.fits_in_bits(cs.namespace(|| "rangecheck"), n.params.limb_width - 1)?;
+    cs.enforce(
+        || "parity",
+        |lc| lc,
+        |lc| lc,
+        |lc| lc + &n.limbs[0] - &half.num - &half.num - &odd.bit,
+    );
+    Ok(())
+}
+
+impl<Scalar: PrimeField> EmulatedAffinePoint<Scalar> {
+    /// Decompresses the point with (canonical) x-coordinate given by its (little-endian) bits
+    /// `x`, and y-coordinate of parity `odd`. The result is constrained to be on the curve, with
+    /// canonical coordinates, and with the given parity.
+    pub fn decompress<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        x: &Bitvector<Scalar>,
+        odd: &Bit<Scalar>,
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        let p = params.modulus::<Scalar, CS>()?;
+        let x = from_bits::<Scalar, CS>(x, params.limb_width, params.n_limbs)?;
+        x.assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+        let y = BigNat::alloc_from_nat(
+            cs.namespace(|| "y"),
+            || {
+                // An invalid encoding has no y-coordinate; any witness will fail the checks
+                let y = params
+                    .lift_x(x.value.grab()?)
+                    .map_or(BigInt::from(0), |(_, y)| y);
+                Ok(if y.is_odd() == *odd.value.grab()? {
+                    y
+                } else {
+                    (&params.p - y).mod_floor(&params.p)
+                })
+            },
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        y.assert_well_formed(cs.namespace(|| "y rangecheck"))?;
+        y.assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+        assert_parity(cs.namespace(|| "y parity"), &y, odd)?;
+        let point = Self::from_coords(x, y, params);
+        point.assert_on_curve(cs.namespace(|| "on curve"))?;
+        Ok(point)
+    }
+
+    /// Decompresses a point from its SEC1 compressed encoding (see `CurveParams::encode_sec1`),
+    /// given as the bits of its bytes, each byte's most significant bit first.
+    pub fn decompress_sec1<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        encoding: &Bitvector<Scalar>,
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        let n_bytes = (params.p.bits() as usize).div_ceil(8);
+        if encoding.bits.len() != 8 * (n_bytes + 1) {
+            eprintln!(
+                "A SEC1 encoding has {} bytes, not {} bits",
+                n_bytes + 1,
+                encoding.bits.len()
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        // The prefix is 0b0000001 followed by the parity
+        for i in 0..7 {
+            encoding
+                .get(i)
+                .unwrap()
+                .constrain_value(cs.namespace(|| format!("prefix {}", i)), i == 6);
+        }
+        let odd = encoding.get(7).unwrap();
+        let x = be_bytes_to_bits(&encoding.clone().shr(8));
+        Self::decompress(cs.namespace(|| "decompress"), &x, &odd, params)
+    }
+
+    /// The SEC1 compressed encoding of `self` (see `CurveParams::encode_sec1`), as the bits of its
+    /// bytes, each byte's most significant bit first. The coordinates are constrained to be
+    /// canonical.
+    pub fn compress_sec1<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Bitvector<Scalar>, SynthesisError> {
+        let params = &self.params;
+        let n_bytes = (params.p.bits() as usize).div_ceil(8);
+        let p = params.modulus::<Scalar, CS>()?;
+        self.x
+            .assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+        self.y
+            .assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+        let odd = Bit::alloc(
+            cs.namespace(|| "odd"),
+            self.y.value.as_ref().map(|y| y.is_odd()),
+        )?;
+        assert_parity(cs.namespace(|| "y parity"), &self.y, &odd)?;
+        // x < p, so its bits beyond the encoding are zero
+        let mut x = self.x.decompose(cs.namespace(|| "x bits"))?.into_bits();
+        x.resize(8 * n_bytes, Bit::new_false::<CS>());
+        // The prefix is 0b0000001 followed by the parity
+        let mut bits: Vec<Bit<Scalar>> = (0..6).map(|_| Bit::new_false::<CS>()).collect();
+        bits.push(Bit::new_true::<CS>());
+        bits.push(odd);
+        bits.extend(x.into_iter().rev());
+        Ok(Bitvector::from_bits(bits))
+    }
+}
+
+impl<Scalar: PrimeField> EmulatedEdwardsPoint<Scalar> {
+    /// Decompresses a point from its encoding as in RFC 8032 (see `EdwardsParams::encode`), given
+    /// as the bits of its bytes, each byte's most significant bit first. The result is constrained
+    /// to be on the curve, with a canonical y-coordinate and an x-coordinate of the encoded sign.
+    pub fn decompress<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        encoding: &Bitvector<Scalar>,
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        let n_bits = params.p.bits() as usize + 1;
+        if encoding.bits.len() != n_bits.div_ceil(8) * 8 {
+            eprintln!(
+                "An encoding has {} bytes, not {} bits",
+                n_bits.div_ceil(8),
+                encoding.bits.len()
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let bits = le_bytes_to_bits(encoding);
+        // The bits above the sign are zero
+        for i in n_bits..bits.bits.len() {
+            bits.get(i)
+                .unwrap()
+                .constrain_value(cs.namespace(|| format!("padding {}", i)), false);
+        }
+        let odd = bits.get(n_bits - 1).unwrap();
+        let p = params.modulus::<Scalar, CS>()?;
+        let y = from_bits::<Scalar, CS>(
+            &bits.truncate(n_bits - 1),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        y.assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+        let x = BigNat::alloc_from_nat(
+            cs.namespace(|| "x"),
+            || {
+                // An invalid encoding has no x-coordinate; any witness will fail the checks
+                let odd = *odd.value.grab()?;
+                Ok(params
+                    .recover_x(y.value.grab()?, odd)
+                    .unwrap_or(BigInt::from(0)))
+            },
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        x.assert_well_formed(cs.namespace(|| "x rangecheck"))?;
+        x.assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+        assert_parity(cs.namespace(|| "x parity"), &x, &odd)?;
+        let point = Self::from_affine(cs.namespace(|| "extend"), x, y, params)?;
+        point.assert_on_curve(cs.namespace(|| "on curve"))?;
+        Ok(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::projective::EmulatedProjectivePoint;
+    use crate::util::test_helpers::*;
+
+    pub struct Sec1 {
+        encoding: Vec<u8>,
+        expected: (BigInt, BigInt),
+        params: CurveParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Sec1 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let encoding = Bitvector::alloc_bytes(
+                cs.namespace(|| "encoding"),
+                Some(&self.encoding),
+                self.encoding.len(),
+            )?;
+            let point =
+                EmulatedAffinePoint::decompress_sec1(cs.namespace(|| "point"), &encoding, params)?;
+            let (x, y) = &self.expected;
+            let expected = EmulatedAffinePoint::constant::<CS>(x, y, params)?;
+            EmulatedProjectivePoint::from_affine::<CS>(&point)?.assert_equivalent(
+                cs.namespace(|| "check"),
+                &EmulatedProjectivePoint::from_affine::<CS>(&expected)?,
+            )
+        }
+    }
+
+    /// The encoding of `k * G`, with `tamper` applied.
+    fn sec1(params: CurveParams, k: u64, tamper: fn(&CurveParams, &mut [u8])) -> Sec1 {
+        let point = params
+            .mul(&BigInt::from(k), &Some(params.generator.clone()))
+            .unwrap();
+        let mut encoding = params.encode_sec1(&point);
+        tamper(&params, &mut encoding);
+        Sec1 {
+            encoding,
+            expected: point,
+            params,
+        }
+    }
+
+    fn honest(_: &CurveParams, _: &mut [u8]) {}
+
+    /// Flips the parity in the prefix.
+    fn flip_parity(_: &CurveParams, encoding: &mut [u8]) {
+        encoding[0] ^= 1;
+    }
+
+    /// Replaces the prefix with the uncompressed prefix.
+    fn bad_prefix(_: &CurveParams, encoding: &mut [u8]) {
+        encoding[0] = 4;
+    }
+
+    /// The encoding of the secp256k1 point with the least x-coordinate, with `tamper` applied.
+    fn sec1_small_x(tamper: fn(&CurveParams, &mut [u8])) -> Sec1 {
+        let params = CurveParams::secp256k1();
+        let point = (1u64..)
+            .find_map(|x| params.lift_x(&BigInt::from(x)))
+            .unwrap();
+        let mut encoding = params.encode_sec1(&point);
+        tamper(&params, &mut encoding);
+        Sec1 {
+            encoding,
+            expected: point,
+            params,
+        }
+    }
+
+    /// Adds the modulus to the x-coordinate, which must leave room for it.
+    fn non_canonical(params: &CurveParams, encoding: &mut [u8]) {
+        let x = BigInt::from_bytes_be(num_bigint::Sign::Plus, &encoding[1..]) + &params.p;
+        let x = x.to_bytes_be().1;
+        assert_eq!(x.len(), encoding.len() - 1);
+        encoding[1..].copy_from_slice(&x);
+    }
+
+    pub struct Compress {
+        point: (BigInt, BigInt),
+        expected: Vec<u8>,
+        params: CurveParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Compress {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let point = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "point"),
+                || Ok(self.point.clone()),
+                &self.params,
+            )?;
+            let encoding = point.compress_sec1(cs.namespace(|| "compress"))?;
+            if encoding.bits.len() != 8 * self.expected.len() {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+            for i in 0..encoding.bits.len() {
+                encoding.get(i).unwrap().constrain_value(
+                    cs.namespace(|| format!("bit {}", i)),
+                    (self.expected[i / 8] >> (7 - i % 8)) & 1 == 1,
+                );
+            }
+            Ok(())
+        }
+    }
+
+    /// The point `k * G` and its encoding.
+    fn compress(params: CurveParams, k: u64) -> Compress {
+        let point = params
+            .mul(&BigInt::from(k), &Some(params.generator.clone()))
+            .unwrap();
+        Compress {
+            expected: params.encode_sec1(&point),
+            point,
+            params,
+        }
+    }
+
+    /// The secp256k1 point with the least x-coordinate and its encoding, with the modulus added
+    /// to the x-coordinate of the point.
+    fn compress_non_canonical() -> Compress {
+        let params = CurveParams::secp256k1();
+        let (x, y) = (1u64..)
+            .find_map(|x| params.lift_x(&BigInt::from(x)))
+            .unwrap();
+        Compress {
+            expected: params.encode_sec1(&(x.clone(), y.clone())),
+            point: (x + &params.p, y),
+            params,
+        }
+    }
+
+    pub struct Ed25519 {
+        encoding: Vec<u8>,
+        expected: (BigInt, BigInt),
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Ed25519 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = EdwardsParams::ed25519();
+            let encoding = Bitvector::alloc_bytes(
+                cs.namespace(|| "encoding"),
+                Some(&self.encoding),
+                self.encoding.len(),
+            )?;
+            let point =
+                EmulatedEdwardsPoint::decompress(cs.namespace(|| "point"), &encoding, &params)?;
+            let expected = EmulatedEdwardsPoint::constant::<CS>(&self.expected, &params)?;
+            point.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    /// The encoding of `k * B` (negated if `negate`), with `tamper` applied.
+    fn ed25519(k: u64, negate: bool, tamper: fn(&mut [u8])) -> Ed25519 {
+        let params = EdwardsParams::ed25519();
+        let mut point = params.mul(&BigInt::from(k), &params.generator);
+        if negate {
+            point = params.negate(&point);
+        }
+        let mut encoding = params.encode(&point);
+        tamper(&mut encoding);
+        Ed25519 {
+            encoding,
+            expected: point,
+        }
+    }
+
+    fn ed_honest(_: &mut [u8]) {}
+
+    fn flip_sign(encoding: &mut [u8]) {
+        encoding[31] ^= 0x80;
+    }
+
+    /// Adds the modulus to the y-coordinate, which must leave room for it.
+    fn ed_non_canonical(encoding: &mut [u8]) {
+        let params = EdwardsParams::ed25519();
+        let y = BigInt::from_bytes_le(num_bigint::Sign::Plus, encoding) + &params.p;
+        assert!(y.bits() <= 255);
+        encoding.copy_from_slice(&y.to_bytes_le().1);
+    }
+
+    #[test]
+    fn bytes_to_bits_orders() {
+        let mut cs = crate::util::value_cs::ValueConstraintSystem::<crate::util::scalar::Fr>::new();
+        let bytes =
+            Bitvector::alloc_bytes(cs.namespace(|| "bytes"), Some(&[0x01, 0x80]), 2).unwrap();
+        let be = be_bytes_to_bits(&bytes).values.unwrap();
+        let le = le_bytes_to_bits(&bytes).values.unwrap();
+        // 0x0180 and 0x8001
+        assert!(be[7] && be[8] && be.iter().filter(|b| **b).count() == 2);
+        assert!(le[0] && le[15] && le.iter().filter(|b| **b).count() == 2);
+    }
+
+    circuit_tests! {
+        decompress_secp256k1: (sec1(CurveParams::secp256k1(), 3, honest), true),
+        decompress_secp256k1_small_x: (sec1_small_x(honest), true),
+        decompress_secp256k1_odd: (sec1(CurveParams::secp256k1(), 7, honest), true),
+        decompress_p256: (sec1(CurveParams::p256(), 5, honest), true),
+        decompress_secp256k1_flipped_parity: (sec1(CurveParams::secp256k1(), 3, flip_parity), false),
+        decompress_secp256k1_bad_prefix: (sec1(CurveParams::secp256k1(), 3, bad_prefix), false),
+        decompress_secp256k1_non_canonical: (sec1_small_x(non_canonical), false),
+        compress_secp256k1: (compress(CurveParams::secp256k1(), 3), true),
+        compress_secp256k1_odd: (compress(CurveParams::secp256k1(), 7), true),
+        compress_p256: (compress(CurveParams::p256(), 5), true),
+        compress_secp256k1_non_canonical: (compress_non_canonical(), false),
+        decompress_ed25519: (ed25519(5, false, ed_honest), true),
+        decompress_ed25519_negated: (ed25519(5, true, ed_honest), true),
+        decompress_ed25519_flipped_sign: (ed25519(5, false, flip_sign), false),
+        decompress_ed25519_identity: (ed25519(0, false, ed_honest), true),
+        decompress_ed25519_non_canonical: (ed25519(0, false, ed_non_canonical), false),
+    }
+}
@@ -0,0 +1,588 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use std::fmt::{self, Debug, Formatter};
+
+use super::affine::EmulatedAffinePoint;
+use super::msm::{msm, Base};
+use super::{CurveParams, NativePoint};
+use crate::hash::sha256::sha256;
+use crate::hash::to_bitvector;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, Term};
+use crate::util::bit::{Bit, Bitvector};
+use crate::OptionExt;
+
+/// The window of the multi-scalar multiplication `u1 * G + u2 * Q`.
+const WINDOW: usize = 4;
+
+impl CurveParams {
+    /// The message hash of `message` for ECDSA with SHA-256, computed natively: the leftmost bits
+    /// of the digest, as many as the group order has (SEC1, section 4.1.3).
+    pub fn sha256_message_hash(&self, message: &[u8]) -> BigInt {
+        let digest = BigInt::from_bytes_be(Sign::Plus, &sha256(message));
+        digest >> 256u64.saturating_sub(self.order.bits())
+    }
+
+    /// Signs the message hash `z` with the secret key `secret` and the nonce `k`, natively.
+    /// Returns `None` if the nonce gives `r = 0` or `s = 0`.
+    pub fn ecdsa_sign(&self, secret: &BigInt, z: &BigInt, k: &BigInt) -> Option<(BigInt, BigInt)> {
+        let n = &self.order;
+        let (x, _) = self.mul(k, &Some(self.generator.clone()))?;
+        let r = x.mod_floor(n);
+        let s = (invert(k, n).ok()? * (z + &r * secret)).mod_floor(n);
+        if r == BigInt::from(0) || s == BigInt::from(0) {
+            None
+        } else {
+            Some((r, s))
+        }
+    }
+
+    /// Verifies the signature `(r, s)` of the message hash `z` under the public key
+    /// `public_key`, natively.
+    pub fn ecdsa_verify(
+        &self,
+        z: &BigInt,
+        public_key: &NativePoint,
+        (r, s): &(BigInt, BigInt),
+    ) -> bool {
+        let n = &self.order;
+        let in_range = |v: &BigInt| *v >= BigInt::from(1) && v < n;
+        if !in_range(r) || !in_range(s) || public_key.is_none() || !self.is_on_curve(public_key) {
+            return false;
+        }
+        let w = invert(s, n).unwrap();
+        let u1 = (z * &w).mod_floor(n);
+        let u2 = (r * &w).mod_floor(n);
+        let point = self.add(
+            &self.mul(&u1, &Some(self.generator.clone())),
+            &self.mul(&u2, public_key),
+        );
+        match point {
+            None => false,
+            Some((x, _)) => x.mod_floor(n) == *r,
+        }
+    }
+
+    /// Recovers the public key under which `(r, s)` is a signature of the message hash `z`,
+    /// natively, where the point `R` of the signature has x-coordinate `r` and y-coordinate of
+    /// parity `odd` (the recovery id). Returns `None` if there is no such point or key.
+    pub fn ecdsa_recover(&self, z: &BigInt, (r, s): &(BigInt, BigInt), odd: bool) -> NativePoint {
+        let n = &self.order;
+        let in_range = |v: &BigInt| *v >= BigInt::from(1) && v < n;
+        if !in_range(r) || !in_range(s) {
+            return None;
+        }
+        let (x, y) = self.lift_x(r)?;
+        let y = if y.is_odd() == odd {
+            y
+        } else {
+            (&self.p - y).mod_floor(&self.p)
+        };
+        // Q = r^-1 (s R - z G)
+        let w = invert(r, n).unwrap();
+        let u1 = (-z * &w).mod_floor(n);
+        let u2 = (s * &w).mod_floor(n);
+        self.add(
+            &self.mul(&u1, &Some(self.generator.clone())),
+            &self.mul(&u2, &Some((x, y))),
+        )
+    }
+}
+
+/// An ECDSA signature `(r, s)` over an emulated curve. The scalars have the limb layout of the
+/// coordinates.
+#[derive(Clone)]
+pub struct EcdsaSignature<Scalar: PrimeField> {
+    pub r: BigNat<Scalar>,
+    pub s: BigNat<Scalar>,
+    /// Parameters
+    pub params: CurveParams,
+}
+
+impl<Scalar: PrimeField> EcdsaSignature<Scalar> {
+    /// Allocates a signature with range checked limbs; `1 <= r, s < n` is enforced by `verify`.
+    /// The value is provided by a closure returning `(r, s)`.
+    pub fn alloc<CS, F>(mut cs: CS, f: F, params: &CurveParams) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<(BigInt, BigInt), SynthesisError>,
+    {
+        let value = f().ok();
+        let r = BigNat::alloc_from_nat(
+            cs.namespace(|| "r"),
+            || Ok(value.grab()?.0.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        r.assert_well_formed(cs.namespace(|| "r rangecheck"))?;
+        let s = BigNat::alloc_from_nat(
+            cs.namespace(|| "s"),
+            || Ok(value.grab()?.1.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        s.assert_well_formed(cs.namespace(|| "s rangecheck"))?;
+        Ok(Self {
+            r,
+            s,
+            params: params.clone(),
+        })
+    }
+
+    /// Constrain `self` to be a valid signature of the message hash `z` under the public key
+    /// `public_key`.
+    ///
+    /// The message hash is the integer given by the leftmost bits of the hash, as many as the
+    /// group order has (SEC1, section 4.1.4); it must be well formed, and need not be reduced.
+    /// The public key is constrained to be on the curve, which must have prime order. Then
+    /// `1 <= r, s < n` is enforced, with `s^-1 mod n` witnessed, the point
+    /// `R = (z s^-1 mod n) * G + (r s^-1 mod n) * Q` is computed with a multi-scalar
+    /// multiplication, and it is constrained not to be the identity, with `x(R) mod n == r`.
+    pub fn verify<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        z: &BigNat<Scalar>,
+        public_key: &EmulatedAffinePoint<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        let params = &self.params;
+        if public_key.params != *params {
+            eprintln!("Curves do not agree at EcdsaSignature::verify");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        public_key.assert_on_curve(cs.namespace(|| "public key on curve"))?;
+        let p = params.modulus::<Scalar, CS>()?;
+        let n = params.order_modulus::<Scalar, CS>()?;
+
+        // 1 <= r, s < n: canonical and invertible
+        self.r.assert_canonical(cs.namespace(|| "r < n"), &n)?;
+        self.s.assert_canonical(cs.namespace(|| "s < n"), &n)?;
+        self.r.inverse_mod(cs.namespace(|| "r != 0"), &n)?;
+        let w = self.s.inverse_mod(cs.namespace(|| "s inverse"), &n)?;
+
+        // u1 = z w and u2 = r w, as bits
+        let u1 = scalar_product(cs.namespace(|| "u1"), z, &w, params)?;
+        let u2 = scalar_product(cs.namespace(|| "u2"), &self.r, &w, params)?;
+        let point = msm(
+            cs.namespace(|| "u1 G + u2 Q"),
+            &[
+                (Base::Fixed(&params.generator), &u1),
+                (Base::Variable(public_key), &u2),
+            ],
+            WINDOW,
+            params,
+        )?;
+
+        // x(R) mod n == r, where x(R) < p
+        let point = point.to_affine(cs.namespace(|| "affine"))?;
+        point
+            .x
+            .assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x mod n"),
+            &[Term::Single(&point.x)],
+            &[Term::Single(&self.r)],
+            &n,
+        )
+    }
+}
+
+impl<Scalar: PrimeField> EcdsaSignature<Scalar> {
+    /// Recovers the public key under which `self` is a signature of the message hash `z`, as
+    /// Ethereum's `ecrecover` does; the recovery id is the parity `odd` of the y-coordinate of
+    /// the point `R` of the signature.
+    ///
+    /// The message hash is as in `verify`. `1 <= r, s < n` is enforced, and `R` is decompressed
+    /// from the x-coordinate `r` and the parity `odd`; the recovery ids for which x(R) is
+    /// `r + n` are not supported, as such signatures occur with negligible probability. Then
+    /// `Q = (-z r^-1 mod n) * G + (s r^-1 mod n) * R` is computed with a multi-scalar
+    /// multiplication, and constrained not to be the identity. The coordinates of the result are
+    /// canonical, so they can be serialized, e.g. to be hashed into an address.
+    pub fn recover<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        z: &BigNat<Scalar>,
+        odd: &Bit<Scalar>,
+    ) -> Result<EmulatedAffinePoint<Scalar>, SynthesisError> {
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let n = params.order_modulus::<Scalar, CS>()?;
+
+        // 1 <= r, s < n: canonical and invertible
+        self.r.assert_canonical(cs.namespace(|| "r < n"), &n)?;
+        self.s.assert_canonical(cs.namespace(|| "s < n"), &n)?;
+        self.s.inverse_mod(cs.namespace(|| "s != 0"), &n)?;
+        let w = self.r.inverse_mod(cs.namespace(|| "r inverse"), &n)?;
+
+        // R from x(R) = r < n < p
+        let x = self.r.decompose(cs.namespace(|| "r bits"))?;
+        let point = EmulatedAffinePoint::decompress(cs.namespace(|| "R"), &x, odd, params)?;
+
+        // u1 = z w with -G, and u2 = s w, as bits
+        let u1 = scalar_product(cs.namespace(|| "u1"), z, &w, params)?;
+        let u2 = scalar_product(cs.namespace(|| "u2"), &self.s, &w, params)?;
+        let (gx, gy) = &params.generator;
+        let minus_g = (gx.clone(), (&params.p - gy).mod_floor(&params.p));
+        let public_key = msm(
+            cs.namespace(|| "u1 (-G) + u2 R"),
+            &[(Base::Fixed(&minus_g), &u1), (Base::Variable(&point), &u2)],
+            WINDOW,
+            params,
+        )?;
+        let public_key = public_key.to_affine(cs.namespace(|| "affine"))?;
+        public_key
+            .x
+            .assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+        public_key
+            .y
+            .assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+        Ok(public_key)
+    }
+}
+
+/// The product `a b mod n` of the scalars `a` and `b`, as bits.
+fn scalar_product<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    a: &BigNat<Scalar>,
+    b: &BigNat<Scalar>,
+    params: &CurveParams,
+) -> Result<Bitvector<Scalar>, SynthesisError> {
+    let n = params.order_modulus::<Scalar, CS>()?;
+    let u = BigNat::alloc_from_nat(
+        cs.namespace(|| "product"),
+        || Ok((a.value.grab()? * b.value.grab()?).mod_floor(&params.order)),
+        params.limb_width,
+        params.n_limbs,
+    )?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "check"),
+        &[Term::Product(a, b)],
+        &[Term::Single(&u)],
+        &n,
+    )?;
+    u.decompose(cs.namespace(|| "bits"))
+}
+
+/// The message hash of `message` for ECDSA with SHA-256: the leftmost bits of the digest, as many
+/// as the group order has (SEC1, section 4.1.3). The message is given as the bits of its bytes,
+/// each byte's most significant bit first; the hash has the limb layout of the coordinates.
+pub fn sha256_message_hash<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    message: &[Boolean],
+    params: &CurveParams,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    if !message.len().is_multiple_of(8) {
+        eprintln!("The message has {} bits, not whole bytes", message.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let digest = sha256_circuit(cs.namespace(|| "sha256"), message)?;
+    let n_bits = (params.order.bits() as usize).min(digest.len());
+    // Most significant bit last
+    let bits: Vec<Boolean> = digest[..n_bits].iter().rev().cloned().collect();
+    Ok(BigNat::recompose(
+        &to_bitvector::<Scalar, CS>(&bits),
+        params.limb_width,
+    ))
+}
+
+impl<Scalar: PrimeField> EcdsaSignature<Scalar> {
+    /// Constrain `self` to be a valid signature of a WebAuthn assertion under the credential
+    /// public key `public_key`. With P-256, this is the ES256 algorithm.
+    ///
+    /// `signed_data` is `authenticatorData || SHA-256(clientDataJSON)`, given as the bits of its
+    /// bytes, each byte's most significant bit first. It is hashed to the message hash in the
+    /// circuit, so the authenticator data, e.g. its flags and counter, can be constrained by the
+    /// caller.
+    pub fn verify_webauthn<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        signed_data: &[Boolean],
+        public_key: &EmulatedAffinePoint<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        let z = sha256_message_hash(cs.namespace(|| "message hash"), signed_data, &self.params)?;
+        self.verify(cs.namespace(|| "verify"), &z, public_key)
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EcdsaSignature<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EcdsaSignature")
+            .field("r", &self.r.value)
+            .field("s", &self.s.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::test_helpers::*;
+
+    pub struct EcdsaInputs {
+        pub z: BigInt,
+        pub public_key: (BigInt, BigInt),
+        pub signature: (BigInt, BigInt),
+    }
+
+    pub struct Ecdsa {
+        inputs: Option<EcdsaInputs>,
+        params: CurveParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Ecdsa {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.as_ref();
+            let z = BigNat::alloc_from_nat(
+                cs.namespace(|| "z"),
+                || Ok(inputs.grab()?.z.clone()),
+                params.limb_width,
+                params.n_limbs,
+            )?;
+            z.assert_well_formed(cs.namespace(|| "z rangecheck"))?;
+            let public_key = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "public key"),
+                || Ok(inputs.grab()?.public_key.clone()),
+                params,
+            )?;
+            let signature = EcdsaSignature::alloc(
+                cs.namespace(|| "signature"),
+                || Ok(inputs.grab()?.signature.clone()),
+                params,
+            )?;
+            signature.verify(cs.namespace(|| "verify"), &z, &public_key)
+        }
+    }
+
+    /// A signature of the hash `z` with `tamper` applied to the (hash, signature) pair.
+    fn ecdsa(
+        params: CurveParams,
+        tamper: fn(&CurveParams, &mut BigInt, &mut (BigInt, BigInt)),
+    ) -> Ecdsa {
+        let secret = BigInt::from(0x5ec7e7u64) << 200u32;
+        let public_key = params
+            .mul(&secret, &Some(params.generator.clone()))
+            .unwrap();
+        let mut z = (BigInt::from(0xd16e57u64) << 232u32) + 0xabcdefu64;
+        let k = (BigInt::from(0x4e0ce5u64) << 220u32) + 0x123u64;
+        let mut signature = params.ecdsa_sign(&secret, &z, &k).unwrap();
+        assert!(params.ecdsa_verify(&z, &Some(public_key.clone()), &signature));
+        tamper(&params, &mut z, &mut signature);
+        Ecdsa {
+            inputs: Some(EcdsaInputs {
+                z,
+                public_key,
+                signature,
+            }),
+            params,
+        }
+    }
+
+    fn honest(_: &CurveParams, _: &mut BigInt, _: &mut (BigInt, BigInt)) {}
+
+    /// `(r, n - s)` is also a valid signature.
+    fn negate_s(params: &CurveParams, _: &mut BigInt, (_, s): &mut (BigInt, BigInt)) {
+        *s = &params.order - &*s;
+    }
+
+    fn wrong_hash(_: &CurveParams, z: &mut BigInt, _: &mut (BigInt, BigInt)) {
+        *z += 1;
+    }
+
+    fn zero_s(_: &CurveParams, _: &mut BigInt, (_, s): &mut (BigInt, BigInt)) {
+        *s = BigInt::from(0);
+    }
+
+    pub struct Webauthn {
+        signed_data: Vec<u8>,
+        public_key: (BigInt, BigInt),
+        signature: (BigInt, BigInt),
+        params: CurveParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Webauthn {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let signed_data = alloc_bits(
+                cs.namespace(|| "signed data"),
+                Some(&self.signed_data),
+                self.signed_data.len(),
+            )?;
+            let public_key = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "public key"),
+                || Ok(self.public_key.clone()),
+                params,
+            )?;
+            let signature = EcdsaSignature::alloc(
+                cs.namespace(|| "signature"),
+                || Ok(self.signature.clone()),
+                params,
+            )?;
+            signature.verify_webauthn(cs.namespace(|| "verify"), &signed_data, &public_key)
+        }
+    }
+
+    /// A passkey assertion over P-256, with the flags of the authenticator data replaced by
+    /// `flags` after signing.
+    fn webauthn(flags: u8) -> Webauthn {
+        let params = CurveParams::p256();
+        let secret = BigInt::from(0x9a55e7u64) << 200u32;
+        let public_key = params
+            .mul(&secret, &Some(params.generator.clone()))
+            .unwrap();
+        let client_data_json =
+            br#"{"type":"webauthn.get","challenge":"c2lnbi1tZQ","origin":"https://example.com"}"#;
+        // rpIdHash || flags (UP, UV) || signCount
+        let mut signed_data = sha256(b"example.com").to_vec();
+        signed_data.push(0x05);
+        signed_data.extend(7u32.to_be_bytes());
+        signed_data.extend(sha256(client_data_json));
+        let z = params.sha256_message_hash(&signed_data);
+        let k = (BigInt::from(0x4e0ce5u64) << 220u32) + 0x456u64;
+        let signature = params.ecdsa_sign(&secret, &z, &k).unwrap();
+        signed_data[32] = flags;
+        Webauthn {
+            signed_data,
+            public_key,
+            signature,
+            params,
+        }
+    }
+
+    pub struct Recover {
+        z: BigInt,
+        signature: (BigInt, BigInt),
+        odd: bool,
+        public_key: (BigInt, BigInt),
+        params: CurveParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Recover {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let z = BigNat::alloc_from_nat(
+                cs.namespace(|| "z"),
+                || Ok(self.z.clone()),
+                params.limb_width,
+                params.n_limbs,
+            )?;
+            z.assert_well_formed(cs.namespace(|| "z rangecheck"))?;
+            let signature = EcdsaSignature::alloc(
+                cs.namespace(|| "signature"),
+                || Ok(self.signature.clone()),
+                params,
+            )?;
+            let odd = Bit::alloc(cs.namespace(|| "odd"), Some(self.odd))?;
+            let recovered = signature.recover(cs.namespace(|| "recover"), &z, &odd)?;
+            let (x, y) = &self.public_key;
+            let expected = EmulatedAffinePoint::constant::<CS>(x, y, params)?;
+            recovered.x.equal(cs.namespace(|| "x"), &expected.x)?;
+            recovered.y.equal(cs.namespace(|| "y"), &expected.y)
+        }
+    }
+
+    /// A secp256k1 signature, with its recovery id flipped if `flip`, and the public key.
+    fn recover(flip: bool) -> Recover {
+        let params = CurveParams::secp256k1();
+        let secret = BigInt::from(0xec4ec0u64) << 200u32;
+        let public_key = params
+            .mul(&secret, &Some(params.generator.clone()))
+            .unwrap();
+        let z = (BigInt::from(0xd16e57u64) << 232u32) + 0x789u64;
+        let k = (BigInt::from(0x4e0ce5u64) << 220u32) + 0x789u64;
+        let signature = params.ecdsa_sign(&secret, &z, &k).unwrap();
+        let (_, y) = params.mul(&k, &Some(params.generator.clone())).unwrap();
+        let odd = y.is_odd() != flip;
+        Recover {
+            z,
+            signature,
+            odd,
+            public_key,
+            params,
+        }
+    }
+
+    #[test]
+    fn ecrecover_native() {
+        let Recover {
+            z,
+            signature,
+            odd,
+            public_key,
+            params,
+        } = recover(false);
+        assert_eq!(
+            params.ecdsa_recover(&z, &signature, odd),
+            Some(public_key.clone())
+        );
+        let other = params.ecdsa_recover(&z, &signature, !odd);
+        assert!(other.is_some() && other != Some(public_key.clone()));
+        // (r, n - s) is a signature with R negated
+        let (r, s) = &signature;
+        let negated = (r.clone(), &params.order - s);
+        assert_eq!(params.ecdsa_recover(&z, &negated, !odd), Some(public_key));
+        assert_eq!(
+            params.ecdsa_recover(&z, &(r.clone(), BigInt::from(0)), odd),
+            None
+        );
+    }
+
+    #[test]
+    fn ecrecover_secp256k1() {
+        assert!(is_satisfied(recover(false)));
+    }
+
+    #[test]
+    fn ecrecover_secp256k1_wrong_recovery_id() {
+        assert!(!is_satisfied(recover(true)));
+    }
+
+    #[test]
+    fn ecdsa_secp256k1() {
+        assert!(is_satisfied(ecdsa(CurveParams::secp256k1(), honest)));
+    }
+
+    #[test]
+    fn ecdsa_secp256k1_high_s() {
+        assert!(is_satisfied(ecdsa(CurveParams::secp256k1(), negate_s)));
+    }
+
+    #[test]
+    fn ecdsa_secp256k1_wrong_hash() {
+        assert!(!is_satisfied(ecdsa(CurveParams::secp256k1(), wrong_hash)));
+    }
+
+    #[test]
+    fn ecdsa_secp256k1_zero_s() {
+        assert!(!is_satisfied(ecdsa(CurveParams::secp256k1(), zero_s)));
+    }
+
+    #[test]
+    fn ecdsa_p256() {
+        assert!(is_satisfied(ecdsa(CurveParams::p256(), honest)));
+    }
+
+    #[test]
+    fn ecdsa_webauthn_p256() {
+        assert!(is_satisfied(webauthn(0x05)));
+    }
+
+    #[test]
+    fn ecdsa_webauthn_p256_wrong_flags() {
+        assert!(!is_satisfied(webauthn(0x01)));
+    }
+}
@@ -0,0 +1,427 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use super::affine::EmulatedAffinePoint;
+use super::compress::be_bytes_to_bits;
+use super::hash_to_curve::{encode_to_curve, HashToCurveParams};
+use super::msm::{msm, Base};
+use super::{CurveParams, NativePoint};
+use crate::hash::sha256::sha256;
+use crate::hash::{constant_bits, to_bitvector, to_booleans};
+use crate::mp::bignat::BigNat;
+
+/// The window of the multi-scalar multiplications `s B - c Y` and `s H - c Gamma`.
+const WINDOW: usize = 4;
+
+/// The domain separators of the challenge and of the output hash (RFC 9381, sections 5.2 and
+/// 5.4.3).
+const CHALLENGE_FRONT: u8 = 0x02;
+const PROOF_TO_HASH_FRONT: u8 = 0x03;
+const BACK: u8 = 0x00;
+
+/// Parameters of an ECVRF suite of RFC 9381 over a short Weierstrass curve of prime order, with
+/// SHA-256 and `encode_to_curve` of RFC 9380, e.g. `ECVRF-P256-SHA256-SSWU`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcvrfParams {
+    /// The hash-to-curve suite of the encoding to the curve
+    pub hash: HashToCurveParams,
+    /// The identifier `h2c_suite_ID_string` of the hash-to-curve suite
+    pub h2c_suite: &'static [u8],
+    /// The single byte `suite_string` identifying the suite
+    pub suite: u8,
+    /// The length `cLen`, in bytes, of the challenge
+    pub c_len: usize,
+}
+
+impl EcvrfParams {
+    /// The suite `ECVRF-P256-SHA256-SSWU` (RFC 9381, section 5.5).
+    pub fn p256() -> Self {
+        EcvrfParams {
+            hash: HashToCurveParams::p256(),
+            h2c_suite: b"P256_XMD:SHA-256_SSWU_NU_",
+            suite: 0x02,
+            c_len: 16,
+        }
+    }
+
+    fn curve(&self) -> &CurveParams {
+        &self.hash.curve
+    }
+
+    /// The length `ptLen`, in bytes, of an encoded point.
+    fn pt_len(&self) -> usize {
+        (self.curve().p.bits() as usize).div_ceil(8) + 1
+    }
+
+    /// The length `qLen`, in bytes, of an encoded scalar.
+    fn q_len(&self) -> usize {
+        (self.curve().order.bits() as usize).div_ceil(8)
+    }
+
+    /// The length, in bytes, of a proof `Gamma || c || s`.
+    pub fn proof_len(&self) -> usize {
+        self.pt_len() + self.c_len + self.q_len()
+    }
+
+    /// The domain separation tag `"ECVRF_" || h2c_suite_ID_string || suite_string` of the
+    /// encoding to the curve.
+    pub fn dst(&self) -> Vec<u8> {
+        [b"ECVRF_", self.h2c_suite, &[self.suite]].concat()
+    }
+
+    /// The point with the SEC1 compressed encoding `bytes`, if it is a valid encoding.
+    fn decode_point(&self, bytes: &[u8]) -> NativePoint {
+        let curve = self.curve();
+        if bytes.len() != self.pt_len() || (bytes[0] != 2 && bytes[0] != 3) {
+            return None;
+        }
+        let x = BigInt::from_bytes_be(Sign::Plus, &bytes[1..]);
+        if x >= curve.p {
+            return None;
+        }
+        let (x, y) = curve.lift_x(&x)?;
+        if y.is_odd() == (bytes[0] == 3) {
+            Some((x, y))
+        } else {
+            Some((x, (&curve.p - y).mod_floor(&curve.p)))
+        }
+    }
+
+    /// The `len` big-endian bytes of `v`.
+    fn int_to_string(v: &BigInt, len: usize) -> Vec<u8> {
+        let v = v.to_bytes_be().1;
+        let mut bytes = vec![0u8; len - v.len()];
+        bytes.extend(v);
+        bytes
+    }
+
+    /// The truncated hash of the encoded points `Y, H, Gamma, U, V`
+    /// (`ECVRF_challenge_generation`).
+    fn challenge(&self, points: &[Vec<u8>]) -> Vec<u8> {
+        let mut input = vec![self.suite, CHALLENGE_FRONT];
+        for point in points {
+            input.extend(point);
+        }
+        input.push(BACK);
+        sha256(&input)[..self.c_len].to_vec()
+    }
+
+    /// The output `beta` of the proof with the encoded point `Gamma` (`ECVRF_proof_to_hash`);
+    /// the cofactor is 1.
+    fn proof_to_hash(&self, gamma: &[u8]) -> [u8; 32] {
+        sha256(&[&[self.suite, PROOF_TO_HASH_FRONT], gamma, &[BACK]].concat())
+    }
+
+    /// The encoded public key of the secret key `secret`, computed natively. Returns `None` if
+    /// the secret key is out of range.
+    pub fn public_key(&self, secret: &BigInt) -> Option<Vec<u8>> {
+        let curve = self.curve();
+        if *secret <= BigInt::from(0) || *secret >= curve.order {
+            return None;
+        }
+        Some(curve.encode_sec1(&curve.mul(secret, &Some(curve.generator.clone()))?))
+    }
+
+    /// Proves the output of the VRF with the secret key `secret` on the input `alpha`, natively,
+    /// with the nonce `k` (`ECVRF_prove`; the nonce should be generated as in RFC 6979). Returns
+    /// `None` if the secret key or the nonce is out of range.
+    pub fn prove(&self, secret: &BigInt, alpha: &[u8], k: &BigInt) -> Option<Vec<u8>> {
+        let curve = self.curve();
+        let n = &curve.order;
+        if *k <= BigInt::from(0) || k >= n {
+            return None;
+        }
+        let public_key = self.public_key(secret)?;
+        let h = self
+            .hash
+            .encode_to_curve(&[&public_key[..], alpha].concat(), &self.dst());
+        let gamma = curve.mul(secret, &h);
+        let u = curve.mul(k, &Some(curve.generator.clone()));
+        let v = curve.mul(k, &h);
+        let mut points = vec![public_key];
+        for point in [&h, &gamma, &u, &v] {
+            points.push(curve.encode_sec1(point.as_ref()?));
+        }
+        let c_string = self.challenge(&points);
+        let c = BigInt::from_bytes_be(Sign::Plus, &c_string);
+        let s = (k + c * secret).mod_floor(n);
+        Some(
+            [
+                &points[2][..],
+                &c_string,
+                &Self::int_to_string(&s, self.q_len()),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Verifies the proof `proof` of the VRF output on the input `alpha` under the encoded public
+    /// key `public_key`, natively (`ECVRF_verify`). Returns the output `beta` if it is valid.
+    pub fn verify(&self, public_key: &[u8], alpha: &[u8], proof: &[u8]) -> Option<[u8; 32]> {
+        let curve = self.curve();
+        if proof.len() != self.proof_len() {
+            return None;
+        }
+        let y = Some(self.decode_point(public_key)?);
+        let (gamma_string, rest) = proof.split_at(self.pt_len());
+        let (c_string, s_string) = rest.split_at(self.c_len);
+        let gamma = Some(self.decode_point(gamma_string)?);
+        let c = BigInt::from_bytes_be(Sign::Plus, c_string);
+        let s = BigInt::from_bytes_be(Sign::Plus, s_string);
+        if s >= curve.order {
+            return None;
+        }
+        let h = self
+            .hash
+            .encode_to_curve(&[public_key, alpha].concat(), &self.dst());
+        // U = s B - c Y and V = s H - c Gamma
+        let u = curve.add(
+            &curve.mul(&s, &Some(curve.generator.clone())),
+            &curve.negate(&curve.mul(&c, &y)),
+        );
+        let v = curve.add(&curve.mul(&s, &h), &curve.negate(&curve.mul(&c, &gamma)));
+        let points = [
+            public_key.to_vec(),
+            curve.encode_sec1(h.as_ref()?),
+            gamma_string.to_vec(),
+            curve.encode_sec1(u.as_ref()?),
+            curve.encode_sec1(v.as_ref()?),
+        ];
+        if self.challenge(&points) == c_string {
+            Some(self.proof_to_hash(gamma_string))
+        } else {
+            None
+        }
+    }
+}
+
+/// Constrain `proof` to be a valid proof of the VRF output on the input `alpha` under the public
+/// key `public_key` (`ECVRF_verify`), returning the output `beta`. All are given as the bits of
+/// their bytes, each byte's most significant bit first.
+///
+/// The public key and `Gamma` are decompressed, which rejects encodings which are not canonical
+/// or not on the curve, and `s < q` is enforced; the curve has prime order, so the keys need no
+/// further validation. `H` is the encoding of `Y || alpha` to the curve. Then `U = s B - c Y` and
+/// `V = s H - c Gamma` are computed with multi-scalar multiplications, constrained not to be the
+/// identity, and encoded, and the challenge, the truncated hash of the encodings of
+/// `Y, H, Gamma, U, V`, is constrained to be `c`. The output is the hash of `Gamma`.
+pub fn verify_ecvrf<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &[Boolean],
+    alpha: &[Boolean],
+    proof: &[Boolean],
+    params: &EcvrfParams,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let (pt_len, c_len) = (params.pt_len(), params.c_len);
+    if public_key.len() != 8 * pt_len || proof.len() != 8 * params.proof_len() {
+        eprintln!(
+            "An ECVRF public key has {} bits and a proof {}, not {} and {}",
+            public_key.len(),
+            proof.len(),
+            8 * pt_len,
+            8 * params.proof_len()
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    if !alpha.len().is_multiple_of(8) {
+        eprintln!("The input has {} bits, not whole bytes", alpha.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let curve = params.curve();
+    let n = curve.order_modulus::<Scalar, CS>()?;
+    let (gamma_bytes, rest) = proof.split_at(8 * pt_len);
+    let (c_bytes, s_bytes) = rest.split_at(8 * c_len);
+
+    let y = EmulatedAffinePoint::decompress_sec1(
+        cs.namespace(|| "Y"),
+        &to_bitvector::<Scalar, CS>(public_key),
+        curve,
+    )?;
+    let gamma = EmulatedAffinePoint::decompress_sec1(
+        cs.namespace(|| "Gamma"),
+        &to_bitvector::<Scalar, CS>(gamma_bytes),
+        curve,
+    )?;
+    let c_bits = be_bytes_to_bits(&to_bitvector::<Scalar, CS>(c_bytes));
+    let s_bits = be_bytes_to_bits(&to_bitvector::<Scalar, CS>(s_bytes));
+    BigNat::recompose(&s_bits, curve.limb_width).assert_canonical(cs.namespace(|| "s < q"), &n)?;
+
+    let h = encode_to_curve(
+        cs.namespace(|| "H"),
+        &[public_key, alpha].concat(),
+        &params.dst(),
+        &params.hash,
+    )?
+    .to_affine(cs.namespace(|| "H affine"))?;
+    let minus_y = y.negate(cs.namespace(|| "-Y"))?;
+    let u = msm(
+        cs.namespace(|| "s B - c Y"),
+        &[
+            (Base::Fixed(&curve.generator), &s_bits),
+            (Base::Variable(&minus_y), &c_bits),
+        ],
+        WINDOW,
+        curve,
+    )?
+    .to_affine(cs.namespace(|| "U affine"))?;
+    let minus_gamma = gamma.negate(cs.namespace(|| "-Gamma"))?;
+    let v = msm(
+        cs.namespace(|| "s H - c Gamma"),
+        &[
+            (Base::Variable(&h), &s_bits),
+            (Base::Variable(&minus_gamma), &c_bits),
+        ],
+        WINDOW,
+        curve,
+    )?
+    .to_affine(cs.namespace(|| "V affine"))?;
+
+    // The challenge, from the encodings of Y, H, Gamma, U and V
+    let mut encode = |name: &str, point: &EmulatedAffinePoint<Scalar>| {
+        let encoding = point.compress_sec1(cs.namespace(|| format!("{} encoding", name)))?;
+        to_booleans(cs.namespace(|| format!("{} bits", name)), &encoding)
+    };
+    let h_bytes = encode("H", &h)?;
+    let u_bytes = encode("U", &u)?;
+    let v_bytes = encode("V", &v)?;
+    let input = [
+        &constant_bits(&[params.suite, CHALLENGE_FRONT])[..],
+        public_key,
+        &h_bytes,
+        gamma_bytes,
+        &u_bytes,
+        &v_bytes,
+        &constant_bits(&[BACK]),
+    ]
+    .concat();
+    let digest = sha256_circuit(cs.namespace(|| "challenge"), &input)?;
+    for (i, (a, b)) in digest.iter().zip(c_bytes).enumerate() {
+        Boolean::enforce_equal(cs.namespace(|| format!("c bit {}", i)), a, b)?;
+    }
+
+    let input = [
+        &constant_bits(&[params.suite, PROOF_TO_HASH_FRONT])[..],
+        gamma_bytes,
+        &constant_bits(&[BACK]),
+    ]
+    .concat();
+    sha256_circuit(cs.namespace(|| "beta"), &input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::test_helpers::*;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// The P-256 key of the examples of RFC 6979 and RFC 9381.
+    fn secret() -> BigInt {
+        BigInt::from_bytes_be(
+            Sign::Plus,
+            &from_hex("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721"),
+        )
+    }
+
+    /// `Gamma` and the output `beta` of the example of RFC 9381 (appendix B.2) with the input
+    /// "sample", which are determined by the key and the input.
+    const GAMMA: &str = "0331d984ca8fece9cbb9a144c0d53df3c4c7a33080c1e02ddb1a96a365394c7888";
+    const BETA: &str = "21e66dc9747430f17ed9efeda054cf4a264b097b9e8956a1787526ed00dc664b";
+
+    fn nonce() -> BigInt {
+        (BigInt::from(0x4e0ce5u64) << 220u32) + 0xf00du64
+    }
+
+    #[test]
+    fn ecvrf_native() {
+        let params = EcvrfParams::p256();
+        let public_key = params.public_key(&secret()).unwrap();
+        assert_eq!(
+            public_key,
+            from_hex("0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6")
+        );
+        let proof = params.prove(&secret(), b"sample", &nonce()).unwrap();
+        assert_eq!(proof.len(), 81);
+        assert_eq!(proof[..33], from_hex(GAMMA));
+        let beta = params.verify(&public_key, b"sample", &proof).unwrap();
+        assert_eq!(beta.to_vec(), from_hex(BETA));
+        assert_eq!(params.verify(&public_key, b"test", &proof), None);
+        // Another nonce gives another proof of the same output
+        let other = params.prove(&secret(), b"sample", &(nonce() + 1)).unwrap();
+        assert_ne!(other, proof);
+        assert_eq!(params.verify(&public_key, b"sample", &other), Some(beta));
+        let mut tampered = proof.clone();
+        tampered[80] ^= 1;
+        assert_eq!(params.verify(&public_key, b"sample", &tampered), None);
+    }
+
+    pub struct Ecvrf {
+        public_key: Vec<u8>,
+        alpha: Vec<u8>,
+        proof: Vec<u8>,
+        beta: [u8; 32],
+        params: EcvrfParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Ecvrf {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let mut alloc = |name: &str, bytes: &[u8]| {
+                alloc_bits(cs.namespace(|| name.to_string()), Some(bytes), bytes.len())
+            };
+            let public_key = alloc("public key", &self.public_key)?;
+            let alpha = alloc("alpha", &self.alpha)?;
+            let proof = alloc("proof", &self.proof)?;
+            let beta = verify_ecvrf(
+                cs.namespace(|| "verify"),
+                &public_key,
+                &alpha,
+                &proof,
+                &self.params,
+            )?;
+            for (i, (a, b)) in beta.iter().zip(constant_bits(&self.beta)).enumerate() {
+                Boolean::enforce_equal(cs.namespace(|| format!("beta {}", i)), a, &b)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// A proof on the input `proved`, checked on the input `checked`.
+    fn ecvrf(proved: &[u8], checked: &[u8]) -> Ecvrf {
+        let params = EcvrfParams::p256();
+        let public_key = params.public_key(&secret()).unwrap();
+        let proof = params.prove(&secret(), proved, &nonce()).unwrap();
+        let beta = params.proof_to_hash(&proof[..33]);
+        Ecvrf {
+            public_key,
+            alpha: checked.to_vec(),
+            proof,
+            beta,
+            params,
+        }
+    }
+
+    #[test]
+    fn ecvrf_p256() {
+        let circuit = ecvrf(b"sample", b"sample");
+        assert_eq!(circuit.beta.to_vec(), from_hex(BETA));
+        assert!(is_satisfied(circuit));
+    }
+
+    #[test]
+    fn ecvrf_p256_wrong_input() {
+        assert!(!is_satisfied(ecvrf(b"sample", b"tests!")));
+    }
+}
@@ -0,0 +1,324 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use super::compress::le_bytes_to_bits;
+use super::edwards::{EdwardsParams, EmulatedEdwardsPoint};
+use crate::hash::sha512::{native_sha512, sha512};
+use crate::hash::to_bitvector;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::OptionExt;
+
+/// The window of the scalar multiplications `[S]B` and `[k]A`.
+const WINDOW: usize = 4;
+
+/// The lengths of an encoded point and of a signature, in bytes.
+const POINT_BYTES: usize = 32;
+const SIGNATURE_BYTES: usize = 64;
+
+/// The verification equation of an Ed25519 signature (RFC 8032, section 5.1.7).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CofactorCheck {
+    /// `[8][S]B = [8]R + [8][k]A`, as recommended by RFC 8032. Signatures are accepted
+    /// regardless of the small order components of `R` and `A`.
+    Cofactored,
+    /// `[S]B = R + [k]A`, as in most implementations.
+    Cofactorless,
+}
+
+impl EdwardsParams {
+    /// The secret scalar and the nonce prefix of an Ed25519 secret key (RFC 8032, section 5.1.5).
+    fn ed25519_expand(secret: &[u8]) -> (BigInt, Vec<u8>) {
+        let h = native_sha512(secret);
+        let mut scalar = h[..32].to_vec();
+        scalar[0] &= 248;
+        scalar[31] &= 127;
+        scalar[31] |= 64;
+        (BigInt::from_bytes_le(Sign::Plus, &scalar), h[32..].to_vec())
+    }
+
+    /// `SHA-512(R || A || M) mod L`, computed natively.
+    fn ed25519_challenge(&self, r: &[u8], public_key: &[u8], message: &[u8]) -> BigInt {
+        let digest = native_sha512(&[r, public_key, message].concat());
+        BigInt::from_bytes_le(Sign::Plus, &digest).mod_floor(&self.order)
+    }
+
+    /// The Ed25519 public key of the secret key `secret`, computed natively.
+    pub fn ed25519_public_key(&self, secret: &[u8]) -> Vec<u8> {
+        let (scalar, _) = Self::ed25519_expand(secret);
+        self.encode(&self.mul(&scalar, &self.generator))
+    }
+
+    /// Signs `message` with the secret key `secret` (RFC 8032, section 5.1.6), natively.
+    pub fn ed25519_sign(&self, secret: &[u8], message: &[u8]) -> Vec<u8> {
+        let (scalar, prefix) = Self::ed25519_expand(secret);
+        let public_key = self.encode(&self.mul(&scalar, &self.generator));
+        let nonce = BigInt::from_bytes_le(Sign::Plus, &native_sha512(&[&prefix, message].concat()))
+            .mod_floor(&self.order);
+        let r = self.encode(&self.mul(&nonce, &self.generator));
+        let k = self.ed25519_challenge(&r, &public_key, message);
+        let s = (nonce + k * scalar).mod_floor(&self.order);
+        let mut s = s.to_bytes_le().1;
+        s.resize(POINT_BYTES, 0);
+        [r, s].concat()
+    }
+
+    /// Verifies the signature `signature` of `message` under `public_key` (RFC 8032,
+    /// section 5.1.7), natively.
+    pub fn ed25519_verify(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+        check: CofactorCheck,
+    ) -> bool {
+        if signature.len() != SIGNATURE_BYTES {
+            return false;
+        }
+        let (r_encoding, s) = signature.split_at(POINT_BYTES);
+        let (Some(a), Some(r)) = (self.decode(public_key), self.decode(r_encoding)) else {
+            return false;
+        };
+        let s = BigInt::from_bytes_le(Sign::Plus, s);
+        if s >= self.order {
+            return false;
+        }
+        let k = self.ed25519_challenge(r_encoding, public_key, message);
+        let sb = self.mul(&s, &self.generator);
+        let rhs = self.add(&r, &self.mul(&k, &a));
+        match check {
+            CofactorCheck::Cofactorless => sb == rhs,
+            CofactorCheck::Cofactored => {
+                let cofactor = BigInt::from(self.cofactor);
+                self.mul(&cofactor, &self.add(&sb, &self.negate(&rhs))) == self.identity()
+            }
+        }
+    }
+}
+
+/// Constrain `signature` to be a valid Ed25519 signature (RFC 8032) of `message` under the public
+/// key `public_key`. All are given as the bits of their bytes, each byte's most significant bit
+/// first.
+///
+/// The points `A` and `R` are decompressed, which rejects non-canonical encodings, and `S < L` is
+/// enforced. `k = SHA-512(R || A || M) mod L` is computed in the circuit, with the digest reduced
+/// by a witnessed quotient, and `[S]B = R + [k]A` is checked as specified by `check`.
+pub fn verify_ed25519<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &[Boolean],
+    message: &[Boolean],
+    signature: &[Boolean],
+    check: CofactorCheck,
+) -> Result<(), SynthesisError> {
+    if public_key.len() != 8 * POINT_BYTES || signature.len() != 8 * SIGNATURE_BYTES {
+        eprintln!(
+            "An Ed25519 public key has {} bits and a signature {}, not {} and {}",
+            public_key.len(),
+            signature.len(),
+            8 * POINT_BYTES,
+            8 * SIGNATURE_BYTES
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let params = EdwardsParams::ed25519();
+    let l = BigNat::constant::<CS>(&params.order, params.limb_width, params.n_limbs)?;
+    let (r_encoding, s) = signature.split_at(8 * POINT_BYTES);
+    let a = EmulatedEdwardsPoint::decompress(
+        cs.namespace(|| "A"),
+        &to_bitvector::<Scalar, CS>(public_key),
+        &params,
+    )?;
+    let r = EmulatedEdwardsPoint::decompress(
+        cs.namespace(|| "R"),
+        &to_bitvector::<Scalar, CS>(r_encoding),
+        &params,
+    )?;
+
+    // S < L
+    let s_bits = le_bytes_to_bits(&to_bitvector::<Scalar, CS>(s));
+    let s = BigNat::recompose(&s_bits, params.limb_width);
+    s.assert_canonical(cs.namespace(|| "S < L"), &l)?;
+
+    // k = SHA-512(R || A || M) mod L, canonical so that [k]A is exact when A has torsion
+    let digest = sha512(
+        cs.namespace(|| "k hash"),
+        &[r_encoding, public_key, message].concat(),
+    )?;
+    let digest = BigNat::recompose(
+        &le_bytes_to_bits(&to_bitvector::<Scalar, CS>(&digest)),
+        params.limb_width,
+    );
+    let k = BigNat::alloc_from_nat(
+        cs.namespace(|| "k"),
+        || Ok(digest.value.grab()?.mod_floor(&params.order)),
+        params.limb_width,
+        params.n_limbs,
+    )?;
+    let k_bits = k.decompose(cs.namespace(|| "k bits"))?;
+    k.assert_canonical(cs.namespace(|| "k < L"), &l)?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "k check"),
+        &[Term::Single(&digest)],
+        &[Term::Single(&k)],
+        &l,
+    )?;
+
+    let sb = EmulatedEdwardsPoint::fixed_base_mul(
+        cs.namespace(|| "[S]B"),
+        &params.generator,
+        &s_bits,
+        WINDOW,
+        &params,
+    )?;
+    let ka = a.scalar_mul(cs.namespace(|| "[k]A"), &k_bits, WINDOW)?;
+    let rhs = r.add(cs.namespace(|| "R + [k]A"), &ka)?;
+    match check {
+        CofactorCheck::Cofactorless => sb.assert_equivalent(cs.namespace(|| "check"), &rhs),
+        CofactorCheck::Cofactored => {
+            let rhs = rhs.negate(cs.namespace(|| "negate"))?;
+            let difference = sb.add(cs.namespace(|| "difference"), &rhs)?;
+            let difference = difference.clear_cofactor(cs.namespace(|| "clear cofactor"))?;
+            let identity = EmulatedEdwardsPoint::identity::<CS>(&params)?;
+            difference.assert_equivalent(cs.namespace(|| "check"), &identity)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::test_helpers::*;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// RFC 8032, section 7.1, test 1
+    const SECRET: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    const PUBLIC_KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    const SIGNATURE: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+
+    #[test]
+    fn ed25519_vector() {
+        let params = EdwardsParams::ed25519();
+        let secret = from_hex(SECRET);
+        let public_key = params.ed25519_public_key(&secret);
+        assert_eq!(public_key, from_hex(PUBLIC_KEY));
+        let signature = params.ed25519_sign(&secret, b"");
+        assert_eq!(signature, from_hex(SIGNATURE));
+        for check in [CofactorCheck::Cofactored, CofactorCheck::Cofactorless] {
+            assert!(params.ed25519_verify(&public_key, b"", &signature, check));
+            assert!(!params.ed25519_verify(&public_key, b"x", &signature, check));
+        }
+    }
+
+    pub struct Ed25519 {
+        public_key: Vec<u8>,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+        check: CofactorCheck,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Ed25519 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let mut alloc = |name: &str, bytes: &[u8]| {
+                alloc_bits(cs.namespace(|| name.to_string()), Some(bytes), bytes.len())
+            };
+            let public_key = alloc("public key", &self.public_key)?;
+            let message = alloc("message", &self.message)?;
+            let signature = alloc("signature", &self.signature)?;
+            verify_ed25519(
+                cs.namespace(|| "verify"),
+                &public_key,
+                &message,
+                &signature,
+                self.check,
+            )
+        }
+    }
+
+    const MESSAGE: &[u8] = b"transfer 1 SOL";
+
+    /// A signature of `MESSAGE`, whose nonce point is replaced by `R + T` for a point `T` of
+    /// order 2 if `torsion`, and whose `S` is replaced by `S + L` if `s_plus_l`.
+    fn ed25519(torsion: bool, s_plus_l: bool, check: CofactorCheck) -> Ed25519 {
+        let params = EdwardsParams::ed25519();
+        let secret = from_hex(SECRET);
+        let public_key = params.ed25519_public_key(&secret);
+        let mut signature = params.ed25519_sign(&secret, MESSAGE);
+        if torsion {
+            // S = r + k' a, with k' the challenge of R + T
+            let (scalar, _) = EdwardsParams::ed25519_expand(&secret);
+            let nonce = BigInt::from(0x5eed);
+            let t = (BigInt::from(0), &params.p - 1u32);
+            let r = params.encode(&params.add(&params.mul(&nonce, &params.generator), &t));
+            let k = params.ed25519_challenge(&r, &public_key, MESSAGE);
+            let s = (nonce + k * scalar).mod_floor(&params.order);
+            let mut s = s.to_bytes_le().1;
+            s.resize(POINT_BYTES, 0);
+            signature = [r, s].concat();
+        }
+        if s_plus_l {
+            let s = BigInt::from_bytes_le(Sign::Plus, &signature[POINT_BYTES..]) + &params.order;
+            let mut s = s.to_bytes_le().1;
+            s.resize(POINT_BYTES, 0);
+            signature[POINT_BYTES..].copy_from_slice(&s);
+        }
+        assert_eq!(
+            params.ed25519_verify(&public_key, MESSAGE, &signature, check),
+            !s_plus_l && (!torsion || check == CofactorCheck::Cofactored)
+        );
+        Ed25519 {
+            public_key,
+            message: MESSAGE.to_vec(),
+            signature,
+            check,
+        }
+    }
+
+    #[test]
+    fn ed25519_cofactorless() {
+        assert!(is_satisfied(ed25519(
+            false,
+            false,
+            CofactorCheck::Cofactorless
+        )));
+    }
+
+    #[test]
+    fn ed25519_torsion_cofactored() {
+        assert!(is_satisfied(ed25519(
+            true,
+            false,
+            CofactorCheck::Cofactored
+        )));
+    }
+
+    #[test]
+    fn ed25519_torsion_cofactorless() {
+        assert!(!is_satisfied(ed25519(
+            true,
+            false,
+            CofactorCheck::Cofactorless
+        )));
+    }
+
+    #[test]
+    fn ed25519_s_plus_l() {
+        assert!(!is_satisfied(ed25519(
+            false,
+            true,
+            CofactorCheck::Cofactored
+        )));
+    }
+}
@@ -0,0 +1,893 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Num as BigNum;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::fixed_base::{scalar_windows, ConstantSelector};
+use super::residue;
+use super::scalar_mul::windows;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, sqrt, Term};
+use crate::util::bit::Bitvector;
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// A point of a twisted Edwards curve in affine coordinates `(x, y)`, computed natively. The
+/// identity is `(0, 1)`.
+pub type NativeEdwardsPoint = (BigInt, BigInt);
+
+/// Extended coordinates `(X, Y, Z, T)` of a point, with `x = X/Z`, `y = Y/Z` and `xy = T/Z`,
+/// computed natively.
+pub type NativeExtendedPoint = (BigInt, BigInt, BigInt, BigInt);
+
+/// Parameters of a twisted Edwards curve `a*x^2 + y^2 = 1 + d*x^2*y^2` over an emulated prime
+/// field, together with the limb layout used to represent its coordinates.
+///
+/// When `a` is a square and `d` is not, as for ed25519, the unified addition formulas are
+/// complete: they hold for all pairs of points, including doublings and the identity.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EdwardsParams {
+    /// The modulus of the base field
+    pub p: BigInt,
+    pub a: BigInt,
+    pub d: BigInt,
+    /// The (prime) order of the subgroup generated by `generator`
+    pub order: BigInt,
+    /// The order of the curve divided by `order`
+    pub cofactor: u64,
+    pub generator: NativeEdwardsPoint,
+    pub limb_width: usize,
+    pub n_limbs: usize,
+}
+
+impl EdwardsParams {
+    /// The ed25519 curve (edwards25519 with the base point of RFC 8032), with 4 limbs of 64 bits.
+    pub fn ed25519() -> Self {
+        let p = (BigInt::from(1) << 255u32) - 19;
+        let d = (BigInt::from(-121665) * invert(&BigInt::from(121666), &p).unwrap()).mod_floor(&p);
+        let mut params = EdwardsParams {
+            a: &p - 1,
+            d,
+            order: (BigInt::from(1) << 252u32)
+                + BigInt::from_str_radix("27742317777372353535851937790883648493", 10).unwrap(),
+            cofactor: 8,
+            generator: (BigInt::from(0), BigInt::from(0)),
+            limb_width: 64,
+            n_limbs: 4,
+            p,
+        };
+        // y = 4/5, with x even
+        let y =
+            (BigInt::from(4) * invert(&BigInt::from(5), &params.p).unwrap()).mod_floor(&params.p);
+        params.generator = (params.recover_x(&y, false).unwrap(), y);
+        params
+    }
+
+    /// The modulus of the base field, as a constant.
+    pub fn modulus<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&self.p, self.limb_width, self.n_limbs)
+    }
+
+    /// The base field element `v`, as a constant.
+    pub fn constant<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+        v: &BigInt,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&v.mod_floor(&self.p), self.limb_width, self.n_limbs)
+    }
+
+    pub fn identity(&self) -> NativeEdwardsPoint {
+        (BigInt::from(0), BigInt::from(1))
+    }
+
+    pub fn is_on_curve(&self, (x, y): &NativeEdwardsPoint) -> bool {
+        let (xx, yy) = (x * x, y * y);
+        (&self.a * &xx + &yy - 1u32 - &self.d * xx * yy).mod_floor(&self.p) == BigInt::from(0)
+    }
+
+    /// The x-coordinate of the point with y-coordinate `y` whose parity is `odd`, if there is one.
+    pub fn recover_x(&self, y: &BigInt, odd: bool) -> Option<BigInt> {
+        let p = &self.p;
+        let yy = y * y;
+        // x^2 = (y^2 - 1) / (d y^2 - a)
+        let den = (&self.d * &yy - &self.a).mod_floor(p);
+        if den == BigInt::from(0) {
+            return None;
+        }
+        let xx = (yy - 1u32) * invert(&den, p).unwrap();
+        let x = sqrt(&xx, p)?;
+        if x == BigInt::from(0) && odd {
+            return None;
+        }
+        Some(if x.is_odd() == odd { x } else { p - x })
+    }
+
+    /// The encoding of a point as in RFC 8032: the little-endian y-coordinate, with the parity of
+    /// the x-coordinate in the most significant bit.
+    pub fn encode(&self, (x, y): &NativeEdwardsPoint) -> Vec<u8> {
+        let n_bytes = (self.p.bits() as usize + 1).div_ceil(8);
+        let mut encoding = y.to_bytes_le().1;
+        encoding.resize(n_bytes, 0);
+        if x.is_odd() {
+            encoding[n_bytes - 1] |= 0x80;
+        }
+        encoding
+    }
+
+    /// Decodes a point encoded as in RFC 8032 (see `encode`). Returns `None` if the encoding has
+    /// the wrong length, a non-canonical y-coordinate, or no point.
+    pub fn decode(&self, encoding: &[u8]) -> Option<NativeEdwardsPoint> {
+        let n_bits = self.p.bits() + 1;
+        if encoding.len() as u64 != n_bits.div_ceil(8) {
+            return None;
+        }
+        let mut y = BigInt::from_bytes_le(Sign::Plus, encoding);
+        if y.bits() > n_bits {
+            return None;
+        }
+        let odd = y.bit(n_bits - 1);
+        y.set_bit(n_bits - 1, false);
+        if y >= self.p {
+            return None;
+        }
+        Some((self.recover_x(&y, odd)?, y))
+    }
+
+    pub fn negate(&self, (x, y): &NativeEdwardsPoint) -> NativeEdwardsPoint {
+        ((-x).mod_floor(&self.p), y.clone())
+    }
+
+    /// Adds two points with the unified addition law.
+    pub fn add(
+        &self,
+        (x1, y1): &NativeEdwardsPoint,
+        (x2, y2): &NativeEdwardsPoint,
+    ) -> NativeEdwardsPoint {
+        let p = &self.p;
+        let dxxyy = &self.d * x1 * x2 * y1 * y2;
+        let x3 = (x1 * y2 + y1 * x2) * invert(&(&dxxyy + 1u32), p).unwrap();
+        let y3 = (y1 * y2 - &self.a * x1 * x2) * invert(&(BigInt::from(1) - dxxyy), p).unwrap();
+        (x3.mod_floor(p), y3.mod_floor(p))
+    }
+
+    pub fn double(&self, point: &NativeEdwardsPoint) -> NativeEdwardsPoint {
+        self.add(point, point)
+    }
+
+    /// Computes `k * point` by double-and-add. `k` is not reduced modulo `order`, since `point`
+    /// may have a torsion component.
+    pub fn mul(&self, k: &BigInt, point: &NativeEdwardsPoint) -> NativeEdwardsPoint {
+        let (k, point) = if k < &BigInt::from(0) {
+            (-k, self.negate(point))
+        } else {
+            (k.clone(), point.clone())
+        };
+        let mut acc = self.identity();
+        for i in (0..k.bits()).rev() {
+            acc = self.double(&acc);
+            if k.bit(i) {
+                acc = self.add(&acc, &point);
+            }
+        }
+        acc
+    }
+
+    pub fn to_extended(&self, (x, y): &NativeEdwardsPoint) -> NativeExtendedPoint {
+        (
+            x.clone(),
+            y.clone(),
+            BigInt::from(1),
+            (x * y).mod_floor(&self.p),
+        )
+    }
+
+    pub fn to_affine(&self, (x, y, z, _): &NativeExtendedPoint) -> NativeEdwardsPoint {
+        let z_inv = invert(z, &self.p).unwrap();
+        (
+            (x * &z_inv).mod_floor(&self.p),
+            (y * &z_inv).mod_floor(&self.p),
+        )
+    }
+}
+
+impl Debug for EdwardsParams {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EdwardsParams")
+            .field("p", &format_args!("{}", &self.p))
+            .field("a", &format_args!("{}", &self.a))
+            .field("d", &format_args!("{}", &self.d))
+            .field("limb_width", &self.limb_width)
+            .field("n_limbs", &self.n_limbs)
+            .finish()
+    }
+}
+
+/// A point, in extended coordinates, of a twisted Edwards curve over an emulated prime field.
+///
+/// Addition uses the unified formulas of Hisil, Wong, Carter and Dawson ("Twisted Edwards curves
+/// revisited", 2008), which are complete on curves like ed25519, so no input needs special
+/// handling.
+#[derive(Clone)]
+pub struct EmulatedEdwardsPoint<Scalar: PrimeField> {
+    pub x: BigNat<Scalar>,
+    pub y: BigNat<Scalar>,
+    pub z: BigNat<Scalar>,
+    pub t: BigNat<Scalar>,
+    /// The reduced coordinates of the point (filled at witness-time)
+    pub value: Option<NativeExtendedPoint>,
+    /// Parameters
+    pub params: EdwardsParams,
+}
+
+impl<Scalar: PrimeField> EmulatedEdwardsPoint<Scalar> {
+    fn from_coords(
+        x: BigNat<Scalar>,
+        y: BigNat<Scalar>,
+        z: BigNat<Scalar>,
+        t: BigNat<Scalar>,
+        params: &EdwardsParams,
+    ) -> Self {
+        let p = &params.p;
+        let value = x.value.as_ref().and_then(|x| {
+            y.value.as_ref().and_then(|y| {
+                z.value.as_ref().and_then(|z| {
+                    t.value.as_ref().map(|t| {
+                        (
+                            x.mod_floor(p),
+                            y.mod_floor(p),
+                            z.mod_floor(p),
+                            t.mod_floor(p),
+                        )
+                    })
+                })
+            })
+        });
+        Self {
+            x,
+            y,
+            z,
+            t,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// Allocates a point from range checked affine coordinates. The point is not checked to be on
+    /// the curve, see `assert_on_curve`.
+    /// The value is provided by a closure returning the affine coordinates.
+    pub fn alloc_from_coords<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<NativeEdwardsPoint, SynthesisError>,
+    {
+        let coords = f().ok();
+        let x = BigNat::alloc_from_nat(
+            cs.namespace(|| "x"),
+            || Ok(coords.grab()?.0.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        x.assert_well_formed(cs.namespace(|| "x rangecheck"))?;
+        let y = BigNat::alloc_from_nat(
+            cs.namespace(|| "y"),
+            || Ok(coords.grab()?.1.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        y.assert_well_formed(cs.namespace(|| "y rangecheck"))?;
+        Self::from_affine(cs, x, y, params)
+    }
+
+    /// Lifts the affine coordinates `(x, y)` to extended coordinates `(x : y : 1 : xy)`.
+    pub fn from_affine<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        x: BigNat<Scalar>,
+        y: BigNat<Scalar>,
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        let p = params.modulus::<Scalar, CS>()?;
+        let t = residue(&mut cs, "xy", &[Term::Product(&x, &y)], &p)?;
+        let z = params.constant::<Scalar, CS>(&BigInt::from(1))?;
+        Ok(Self::from_coords(x, y, z, t, params))
+    }
+
+    /// Creates a constant point.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        point: &NativeEdwardsPoint,
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        let (x, y, z, t) = params.to_extended(point);
+        Ok(Self::from_coords(
+            params.constant::<Scalar, CS>(&x)?,
+            params.constant::<Scalar, CS>(&y)?,
+            params.constant::<Scalar, CS>(&z)?,
+            params.constant::<Scalar, CS>(&t)?,
+            params,
+        ))
+    }
+
+    /// The identity `(0, 1)`, as a constant.
+    pub fn identity<CS: ConstraintSystem<Scalar>>(
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.identity(), params)
+    }
+
+    /// The generator of the prime order subgroup, as a constant.
+    pub fn generator<CS: ConstraintSystem<Scalar>>(
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.generator, params)
+    }
+
+    /// The affine point represented by `self` (filled at witness-time).
+    pub fn affine_value(&self) -> Option<NativeEdwardsPoint> {
+        self.value.as_ref().map(|v| self.params.to_affine(v))
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!("Curves do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Constrain `self` to satisfy the curve equation in extended coordinates,
+    /// `a*X^2 + Y^2 = Z^2 + d*T^2` and `X*Y = Z*T`.
+    pub fn assert_on_curve<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let a = params.constant::<Scalar, CS>(&params.a)?;
+        let d = params.constant::<Scalar, CS>(&params.d)?;
+        let xx = residue(&mut cs, "x^2", &[Term::Product(&self.x, &self.x)], &p)?;
+        let tt = residue(&mut cs, "t^2", &[Term::Product(&self.t, &self.t)], &p)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "curve equation"),
+            &[Term::Product(&a, &xx), Term::Product(&self.y, &self.y)],
+            &[Term::Product(&self.z, &self.z), Term::Product(&d, &tt)],
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "t"),
+            &[Term::Product(&self.x, &self.y)],
+            &[Term::Product(&self.z, &self.t)],
+            &p,
+        )
+    }
+
+    /// Computes `self + other` with the unified addition formulas:
+    /// `A = X1 X2`, `B = Y1 Y2`, `C = d T1 T2`, `D = Z1 Z2`, `E = X1 Y2 + Y1 X2`, `F = D - C`,
+    /// `G = D + C`, `H = B - a A`, and `(E F : G H : F G : E H)`.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let a = params.constant::<Scalar, CS>(&params.a)?;
+        let d = params.constant::<Scalar, CS>(&params.d)?;
+
+        let c_a = residue(&mut cs, "A", &[Term::Product(&self.x, &other.x)], &p)?;
+        let c_b = residue(&mut cs, "B", &[Term::Product(&self.y, &other.y)], &p)?;
+        let tt = residue(&mut cs, "T1T2", &[Term::Product(&self.t, &other.t)], &p)?;
+        let c_d = residue(&mut cs, "D", &[Term::Product(&self.z, &other.z)], &p)?;
+        let c_e = residue(
+            &mut cs,
+            "E",
+            &[
+                Term::Product(&self.x, &other.y),
+                Term::Product(&self.y, &other.x),
+            ],
+            &p,
+        )?;
+        // F + C = D
+        let c_f = BigNat::alloc_residue(
+            cs.namespace(|| "F"),
+            || Ok(c_d.value.grab()? - &params.d * tt.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "F check"),
+            &[Term::Single(&c_f), Term::Product(&d, &tt)],
+            &[Term::Single(&c_d)],
+            &p,
+        )?;
+        let c_g = residue(
+            &mut cs,
+            "G",
+            &[Term::Single(&c_d), Term::Product(&d, &tt)],
+            &p,
+        )?;
+        // H + a A = B
+        let c_h = BigNat::alloc_residue(
+            cs.namespace(|| "H"),
+            || Ok(c_b.value.grab()? - &params.a * c_a.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "H check"),
+            &[Term::Single(&c_h), Term::Product(&a, &c_a)],
+            &[Term::Single(&c_b)],
+            &p,
+        )?;
+
+        let x3 = residue(&mut cs, "x3", &[Term::Product(&c_e, &c_f)], &p)?;
+        let y3 = residue(&mut cs, "y3", &[Term::Product(&c_g, &c_h)], &p)?;
+        let z3 = residue(&mut cs, "z3", &[Term::Product(&c_f, &c_g)], &p)?;
+        let t3 = residue(&mut cs, "t3", &[Term::Product(&c_e, &c_h)], &p)?;
+        Ok(Self::from_coords(x3, y3, z3, t3, params))
+    }
+
+    /// Computes `2 * self`.
+    pub fn double<CS: ConstraintSystem<Scalar>>(&self, cs: CS) -> Result<Self, SynthesisError> {
+        self.add(cs, self)
+    }
+
+    /// Computes `-self = (-X : Y : Z : -T)`.
+    pub fn negate<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let x = self.x.neg_mod(cs.namespace(|| "x"), &p)?;
+        let t = self.t.neg_mod(cs.namespace(|| "t"), &p)?;
+        Ok(Self::from_coords(
+            x,
+            self.y.clone(),
+            self.z.clone(),
+            t,
+            &self.params,
+        ))
+    }
+
+    /// Computes `scalar * self`, where `scalar` is given by its (little-endian) bits, with fixed
+    /// windows of `window` bits: the multiples `d * self` for each digit `d < 2^window` are
+    /// tabulated, and each window costs `window` doublings, a `mux_tree` lookup and an addition.
+    /// Since the formulas are complete, `self` may be any point and no offset is needed.
+    pub fn scalar_mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        scalar: &Bitvector<Scalar>,
+        window: usize,
+    ) -> Result<Self, SynthesisError> {
+        let windows = windows(scalar, window)?;
+        let mut table = vec![Self::identity::<CS>(&self.params)?, self.clone()];
+        for d in 2..(1usize << window) {
+            let entry = table[d - 1].add(cs.namespace(|| format!("table {}", d)), self)?;
+            table.push(entry);
+        }
+        let mut acc: Option<Self> = None;
+        for (i, bits) in windows.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("window {}", i));
+            let entry = Gadget::mux_tree(
+                cs.namespace(|| "lookup"),
+                bits.iter(),
+                &table[..(1 << bits.len())],
+            )?;
+            acc = Some(match acc {
+                None => entry,
+                Some(mut acc) => {
+                    for j in 0..window {
+                        acc = acc.double(cs.namespace(|| format!("double {}", j)))?;
+                    }
+                    acc.add(cs.namespace(|| "add"), &entry)?
+                }
+            });
+        }
+        Ok(acc.unwrap())
+    }
+
+    /// Computes `scalar * base` for the constant point `base` (e.g. the generator), where
+    /// `scalar` is given by its (little-endian) bits, with fixed windows of `window` bits. Window
+    /// `i` selects from the constant table of the points `d * 2^(window * i) * base` with a
+    /// `ConstantSelector`, so no doublings are needed.
+    pub fn fixed_base_mul<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        base: &NativeEdwardsPoint,
+        scalar: &Bitvector<Scalar>,
+        window: usize,
+        params: &EdwardsParams,
+    ) -> Result<Self, SynthesisError> {
+        if window == 0 || scalar.bits.is_empty() {
+            eprintln!(
+                "Cannot split {} bits into windows of {}",
+                scalar.bits.len(),
+                window
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let one = params.constant::<Scalar, CS>(&BigInt::from(1))?;
+        let mut window_base = base.clone();
+        let mut acc: Option<Self> = None;
+        for (i, bits) in scalar_windows(scalar, window).enumerate() {
+            let mut cs = cs.namespace(|| format!("window {}", i));
+            let selector = ConstantSelector::new(cs.namespace(|| "selector"), &bits)?;
+            let mut entry = params.identity();
+            let mut table = Vec::with_capacity(selector.len());
+            for _ in 0..selector.len() {
+                table.push(params.to_extended(&entry));
+                entry = params.add(&entry, &window_base);
+            }
+            for _ in 0..window {
+                window_base = params.double(&window_base);
+            }
+            let select = |coordinate: fn(&NativeExtendedPoint) -> &BigInt| {
+                let column: Vec<BigInt> = table.iter().map(|e| coordinate(e).clone()).collect();
+                selector.select_nat(&column, params.limb_width, params.n_limbs)
+            };
+            let entry = Self::from_coords(
+                select(|e| &e.0)?,
+                select(|e| &e.1)?,
+                one.clone(),
+                select(|e| &e.3)?,
+                params,
+            );
+            acc = Some(match acc {
+                None => entry,
+                Some(acc) => acc.add(cs.namespace(|| "add"), &entry)?,
+            });
+        }
+        Ok(acc.unwrap())
+    }
+
+    /// Computes `cofactor * self`, which lies in the prime order subgroup. The cofactor must be
+    /// a power of two.
+    pub fn clear_cofactor<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let cofactor = self.params.cofactor;
+        if !cofactor.is_power_of_two() {
+            eprintln!("Cofactor {} is not a power of two", cofactor);
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut acc = self.clone();
+        for i in 0..cofactor.trailing_zeros() {
+            acc = acc.double(cs.namespace(|| format!("double {}", i)))?;
+        }
+        Ok(acc)
+    }
+
+    /// Computes the affine coordinates `(X/Z, Y/Z)`.
+    pub fn to_affine<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(BigNat<Scalar>, BigNat<Scalar>), SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let z_inv = self.z.inverse_mod(cs.namespace(|| "z inverse"), &p)?;
+        let x = residue(&mut cs, "x", &[Term::Product(&self.x, &z_inv)], &p)?;
+        let y = residue(&mut cs, "y", &[Term::Product(&self.y, &z_inv)], &p)?;
+        Ok((x, y))
+    }
+
+    /// Constrain `self` and `other` to represent the same point, i.e. `X1 Z2 = X2 Z1` and
+    /// `Y1 Z2 = Y2 Z1`.
+    pub fn assert_equivalent<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(other, "assert_equivalent")?;
+        let p = self.params.modulus::<Scalar, CS>()?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x"),
+            &[Term::Product(&self.x, &other.z)],
+            &[Term::Product(&other.x, &self.z)],
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "y"),
+            &[Term::Product(&self.y, &other.z)],
+            &[Term::Product(&other.y, &self.z)],
+            &p,
+        )
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedEdwardsPoint<Scalar> {
+    type Scalar = Scalar;
+    type Value = NativeExtendedPoint;
+    type Params = EdwardsParams;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        let mut alloc = |name: &str, f: fn(&NativeExtendedPoint) -> &BigInt| {
+            BigNat::alloc_from_nat(
+                cs.namespace(|| name.to_string()),
+                || Ok(f(value.grab()?).clone()),
+                params.limb_width,
+                params.n_limbs,
+            )
+        };
+        let x = alloc("x", |v| &v.0)?;
+        let y = alloc("y", |v| &v.1)?;
+        let z = alloc("z", |v| &v.2)?;
+        let t = alloc("t", |v| &v.3)?;
+        for (name, coord) in [("x", &x), ("y", &y), ("z", &z), ("t", &t)] {
+            coord.assert_well_formed(cs.namespace(|| format!("{} rangecheck", name)))?;
+        }
+        Ok(Self::from_coords(x, y, z, t, params))
+    }
+    fn value(&self) -> Option<&NativeExtendedPoint> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        let mut values = self.x.limb_values.clone()?;
+        values.extend(self.y.limb_values.as_ref()?.iter().cloned());
+        values.extend(self.z.limb_values.as_ref()?.iter().cloned());
+        values.extend(self.t.limb_values.as_ref()?.iter().cloned());
+        Some(values)
+    }
+    fn params(&self) -> &EdwardsParams {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        let mut wires = self.x.limbs.clone();
+        wires.extend(self.y.limbs.iter().cloned());
+        wires.extend(self.z.limbs.iter().cloned());
+        wires.extend(self.t.limbs.iter().cloned());
+        wires
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedEdwardsPoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some((x, y, z, t)) => {
+                write!(f, "EmulatedEdwardsPoint({} : {} : {} : {})", x, y, z, t)
+            }
+            None => write!(f, "EmulatedEdwardsPoint(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedEdwardsPoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedEdwardsPoint")
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("z", &self.z)
+            .field("t", &self.t)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::num::Num;
+    use crate::util::scalar::Bn254Fr;
+    use crate::util::test_helpers::*;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    #[test]
+    fn ed25519_base_point() {
+        let params = EdwardsParams::ed25519();
+        let x = BigInt::from_str_radix(
+            "15112221349535400772501151409588531511454012693041857206046113283949847762202",
+            10,
+        )
+        .unwrap();
+        assert_eq!(params.generator.0, x);
+        assert!(params.is_on_curve(&params.generator));
+        let l_minus_one = &params.order - 1;
+        let g = &params.generator;
+        assert_eq!(params.mul(&l_minus_one, g), params.negate(g));
+        assert_eq!(params.mul(&params.order, g), params.identity());
+    }
+
+    /// A point of order 4, `(sqrt(-1), 0)`.
+    fn torsion_point(params: &EdwardsParams) -> NativeEdwardsPoint {
+        let x = sqrt(&(&params.p - 1), &params.p).unwrap();
+        let point = (x, BigInt::from(0));
+        assert!(params.is_on_curve(&point));
+        point
+    }
+
+    fn assert_point<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        cs: CS,
+        point: &EmulatedEdwardsPoint<Scalar>,
+        expected: &NativeEdwardsPoint,
+    ) -> Result<(), SynthesisError> {
+        let expected = EmulatedEdwardsPoint::constant::<CS>(expected, &point.params)?;
+        point.assert_equivalent(cs, &expected)
+    }
+
+    pub struct EdwardsOpsInputs {
+        pub p: NativeEdwardsPoint,
+        pub q: NativeEdwardsPoint,
+        pub k: u64,
+    }
+
+    pub struct EdwardsOps {
+        inputs: Option<EdwardsOpsInputs>,
+        params: EdwardsParams,
+        n_bits: usize,
+        window: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for EdwardsOps {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let p = EmulatedEdwardsPoint::alloc_from_coords(
+                cs.namespace(|| "p"),
+                || Ok(inputs.p.clone()),
+                params,
+            )?;
+            let q = EmulatedEdwardsPoint::alloc_from_coords(
+                cs.namespace(|| "q"),
+                || Ok(inputs.q.clone()),
+                params,
+            )?;
+            p.assert_on_curve(cs.namespace(|| "p on curve"))?;
+            q.assert_on_curve(cs.namespace(|| "q on curve"))?;
+
+            let sum = p.add(cs.namespace(|| "p + q"), &q)?;
+            sum.assert_on_curve(cs.namespace(|| "sum on curve"))?;
+            assert_point(
+                cs.namespace(|| "sum"),
+                &sum,
+                &params.add(&inputs.p, &inputs.q),
+            )?;
+            let double = p.double(cs.namespace(|| "2p"))?;
+            assert_point(
+                cs.namespace(|| "double"),
+                &double,
+                &params.double(&inputs.p),
+            )?;
+            let zero = q
+                .negate(cs.namespace(|| "-q"))?
+                .add(cs.namespace(|| "q - q"), &q)?;
+            assert_point(cs.namespace(|| "zero"), &zero, &params.identity())?;
+
+            let k = Num::alloc(cs.namespace(|| "k"), || Ok(Scalar::from(inputs.k)))?;
+            let bits = k.decompose(cs.namespace(|| "decompose"), self.n_bits)?;
+            let product = p.scalar_mul(cs.namespace(|| "k * p"), &bits, self.window)?;
+            let k_big = BigInt::from(inputs.k);
+            assert_point(
+                cs.namespace(|| "product"),
+                &product,
+                &params.mul(&k_big, &inputs.p),
+            )?;
+            let fixed = EmulatedEdwardsPoint::fixed_base_mul(
+                cs.namespace(|| "k * g"),
+                &params.generator,
+                &bits,
+                self.window,
+                params,
+            )?;
+            assert_point(
+                cs.namespace(|| "fixed"),
+                &fixed,
+                &params.mul(&k_big, &params.generator),
+            )?;
+            let cleared = p.clear_cofactor(cs.namespace(|| "clear"))?;
+            assert_point(
+                cs.namespace(|| "cleared"),
+                &cleared,
+                &params.mul(&BigInt::from(params.cofactor), &inputs.p),
+            )?;
+            let (x, y) = sum.to_affine(cs.namespace(|| "affine"))?;
+            let expected = params.add(&inputs.p, &inputs.q);
+            x.equal(
+                cs.namespace(|| "x"),
+                &params.constant::<Scalar, CS>(&expected.0)?,
+            )?;
+            y.equal(
+                cs.namespace(|| "y"),
+                &params.constant::<Scalar, CS>(&expected.1)?,
+            )?;
+            Ok(())
+        }
+    }
+
+    /// `p` and `q` are multiples of the generator, with `p` shifted by a point of order 4 if
+    /// `torsion`.
+    fn edwards_ops(p: u64, q: u64, k: u64, torsion: bool, off_curve: bool) -> EdwardsOps {
+        let params = EdwardsParams::ed25519();
+        let g = &params.generator;
+        let mut p = params.mul(&BigInt::from(p), g);
+        if torsion {
+            p = params.add(&p, &torsion_point(&params));
+        }
+        let mut q = params.mul(&BigInt::from(q), g);
+        if off_curve {
+            q.1 += 1;
+        }
+        EdwardsOps {
+            inputs: Some(EdwardsOpsInputs { p, q, k }),
+            params,
+            n_bits: 8,
+            window: 3,
+        }
+    }
+
+    circuit_tests! {
+        edwards_ed25519_ops: (edwards_ops(3, 5, 0xa7, false, false), true),
+        edwards_ed25519_ops_torsion: (edwards_ops(2, 2, 0x3c, true, false), true),
+        edwards_ed25519_ops_zero: (edwards_ops(1, 9, 0, false, false), true),
+        edwards_ed25519_off_curve: (edwards_ops(3, 5, 0xa7, false, true), false),
+    }
+
+    #[test]
+    fn edwards_ed25519_ops_bn254() {
+        let mut cs = ValueConstraintSystem::<Bn254Fr>::new();
+        edwards_ops(3, 5, 0xa7, true, false)
+            .synthesize(&mut cs)
+            .unwrap();
+        assert_eq!(cs.which_is_unsatisfied(), None);
+    }
+
+    pub struct FullWidth {
+        k: BigInt,
+        fixed: bool,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for FullWidth {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = EdwardsParams::ed25519();
+            let k = BigNat::alloc_from_nat(cs.namespace(|| "k"), || Ok(self.k.clone()), 64, 4)?;
+            let bits = k.decompose(cs.namespace(|| "decompose"))?.truncate(253);
+            let g = &params.generator;
+            let product = if self.fixed {
+                EmulatedEdwardsPoint::fixed_base_mul(
+                    cs.namespace(|| "k * g"),
+                    g,
+                    &bits,
+                    4,
+                    &params,
+                )?
+            } else {
+                let p = EmulatedEdwardsPoint::alloc_from_coords(
+                    cs.namespace(|| "g"),
+                    || Ok(g.clone()),
+                    &params,
+                )?;
+                p.scalar_mul(cs.namespace(|| "k * p"), &bits, 4)?
+            };
+            assert_point(cs.namespace(|| "check"), &product, &params.mul(&self.k, g))
+        }
+    }
+
+    /// Reports the cost of full-width scalar multiplications.
+    #[test]
+    fn edwards_ed25519_scalar_mul_cost() {
+        let k = full_width_scalar(&EdwardsParams::ed25519().order);
+        report_full_width(
+            "ed25519 variable base, window 4",
+            FullWidth {
+                k: k.clone(),
+                fixed: false,
+            },
+        );
+        report_full_width("ed25519 fixed base, window 4", FullWidth { k, fixed: true });
+    }
+}
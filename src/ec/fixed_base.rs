@@ -0,0 +1,386 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+use super::affine::EmulatedAffinePoint;
+use super::projective::EmulatedProjectivePoint;
+use super::{CurveParams, NativePoint};
+use crate::mp::bignat::{nat_to_limbs, BigNat, BigNatParams};
+use crate::util::bit::{Bit, Bitvector};
+use crate::OptionExt;
+
+/// The seed of the first offset point used by fixed-base scalar multiplication. Window `i` uses
+/// the offset point with seed `FIXED_BASE_OFFSET_SEED + i`.
+const FIXED_BASE_OFFSET_SEED: u64 = 1 << 32;
+
+/// Selects among constants with the bits of a digit, without allocating for each selection.
+///
+/// A table indexed by the `w` bits of a digit is a multilinear polynomial in those bits. Once the
+/// products of all subsets of the bits are allocated (`2^w - w - 1` constraints), an entry of
+/// any table of constants is a linear combination of the products, so the selector can be reused
+/// for every coordinate and limb of a table at no further cost.
+pub struct ConstantSelector<Scalar: PrimeField> {
+    /// The product of the bits in each subset, indexed by the bitmask of the subset
+    monomials: Vec<LinearCombination<Scalar>>,
+    /// The digit (filled at witness-time)
+    index: Option<usize>,
+}
+
+impl<Scalar: PrimeField> ConstantSelector<Scalar> {
+    /// Creates a selector for the digit with (little-endian) bits `bits`.
+    pub fn new<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        bits: &[Bit<Scalar>],
+    ) -> Result<Self, SynthesisError> {
+        let mut monomials = vec![LinearCombination::zero() + CS::one()];
+        let mut values = vec![Some(true)];
+        for mask in 1..(1usize << bits.len()) {
+            let top = usize::BITS - 1 - mask.leading_zeros();
+            let rest = mask ^ (1 << top);
+            let bit = &bits[top as usize];
+            let (monomial, value) = if rest == 0 {
+                (bit.bit.clone(), bit.value)
+            } else {
+                let value = values[rest].and_then(|a| bit.value.map(|b| a && b));
+                let product = cs.alloc(
+                    || format!("product {}", mask),
+                    || {
+                        Ok(if *value.grab()? {
+                            Scalar::ONE
+                        } else {
+                            Scalar::ZERO
+                        })
+                    },
+                )?;
+                cs.enforce(
+                    || format!("product {} check", mask),
+                    |lc| lc + &monomials[rest],
+                    |lc| lc + &bit.bit,
+                    |lc| lc + product,
+                );
+                (LinearCombination::zero() + product, value)
+            };
+            monomials.push(monomial);
+            values.push(value);
+        }
+        let index = bits
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b.value.map(|b| (b as usize) << i))
+            .sum();
+        Ok(Self { monomials, index })
+    }
+
+    /// The number of entries of the tables this selects from.
+    pub fn len(&self) -> usize {
+        self.monomials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.monomials.is_empty()
+    }
+
+    /// The selected index (filled at witness-time)
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The linear combination equal to `table[index]`, and its value.
+    pub fn select(
+        &self,
+        table: &[Scalar],
+    ) -> Result<(LinearCombination<Scalar>, Option<Scalar>), SynthesisError> {
+        if table.len() != self.len() {
+            eprintln!(
+                "Cannot select from {} entries with {} bits",
+                table.len(),
+                self.len().trailing_zeros()
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        // The coefficients of the multilinear polynomial, by Möbius inversion over subsets
+        let mut coefficients = table.to_vec();
+        for j in 0..self.len().trailing_zeros() {
+            for mask in 0..self.len() {
+                if mask & (1 << j) != 0 {
+                    let lower = coefficients[mask ^ (1 << j)];
+                    coefficients[mask] -= lower;
+                }
+            }
+        }
+        let lc = coefficients
+            .iter()
+            .zip(&self.monomials)
+            .filter(|(c, _)| !bool::from(c.is_zero()))
+            .fold(LinearCombination::zero(), |lc, (c, m)| lc + (*c, m));
+        Ok((lc, self.index.map(|i| table[i])))
+    }
+
+    /// The `BigNat` equal to `table[index]`. Its limbs are linear combinations of the allocated
+    /// products.
+    pub fn select_nat(
+        &self,
+        table: &[BigInt],
+        limb_width: usize,
+        n_limbs: usize,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        let table_limbs = table
+            .iter()
+            .map(|v| nat_to_limbs::<Scalar>(v, limb_width, n_limbs))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (limbs, limb_values): (Vec<_>, Vec<_>) = (0..n_limbs)
+            .map(|i| {
+                let column: Vec<Scalar> = table_limbs.iter().map(|limbs| limbs[i]).collect();
+                self.select(&column)
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        Ok(BigNat {
+            limbs,
+            limb_values: limb_values.into_iter().collect(),
+            value: self.index.map(|i| table[i].clone()),
+            params: BigNatParams::new(limb_width, n_limbs),
+        })
+    }
+}
+
+impl<Scalar: PrimeField> EmulatedAffinePoint<Scalar> {
+    /// Selects `table[index]` from a table of constant points, without allocating.
+    pub fn select_constant(
+        selector: &ConstantSelector<Scalar>,
+        table: &[(BigInt, BigInt)],
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        let (xs, ys): (Vec<BigInt>, Vec<BigInt>) = table.iter().cloned().unzip();
+        Ok(Self::from_coords(
+            selector.select_nat(&xs, params.limb_width, params.n_limbs)?,
+            selector.select_nat(&ys, params.limb_width, params.n_limbs)?,
+            params,
+        ))
+    }
+
+    /// Computes `scalar * base` for the constant point `base` (e.g. the generator), where
+    /// `scalar` is given by its (little-endian) bits, with fixed windows of `window` bits.
+    ///
+    /// Window `i` selects from the constant table of the points `d * 2^(window * i) * base + R_i`,
+    /// for each digit `d` and an offset point `R_i` of unknown discrete logarithm, with a
+    /// `ConstantSelector`, so no doublings are needed and the only allocations are the products
+    /// of the window bits and one incomplete addition per window. As for `scalar_mul`, the
+    /// offsets keep the accumulator away from exceptional cases, and their sum is removed at the
+    /// end with the complete projective formulas.
+    pub fn fixed_base_mul<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        base: &(BigInt, BigInt),
+        scalar: &Bitvector<Scalar>,
+        window: usize,
+        params: &CurveParams,
+    ) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+        let tables = fixed_base_tables(
+            params,
+            base,
+            scalar.bits.len(),
+            window,
+            FIXED_BASE_OFFSET_SEED,
+        )?;
+        let mut acc: Option<Self> = None;
+        for (i, (bits, table)) in scalar_windows(scalar, window).zip(&tables).enumerate() {
+            let mut cs = cs.namespace(|| format!("window {}", i));
+            let selector = ConstantSelector::new(cs.namespace(|| "selector"), &bits)?;
+            let entry = Self::select_constant(&selector, &table[..selector.len()], params)?;
+            acc = Some(match acc {
+                None => entry,
+                Some(acc) => acc.add(cs.namespace(|| "add"), &entry)?,
+            });
+        }
+
+        // acc = scalar * base + sum_i R_i
+        let offsets = fixed_base_offsets(params, tables.len(), FIXED_BASE_OFFSET_SEED);
+        let correction = EmulatedProjectivePoint::constant::<CS>(
+            &params.to_projective(&params.negate(&offsets)),
+            params,
+        )?;
+        let acc = EmulatedProjectivePoint::from_affine::<CS>(&acc.unwrap())?;
+        acc.add(cs.namespace(|| "remove offset"), &correction)
+    }
+}
+
+/// The (little-endian) bits of each window of `scalar`, from the least significant window up.
+pub(crate) fn scalar_windows<Scalar: PrimeField>(
+    scalar: &Bitvector<Scalar>,
+    window: usize,
+) -> impl Iterator<Item = Vec<Bit<Scalar>>> + '_ {
+    (0..scalar.bits.len()).step_by(window).map(move |start| {
+        (start..(start + window).min(scalar.bits.len()))
+            .map(|i| scalar.get(i).unwrap())
+            .collect()
+    })
+}
+
+/// The sum of the offset points `R_i` of the tables of `n_windows` windows, where `R_i` has seed
+/// `seed + i`.
+pub(crate) fn fixed_base_offsets(params: &CurveParams, n_windows: usize, seed: u64) -> NativePoint {
+    (0..n_windows)
+        .map(|i| Some(params.offset_point(seed + i as u64)))
+        .fold(None, |acc, r| params.add(&acc, &r))
+}
+
+/// The tables `d * 2^(window * i) * base + R_i` for each window `i` of an `n_bits`-bit scalar,
+/// where `R_i` is the offset point with seed `seed + i`.
+pub(crate) fn fixed_base_tables(
+    params: &CurveParams,
+    base: &(BigInt, BigInt),
+    n_bits: usize,
+    window: usize,
+    seed: u64,
+) -> Result<Vec<Vec<(BigInt, BigInt)>>, SynthesisError> {
+    if window == 0 || n_bits == 0 {
+        eprintln!("Cannot split {} bits into windows of {}", n_bits, window);
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let mut window_base = Some(base.clone());
+    (0..n_bits.div_ceil(window))
+        .map(|i| {
+            let mut entry = Some(params.offset_point(seed + i as u64));
+            let mut table = Vec::with_capacity(1 << window);
+            for _ in 0..(1 << window) {
+                table.push(entry.clone().grab()?.clone());
+                entry = params.add(&entry, &window_base);
+            }
+            for _ in 0..window {
+                window_base = params.double(&window_base);
+            }
+            Ok(table)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp::bignat::BigNat;
+    use crate::util::test_helpers::*;
+
+    pub struct FixedBaseMulInputs {
+        pub k: BigInt,
+        pub expected: NativePoint,
+    }
+
+    pub struct FixedBaseMul {
+        inputs: Option<FixedBaseMulInputs>,
+        params: CurveParams,
+        n_bits: usize,
+        window: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for FixedBaseMul {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let k = BigNat::alloc_from_nat(
+                cs.namespace(|| "k"),
+                || Ok(inputs.k.clone()),
+                params.limb_width,
+                params.n_limbs,
+            )?;
+            let bits = k
+                .decompose(cs.namespace(|| "decompose"))?
+                .truncate(self.n_bits);
+            let product = EmulatedAffinePoint::fixed_base_mul(
+                cs.namespace(|| "k * g"),
+                &params.generator,
+                &bits,
+                self.window,
+                params,
+            )?;
+            let expected = EmulatedProjectivePoint::constant::<CS>(
+                &params.to_projective(&inputs.expected),
+                params,
+            )?;
+            product.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    fn fixed_base_mul(
+        params: CurveParams,
+        k: BigInt,
+        n_bits: usize,
+        window: usize,
+        honest: bool,
+    ) -> FixedBaseMul {
+        let g = Some(params.generator.clone());
+        let mut expected = params.mul(&k, &g);
+        if !honest {
+            expected = params.add(&expected, &g);
+        }
+        FixedBaseMul {
+            inputs: Some(FixedBaseMulInputs { k, expected }),
+            params,
+            n_bits,
+            window,
+        }
+    }
+
+    pub struct Select {
+        digit: u64,
+        window: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Select {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let bits = (0..self.window)
+                .map(|i| {
+                    Bit::alloc(
+                        cs.namespace(|| format!("bit {}", i)),
+                        Some((self.digit >> i) & 1 == 1),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let selector = ConstantSelector::new(cs.namespace(|| "selector"), &bits)?;
+            let table: Vec<BigInt> = (0..selector.len() as u64)
+                .map(|d| (BigInt::from(d * d + 7) << 100u32) + d)
+                .collect();
+            let selected = selector.select_nat(&table, 32, 4)?;
+            let expected = BigNat::constant::<CS>(&table[self.digit as usize], 32, 4)?;
+            selected.equal(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    circuit_tests! {
+        select_constant_w1: (Select { digit: 1, window: 1 }, true),
+        select_constant_w3: (Select { digit: 5, window: 3 }, true),
+        select_constant_w4: (Select { digit: 14, window: 4 }, true),
+        fixed_base_mul_secp256k1: (fixed_base_mul(CurveParams::secp256k1(), BigInt::from(0xb3c5), 16, 4, true), true),
+        fixed_base_mul_secp256k1_zero: (fixed_base_mul(CurveParams::secp256k1(), BigInt::from(0), 7, 3, true), true),
+        fixed_base_mul_curve25519: (fixed_base_mul(CurveParams::curve25519(), BigInt::from(0x2d), 6, 4, true), true),
+        fixed_base_mul_secp256k1_wrong: (fixed_base_mul(CurveParams::secp256k1(), BigInt::from(0x2b), 6, 2, false), false),
+    }
+
+    /// A fixed-base scalar multiplication by a full-width scalar.
+    fn full_width(params: CurveParams, window: usize) -> FixedBaseMul {
+        let k = full_width_scalar(&params.order);
+        fixed_base_mul(params, k, 256, window, true)
+    }
+
+    #[test]
+    fn fixed_base_mul_secp256k1_cost() {
+        report_full_width(
+            "secp256k1 fixed base, window 4",
+            full_width(CurveParams::secp256k1(), 4),
+        );
+    }
+
+    #[test]
+    fn fixed_base_mul_curve25519_cost() {
+        report_full_width(
+            "curve25519 fixed base, window 4",
+            full_width(CurveParams::curve25519(), 4),
+        );
+    }
+}
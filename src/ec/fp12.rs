@@ -0,0 +1,869 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::ToPrimitive;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::fp2::{Fp2Params, NativeFp2};
+use super::residue;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, Term};
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// The coefficients of an element `a_0 + a_1 w + ... + a_11 w^11` of `Fp12`, computed natively.
+pub type NativeFp12 = Vec<BigInt>;
+
+/// Parameters of the extension `Fp12 = Fp2[w] / (w^6 - xi)` of an emulated prime field, for a
+/// sextic non-residue `xi = xi0 + u` of `Fp2`.
+///
+/// Elements are represented over the base field, as polynomials of degree less than 12 in `w`:
+/// since `u = w^6 - xi0`, `w` is a root of `w^12 - 2 xi0 w^6 + (xi0^2 + 1)`, whose coefficients
+/// are small. This keeps the reduction of products cheap to emulate. `Fp6 = Fp2[w^2]` is the
+/// subfield of elements with only even powers of `w`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Fp12Params {
+    pub fp2: Fp2Params,
+    /// The sextic non-residue of `Fp2` equal to `w^6`
+    pub xi: NativeFp2,
+}
+
+impl Fp12Params {
+    /// Panics unless the `u` coefficient of `xi` is one.
+    pub fn new(fp2: Fp2Params, xi: NativeFp2) -> Self {
+        assert_eq!(xi.1, BigInt::from(1), "Only xi = xi0 + u is supported");
+        Fp12Params { fp2, xi }
+    }
+
+    /// The modulus of the base field, as a constant.
+    pub fn modulus<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        self.fp2.modulus::<Scalar, CS>()
+    }
+
+    /// The (unreduced, integer) coefficients of `w^d` for `d < 23`, the degrees of the products
+    /// of two elements.
+    pub fn reduction(&self) -> Vec<Vec<BigInt>> {
+        let xi0 = &self.xi.0;
+        let mut rows: Vec<Vec<BigInt>> = Vec::new();
+        for d in 0..23 {
+            let mut row = vec![BigInt::from(0); 12];
+            if d < 12 {
+                row[d] = BigInt::from(1);
+            } else {
+                // w^d = w * w^(d - 1), and w^12 = 2 xi0 w^6 - (xi0^2 + 1)
+                let prev = &rows[d - 1];
+                row[1..12].clone_from_slice(&prev[0..11]);
+                row[6] += &prev[11] * xi0 * 2u32;
+                row[0] -= &prev[11] * (xi0 * xi0 + 1u32);
+            }
+            rows.push(row);
+        }
+        rows
+    }
+
+    /// Reduces a polynomial in `w` of degree less than 23.
+    pub fn reduce(&self, a: &[BigInt]) -> NativeFp12 {
+        let mut out = vec![BigInt::from(0); 12];
+        for (coeff, row) in a.iter().zip(self.reduction()) {
+            for (o, r) in out.iter_mut().zip(row) {
+                *o += coeff * r;
+            }
+        }
+        out.iter().map(|o| o.mod_floor(&self.fp2.p)).collect()
+    }
+
+    pub fn from_int(&self, n: i64) -> NativeFp12 {
+        self.reduce(&[BigInt::from(n)])
+    }
+
+    /// Embeds `a0 + a1 u = (a0 - xi0 a1) + a1 w^6`.
+    pub fn from_fp2(&self, (a0, a1): &NativeFp2) -> NativeFp12 {
+        let mut a = vec![BigInt::from(0); 7];
+        a[0] = a0 - &self.xi.0 * a1;
+        a[6] = a1.clone();
+        self.reduce(&a)
+    }
+
+    pub fn add(&self, a: &[BigInt], b: &[BigInt]) -> NativeFp12 {
+        let sum: Vec<BigInt> = a.iter().zip(b).map(|(a, b)| a + b).collect();
+        self.reduce(&sum)
+    }
+
+    pub fn sub(&self, a: &[BigInt], b: &[BigInt]) -> NativeFp12 {
+        let diff: Vec<BigInt> = a.iter().zip(b).map(|(a, b)| a - b).collect();
+        self.reduce(&diff)
+    }
+
+    pub fn mul(&self, a: &[BigInt], b: &[BigInt]) -> NativeFp12 {
+        let mut product = vec![BigInt::from(0); 23];
+        for (i, a) in a.iter().enumerate() {
+            for (j, b) in b.iter().enumerate() {
+                product[i + j] += a * b;
+            }
+        }
+        self.reduce(&product)
+    }
+
+    pub fn square(&self, a: &[BigInt]) -> NativeFp12 {
+        self.mul(a, a)
+    }
+
+    /// The image `a(-w)` of `a` under `a -> a^(p^6)`, which is the inverse of `a` in the
+    /// cyclotomic subgroup.
+    pub fn conjugate(&self, a: &[BigInt]) -> NativeFp12 {
+        let a: Vec<BigInt> = a
+            .iter()
+            .enumerate()
+            .map(|(i, c)| if i % 2 == 1 { -c } else { c.clone() })
+            .collect();
+        self.reduce(&a)
+    }
+
+    /// Whether `a` is in the subfield `Fp6`, i.e. has no odd powers of `w`.
+    pub fn is_in_fp6(&self, a: &[BigInt]) -> bool {
+        self.reduce(a)
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|c| *c == BigInt::from(0))
+    }
+
+    /// The inverse of `a`, if `a` is non-zero, found by solving the linear system `a * b = 1`.
+    pub fn inverse(&self, a: &[BigInt]) -> Option<NativeFp12> {
+        let p = &self.fp2.p;
+        // Column `j` of the matrix of the multiplication by `a` is `a * w^j`, followed by the
+        // right-hand side
+        let columns: Vec<NativeFp12> = (0..12)
+            .map(|j| {
+                let mut monomial = vec![BigInt::from(0); j + 1];
+                monomial[j] = BigInt::from(1);
+                self.mul(a, &monomial)
+            })
+            .collect();
+        let mut rows: Vec<Vec<BigInt>> = (0..12)
+            .map(|i| {
+                let mut row: Vec<BigInt> = columns.iter().map(|c| c[i].clone()).collect();
+                row.push(BigInt::from((i == 0) as u64));
+                row
+            })
+            .collect();
+        for col in 0..12 {
+            let pivot = (col..12).find(|&i| rows[i][col] != BigInt::from(0))?;
+            rows.swap(col, pivot);
+            let inv = invert(&rows[col][col], p).ok()?;
+            for v in rows[col].iter_mut() {
+                *v = (&*v * &inv).mod_floor(p);
+            }
+            let pivot_row = rows[col].clone();
+            for (i, row) in rows.iter_mut().enumerate() {
+                if i != col && row[col] != BigInt::from(0) {
+                    let factor = row[col].clone();
+                    for (v, pv) in row.iter_mut().zip(&pivot_row) {
+                        *v = (&*v - &factor * pv).mod_floor(p);
+                    }
+                }
+            }
+        }
+        Some(rows.into_iter().map(|row| row[12].clone()).collect())
+    }
+
+    /// Computes `a^e` by square-and-multiply. A negative `e` inverts `a`.
+    pub fn pow(&self, a: &[BigInt], e: &BigInt) -> NativeFp12 {
+        let base = if e.sign() == Sign::Minus {
+            self.inverse(a).unwrap()
+        } else {
+            self.reduce(a)
+        };
+        let mut acc = self.from_int(1);
+        for i in (0..e.bits()).rev() {
+            acc = self.square(&acc);
+            if e.magnitude().bit(i) {
+                acc = self.mul(&acc, &base);
+            }
+        }
+        acc
+    }
+
+    /// The matrix of the Frobenius map `a -> a^(p^k)`, which is linear over the base field: its
+    /// column `i` is `(w^i)^(p^k) = (gamma w)^i`, where `gamma = xi^((p^k - 1) / 6)` is in
+    /// `Fp2`.
+    pub fn frobenius_matrix(&self, k: usize) -> Vec<Vec<BigInt>> {
+        let f = &self.fp2;
+        let exponent = (f.p.pow(k as u32) - 1u32) / 6u32;
+        let gamma = f.pow(&self.xi, &exponent.mod_floor(&(&f.p * &f.p - 1u32)));
+        let w_gamma = self.mul(&self.from_fp2(&gamma), &[BigInt::from(0), BigInt::from(1)]);
+        let mut columns = vec![self.from_int(1)];
+        for i in 1..12 {
+            columns.push(self.mul(&columns[i - 1], &w_gamma));
+        }
+        (0..12)
+            .map(|i| columns.iter().map(|c| c[i].clone()).collect())
+            .collect()
+    }
+
+    /// Computes `a^(p^k)`.
+    pub fn frobenius(&self, a: &[BigInt], k: usize) -> NativeFp12 {
+        let a = self.reduce(a);
+        let out: Vec<BigInt> = self
+            .frobenius_matrix(k)
+            .iter()
+            .map(|row| row.iter().zip(&a).map(|(m, a)| m * a).sum())
+            .collect();
+        self.reduce(&out)
+    }
+}
+
+impl Debug for Fp12Params {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Fp12Params")
+            .field("fp2", &self.fp2)
+            .field("xi0", &format_args!("{}", &self.xi.0))
+            .finish()
+    }
+}
+
+/// The sums of products making up each coefficient of a product, split into the products with
+/// positive and negative multiplicities. The multiplicity is applied by scaling the first factor.
+struct ProductRow<'a, Scalar: PrimeField> {
+    positive: Vec<(BigNat<Scalar>, &'a BigNat<Scalar>)>,
+    negative: Vec<(BigNat<Scalar>, &'a BigNat<Scalar>)>,
+}
+
+impl<'a, Scalar: PrimeField> ProductRow<'a, Scalar> {
+    fn terms<'b>(side: &'b [(BigNat<Scalar>, &'b BigNat<Scalar>)]) -> Vec<Term<'b, Scalar>> {
+        side.iter().map(|(a, b)| Term::Product(a, b)).collect()
+    }
+}
+
+/// The rows of the product of two elements given by their non-zero coefficients `(i, a_i)`.
+/// `a_i b_j` contributes to the coefficients of the reduction of `w^(i + j)`, with its small
+/// integer coefficients as multiplicities. For a square, the products `a_i a_j` and `a_j a_i` are
+/// merged.
+fn product_rows<'a, Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    params: &Fp12Params,
+    a: &[(usize, &'a BigNat<Scalar>)],
+    b: &[(usize, &'a BigNat<Scalar>)],
+    square: bool,
+) -> Vec<ProductRow<'a, Scalar>> {
+    let reduction = params.reduction();
+    let mut rows: Vec<ProductRow<Scalar>> = (0..12)
+        .map(|_| ProductRow {
+            positive: Vec::new(),
+            negative: Vec::new(),
+        })
+        .collect();
+    for (x, &(i, a_i)) in a.iter().enumerate() {
+        for (y, &(j, b_j)) in b.iter().enumerate() {
+            if square && y < x {
+                continue;
+            }
+            let multiplicity = if square && y > x { 2u64 } else { 1 };
+            for (row, m) in rows.iter_mut().zip(&reduction[i + j]) {
+                let m = m * multiplicity;
+                if m == BigInt::from(0) {
+                    continue;
+                }
+                let scaled = match m.magnitude().to_u64().unwrap() {
+                    1 => a_i.clone(),
+                    k => a_i.scale::<CS>(Scalar::from(k)),
+                };
+                if m.sign() == Sign::Minus {
+                    row.negative.push((scaled, b_j));
+                } else {
+                    row.positive.push((scaled, b_j));
+                }
+            }
+        }
+    }
+    rows
+}
+
+/// An element of `Fp12` with some coefficients known to be zero, such as a line evaluation, for
+/// cheaper multiplications (see `EmulatedFp12::mul_by_sparse`).
+#[derive(Clone)]
+pub struct SparseFp12<Scalar: PrimeField> {
+    /// The coefficients which may be non-zero, with their powers of `w`
+    pub coeffs: Vec<(usize, BigNat<Scalar>)>,
+    /// The reduced value of the element (filled at witness-time)
+    pub value: Option<NativeFp12>,
+    /// Parameters
+    pub params: Fp12Params,
+}
+
+impl<Scalar: PrimeField> SparseFp12<Scalar> {
+    pub fn new(coeffs: Vec<(usize, BigNat<Scalar>)>, params: &Fp12Params) -> Self {
+        let value = coeffs
+            .iter()
+            .try_fold(params.from_int(0), |mut acc, (i, c)| {
+                acc[*i] += c.value.as_ref()?;
+                Some(acc)
+            })
+            .map(|acc| params.reduce(&acc));
+        Self {
+            coeffs,
+            value,
+            params: params.clone(),
+        }
+    }
+}
+
+/// An element of the extension `Fp12` of an emulated prime field (see `Fp12Params`).
+///
+/// The coefficients are residues: they have as many limbs as the modulus, each range checked, but
+/// are not necessarily smaller than the modulus.
+#[derive(Clone)]
+pub struct EmulatedFp12<Scalar: PrimeField> {
+    /// The coefficients of `1, w, ..., w^11`
+    pub coeffs: Vec<BigNat<Scalar>>,
+    /// The reduced value of the element (filled at witness-time)
+    pub value: Option<NativeFp12>,
+    /// Parameters
+    pub params: Fp12Params,
+}
+
+impl<Scalar: PrimeField> EmulatedFp12<Scalar> {
+    pub(crate) fn from_coeffs(coeffs: Vec<BigNat<Scalar>>, params: &Fp12Params) -> Self {
+        let value = coeffs
+            .iter()
+            .map(|c| c.value.clone())
+            .collect::<Option<Vec<BigInt>>>()
+            .map(|v| params.reduce(&v));
+        Self {
+            coeffs,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// Allocates an element with range checked coefficients.
+    /// The value is provided by a closure returning the coefficients.
+    pub fn alloc_from_coeffs<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &Fp12Params,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<NativeFp12, SynthesisError>,
+    {
+        let value = f().ok().map(|v| params.reduce(&v));
+        let p = params.modulus::<Scalar, CS>()?;
+        let coeffs = (0..12)
+            .map(|i| {
+                BigNat::alloc_residue(
+                    cs.namespace(|| format!("c{}", i)),
+                    || Ok(value.grab()?[i].clone()),
+                    &p,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+
+    /// Creates a constant element.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        value: &[BigInt],
+        params: &Fp12Params,
+    ) -> Result<Self, SynthesisError> {
+        let coeffs = params
+            .reduce(value)
+            .iter()
+            .map(|c| BigNat::constant::<CS>(c, params.fp2.limb_width, params.fp2.n_limbs))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+
+    pub fn one<CS: ConstraintSystem<Scalar>>(params: &Fp12Params) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.from_int(1), params)
+    }
+
+    /// Converts a sparse element, with constant zero coefficients.
+    pub fn from_sparse<CS: ConstraintSystem<Scalar>>(
+        sparse: &SparseFp12<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        let params = &sparse.params;
+        let mut coeffs = (0..12)
+            .map(|_| {
+                BigNat::constant::<CS>(&BigInt::from(0), params.fp2.limb_width, params.fp2.n_limbs)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (i, c) in &sparse.coeffs {
+            coeffs[*i] = c.clone();
+        }
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+
+    fn enforce_params_agreement(
+        &self,
+        other: &Fp12Params,
+        location: &str,
+    ) -> Result<(), SynthesisError> {
+        if self.params == *other {
+            Ok(())
+        } else {
+            eprintln!("Fields do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    fn indexed_coeffs(&self) -> Vec<(usize, &BigNat<Scalar>)> {
+        self.coeffs.iter().enumerate().collect()
+    }
+
+    /// Allocates the product of two elements given by their non-zero coefficients, constraining
+    /// each coefficient with `BigNat::assert_sums_congruent`.
+    fn alloc_product<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        a: &[(usize, &BigNat<Scalar>)],
+        b: &[(usize, &BigNat<Scalar>)],
+        value: Option<NativeFp12>,
+        square: bool,
+        params: &Fp12Params,
+    ) -> Result<Self, SynthesisError> {
+        let p = params.modulus::<Scalar, CS>()?;
+        let rows = product_rows::<Scalar, CS>(params, a, b, square);
+        let mut coeffs = Vec::new();
+        for (k, row) in rows.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("c{}", k));
+            let c = BigNat::alloc_residue(
+                cs.namespace(|| "residue"),
+                || Ok(value.grab()?[k].clone()),
+                &p,
+            )?;
+            let mut rhs = ProductRow::terms(&row.negative);
+            rhs.push(Term::Single(&c));
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "check"),
+                &ProductRow::terms(&row.positive),
+                &rhs,
+                &p,
+            )?;
+            coeffs.push(c);
+        }
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+
+    /// Computes `self * other`.
+    pub fn mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(&other.params, "mul")?;
+        let value = self
+            .value
+            .as_ref()
+            .and_then(|a| other.value.as_ref().map(|b| self.params.mul(a, b)));
+        Self::alloc_product(
+            cs,
+            &self.indexed_coeffs(),
+            &other.indexed_coeffs(),
+            value,
+            false,
+            &self.params,
+        )
+    }
+
+    /// Computes `self^2`, merging the symmetric products.
+    pub fn square<CS: ConstraintSystem<Scalar>>(&self, cs: CS) -> Result<Self, SynthesisError> {
+        let value = self.value.as_ref().map(|a| self.params.square(a));
+        let coeffs = self.indexed_coeffs();
+        Self::alloc_product(cs, &coeffs, &coeffs, value, true, &self.params)
+    }
+
+    /// Computes `self * other`, skipping the coefficients of `other` known to be zero.
+    pub fn mul_by_sparse<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+        other: &SparseFp12<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(&other.params, "mul_by_sparse")?;
+        let value = self
+            .value
+            .as_ref()
+            .and_then(|a| other.value.as_ref().map(|b| self.params.mul(a, b)));
+        let b: Vec<(usize, &BigNat<Scalar>)> = other.coeffs.iter().map(|(i, c)| (*i, c)).collect();
+        Self::alloc_product(cs, &self.indexed_coeffs(), &b, value, false, &self.params)
+    }
+
+    /// Computes the inverse of `self`. This also proves that `self` is non-zero.
+    pub fn inverse<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let inverse = Self::alloc_from_coeffs(
+            cs.namespace(|| "inverse"),
+            || params.inverse(self.value.grab()?).grab().cloned(),
+            params,
+        )?;
+        let product = self.mul(cs.namespace(|| "product"), &inverse)?;
+        product.assert_equal(cs.namespace(|| "check"), &Self::one::<CS>(params)?)?;
+        Ok(inverse)
+    }
+
+    /// Computes the conjugate `self(-w) = self^(p^6)`, by negating the odd coefficients.
+    pub fn conjugate<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let coeffs = self
+            .coeffs
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                if i % 2 == 1 {
+                    c.neg_mod(cs.namespace(|| format!("c{}", i)), &p)
+                } else {
+                    Ok(c.clone())
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_coeffs(coeffs, &self.params))
+    }
+
+    /// Computes `self^(p^k)`, a linear map with constant coefficients.
+    pub fn frobenius<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        k: usize,
+    ) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        if k % 12 == 6 {
+            return self.conjugate(cs);
+        }
+        let p = params.modulus::<Scalar, CS>()?;
+        let matrix = params.frobenius_matrix(k);
+        let mut coeffs = Vec::new();
+        for (i, row) in matrix.iter().enumerate() {
+            let constants = row
+                .iter()
+                .map(|m| BigNat::constant::<CS>(m, params.fp2.limb_width, params.fp2.n_limbs))
+                .collect::<Result<Vec<_>, _>>()?;
+            let terms: Vec<Term<Scalar>> = constants
+                .iter()
+                .zip(&self.coeffs)
+                .zip(row)
+                .filter(|(_, m)| **m != BigInt::from(0))
+                .map(|((m, c), v)| {
+                    if *v == BigInt::from(1) {
+                        Term::Single(c)
+                    } else {
+                        Term::Product(m, c)
+                    }
+                })
+                .collect();
+            coeffs.push(residue(&mut cs, &format!("c{}", i), &terms, &p)?);
+        }
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+
+    /// Computes `self^e` for a constant `e` by square-and-multiply, where `self` is in the
+    /// cyclotomic subgroup (of order `p^4 - p^2 + 1`), so that a negative `e` is handled by
+    /// conjugating.
+    pub fn cyclotomic_pow<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        e: &BigInt,
+    ) -> Result<Self, SynthesisError> {
+        let bits = e.bits();
+        if bits == 0 {
+            return Self::one::<CS>(&self.params);
+        }
+        let mut acc = self.clone();
+        for i in (0..(bits - 1)).rev() {
+            acc = acc.square(cs.namespace(|| format!("square {}", i)))?;
+            if e.magnitude().bit(i) {
+                acc = acc.mul(cs.namespace(|| format!("mul {}", i)), self)?;
+            }
+        }
+        if e.sign() == Sign::Minus {
+            acc = acc.conjugate(cs.namespace(|| "conjugate"))?;
+        }
+        Ok(acc)
+    }
+
+    /// Constrain `self` to be in the subfield `Fp6`, i.e. its odd coefficients to be zero.
+    pub fn assert_in_fp6<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        for (i, c) in self.coeffs.iter().enumerate().skip(1).step_by(2) {
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| format!("c{}", i)),
+                &[Term::Single(c)],
+                &[],
+                &p,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Constrain `self` and `other` to be congruent, i.e. to represent the same element.
+    pub fn assert_equal<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(&other.params, "assert_equal")?;
+        let p = self.params.modulus::<Scalar, CS>()?;
+        for (i, (a, b)) in self.coeffs.iter().zip(&other.coeffs).enumerate() {
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| format!("c{}", i)),
+                &[Term::Single(a)],
+                &[Term::Single(b)],
+                &p,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedFp12<Scalar> {
+    type Scalar = Scalar;
+    type Value = NativeFp12;
+    type Params = Fp12Params;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        let coeffs = (0..12)
+            .map(|i| {
+                BigNat::alloc_from_nat(
+                    cs.namespace(|| format!("c{}", i)),
+                    || Ok(value.grab()?[i].clone()),
+                    params.fp2.limb_width,
+                    params.fp2.n_limbs,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_coeffs(coeffs, params))
+    }
+    fn value(&self) -> Option<&NativeFp12> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        let mut values = Vec::new();
+        for c in &self.coeffs {
+            values.extend(c.limb_values.as_ref()?.iter().cloned());
+        }
+        Some(values)
+    }
+    fn params(&self) -> &Fp12Params {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        self.coeffs
+            .iter()
+            .flat_map(|c| c.limbs.iter().cloned())
+            .collect()
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedFp12<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some(coeffs) => {
+                write!(f, "EmulatedFp12(")?;
+                for (i, c) in coeffs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " + ")?;
+                    }
+                    write!(f, "{} w^{}", c, i)?;
+                }
+                write!(f, ")")
+            }
+            None => write!(f, "EmulatedFp12(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedFp12<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedFp12")
+            .field("coeffs", &self.coeffs)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::g2::G2Params;
+    use crate::util::test_helpers::*;
+
+    /// An element with large coefficients, from a small seed.
+    fn element(params: &Fp12Params, seed: i64) -> NativeFp12 {
+        let p = &params.fp2.p;
+        let a: Vec<BigInt> = (0..12)
+            .map(|i| BigInt::from(seed * (i + 1) - 7 * i * i) * (p / 1000u32))
+            .collect();
+        params.reduce(&a)
+    }
+
+    #[test]
+    fn fp12_native_field_laws() {
+        for params in [G2Params::bn254().fp12(), G2Params::bls12_381().fp12()] {
+            let f2 = &params.fp2;
+            let a = element(&params, 3);
+            let b = element(&params, -11);
+            assert_eq!(
+                params.mul(&a, &params.inverse(&a).unwrap()),
+                params.from_int(1)
+            );
+            let mut w = vec![BigInt::from(0); 12];
+            w[1] = BigInt::from(1);
+            assert_eq!(
+                params.pow(&w, &BigInt::from(6)),
+                params.from_fp2(&params.xi)
+            );
+            // The embedding of Fp2 is a homomorphism into Fp6
+            let x = (BigInt::from(12345), BigInt::from(-678));
+            let y = (BigInt::from(-9), BigInt::from(31337));
+            assert_eq!(
+                params.mul(&params.from_fp2(&x), &params.from_fp2(&y)),
+                params.from_fp2(&f2.mul(&x, &y))
+            );
+            assert!(params.is_in_fp6(&params.from_fp2(&x)));
+            assert!(!params.is_in_fp6(&a));
+            assert!(params.is_in_fp6(&params.mul(&a, &params.conjugate(&a))));
+            // The Frobenius maps
+            assert_eq!(params.frobenius(&a, 1), params.pow(&a, &f2.p));
+            assert_eq!(params.frobenius(&a, 6), params.conjugate(&a));
+            assert_eq!(
+                params.frobenius(&params.frobenius(&a, 1), 2),
+                params.frobenius(&a, 3)
+            );
+            assert_eq!(params.frobenius(&a, 12), a);
+            assert_eq!(
+                params.frobenius(&params.mul(&a, &b), 2),
+                params.mul(&params.frobenius(&a, 2), &params.frobenius(&b, 2))
+            );
+        }
+    }
+
+    pub struct Fp12OpsInputs {
+        pub a: NativeFp12,
+        pub b: NativeFp12,
+    }
+
+    pub struct Fp12Ops {
+        inputs: Option<Fp12OpsInputs>,
+        params: Fp12Params,
+        /// Whether to check against the honest results
+        honest: bool,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Fp12Ops {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let a = EmulatedFp12::alloc_from_coeffs(
+                cs.namespace(|| "a"),
+                || Ok(inputs.a.clone()),
+                params,
+            )?;
+            let b = EmulatedFp12::alloc_from_coeffs(
+                cs.namespace(|| "b"),
+                || Ok(inputs.b.clone()),
+                params,
+            )?;
+            // The coefficients of 1, w^2, w^3 and w^8 of b
+            let sparse_indices = [0, 2, 3, 8];
+            let sparse = SparseFp12::new(
+                sparse_indices
+                    .iter()
+                    .map(|i| (*i, b.coeffs[*i].clone()))
+                    .collect(),
+                params,
+            );
+            let product = a.mul(cs.namespace(|| "a * b"), &b)?;
+            let square = a.square(cs.namespace(|| "a^2"))?;
+            let sparse_product = a.mul_by_sparse(cs.namespace(|| "a * sparse b"), &sparse)?;
+            let inverse = a.inverse(cs.namespace(|| "a inverse"))?;
+            let conj = a.conjugate(cs.namespace(|| "conj a"))?;
+            let frob = a.frobenius(cs.namespace(|| "frob a"), 1)?;
+            let frob2 = a.frobenius(cs.namespace(|| "frob^2 a"), 2)?;
+            // An element of the cyclotomic subgroup: a^((p^6 - 1)(p^2 + 1))
+            let easy = conj.mul(cs.namespace(|| "easy"), &inverse)?;
+            let easy = easy
+                .frobenius(cs.namespace(|| "easy frob"), 2)?
+                .mul(cs.namespace(|| "easy mul"), &easy)?;
+            let pow = easy.cyclotomic_pow(cs.namespace(|| "pow"), &BigInt::from(-13))?;
+            let norm = a.mul(cs.namespace(|| "a conj a"), &conj)?;
+            norm.assert_in_fp6(cs.namespace(|| "norm in fp6"))?;
+
+            let (av, bv) = (&inputs.a, &inputs.b);
+            let mut sparse_bv = params.from_int(0);
+            for i in sparse_indices {
+                sparse_bv[i] = bv[i].clone();
+            }
+            let easy_v = params.mul(&params.conjugate(av), &params.inverse(av).unwrap());
+            let easy_v = params.mul(&params.frobenius(&easy_v, 2), &easy_v);
+            let mut expected = vec![
+                params.mul(av, bv),
+                params.square(av),
+                params.mul(av, &sparse_bv),
+                params.inverse(av).unwrap(),
+                params.conjugate(av),
+                params.frobenius(av, 1),
+                params.frobenius(av, 2),
+                params.pow(&easy_v, &BigInt::from(-13)),
+            ];
+            if !self.honest {
+                expected[0] = params.add(&expected[0], &params.from_int(1));
+            }
+            for (i, (actual, expected)) in [
+                product,
+                square,
+                sparse_product,
+                inverse,
+                conj,
+                frob,
+                frob2,
+                pow,
+            ]
+            .iter()
+            .zip(expected)
+            .enumerate()
+            {
+                let expected = EmulatedFp12::constant::<CS>(&expected, params)?;
+                actual.assert_equal(cs.namespace(|| format!("check {}", i)), &expected)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn fp12_ops(params: Fp12Params, a: i64, b: i64, honest: bool) -> Fp12Ops {
+        Fp12Ops {
+            inputs: Some(Fp12OpsInputs {
+                a: element(&params, a),
+                b: element(&params, b),
+            }),
+            params,
+            honest,
+        }
+    }
+
+    circuit_tests! {
+        fp12_ops_bn254: (fp12_ops(G2Params::bn254().fp12(), 5, -3, true), true),
+        fp12_ops_bls12_381: (fp12_ops(G2Params::bls12_381().fp12(), 17, 2, true), true),
+        fp12_ops_bn254_wrong: (fp12_ops(G2Params::bn254().fp12(), 5, -3, false), false),
+    }
+}
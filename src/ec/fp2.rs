@@ -0,0 +1,708 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, Term};
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// An element `c0 + c1 u` of `Fp2 = Fp[u] / (u^2 + 1)`, computed natively.
+pub type NativeFp2 = (BigInt, BigInt);
+
+/// Parameters of the quadratic extension `Fp2 = Fp[u] / (u^2 + 1)` of an emulated prime field,
+/// where `p = 3 mod 4` (so that `-1` is not a square), together with the limb layout used to
+/// represent the coefficients.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Fp2Params {
+    /// The modulus of the base field
+    pub p: BigInt,
+    pub limb_width: usize,
+    pub n_limbs: usize,
+}
+
+impl Fp2Params {
+    /// The modulus of the base field, as a constant.
+    pub fn modulus<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&self.p, self.limb_width, self.n_limbs)
+    }
+
+    pub fn reduce(&self, (a0, a1): &NativeFp2) -> NativeFp2 {
+        (a0.mod_floor(&self.p), a1.mod_floor(&self.p))
+    }
+
+    pub fn from_int(&self, n: i64) -> NativeFp2 {
+        self.reduce(&(BigInt::from(n), BigInt::from(0)))
+    }
+
+    pub fn is_zero(&self, a: &NativeFp2) -> bool {
+        self.reduce(a) == (BigInt::from(0), BigInt::from(0))
+    }
+
+    pub fn add(&self, (a0, a1): &NativeFp2, (b0, b1): &NativeFp2) -> NativeFp2 {
+        self.reduce(&(a0 + b0, a1 + b1))
+    }
+
+    pub fn sub(&self, (a0, a1): &NativeFp2, (b0, b1): &NativeFp2) -> NativeFp2 {
+        self.reduce(&(a0 - b0, a1 - b1))
+    }
+
+    pub fn neg(&self, (a0, a1): &NativeFp2) -> NativeFp2 {
+        self.reduce(&(-a0, -a1))
+    }
+
+    pub fn mul(&self, (a0, a1): &NativeFp2, (b0, b1): &NativeFp2) -> NativeFp2 {
+        self.reduce(&(a0 * b0 - a1 * b1, a0 * b1 + a1 * b0))
+    }
+
+    pub fn square(&self, a: &NativeFp2) -> NativeFp2 {
+        self.mul(a, a)
+    }
+
+    /// Multiplies by an element of the base field.
+    pub fn scale(&self, (a0, a1): &NativeFp2, k: &BigInt) -> NativeFp2 {
+        self.reduce(&(a0 * k, a1 * k))
+    }
+
+    /// The conjugate `c0 - c1 u`, which is also the image under the Frobenius map `a -> a^p`.
+    pub fn conjugate(&self, (a0, a1): &NativeFp2) -> NativeFp2 {
+        self.reduce(&(a0.clone(), -a1))
+    }
+
+    /// The inverse `conj(a) / (c0^2 + c1^2)`, if `a` is non-zero.
+    pub fn inverse(&self, a: &NativeFp2) -> Option<NativeFp2> {
+        if self.is_zero(a) {
+            return None;
+        }
+        let (a0, a1) = a;
+        let norm_inv = invert(&(a0 * a0 + a1 * a1), &self.p).ok()?;
+        Some(self.scale(&self.conjugate(a), &norm_inv))
+    }
+
+    /// Computes `a^e` by square-and-multiply.
+    pub fn pow(&self, a: &NativeFp2, e: &BigInt) -> NativeFp2 {
+        let mut acc = self.from_int(1);
+        for i in (0..e.bits()).rev() {
+            acc = self.square(&acc);
+            if e.bit(i) {
+                acc = self.mul(&acc, a);
+            }
+        }
+        acc
+    }
+
+    /// A square root of `a`, if there is one (Adj and Rodriguez-Henriquez, "Square root
+    /// computation over even extension fields", Algorithm 9, for `p = 3 mod 4`).
+    pub fn sqrt(&self, a: &NativeFp2) -> Option<NativeFp2> {
+        let p = &self.p;
+        let a1 = self.pow(a, &((p - 3u32) / 4u32));
+        let alpha = self.mul(&self.square(&a1), a);
+        let x0 = self.mul(&a1, a);
+        let root = if alpha == self.from_int(-1) {
+            self.mul(&(BigInt::from(0), BigInt::from(1)), &x0)
+        } else {
+            let b = self.pow(&self.add(&self.from_int(1), &alpha), &((p - 1u32) / 2u32));
+            self.mul(&b, &x0)
+        };
+        if self.square(&root) == self.reduce(a) {
+            Some(root)
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for Fp2Params {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Fp2Params")
+            .field("p", &format_args!("{}", &self.p))
+            .field("limb_width", &self.limb_width)
+            .field("n_limbs", &self.n_limbs)
+            .finish()
+    }
+}
+
+/// A term of a sum whose residue is checked by `EmulatedFp2::assert_sums_congruent`.
+pub enum Fp2Term<'a, Scalar: PrimeField> {
+    /// A single element
+    Single(&'a EmulatedFp2<Scalar>),
+    /// The product of two elements
+    Product(&'a EmulatedFp2<Scalar>, &'a EmulatedFp2<Scalar>),
+}
+
+impl<'a, Scalar: PrimeField> Fp2Term<'a, Scalar> {
+    /// The value of the term (filled at witness-time)
+    pub fn value(&self) -> Option<NativeFp2> {
+        match self {
+            Fp2Term::Single(a) => a.value.clone(),
+            Fp2Term::Product(a, b) => a
+                .value
+                .as_ref()
+                .and_then(|v| b.value.as_ref().map(|w| a.params.mul(v, w))),
+        }
+    }
+}
+
+/// Splits a sum of `Fp2` terms into sums of base field terms: the positive and negative parts of
+/// the `1` coefficient, and the `u` coefficient.
+#[allow(clippy::type_complexity)]
+fn components<'a, Scalar: PrimeField>(
+    terms: &[Fp2Term<'a, Scalar>],
+) -> (
+    Vec<Term<'a, Scalar>>,
+    Vec<Term<'a, Scalar>>,
+    Vec<Term<'a, Scalar>>,
+) {
+    let (mut real, mut real_neg, mut imag) = (Vec::new(), Vec::new(), Vec::new());
+    for term in terms {
+        match term {
+            Fp2Term::Single(a) => {
+                real.push(Term::Single(&a.c0));
+                imag.push(Term::Single(&a.c1));
+            }
+            // (a0 + a1 u)(b0 + b1 u) = a0 b0 - a1 b1 + (a0 b1 + a1 b0) u
+            Fp2Term::Product(a, b) => {
+                real.push(Term::Product(&a.c0, &b.c0));
+                real_neg.push(Term::Product(&a.c1, &b.c1));
+                imag.push(Term::Product(&a.c0, &b.c1));
+                imag.push(Term::Product(&a.c1, &b.c0));
+            }
+        }
+    }
+    (real, real_neg, imag)
+}
+
+/// An element of the quadratic extension `Fp2` of an emulated prime field (see `Fp2Params`).
+///
+/// The coefficients are residues: they have as many limbs as the modulus, each range checked, but
+/// are not necessarily smaller than the modulus.
+#[derive(Clone)]
+pub struct EmulatedFp2<Scalar: PrimeField> {
+    pub c0: BigNat<Scalar>,
+    pub c1: BigNat<Scalar>,
+    /// The reduced value of the element (filled at witness-time)
+    pub value: Option<NativeFp2>,
+    /// Parameters
+    pub params: Fp2Params,
+}
+
+impl<Scalar: PrimeField> EmulatedFp2<Scalar> {
+    pub(crate) fn from_coeffs(c0: BigNat<Scalar>, c1: BigNat<Scalar>, params: &Fp2Params) -> Self {
+        let value = c0.value.as_ref().and_then(|c0| {
+            c1.value
+                .as_ref()
+                .map(|c1| params.reduce(&(c0.clone(), c1.clone())))
+        });
+        Self {
+            c0,
+            c1,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// Allocates an element with range checked coefficients.
+    /// The value is provided by a closure returning the coefficients.
+    pub fn alloc_from_coeffs<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &Fp2Params,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<NativeFp2, SynthesisError>,
+    {
+        let value = f().ok();
+        let c0 = BigNat::alloc_from_nat(
+            cs.namespace(|| "c0"),
+            || Ok(value.grab()?.0.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        c0.assert_well_formed(cs.namespace(|| "c0 rangecheck"))?;
+        let c1 = BigNat::alloc_from_nat(
+            cs.namespace(|| "c1"),
+            || Ok(value.grab()?.1.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        c1.assert_well_formed(cs.namespace(|| "c1 rangecheck"))?;
+        Ok(Self::from_coeffs(c0, c1, params))
+    }
+
+    /// Creates a constant element.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        value: &NativeFp2,
+        params: &Fp2Params,
+    ) -> Result<Self, SynthesisError> {
+        let (c0, c1) = params.reduce(value);
+        Ok(Self::from_coeffs(
+            BigNat::constant::<CS>(&c0, params.limb_width, params.n_limbs)?,
+            BigNat::constant::<CS>(&c1, params.limb_width, params.n_limbs)?,
+            params,
+        ))
+    }
+
+    pub fn zero<CS: ConstraintSystem<Scalar>>(params: &Fp2Params) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.from_int(0), params)
+    }
+
+    pub fn one<CS: ConstraintSystem<Scalar>>(params: &Fp2Params) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.from_int(1), params)
+    }
+
+    /// Constrain the sum of the `lhs` terms to be congruent to the sum of the `rhs` terms, by
+    /// checking each coefficient with `BigNat::assert_sums_congruent`.
+    pub fn assert_sums_congruent<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        lhs: &[Fp2Term<Scalar>],
+        rhs: &[Fp2Term<Scalar>],
+        params: &Fp2Params,
+    ) -> Result<(), SynthesisError> {
+        let p = params.modulus::<Scalar, CS>()?;
+        let (mut lhs_real, lhs_real_neg, lhs_imag) = components(lhs);
+        let (mut rhs_real, rhs_real_neg, rhs_imag) = components(rhs);
+        lhs_real.extend(rhs_real_neg);
+        rhs_real.extend(lhs_real_neg);
+        BigNat::assert_sums_congruent(cs.namespace(|| "c0"), &lhs_real, &rhs_real, &p)?;
+        BigNat::assert_sums_congruent(cs.namespace(|| "c1"), &lhs_imag, &rhs_imag, &p)
+    }
+
+    /// Allocates an element constrained to be congruent to the sum of `terms`.
+    pub fn residue<CS: ConstraintSystem<Scalar>>(
+        cs: &mut CS,
+        name: &str,
+        terms: &[Fp2Term<Scalar>],
+        params: &Fp2Params,
+    ) -> Result<Self, SynthesisError> {
+        let r = Self::alloc_residue(
+            cs.namespace(|| name.to_string()),
+            || {
+                terms.iter().try_fold(params.from_int(0), |acc, term| {
+                    Ok(params.add(&acc, term.value().grab()?))
+                })
+            },
+            params,
+        )?;
+        Self::assert_sums_congruent(
+            cs.namespace(|| format!("{} check", name)),
+            terms,
+            &[Fp2Term::Single(&r)],
+            params,
+        )?;
+        Ok(r)
+    }
+
+    /// Allocates the reduction of `f()`, range checking each coefficient.
+    fn alloc_residue<CS, F>(cs: CS, f: F, params: &Fp2Params) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<NativeFp2, SynthesisError>,
+    {
+        Self::alloc_from_coeffs(cs, || Ok(params.reduce(&f()?)), params)
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!("Fields do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Computes `self + other`.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        Self::residue(
+            &mut cs,
+            "sum",
+            &[Fp2Term::Single(self), Fp2Term::Single(other)],
+            &self.params,
+        )
+    }
+
+    /// Computes `self - other`.
+    pub fn sub<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "sub")?;
+        let params = &self.params;
+        let diff = Self::alloc_residue(
+            cs.namespace(|| "diff"),
+            || Ok(params.sub(self.value.grab()?, other.value.grab()?)),
+            params,
+        )?;
+        Self::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Fp2Term::Single(&diff), Fp2Term::Single(other)],
+            &[Fp2Term::Single(self)],
+            params,
+        )?;
+        Ok(diff)
+    }
+
+    /// Computes `-self`.
+    pub fn neg<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let neg = Self::alloc_residue(
+            cs.namespace(|| "neg"),
+            || Ok(params.neg(self.value.grab()?)),
+            params,
+        )?;
+        Self::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Fp2Term::Single(&neg), Fp2Term::Single(self)],
+            &[],
+            params,
+        )?;
+        Ok(neg)
+    }
+
+    /// Computes `self * other`.
+    pub fn mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "mul")?;
+        Self::residue(
+            &mut cs,
+            "product",
+            &[Fp2Term::Product(self, other)],
+            &self.params,
+        )
+    }
+
+    /// Computes `self^2`.
+    pub fn square<CS: ConstraintSystem<Scalar>>(&self, cs: CS) -> Result<Self, SynthesisError> {
+        self.mul(cs, self)
+    }
+
+    /// Computes `self * c` for a constant `c`.
+    pub fn mul_by_constant<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        c: &NativeFp2,
+    ) -> Result<Self, SynthesisError> {
+        let c = Self::constant::<CS>(c, &self.params)?;
+        Self::residue(
+            &mut cs,
+            "product",
+            &[Fp2Term::Product(self, &c)],
+            &self.params,
+        )
+    }
+
+    /// Computes `k * self` for a small constant `k`, by scaling the limbs. Costs no constraints.
+    pub fn scale<CS: ConstraintSystem<Scalar>>(&self, k: u64) -> Self {
+        Self::from_coeffs(
+            self.c0.scale::<CS>(Scalar::from(k)),
+            self.c1.scale::<CS>(Scalar::from(k)),
+            &self.params,
+        )
+    }
+
+    /// Computes the conjugate `c0 - c1 u`, i.e. the image of `self` under the Frobenius map.
+    pub fn conjugate<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let c1 = self.c1.neg_mod(cs.namespace(|| "c1"), &p)?;
+        Ok(Self::from_coeffs(self.c0.clone(), c1, &self.params))
+    }
+
+    /// Computes `self / other`, as `self` times the inverse of `other` (see `inverse`). The
+    /// constraints are unsatisfiable if `other` is zero.
+    pub fn div<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "div")?;
+        let inverse = other.inverse(cs.namespace(|| "inverse"))?;
+        self.mul(cs.namespace(|| "quotient"), &inverse)
+    }
+
+    /// Computes the inverse of `self`. This also proves that `self` is non-zero: the constraints
+    /// are unsatisfiable otherwise.
+    pub fn inverse<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let inverse = Self::alloc_residue(
+            cs.namespace(|| "inverse"),
+            || {
+                // There is no inverse of zero: any witness fails the check
+                let zero = params.from_int(0);
+                Ok(params.inverse(self.value.grab()?).unwrap_or(zero))
+            },
+            params,
+        )?;
+        let one = Self::one::<CS>(params)?;
+        Self::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Fp2Term::Product(self, &inverse)],
+            &[Fp2Term::Single(&one)],
+            params,
+        )?;
+        Ok(inverse)
+    }
+
+    /// Constrain `self` and `other` to be congruent, i.e. to represent the same element.
+    pub fn assert_equal<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(other, "assert_equal")?;
+        Self::assert_sums_congruent(
+            cs,
+            &[Fp2Term::Single(self)],
+            &[Fp2Term::Single(other)],
+            &self.params,
+        )
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedFp2<Scalar> {
+    type Scalar = Scalar;
+    type Value = NativeFp2;
+    type Params = Fp2Params;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        Self::alloc_from_coeffs(cs, || Ok((*value.grab()?).clone()), params)
+    }
+    fn value(&self) -> Option<&NativeFp2> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        let mut values = self.c0.limb_values.clone()?;
+        values.extend(self.c1.limb_values.as_ref()?.iter().cloned());
+        Some(values)
+    }
+    fn params(&self) -> &Fp2Params {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        let mut wires = self.c0.limbs.clone();
+        wires.extend(self.c1.limbs.iter().cloned());
+        wires
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedFp2<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some((c0, c1)) => write!(f, "EmulatedFp2({} + {} u)", c0, c1),
+            None => write!(f, "EmulatedFp2(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedFp2<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedFp2")
+            .field("c0", &self.c0)
+            .field("c1", &self.c1)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn bn254() -> Fp2Params {
+        crate::ec::g2::G2Params::bn254().fp2
+    }
+
+    fn bls12_381() -> Fp2Params {
+        crate::ec::g2::G2Params::bls12_381().fp2
+    }
+
+    #[test]
+    fn fp2_native_field_laws() {
+        for params in [bn254(), bls12_381()] {
+            let a = params.reduce(&(BigInt::from(-12345), BigInt::from(67890)));
+            let b = params.reduce(&(BigInt::from(424242), BigInt::from(-7)));
+            assert_eq!(
+                params.mul(&a, &params.inverse(&a).unwrap()),
+                params.from_int(1)
+            );
+            assert_eq!(
+                params.square(&(BigInt::from(0), BigInt::from(1))),
+                params.from_int(-1)
+            );
+            assert_eq!(
+                params.pow(&a, &(&params.p - 1u32)),
+                params.mul(&params.conjugate(&a), &params.inverse(&a).unwrap())
+            );
+            let ab = params.mul(&a, &b);
+            let root = params.sqrt(&params.square(&ab)).unwrap();
+            assert!(root == ab || root == params.neg(&ab));
+            // Exactly one of a non-zero element and its product with a non-square is a square
+            let u_plus_one = (BigInt::from(1), BigInt::from(1));
+            let non_square = if params.sqrt(&u_plus_one).is_none() {
+                u_plus_one
+            } else {
+                (BigInt::from(9), BigInt::from(1))
+            };
+            assert!(params.sqrt(&non_square).is_none());
+            assert_ne!(
+                params.sqrt(&a).is_some(),
+                params.sqrt(&params.mul(&a, &non_square)).is_some()
+            );
+        }
+    }
+
+    pub struct Fp2OpsInputs {
+        pub a: NativeFp2,
+        pub b: NativeFp2,
+    }
+
+    pub struct Fp2Ops {
+        inputs: Option<Fp2OpsInputs>,
+        params: Fp2Params,
+        /// Whether to check against the honest results
+        honest: bool,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Fp2Ops {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let a = EmulatedFp2::alloc_from_coeffs(
+                cs.namespace(|| "a"),
+                || Ok(inputs.a.clone()),
+                params,
+            )?;
+            let b = EmulatedFp2::alloc_from_coeffs(
+                cs.namespace(|| "b"),
+                || Ok(inputs.b.clone()),
+                params,
+            )?;
+            let sum = a.add(cs.namespace(|| "a + b"), &b)?;
+            let diff = a.sub(cs.namespace(|| "a - b"), &b)?;
+            let neg = a.neg(cs.namespace(|| "-a"))?;
+            let product = a.mul(cs.namespace(|| "a * b"), &b)?;
+            let square = a.square(cs.namespace(|| "a^2"))?;
+            let conj = a.conjugate(cs.namespace(|| "conj a"))?;
+            let quotient = a.div(cs.namespace(|| "a div b"), &b)?;
+            let inverse = b.inverse(cs.namespace(|| "b inverse"))?;
+            let one_plus_u = (BigInt::from(1), BigInt::from(1));
+            let scaled = a.mul_by_constant(cs.namespace(|| "a * (1 + u)"), &one_plus_u)?;
+
+            let (av, bv) = (&inputs.a, &inputs.b);
+            let mut expected = vec![
+                params.add(av, bv),
+                params.sub(av, bv),
+                params.neg(av),
+                params.mul(av, bv),
+                params.square(av),
+                params.conjugate(av),
+                params.mul(av, &params.inverse(bv).unwrap()),
+                params.inverse(bv).unwrap(),
+                params.mul(av, &one_plus_u),
+            ];
+            if !self.honest {
+                expected[3] = params.add(&expected[3], &params.from_int(1));
+            }
+            for (i, (actual, expected)) in [
+                sum, diff, neg, product, square, conj, quotient, inverse, scaled,
+            ]
+            .iter()
+            .zip(expected)
+            .enumerate()
+            {
+                let expected = EmulatedFp2::constant::<CS>(&expected, params)?;
+                actual.assert_equal(cs.namespace(|| format!("check {}", i)), &expected)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn fp2_ops(params: Fp2Params, a: (i64, i64), b: (i64, i64), honest: bool) -> Fp2Ops {
+        let p = &params.p;
+        // Large coefficients, from small seeds
+        let big = |s: i64| (BigInt::from(s) * (p / 1000u32)).mod_floor(p);
+        Fp2Ops {
+            inputs: Some(Fp2OpsInputs {
+                a: (big(a.0), big(a.1)),
+                b: (big(b.0), big(b.1)),
+            }),
+            params,
+            honest,
+        }
+    }
+
+    /// Divides `a` by zero, checking the claimed `quotient`.
+    pub struct Fp2DivByZero {
+        a: NativeFp2,
+        quotient: NativeFp2,
+        params: Fp2Params,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Fp2DivByZero {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let a = EmulatedFp2::alloc_from_coeffs(cs.namespace(|| "a"), || Ok(self.a), params)?;
+            let zero = EmulatedFp2::alloc_from_coeffs(
+                cs.namespace(|| "zero"),
+                || Ok(params.from_int(0)),
+                params,
+            )?;
+            let quotient = EmulatedFp2::constant::<CS>(&self.quotient, params)?;
+            a.div(cs.namespace(|| "a div 0"), &zero)?
+                .assert_equal(cs.namespace(|| "check"), &quotient)
+        }
+    }
+
+    fn div_by_zero(a: i64, quotient: i64) -> Fp2DivByZero {
+        let params = bn254();
+        Fp2DivByZero {
+            a: params.from_int(a),
+            quotient: params.from_int(quotient),
+            params,
+        }
+    }
+
+    circuit_tests! {
+        fp2_ops_bn254: (fp2_ops(bn254(), (3, 999), (-17, 5), true), true),
+        fp2_ops_bls12_381: (fp2_ops(bls12_381(), (123, -4), (7, 0), true), true),
+        fp2_ops_bn254_wrong: (fp2_ops(bn254(), (3, 999), (-17, 5), false), false),
+        fp2_div_by_zero: (div_by_zero(5, 0), false),
+        // 0 = q * 0 for any q: the divisor must be proven non-zero
+        fp2_div_zero_by_zero: (div_by_zero(0, 0), false),
+        fp2_div_zero_by_zero_any_quotient: (div_by_zero(0, 7), false),
+    }
+}
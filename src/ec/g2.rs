@@ -0,0 +1,918 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_traits::Num as BigNum;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::fp12::Fp12Params;
+use super::fp2::{EmulatedFp2, Fp2Params, Fp2Term, NativeFp2};
+use crate::util::bit::Bitvector;
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// A point of a curve over `Fp2`, computed natively. `None` is the point at infinity.
+pub type NativeG2Point = Option<(NativeFp2, NativeFp2)>;
+
+/// Projective coordinates `(X, Y, Z)` of a point of a curve over `Fp2`, computed natively.
+pub type NativeG2ProjectivePoint = (NativeFp2, NativeFp2, NativeFp2);
+
+fn hex(s: &str) -> BigInt {
+    BigInt::from_str_radix(s, 16).unwrap()
+}
+
+/// The type of a sextic twist `E'` of a curve `E: y^2 = x^3 + b`, where `w^6 = xi`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Twist {
+    /// `E': y^2 = x^3 + b * xi`, mapped to `E` by `(x, y) -> (x / w^2, y / w^3)`
+    M,
+    /// `E': y^2 = x^3 + b / xi`, mapped to `E` by `(x, y) -> (x * w^2, y * w^3)`
+    D,
+}
+
+/// The family of a pairing-friendly curve with embedding degree 12, parameterized by `x`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Family {
+    /// Barreto-Lynn-Scott: `r = x^4 - x^2 + 1`, `t = x + 1`
+    Bls12,
+    /// Barreto-Naehrig: `r = 36x^4 + 36x^3 + 18x^2 + 6x + 1`, `t = 6x^2 + 1`
+    Bn,
+}
+
+/// Parameters of the group G2 of a pairing-friendly curve: the prime order subgroup of a sextic
+/// twist `y^2 = x^3 + b` over `Fp2`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct G2Params {
+    pub fp2: Fp2Params,
+    /// The sextic non-residue defining the twist
+    pub xi: NativeFp2,
+    pub twist: Twist,
+    pub b: NativeFp2,
+    /// The order of G2 (and of G1)
+    pub order: BigInt,
+    pub generator: (NativeFp2, NativeFp2),
+    pub family: Family,
+    /// The parameter of the curve family
+    pub x: BigInt,
+}
+
+impl G2Params {
+    /// G2 of BLS12-381, on the M-twist `y^2 = x^3 + 4 (1 + u)`, with 6 limbs of 64 bits.
+    pub fn bls12_381() -> Self {
+        let fp2 = Fp2Params {
+            p: hex("1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab"),
+            limb_width: 64,
+            n_limbs: 6,
+        };
+        let x = -hex("d201000000010000");
+        G2Params {
+            xi: (BigInt::from(1), BigInt::from(1)),
+            twist: Twist::M,
+            b: (BigInt::from(4), BigInt::from(4)),
+            order: hex("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001"),
+            generator: (
+                (
+                    hex("024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8"),
+                    hex("13e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e"),
+                ),
+                (
+                    hex("0ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801"),
+                    hex("0606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be"),
+                ),
+            ),
+            family: Family::Bls12,
+            x,
+            fp2,
+        }
+    }
+
+    /// G2 of BN254 (alt_bn128), on the D-twist `y^2 = x^3 + 3 / (9 + u)`, with 4 limbs of 64
+    /// bits.
+    pub fn bn254() -> Self {
+        let fp2 = Fp2Params {
+            p: hex("30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47"),
+            limb_width: 64,
+            n_limbs: 4,
+        };
+        let xi = (BigInt::from(9), BigInt::from(1));
+        let x = BigInt::from(4965661367192848881u64);
+        G2Params {
+            b: fp2.scale(&fp2.inverse(&xi).unwrap(), &BigInt::from(3)),
+            xi,
+            twist: Twist::D,
+            order: hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"),
+            generator: (
+                (
+                    hex("1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed"),
+                    hex("198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2"),
+                ),
+                (
+                    hex("12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"),
+                    hex("090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b"),
+                ),
+            ),
+            family: Family::Bn,
+            x,
+            fp2,
+        }
+    }
+
+    /// The extension `Fp12 = Fp2[w] / (w^6 - xi)` over which the pairing is computed.
+    pub fn fp12(&self) -> Fp12Params {
+        Fp12Params::new(self.fp2.clone(), self.xi.clone())
+    }
+
+    pub fn is_on_curve(&self, point: &NativeG2Point) -> bool {
+        let f = &self.fp2;
+        match point {
+            None => true,
+            Some((x, y)) => {
+                let rhs = f.add(&f.mul(&f.square(x), x), &self.b);
+                f.square(y) == rhs
+            }
+        }
+    }
+
+    pub fn negate(&self, point: &NativeG2Point) -> NativeG2Point {
+        point
+            .as_ref()
+            .map(|(x, y)| (self.fp2.reduce(x), self.fp2.neg(y)))
+    }
+
+    pub fn add(&self, a: &NativeG2Point, b: &NativeG2Point) -> NativeG2Point {
+        let f = &self.fp2;
+        match (a, b) {
+            (None, _) => b.clone(),
+            (_, None) => a.clone(),
+            (Some((x1, y1)), Some((x2, y2))) => {
+                let lambda = if f.is_zero(&f.sub(x1, x2)) {
+                    if f.is_zero(&f.add(y1, y2)) {
+                        return None;
+                    }
+                    let x1_2 = f.square(x1);
+                    f.mul(
+                        &f.add(&x1_2, &f.add(&x1_2, &x1_2)),
+                        &f.inverse(&f.add(y1, y1)).unwrap(),
+                    )
+                } else {
+                    f.mul(&f.sub(y2, y1), &f.inverse(&f.sub(x2, x1)).unwrap())
+                };
+                let x3 = f.sub(&f.sub(&f.square(&lambda), x1), x2);
+                let y3 = f.sub(&f.mul(&lambda, &f.sub(x1, &x3)), y1);
+                Some((x3, y3))
+            }
+        }
+    }
+
+    pub fn double(&self, a: &NativeG2Point) -> NativeG2Point {
+        self.add(a, a)
+    }
+
+    /// Computes `k * point` by double-and-add. `k` may be negative, and is not reduced modulo the
+    /// order, so that points outside G2 are multiplied correctly.
+    pub fn mul(&self, k: &BigInt, point: &NativeG2Point) -> NativeG2Point {
+        let mut acc = None;
+        for i in (0..k.bits()).rev() {
+            acc = self.double(&acc);
+            if k.magnitude().bit(i) {
+                acc = self.add(&acc, point);
+            }
+        }
+        if k.sign() == Sign::Minus {
+            self.negate(&acc)
+        } else {
+            acc
+        }
+    }
+
+    /// The coefficients `(cx, cy)` of `psi(x, y) = (cx * conj(x), cy * conj(y))`, the
+    /// endomorphism obtained by conjugating the Frobenius map of the curve over `Fp12` by the
+    /// twisting isomorphism.
+    pub fn psi_coefficients(&self) -> (NativeFp2, NativeFp2) {
+        let f = &self.fp2;
+        let xi = match self.twist {
+            Twist::M => f.inverse(&self.xi).unwrap(),
+            Twist::D => self.xi.clone(),
+        };
+        let p_minus_one = &f.p - 1u32;
+        (
+            f.pow(&xi, &(&p_minus_one / 3u32)),
+            f.pow(&xi, &(&p_minus_one / 2u32)),
+        )
+    }
+
+    pub fn psi(&self, point: &NativeG2Point) -> NativeG2Point {
+        let f = &self.fp2;
+        let (cx, cy) = self.psi_coefficients();
+        point
+            .as_ref()
+            .map(|(x, y)| (f.mul(&cx, &f.conjugate(x)), f.mul(&cy, &f.conjugate(y))))
+    }
+
+    /// The eigenvalue of `psi` on G2, `p = t - 1` modulo the order: `x` for BLS12 curves and
+    /// `6x^2` for BN curves.
+    pub fn psi_eigenvalue(&self) -> BigInt {
+        match self.family {
+            Family::Bls12 => self.x.clone(),
+            Family::Bn => &self.x * &self.x * 6u32,
+        }
+    }
+
+    /// Whether a point of the twist is in G2 (see `EmulatedG2Point::assert_in_subgroup`).
+    pub fn is_in_subgroup(&self, point: &NativeG2Point) -> bool {
+        match self.family {
+            Family::Bls12 => self.psi(point) == self.mul(&self.x, point),
+            Family::Bn => {
+                let xq = self.mul(&self.x, point);
+                let lhs = self.add(&self.add(&xq, point), &self.psi(&xq));
+                let lhs = self.add(&lhs, &self.psi(&self.psi(&xq)));
+                lhs == self.psi(&self.psi(&self.psi(&self.double(&xq))))
+            }
+        }
+    }
+
+    /// Adds two points in projective coordinates with the complete formulas of Renes, Costello
+    /// and Batina for `a = 0`.
+    pub fn projective_add(
+        &self,
+        (x1, y1, z1): &NativeG2ProjectivePoint,
+        (x2, y2, z2): &NativeG2ProjectivePoint,
+    ) -> NativeG2ProjectivePoint {
+        let f = &self.fp2;
+        let b3 = f.scale(&self.b, &BigInt::from(3));
+        let t0 = f.mul(x1, x2);
+        let t1 = f.mul(y1, y2);
+        let t2 = f.mul(z1, z2);
+        let t3 = f.add(&f.mul(x1, y2), &f.mul(x2, y1));
+        let t4 = f.add(&f.mul(y1, z2), &f.mul(y2, z1));
+        let t5 = f.add(&f.mul(x1, z2), &f.mul(x2, z1));
+        let c_a = f.sub(&t1, &f.mul(&b3, &t2));
+        let c_b = f.add(&t1, &f.mul(&b3, &t2));
+        let c_c = f.mul(&b3, &t5);
+        let c_d = f.scale(&t0, &BigInt::from(3));
+        (
+            f.sub(&f.mul(&t3, &c_a), &f.mul(&t4, &c_c)),
+            f.add(&f.mul(&c_b, &c_a), &f.mul(&c_d, &c_c)),
+            f.add(&f.mul(&t4, &c_b), &f.mul(&t3, &c_d)),
+        )
+    }
+
+    pub fn to_projective(&self, point: &NativeG2Point) -> NativeG2ProjectivePoint {
+        let f = &self.fp2;
+        match point {
+            None => (f.from_int(0), f.from_int(1), f.from_int(0)),
+            Some((x, y)) => (f.reduce(x), f.reduce(y), f.from_int(1)),
+        }
+    }
+
+    pub fn to_affine(&self, (x, y, z): &NativeG2ProjectivePoint) -> NativeG2Point {
+        let f = &self.fp2;
+        f.inverse(z)
+            .map(|z_inv| (f.mul(x, &z_inv), f.mul(y, &z_inv)))
+    }
+}
+
+impl Debug for G2Params {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("G2Params")
+            .field("fp2", &self.fp2)
+            .field("twist", &self.twist)
+            .field("x", &format_args!("{}", &self.x))
+            .finish()
+    }
+}
+
+/// A point, in projective coordinates, of the twist over `Fp2` containing G2 (see `G2Params`),
+/// over an emulated prime field.
+///
+/// Addition uses the complete formulas of Renes, Costello and Batina, so it has no exceptional
+/// cases: the identity `(0 : 1 : 0)` and doubling are handled like any other input.
+#[derive(Clone)]
+pub struct EmulatedG2Point<Scalar: PrimeField> {
+    pub x: EmulatedFp2<Scalar>,
+    pub y: EmulatedFp2<Scalar>,
+    pub z: EmulatedFp2<Scalar>,
+    /// The reduced coordinates of the point (filled at witness-time)
+    pub value: Option<NativeG2ProjectivePoint>,
+    /// Parameters
+    pub params: G2Params,
+}
+
+impl<Scalar: PrimeField> EmulatedG2Point<Scalar> {
+    fn from_coords(
+        x: EmulatedFp2<Scalar>,
+        y: EmulatedFp2<Scalar>,
+        z: EmulatedFp2<Scalar>,
+        params: &G2Params,
+    ) -> Self {
+        let value = x.value.as_ref().and_then(|x| {
+            y.value
+                .as_ref()
+                .and_then(|y| z.value.as_ref().map(|z| (x.clone(), y.clone(), z.clone())))
+        });
+        Self {
+            x,
+            y,
+            z,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// Allocates a point from its affine coordinates, range checked and constrained to be on the
+    /// twist. The point is not checked to be in G2, see `assert_in_subgroup`.
+    pub fn alloc_affine<CS, F>(mut cs: CS, f: F, params: &G2Params) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<(NativeFp2, NativeFp2), SynthesisError>,
+    {
+        let coords = f().ok();
+        let x = EmulatedFp2::alloc_from_coeffs(
+            cs.namespace(|| "x"),
+            || Ok(coords.grab()?.0.clone()),
+            &params.fp2,
+        )?;
+        let y = EmulatedFp2::alloc_from_coeffs(
+            cs.namespace(|| "y"),
+            || Ok(coords.grab()?.1.clone()),
+            &params.fp2,
+        )?;
+        let z = EmulatedFp2::one::<CS>(&params.fp2)?;
+        let point = Self::from_coords(x, y, z, params);
+        point.assert_on_curve(cs.namespace(|| "on curve"))?;
+        Ok(point)
+    }
+
+    /// Creates a point from its affine coordinates `(x, y)`, which are not checked to be on the
+    /// twist.
+    pub fn from_affine<CS: ConstraintSystem<Scalar>>(
+        x: EmulatedFp2<Scalar>,
+        y: EmulatedFp2<Scalar>,
+        params: &G2Params,
+    ) -> Result<Self, SynthesisError> {
+        let z = EmulatedFp2::one::<CS>(&params.fp2)?;
+        Ok(Self::from_coords(x, y, z, params))
+    }
+
+    /// The identity `(0 : 1 : 0)`, as a constant.
+    pub fn identity<CS: ConstraintSystem<Scalar>>(
+        params: &G2Params,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.to_projective(&None), params)
+    }
+
+    /// Creates a constant point.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        (x, y, z): &NativeG2ProjectivePoint,
+        params: &G2Params,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self::from_coords(
+            EmulatedFp2::constant::<CS>(x, &params.fp2)?,
+            EmulatedFp2::constant::<CS>(y, &params.fp2)?,
+            EmulatedFp2::constant::<CS>(z, &params.fp2)?,
+            params,
+        ))
+    }
+
+    /// The generator of G2, as a constant.
+    pub fn generator<CS: ConstraintSystem<Scalar>>(
+        params: &G2Params,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(
+            &params.to_projective(&Some(params.generator.clone())),
+            params,
+        )
+    }
+
+    /// The affine point represented by `self` (filled at witness-time). `Some(None)` is the point
+    /// at infinity.
+    pub fn affine_value(&self) -> Option<NativeG2Point> {
+        self.value.as_ref().map(|v| self.params.to_affine(v))
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!("Curves do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Constrain `self` to be on the twist, i.e. `Y^2 Z = X^3 + b Z^3`.
+    pub fn assert_on_curve<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let f = &self.params.fp2;
+        let b = EmulatedFp2::constant::<CS>(&self.params.b, f)?;
+        let y2 = EmulatedFp2::residue(&mut cs, "y^2", &[Fp2Term::Product(&self.y, &self.y)], f)?;
+        let x2 = EmulatedFp2::residue(&mut cs, "x^2", &[Fp2Term::Product(&self.x, &self.x)], f)?;
+        let z2 = EmulatedFp2::residue(&mut cs, "z^2", &[Fp2Term::Product(&self.z, &self.z)], f)?;
+        let bz = EmulatedFp2::residue(&mut cs, "b z", &[Fp2Term::Product(&b, &self.z)], f)?;
+        EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "curve equation"),
+            &[Fp2Term::Product(&y2, &self.z)],
+            &[Fp2Term::Product(&x2, &self.x), Fp2Term::Product(&bz, &z2)],
+            f,
+        )
+    }
+
+    /// Computes `self + other` with the complete formulas.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let params = &self.params;
+        let f = &params.fp2;
+        let b3 = EmulatedFp2::constant::<CS>(&f.scale(&params.b, &BigInt::from(3)), f)?;
+        let residue = |cs: &mut CS, name: &str, terms: &[Fp2Term<Scalar>]| {
+            EmulatedFp2::residue(cs, name, terms, f)
+        };
+
+        let (x1, y1, z1) = (&self.x, &self.y, &self.z);
+        let (x2, y2, z2) = (&other.x, &other.y, &other.z);
+        let t0 = residue(&mut cs, "x1x2", &[Fp2Term::Product(x1, x2)])?;
+        let t1 = residue(&mut cs, "y1y2", &[Fp2Term::Product(y1, y2)])?;
+        let t2 = residue(&mut cs, "z1z2", &[Fp2Term::Product(z1, z2)])?;
+        let t3 = residue(
+            &mut cs,
+            "x1y2 + x2y1",
+            &[Fp2Term::Product(x1, y2), Fp2Term::Product(x2, y1)],
+        )?;
+        let t4 = residue(
+            &mut cs,
+            "y1z2 + y2z1",
+            &[Fp2Term::Product(y1, z2), Fp2Term::Product(y2, z1)],
+        )?;
+        let t5 = residue(
+            &mut cs,
+            "x1z2 + x2z1",
+            &[Fp2Term::Product(x1, z2), Fp2Term::Product(x2, z1)],
+        )?;
+        let b3_t2 = residue(&mut cs, "3b z1z2", &[Fp2Term::Product(&b3, &t2)])?;
+
+        // A = t1 - 3b t2, B = t1 + 3b t2, C = 3b t5, D = 3 t0
+        let c_a = t1.sub(cs.namespace(|| "A"), &b3_t2)?;
+        let c_b = residue(
+            &mut cs,
+            "B",
+            &[Fp2Term::Single(&t1), Fp2Term::Single(&b3_t2)],
+        )?;
+        let c_c = residue(&mut cs, "C", &[Fp2Term::Product(&b3, &t5)])?;
+        let c_d = t0.scale::<CS>(3);
+
+        // X3 = t3 A - t4 C
+        let x3 = EmulatedFp2::alloc_from_coeffs(
+            cs.namespace(|| "x3"),
+            || {
+                let ta = f.mul(t3.value.grab()?, c_a.value.grab()?);
+                Ok(f.sub(&ta, &f.mul(t4.value.grab()?, c_c.value.grab()?)))
+            },
+            f,
+        )?;
+        EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "x3 check"),
+            &[Fp2Term::Single(&x3), Fp2Term::Product(&t4, &c_c)],
+            &[Fp2Term::Product(&t3, &c_a)],
+            f,
+        )?;
+        // Y3 = B A + D C
+        let y3 = residue(
+            &mut cs,
+            "y3",
+            &[Fp2Term::Product(&c_b, &c_a), Fp2Term::Product(&c_d, &c_c)],
+        )?;
+        // Z3 = t4 B + t3 D
+        let z3 = residue(
+            &mut cs,
+            "z3",
+            &[Fp2Term::Product(&t4, &c_b), Fp2Term::Product(&t3, &c_d)],
+        )?;
+        Ok(Self::from_coords(x3, y3, z3, params))
+    }
+
+    /// Computes `2 * self` with the complete doubling formulas of Renes, Costello and Batina for
+    /// `a = 0`:
+    /// `X3 = 2 X Y (Y^2 - 9b Z^2)`, `Y3 = (Y^2 - 9b Z^2)(Y^2 + 3b Z^2) + 24b Y^2 Z^2` and
+    /// `Z3 = 8 Y^3 Z`.
+    pub fn double<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let f = &params.fp2;
+        let b3 = EmulatedFp2::constant::<CS>(&f.scale(&params.b, &BigInt::from(3)), f)?;
+        let residue = |cs: &mut CS, name: &str, terms: &[Fp2Term<Scalar>]| {
+            EmulatedFp2::residue(cs, name, terms, f)
+        };
+
+        let (x, y, z) = (&self.x, &self.y, &self.z);
+        let y2 = residue(&mut cs, "y^2", &[Fp2Term::Product(y, y)])?;
+        let z2 = residue(&mut cs, "z^2", &[Fp2Term::Product(z, z)])?;
+        let b3_z2 = residue(&mut cs, "3b z^2", &[Fp2Term::Product(&b3, &z2)])?;
+        let yz = residue(&mut cs, "yz", &[Fp2Term::Product(y, z)])?;
+        let xy = residue(&mut cs, "xy", &[Fp2Term::Product(x, y)])?;
+        // A = Y^2 - 9b Z^2, B = Y^2 + 3b Z^2
+        let c_a = y2.sub(cs.namespace(|| "A"), &b3_z2.scale::<CS>(3))?;
+        let c_b = residue(
+            &mut cs,
+            "B",
+            &[Fp2Term::Single(&y2), Fp2Term::Single(&b3_z2)],
+        )?;
+        let x3 = residue(&mut cs, "x3", &[Fp2Term::Product(&c_a, &xy.scale::<CS>(2))])?;
+        let y3 = residue(
+            &mut cs,
+            "y3",
+            &[
+                Fp2Term::Product(&c_a, &c_b),
+                Fp2Term::Product(&y2, &b3_z2.scale::<CS>(8)),
+            ],
+        )?;
+        let z3 = residue(&mut cs, "z3", &[Fp2Term::Product(&y2.scale::<CS>(8), &yz)])?;
+        Ok(Self::from_coords(x3, y3, z3, params))
+    }
+
+    /// Computes `-self`.
+    pub fn negate<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let y = self.y.neg(cs.namespace(|| "y"))?;
+        Ok(Self::from_coords(
+            self.x.clone(),
+            y,
+            self.z.clone(),
+            &self.params,
+        ))
+    }
+
+    /// Computes `psi(self) = (cx X^p : cy Y^p : Z^p)` (see `G2Params::psi_coefficients`).
+    pub fn psi<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let (cx, cy) = self.params.psi_coefficients();
+        let x = self.x.conjugate(cs.namespace(|| "conj x"))?;
+        let x = x.mul_by_constant(cs.namespace(|| "cx conj x"), &cx)?;
+        let y = self.y.conjugate(cs.namespace(|| "conj y"))?;
+        let y = y.mul_by_constant(cs.namespace(|| "cy conj y"), &cy)?;
+        let z = self.z.conjugate(cs.namespace(|| "conj z"))?;
+        Ok(Self::from_coords(x, y, z, &self.params))
+    }
+
+    /// Computes `scalar * self`, where `scalar` is given by its (little-endian) bits, by
+    /// double-and-add. The complete formulas allow any point and any scalar.
+    pub fn scalar_mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        scalar: &Bitvector<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        let identity = Self::identity::<CS>(&self.params)?;
+        let mut acc: Option<Self> = None;
+        for i in (0..scalar.bits.len()).rev() {
+            let mut cs = cs.namespace(|| format!("bit {}", i));
+            let bit = scalar.get(i).unwrap();
+            acc = Some(match acc {
+                None => Gadget::mux(cs.namespace(|| "select"), &bit, &identity, self)?,
+                Some(acc) => {
+                    let acc = acc.double(cs.namespace(|| "double"))?;
+                    let sum = acc.add(cs.namespace(|| "add"), self)?;
+                    Gadget::mux(cs.namespace(|| "select"), &bit, &acc, &sum)?
+                }
+            });
+        }
+        acc.ok_or_else(|| {
+            eprintln!("Cannot multiply by an empty scalar");
+            SynthesisError::Unsatisfiable
+        })
+    }
+
+    /// Computes `k * self` for a constant `k`, which may be negative, by double-and-add.
+    pub fn mul_by_constant<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        k: &BigInt,
+    ) -> Result<Self, SynthesisError> {
+        let bits = k.bits();
+        if bits == 0 {
+            return Self::identity::<CS>(&self.params);
+        }
+        let mut acc = self.clone();
+        for i in (0..(bits - 1)).rev() {
+            acc = acc.double(cs.namespace(|| format!("double {}", i)))?;
+            if k.magnitude().bit(i) {
+                acc = acc.add(cs.namespace(|| format!("add {}", i)), self)?;
+            }
+        }
+        if k.sign() == Sign::Minus {
+            acc = acc.negate(cs.namespace(|| "negate"))?;
+        }
+        Ok(acc)
+    }
+
+    /// Constrain `self`, a point of the twist, to be in G2, with the tests of Scott ("A note on
+    /// group membership tests for G1, G2 and GT on BLS pairing-friendly curves", 2021), which
+    /// only need a multiplication by the curve parameter `x`:
+    /// `psi(Q) = [x] Q` for BLS12 curves, and
+    /// `[x + 1] Q + psi([x] Q) + psi^2([x] Q) = psi^3([2x] Q)` for BN curves.
+    pub fn assert_in_subgroup<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(), SynthesisError> {
+        let xq = self.mul_by_constant(cs.namespace(|| "x q"), &self.params.x)?;
+        match self.params.family {
+            Family::Bls12 => {
+                let psi = self.psi(cs.namespace(|| "psi"))?;
+                psi.assert_equivalent(cs.namespace(|| "check"), &xq)
+            }
+            Family::Bn => {
+                let psi_xq = xq.psi(cs.namespace(|| "psi x q"))?;
+                let psi2_xq = psi_xq.psi(cs.namespace(|| "psi^2 x q"))?;
+                let lhs = xq.add(cs.namespace(|| "x q + q"), self)?;
+                let lhs = lhs.add(cs.namespace(|| "+ psi x q"), &psi_xq)?;
+                let lhs = lhs.add(cs.namespace(|| "+ psi^2 x q"), &psi2_xq)?;
+                let rhs = xq.double(cs.namespace(|| "2x q"))?;
+                let rhs = rhs.psi(cs.namespace(|| "psi 2x q"))?;
+                let rhs = rhs.psi(cs.namespace(|| "psi^2 2x q"))?;
+                let rhs = rhs.psi(cs.namespace(|| "psi^3 2x q"))?;
+                lhs.assert_equivalent(cs.namespace(|| "check"), &rhs)
+            }
+        }
+    }
+
+    /// Computes the affine coordinates `(X/Z, Y/Z)`. This also proves that `self` is not the
+    /// identity.
+    pub fn to_affine<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<(EmulatedFp2<Scalar>, EmulatedFp2<Scalar>), SynthesisError> {
+        let x = self.x.div(cs.namespace(|| "x"), &self.z)?;
+        let y = self.y.div(cs.namespace(|| "y"), &self.z)?;
+        Ok((x, y))
+    }
+
+    /// Constrain `self` and `other` to represent the same point, i.e. `X1 Z2 = X2 Z1` and
+    /// `Y1 Z2 = Y2 Z1`.
+    pub fn assert_equivalent<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(other, "assert_equivalent")?;
+        let f = &self.params.fp2;
+        EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "x"),
+            &[Fp2Term::Product(&self.x, &other.z)],
+            &[Fp2Term::Product(&other.x, &self.z)],
+            f,
+        )?;
+        EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "y"),
+            &[Fp2Term::Product(&self.y, &other.z)],
+            &[Fp2Term::Product(&other.y, &self.z)],
+            f,
+        )
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedG2Point<Scalar> {
+    type Scalar = Scalar;
+    type Value = NativeG2ProjectivePoint;
+    type Params = G2Params;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        let f = &params.fp2;
+        let x = Gadget::alloc(cs.namespace(|| "x"), value.map(|v| &v.0), (), f)?;
+        let y = Gadget::alloc(cs.namespace(|| "y"), value.map(|v| &v.1), (), f)?;
+        let z = Gadget::alloc(cs.namespace(|| "z"), value.map(|v| &v.2), (), f)?;
+        Ok(Self::from_coords(x, y, z, params))
+    }
+    fn value(&self) -> Option<&NativeG2ProjectivePoint> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        let mut values = self.x.wire_values()?;
+        values.extend(self.y.wire_values()?);
+        values.extend(self.z.wire_values()?);
+        Some(values)
+    }
+    fn params(&self) -> &G2Params {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        let mut wires = self.x.wires();
+        wires.extend(self.y.wires());
+        wires.extend(self.z.wires());
+        wires
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedG2Point<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some((x, y, z)) => write!(
+                f,
+                "EmulatedG2Point({} + {} u : {} + {} u : {} + {} u)",
+                x.0, x.1, y.0, y.1, z.0, z.1
+            ),
+            None => write!(f, "EmulatedG2Point(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedG2Point<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedG2Point")
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("z", &self.z)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp::bignat::BigNat;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    /// A point of the twist outside G2: the first point found by lifting `x = k + u`.
+    fn non_subgroup_point(params: &G2Params) -> (NativeFp2, NativeFp2) {
+        let f = &params.fp2;
+        (1i64..)
+            .find_map(|k| {
+                let x = (BigInt::from(k), BigInt::from(1));
+                let rhs = f.add(&f.mul(&f.square(&x), &x), &params.b);
+                f.sqrt(&rhs).map(|y| (x, y))
+            })
+            .unwrap()
+    }
+
+    fn check_params(params: G2Params) {
+        let g = Some(params.generator.clone());
+        assert!(params.is_on_curve(&g));
+        assert_eq!(params.mul(&params.order, &g), None);
+        assert_eq!(params.psi(&g), params.mul(&params.psi_eigenvalue(), &g));
+        assert!(params.is_in_subgroup(&params.mul(&BigInt::from(12345), &g)));
+        let q = Some(non_subgroup_point(&params));
+        assert!(params.is_on_curve(&q));
+        assert!(!params.is_in_subgroup(&q));
+        assert_ne!(params.mul(&params.order, &q), None);
+        // The projective formulas agree with the affine ones, including on exceptional inputs
+        let g2 = params.double(&g);
+        for (a, b) in [(&g, &g2), (&g, &g), (&g, &params.negate(&g)), (&None, &g)] {
+            let sum = params.projective_add(&params.to_projective(a), &params.to_projective(b));
+            assert_eq!(params.to_affine(&sum), params.add(a, b));
+        }
+    }
+
+    #[test]
+    fn bls12_381_g2_params() {
+        check_params(G2Params::bls12_381());
+    }
+
+    #[test]
+    fn bn254_g2_params() {
+        check_params(G2Params::bn254());
+    }
+
+    pub struct G2OpsInputs {
+        pub p: (NativeFp2, NativeFp2),
+        pub q: (NativeFp2, NativeFp2),
+        pub k: BigInt,
+    }
+
+    pub struct G2Ops {
+        inputs: Option<G2OpsInputs>,
+        params: G2Params,
+        n_bits: usize,
+        /// Whether to check against the honest results
+        honest: bool,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for G2Ops {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let p = EmulatedG2Point::alloc_affine(
+                cs.namespace(|| "p"),
+                || Ok(inputs.p.clone()),
+                params,
+            )?;
+            let q = EmulatedG2Point::alloc_affine(
+                cs.namespace(|| "q"),
+                || Ok(inputs.q.clone()),
+                params,
+            )?;
+            let k = BigNat::alloc_from_nat(
+                cs.namespace(|| "k"),
+                || Ok(inputs.k.clone()),
+                params.fp2.limb_width,
+                self.n_bits.div_ceil(params.fp2.limb_width),
+            )?;
+            let bits = k
+                .decompose(cs.namespace(|| "decompose"))?
+                .truncate(self.n_bits);
+            let sum = p.add(cs.namespace(|| "p + q"), &q)?;
+            let double = p.double(cs.namespace(|| "2p"))?;
+            let neg = p.negate(cs.namespace(|| "-p"))?;
+            let zero = p.add(cs.namespace(|| "p - p"), &neg)?;
+            let psi = p.psi(cs.namespace(|| "psi p"))?;
+            let product = q.scalar_mul(cs.namespace(|| "k q"), &bits)?;
+
+            let (pv, qv) = (Some(inputs.p.clone()), Some(inputs.q.clone()));
+            let mut expected = vec![
+                params.add(&pv, &qv),
+                params.double(&pv),
+                params.negate(&pv),
+                None,
+                params.psi(&pv),
+                params.mul(&inputs.k, &qv),
+            ];
+            if !self.honest {
+                expected[0] = params.add(&expected[0], &pv);
+            }
+            for (i, (actual, expected)) in [&sum, &double, &neg, &zero, &psi, &product]
+                .iter()
+                .zip(expected)
+                .enumerate()
+            {
+                let expected =
+                    EmulatedG2Point::constant::<CS>(&params.to_projective(&expected), params)?;
+                actual.assert_equivalent(cs.namespace(|| format!("check {}", i)), &expected)?;
+            }
+            let (x, y) = double.to_affine(cs.namespace(|| "affine"))?;
+            let (ex, ey) = params.double(&pv).unwrap();
+            x.assert_equal(
+                cs.namespace(|| "affine x"),
+                &EmulatedFp2::constant::<CS>(&ex, &params.fp2)?,
+            )?;
+            y.assert_equal(
+                cs.namespace(|| "affine y"),
+                &EmulatedFp2::constant::<CS>(&ey, &params.fp2)?,
+            )
+        }
+    }
+
+    fn g2_ops(params: G2Params, p: u64, q: u64, k: u64, n_bits: usize, honest: bool) -> G2Ops {
+        let g = Some(params.generator.clone());
+        G2Ops {
+            inputs: Some(G2OpsInputs {
+                p: params.mul(&BigInt::from(p), &g).unwrap(),
+                q: params.mul(&BigInt::from(q), &g).unwrap(),
+                k: BigInt::from(k),
+            }),
+            params,
+            n_bits,
+            honest,
+        }
+    }
+
+    circuit_tests! {
+        g2_ops_bn254: (g2_ops(G2Params::bn254(), 3, 5, 0b1011, 4, true), true),
+        g2_ops_bls12_381: (g2_ops(G2Params::bls12_381(), 7, 2, 0b110, 3, true), true),
+        g2_ops_bn254_doubling: (g2_ops(G2Params::bn254(), 3, 3, 0, 2, true), true),
+        g2_ops_bn254_wrong: (g2_ops(G2Params::bn254(), 3, 5, 0b1011, 4, false), false),
+    }
+
+    /// Checks the subgroup membership of a point natively, and reports the cost.
+    fn subgroup_check(name: &str, params: G2Params, point: (NativeFp2, NativeFp2)) -> bool {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let q = EmulatedG2Point::alloc_affine(cs.namespace(|| "q"), || Ok(point), &params).unwrap();
+        q.assert_in_subgroup(cs.namespace(|| "subgroup")).unwrap();
+        println!(
+            "{} subgroup check: {} constraints",
+            name,
+            cs.num_constraints()
+        );
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn g2_subgroup_check_bls12_381() {
+        let params = G2Params::bls12_381();
+        let g = Some(params.generator.clone());
+        let q = params.mul(&BigInt::from(0xdead), &g).unwrap();
+        assert!(subgroup_check("BLS12-381", params.clone(), q));
+        let outside = non_subgroup_point(&params);
+        assert!(!subgroup_check("BLS12-381", params, outside));
+    }
+
+    #[test]
+    fn g2_subgroup_check_bn254() {
+        let params = G2Params::bn254();
+        let g = Some(params.generator.clone());
+        let q = params.mul(&BigInt::from(0xbeef), &g).unwrap();
+        assert!(subgroup_check("BN254", params.clone(), q));
+        let outside = non_subgroup_point(&params);
+        assert!(!subgroup_check("BN254", params, outside));
+    }
+}
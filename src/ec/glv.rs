@@ -0,0 +1,361 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Signed;
+
+use super::affine::EmulatedAffinePoint;
+use super::msm::{msm, Base};
+use super::projective::EmulatedProjectivePoint;
+use super::{residue, CurveParams, NativePoint};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, sqrt, Term};
+use crate::util::bit::{Bit, Bitvector};
+use crate::util::gadget::Gadget;
+
+/// The GLV endomorphism `phi(x, y) = (beta * x, y)` of a curve `y^2 = x^3 + b` whose base field
+/// has a primitive cube root of unity `beta`. On the prime order group, `phi` acts as
+/// multiplication by a cube root of unity `lambda` modulo the order, so `k * P` can be computed
+/// as `k1 * P + k2 * phi(P)` for `k = k1 + lambda * k2` with `k1`, `k2` about half as long as
+/// `k`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GlvParams {
+    /// The cube root of unity modulo the field modulus
+    pub beta: BigInt,
+    /// The cube root of unity modulo the order
+    pub lambda: BigInt,
+    /// A reduced basis `(a, b)` of the lattice of `a + b * lambda == 0` (mod the order)
+    pub basis: [(BigInt, BigInt); 2],
+    /// The number of bits of the magnitudes of the halves of a decomposition
+    pub n_bits: usize,
+}
+
+/// A primitive cube root of unity modulo the prime `m`, if there is one.
+fn cube_root_of_unity(m: &BigInt) -> Option<BigInt> {
+    let s = sqrt(&(-BigInt::from(3)).mod_floor(m), m)?;
+    let half = invert(&BigInt::from(2), m).ok()?;
+    Some(((s - 1u32) * half).mod_floor(m))
+}
+
+impl GlvParams {
+    /// The parameters of the endomorphism of `params`, if it has one, i.e. if `a = 0` and both
+    /// the field modulus and the order are 1 modulo 3.
+    pub fn new(params: &CurveParams) -> Option<Self> {
+        if params.a != BigInt::from(0) {
+            return None;
+        }
+        let beta = cube_root_of_unity(&params.p)?;
+        let n = &params.order;
+        // Of the two cube roots of unity modulo the order, pick the one that matches beta
+        let g = Some(params.generator.clone());
+        let (gx, gy) = &params.generator;
+        let phi_g = Some(((&beta * gx).mod_floor(&params.p), gy.clone()));
+        let lambda = cube_root_of_unity(n)?;
+        let lambda = if params.mul(&lambda, &g) == phi_g {
+            lambda
+        } else {
+            (&lambda * &lambda).mod_floor(n)
+        };
+        if params.mul(&lambda, &g) != phi_g {
+            return None;
+        }
+
+        // The extended Euclidean algorithm on (n, lambda) yields remainders r = s * n + t * lambda,
+        // so each (r, -t) is in the lattice; the short basis is found where r crosses sqrt(n)
+        // (Guide to Elliptic Curve Cryptography, Algorithm 3.74).
+        let sqrt_n = n.sqrt();
+        let mut rows = vec![
+            (n.clone(), BigInt::from(0)),
+            (lambda.clone(), BigInt::from(1)),
+        ];
+        while rows[rows.len() - 1].0 != BigInt::from(0) {
+            let (r0, t0) = &rows[rows.len() - 2];
+            let (r1, t1) = &rows[rows.len() - 1];
+            let q = r0 / r1;
+            let row = (r0 - &q * r1, t0 - &q * t1);
+            rows.push(row);
+        }
+        let l = rows.iter().rposition(|(r, _)| r >= &sqrt_n)?;
+        let vector = |i: usize| (rows[i].0.clone(), -rows[i].1.clone());
+        let norm = |(a, b): &(BigInt, BigInt)| a * a + b * b;
+        let v1 = vector(l + 1);
+        let v2 = [vector(l), vector(l + 2)]
+            .into_iter()
+            .min_by_key(norm)
+            .unwrap();
+
+        // |k1| <= (|a1| + |a2|) / 2 + 1 and |k2| <= (|b1| + |b2|) / 2 + 1
+        let bound = |x: &BigInt, y: &BigInt| (x.abs() + y.abs()) / 2u32 + 1u32;
+        let n_bits = std::cmp::max(bound(&v1.0, &v2.0).bits(), bound(&v1.1, &v2.1).bits()) as usize;
+        Some(GlvParams {
+            beta,
+            lambda,
+            basis: [v1, v2],
+            n_bits,
+        })
+    }
+
+    /// Applies the endomorphism to a point.
+    pub fn endomorphism(&self, params: &CurveParams, point: &NativePoint) -> NativePoint {
+        point
+            .as_ref()
+            .map(|(x, y)| ((&self.beta * x).mod_floor(&params.p), y.clone()))
+    }
+
+    /// Decomposes `k` into `(k1, k2)` with `k == k1 + lambda * k2` modulo `order`, by rounding
+    /// `(k, 0)` to a nearby lattice point.
+    pub fn decompose(&self, k: &BigInt, order: &BigInt) -> (BigInt, BigInt) {
+        let [(a1, b1), (a2, b2)] = &self.basis;
+        let round = |x: BigInt| (2u32 * x + order).div_floor(&(2u32 * order));
+        let c1 = round(b2 * k);
+        let c2 = round(-b1 * k);
+        (k - &c1 * a1 - &c2 * a2, -&c1 * b1 - &c2 * b2)
+    }
+}
+
+/// Allocates the sign (set if negative) and the `n_bits` (little-endian) bits of the magnitude of
+/// `v`.
+fn alloc_signed<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    v: Option<&BigInt>,
+    n_bits: usize,
+) -> Result<(Bit<Scalar>, Bitvector<Scalar>), SynthesisError> {
+    let sign = Bit::alloc(cs.namespace(|| "sign"), v.map(|v| v.sign() == Sign::Minus))?;
+    let bits = (0..n_bits)
+        .map(|i| {
+            Bit::alloc(
+                cs.namespace(|| format!("bit {}", i)),
+                v.map(|v| v.magnitude().bit(i as u64)),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((sign, Bitvector::from_bits(bits)))
+}
+
+impl<Scalar: PrimeField> EmulatedAffinePoint<Scalar> {
+    /// Computes `scalar * self` with the GLV endomorphism of the curve (see `GlvParams`).
+    ///
+    /// The decomposition `scalar == k1 + lambda * k2` (mod the order) is witnessed as the signs
+    /// `s1`, `s2` and the bits of the magnitudes of `k1` and `k2`, and proven by checking that
+    /// `scalar + 2 s1 |k1| + 2 s2 lambda |k2|` is congruent to `|k1| + lambda |k2|`. The bases
+    /// `+-self` and `+-phi(self)` are then selected by the signs, and the two half-length
+    /// multiplications are done jointly by `msm`, sharing their doublings.
+    ///
+    /// `scalar` must be well formed, with limbs of the curve's limb width. `self` must not be the
+    /// identity, and must have the curve's prime order.
+    pub fn glv_mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        scalar: &BigNat<Scalar>,
+        window: usize,
+    ) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+        let params = &self.params;
+        let glv = match GlvParams::new(params) {
+            Some(glv) => glv,
+            None => {
+                eprintln!("The curve has no GLV endomorphism");
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        };
+        let (lw, n_limbs) = (params.limb_width, params.n_limbs);
+        let halves = scalar
+            .value
+            .as_ref()
+            .map(|k| glv.decompose(k, &params.order));
+        let (s1, k1) = alloc_signed(
+            cs.namespace(|| "k1"),
+            halves.as_ref().map(|h| &h.0),
+            glv.n_bits,
+        )?;
+        let (s2, k2) = alloc_signed(
+            cs.namespace(|| "k2"),
+            halves.as_ref().map(|h| &h.1),
+            glv.n_bits,
+        )?;
+
+        // scalar + 2 s1 |k1| + 2 s2 lambda |k2| == |k1| + lambda |k2|
+        let order = BigNat::constant::<CS>(&params.order, lw, n_limbs)?;
+        let lambda = BigNat::constant::<CS>(&glv.lambda, lw, n_limbs)?;
+        let k1_nat = BigNat::recompose(&k1, lw);
+        let k2_nat = BigNat::recompose(&k2, lw);
+        let zero = BigNat::constant::<CS>(&BigInt::from(0), lw, k1_nat.params.n_limbs)?;
+        let m1 = Gadget::mux(cs.namespace(|| "s1 k1"), &s1, &zero, &k1_nat)?;
+        let m2 = Gadget::mux(cs.namespace(|| "s2 k2"), &s2, &zero, &k2_nat)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "decomposition"),
+            &[
+                Term::Single(scalar),
+                Term::Single(&m1),
+                Term::Single(&m1),
+                Term::Product(&lambda, &m2),
+                Term::Product(&lambda, &m2),
+            ],
+            &[Term::Single(&k1_nat), Term::Product(&lambda, &k2_nat)],
+            &order,
+        )?;
+
+        // The bases +-self and +-phi(self)
+        let p = params.modulus::<Scalar, CS>()?;
+        let beta = params.constant::<Scalar, CS>(&glv.beta)?;
+        let phi_x = residue(&mut cs, "phi x", &[Term::Product(&beta, &self.x)], &p)?;
+        let phi = Self::from_coords(phi_x, self.y.clone(), params);
+        let neg = self.negate(cs.namespace(|| "negate"))?;
+        let phi_neg = Self::from_coords(phi.x.clone(), neg.y.clone(), params);
+        let p1 = Gadget::mux(cs.namespace(|| "base 1"), &s1, self, &neg)?;
+        let p2 = Gadget::mux(cs.namespace(|| "base 2"), &s2, &phi, &phi_neg)?;
+
+        msm(
+            cs.namespace(|| "msm"),
+            &[(Base::Variable(&p1), &k1), (Base::Variable(&p2), &k2)],
+            window,
+            params,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_helpers::*;
+    use crate::OptionExt;
+
+    #[test]
+    fn glv_params_decompose() {
+        for params in [CurveParams::secp256k1(), CurveParams::bn254()] {
+            let glv = GlvParams::new(&params).unwrap();
+            let g = Some(params.generator.clone());
+            assert_eq!(glv.endomorphism(&params, &g), params.mul(&glv.lambda, &g));
+            for (a, b) in &glv.basis {
+                assert_eq!(
+                    (a + b * &glv.lambda).mod_floor(&params.order),
+                    BigInt::from(0)
+                );
+            }
+            let n = &params.order;
+            for k in [
+                BigInt::from(1),
+                n - 1u32,
+                n / 2u32,
+                n / 3u32,
+                &glv.lambda - 1u32,
+                (BigInt::from(1) << 200u32) + 12345u32,
+            ] {
+                let (k1, k2) = glv.decompose(&k, n);
+                assert_eq!((&k1 + &k2 * &glv.lambda).mod_floor(n), k);
+                assert!(k1.magnitude().bits() as usize <= glv.n_bits);
+                assert!(k2.magnitude().bits() as usize <= glv.n_bits);
+            }
+        }
+        // secp256k1's halves fit in 129 bits
+        assert!(GlvParams::new(&CurveParams::secp256k1()).unwrap().n_bits <= 129);
+        assert_eq!(GlvParams::new(&CurveParams::p256()), None);
+    }
+
+    pub struct GlvMulInputs {
+        pub k: BigInt,
+        pub p: (BigInt, BigInt),
+        pub expected: NativePoint,
+    }
+
+    pub struct GlvMul {
+        inputs: Option<GlvMulInputs>,
+        params: CurveParams,
+        window: usize,
+        /// Whether to use the GLV method rather than plain variable-base multiplication
+        glv: bool,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for GlvMul {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let p = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "p"),
+                || Ok(inputs.p.clone()),
+                params,
+            )?;
+            p.assert_on_curve(cs.namespace(|| "p on curve"))?;
+            let k = BigNat::alloc_from_nat(
+                cs.namespace(|| "k"),
+                || Ok(inputs.k.clone()),
+                params.limb_width,
+                params.n_limbs,
+            )?;
+            let product = if self.glv {
+                k.assert_well_formed(cs.namespace(|| "k rangecheck"))?;
+                p.glv_mul(cs.namespace(|| "k * p"), &k, self.window)?
+            } else {
+                let bits = k.decompose(cs.namespace(|| "decompose"))?;
+                p.scalar_mul(cs.namespace(|| "k * p"), &bits, self.window)?
+            };
+            let expected = EmulatedProjectivePoint::constant::<CS>(
+                &params.to_projective(&inputs.expected),
+                params,
+            )?;
+            product.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    fn glv_mul(params: CurveParams, k: BigInt, p: u64, window: usize, honest: bool) -> GlvMul {
+        let g = Some(params.generator.clone());
+        let p = params.mul(&BigInt::from(p), &g);
+        let mut expected = params.mul(&k, &p);
+        if !honest {
+            expected = params.add(&expected, &g);
+        }
+        GlvMul {
+            inputs: Some(GlvMulInputs {
+                k,
+                p: p.unwrap(),
+                expected,
+            }),
+            params,
+            window,
+            glv: true,
+        }
+    }
+
+    /// A scalar whose decomposition has halves of both signs.
+    fn mixed_signs(params: &CurveParams) -> BigInt {
+        let glv = GlvParams::new(params).unwrap();
+        (1u64..)
+            .map(|i| (&params.order / 7u32) * i)
+            .find(|k| {
+                let (k1, k2) = glv.decompose(k, &params.order);
+                (k1.sign() == Sign::Minus) != (k2.sign() == Sign::Minus)
+            })
+            .unwrap()
+    }
+
+    circuit_tests! {
+        glv_mul_secp256k1: (glv_mul(CurveParams::secp256k1(), mixed_signs(&CurveParams::secp256k1()), 3, 4, true), true),
+        glv_mul_secp256k1_zero: (glv_mul(CurveParams::secp256k1(), BigInt::from(0), 5, 4, true), true),
+        glv_mul_bn254: (glv_mul(CurveParams::bn254(), mixed_signs(&CurveParams::bn254()), 7, 4, true), true),
+        glv_mul_secp256k1_wrong: (glv_mul(CurveParams::secp256k1(), BigInt::from(0x5e), 5, 4, false), false),
+    }
+
+    /// Reports the costs of a full-width multiplication with both the plain variable-base and
+    /// the GLV methods, checking that GLV is cheaper.
+    fn compare_full_width(name: &str, params: CurveParams, window: usize) {
+        let k = full_width_scalar(&params.order);
+        let mut plain = glv_mul(params.clone(), k.clone(), 1, window, true);
+        plain.glv = false;
+        let plain = report_full_width(&format!("{} window {}, plain", name, window), plain);
+        let glv = glv_mul(params, k, 1, window, true);
+        let glv = report_full_width(&format!("{} window {}, GLV", name, window), glv);
+        assert!(glv < plain);
+    }
+
+    #[test]
+    fn glv_mul_secp256k1_cost() {
+        compare_full_width("secp256k1", CurveParams::secp256k1(), 4);
+    }
+
+    #[test]
+    fn glv_mul_bn254_cost() {
+        compare_full_width("BN254 G1", CurveParams::bn254(), 4);
+    }
+}
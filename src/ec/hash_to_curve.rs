@@ -0,0 +1,666 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use super::affine::EmulatedAffinePoint;
+use super::compress::{assert_parity, be_bytes_to_bits};
+use super::projective::EmulatedProjectivePoint;
+use super::{hex, residue, CurveParams, NativePoint};
+use crate::hash::to_bitvector;
+use crate::hash::xmd::{expand_message_xmd, native_expand_message_xmd};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, sqrt, Term};
+use crate::util::bit::Bit;
+use crate::OptionExt;
+
+fn hexes(coefficients: &[&str]) -> Vec<BigInt> {
+    coefficients.iter().map(|c| hex(c)).collect()
+}
+
+/// The rational maps `(x, y) -> (x_num(x) / x_den(x), y * y_num(x) / y_den(x))` of an isogeny,
+/// each polynomial given by its coefficients, constant term first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IsogenyMap {
+    pub x_num: Vec<BigInt>,
+    pub x_den: Vec<BigInt>,
+    pub y_num: Vec<BigInt>,
+    pub y_den: Vec<BigInt>,
+}
+
+impl IsogenyMap {
+    /// The largest degree of the polynomials.
+    fn degree(&self) -> usize {
+        [&self.x_num, &self.x_den, &self.y_num, &self.y_den]
+            .iter()
+            .map(|c| c.len() - 1)
+            .max()
+            .unwrap()
+    }
+}
+
+/// Evaluates the polynomial with the given coefficients at `x` modulo `p`.
+fn evaluate(coefficients: &[BigInt], x: &BigInt, p: &BigInt) -> BigInt {
+    coefficients
+        .iter()
+        .rev()
+        .fold(BigInt::from(0), |acc, c| (acc * x + c).mod_floor(p))
+}
+
+/// Parameters of a hash-to-curve suite of RFC 9380 with `expand_message_xmd` over SHA-256 and the
+/// simplified SWU map, e.g. `secp256k1_XMD:SHA-256_SSWU_RO_`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashToCurveParams {
+    /// The target curve
+    pub curve: CurveParams,
+    /// The coefficients of the curve `y^2 = x^3 + a*x + b`, with `a * b != 0`, which the SWU map
+    /// targets: the target curve itself, or a curve isogenous to it
+    pub a: BigInt,
+    pub b: BigInt,
+    /// The non-square `Z` of the SWU map
+    pub z: BigInt,
+    /// The isogeny to the target curve, if the SWU map does not target it directly
+    pub isogeny: Option<IsogenyMap>,
+    /// The multiple `h_eff` which clears the cofactor
+    pub h_eff: BigInt,
+    /// The length `L`, in bytes, of the uniform strings reduced to field elements
+    pub l: usize,
+}
+
+impl HashToCurveParams {
+    /// The suites for secp256k1 (RFC 9380, section 8.7), through a 3-isogeny.
+    pub fn secp256k1() -> Self {
+        let curve = CurveParams::secp256k1();
+        HashToCurveParams {
+            a: hex("3f8731abdd661adca08a5558f0f5d272e953d363cb6f0e5d405447c01a444533"),
+            b: BigInt::from(1771),
+            z: &curve.p - 11,
+            isogeny: Some(IsogenyMap {
+                x_num: hexes(&[
+                    "8e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38daaaaa8c7",
+                    "7d3d4c80bc321d5b9f315cea7fd44c5d595d2fc0bf63b92dfff1044f17c6581",
+                    "534c328d23f234e6e2a413deca25caece4506144037c40314ecbd0b53d9dd262",
+                    "8e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38daaaaa88c",
+                ]),
+                x_den: hexes(&[
+                    "d35771193d94918a9ca34ccbb7b640dd86cd409542f8487d9fe6b745781eb49b",
+                    "edadc6f64383dc1df7c4b2d51b54225406d36b641f5e41bbc52a56612a8c6d14",
+                    "1",
+                ]),
+                y_num: hexes(&[
+                    "4bda12f684bda12f684bda12f684bda12f684bda12f684bda12f684b8e38e23c",
+                    "c75e0c32d5cb7c0fa9d0a54b12a0a6d5647ab046d686da6fdffc90fc201d71a3",
+                    "29a6194691f91a73715209ef6512e576722830a201be2018a765e85a9ecee931",
+                    "2f684bda12f684bda12f684bda12f684bda12f684bda12f684bda12f38e38d84",
+                ]),
+                y_den: hexes(&[
+                    "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffff93b",
+                    "7a06534bb8bdb49fd5e9e6632722c2989467c1bfc8e8d978dfb425d2685c2573",
+                    "6484aa716545ca2cf3a70c3fa8fe337e0a3d21162f0d6299a7bf8192bfd2a76f",
+                    "1",
+                ]),
+            }),
+            h_eff: BigInt::from(1),
+            l: 48,
+            curve,
+        }
+    }
+
+    /// The suites for P-256 (RFC 9380, section 8.2), which the SWU map targets directly.
+    pub fn p256() -> Self {
+        let curve = CurveParams::p256();
+        HashToCurveParams {
+            a: curve.a.clone(),
+            b: curve.b.clone(),
+            z: &curve.p - 10,
+            isogeny: None,
+            h_eff: BigInt::from(1),
+            l: 48,
+            curve,
+        }
+    }
+
+    /// The suites for the BLS12-381 curve's group G1 (RFC 9380, section 8.8.1), through an
+    /// 11-isogeny.
+    pub fn bls12_381() -> Self {
+        HashToCurveParams {
+            a: hex("144698a3b8e9433d693a02c96d4982b0ea985383ee66a8d8e8981aefd881ac98936f8da0e0f97f5cf428082d584c1d"),
+            b: hex("12e2908d11688030018b12e8753eee3b2016c1f0f24f4070a0b9c14fcef35ef55a23215a316ceaa5d1cc48e98e172be0"),
+            z: BigInt::from(11),
+            isogeny: Some(IsogenyMap {
+                x_num: hexes(&[
+                    "11a05f2b1e833340b809101dd99815856b303e88a2d7005ff2627b56cdb4e2c85610c2d5f2e62d6eaeac1662734649b7",
+                    "17294ed3e943ab2f0588bab22147a81c7c17e75b2f6a8417f565e33c70d1e86b4838f2a6f318c356e834eef1b3cb83bb",
+                    "d54005db97678ec1d1048c5d10a9a1bce032473295983e56878e501ec68e25c958c3e3d2a09729fe0179f9dac9edcb0",
+                    "1778e7166fcc6db74e0609d307e55412d7f5e4656a8dbf25f1b33289f1b330835336e25ce3107193c5b388641d9b6861",
+                    "e99726a3199f4436642b4b3e4118e5499db995a1257fb3f086eeb65982fac18985a286f301e77c451154ce9ac8895d9",
+                    "1630c3250d7313ff01d1201bf7a74ab5db3cb17dd952799b9ed3ab9097e68f90a0870d2dcae73d19cd13c1c66f652983",
+                    "d6ed6553fe44d296a3726c38ae652bfb11586264f0f8ce19008e218f9c86b2a8da25128c1052ecaddd7f225a139ed84",
+                    "17b81e7701abdbe2e8743884d1117e53356de5ab275b4db1a682c62ef0f2753339b7c8f8c8f475af9ccb5618e3f0c88e",
+                    "80d3cf1f9a78fc47b90b33563be990dc43b756ce79f5574a2c596c928c5d1de4fa295f296b74e956d71986a8497e317",
+                    "169b1f8e1bcfa7c42e0c37515d138f22dd2ecb803a0c5c99676314baf4bb1b7fa3190b2edc0327797f241067be390c9e",
+                    "10321da079ce07e272d8ec09d2565b0dfa7dccdde6787f96d50af36003b14866f69b771f8c285decca67df3f1605fb7b",
+                    "6e08c248e260e70bd1e962381edee3d31d79d7e22c837bc23c0bf1bc24c6b68c24b1b80b64d391fa9c8ba2e8ba2d229",
+                ]),
+                x_den: hexes(&[
+                    "8ca8d548cff19ae18b2e62f4bd3fa6f01d5ef4ba35b48ba9c9588617fc8ac62b558d681be343df8993cf9fa40d21b1c",
+                    "12561a5deb559c4348b4711298e536367041e8ca0cf0800c0126c2588c48bf5713daa8846cb026e9e5c8276ec82b3bff",
+                    "b2962fe57a3225e8137e629bff2991f6f89416f5a718cd1fca64e00b11aceacd6a3d0967c94fedcfcc239ba5cb83e19",
+                    "3425581a58ae2fec83aafef7c40eb545b08243f16b1655154cca8abc28d6fd04976d5243eecf5c4130de8938dc62cd8",
+                    "13a8e162022914a80a6f1d5f43e7a07dffdfc759a12062bb8d6b44e833b306da9bd29ba81f35781d539d395b3532a21e",
+                    "e7355f8e4e667b955390f7f0506c6e9395735e9ce9cad4d0a43bcef24b8982f7400d24bc4228f11c02df9a29f6304a5",
+                    "772caacf16936190f3e0c63e0596721570f5799af53a1894e2e073062aede9cea73b3538f0de06cec2574496ee84a3a",
+                    "14a7ac2a9d64a8b230b3f5b074cf01996e7f63c21bca68a81996e1cdf9822c580fa5b9489d11e2d311f7d99bbdcc5a5e",
+                    "a10ecf6ada54f825e920b3dafc7a3cce07f8d1d7161366b74100da67f39883503826692abba43704776ec3a79a1d641",
+                    "95fc13ab9e92ad4476d6e3eb3a56680f682b4ee96f7d03776df533978f31c1593174e4b4b7865002d6384d168ecdd0a",
+                    "1",
+                ]),
+                y_num: hexes(&[
+                    "90d97c81ba24ee0259d1f094980dcfa11ad138e48a869522b52af6c956543d3cd0c7aee9b3ba3c2be9845719707bb33",
+                    "134996a104ee5811d51036d776fb46831223e96c254f383d0f906343eb67ad34d6c56711962fa8bfe097e75a2e41c696",
+                    "cc786baa966e66f4a384c86a3b49942552e2d658a31ce2c344be4b91400da7d26d521628b00523b8dfe240c72de1f6",
+                    "1f86376e8981c217898751ad8746757d42aa7b90eeb791c09e4a3ec03251cf9de405aba9ec61deca6355c77b0e5f4cb",
+                    "8cc03fdefe0ff135caf4fe2a21529c4195536fbe3ce50b879833fd221351adc2ee7f8dc099040a841b6daecf2e8fedb",
+                    "16603fca40634b6a2211e11db8f0a6a074a7d0d4afadb7bd76505c3d3ad5544e203f6326c95a807299b23ab13633a5f0",
+                    "4ab0b9bcfac1bbcb2c977d027796b3ce75bb8ca2be184cb5231413c4d634f3747a87ac2460f415ec961f8855fe9d6f2",
+                    "987c8d5333ab86fde9926bd2ca6c674170a05bfe3bdd81ffd038da6c26c842642f64550fedfe935a15e4ca31870fb29",
+                    "9fc4018bd96684be88c9e221e4da1bb8f3abd16679dc26c1e8b6e6a1f20cabe69d65201c78607a360370e577bdba587",
+                    "e1bba7a1186bdb5223abde7ada14a23c42a0ca7915af6fe06985e7ed1e4d43b9b3f7055dd4eba6f2bafaaebca731c30",
+                    "19713e47937cd1be0dfd0b8f1d43fb93cd2fcbcb6caf493fd1183e416389e61031bf3a5cce3fbafce813711ad011c132",
+                    "18b46a908f36f6deb918c143fed2edcc523559b8aaf0c2462e6bfe7f911f643249d9cdf41b44d606ce07c8a4d0074d8e",
+                    "b182cac101b9399d155096004f53f447aa7b12a3426b08ec02710e807b4633f06c851c1919211f20d4c04f00b971ef8",
+                    "245a394ad1eca9b72fc00ae7be315dc757b3b080d4c158013e6632d3c40659cc6cf90ad1c232a6442d9d3f5db980133",
+                    "5c129645e44cf1102a159f748c4a3fc5e673d81d7e86568d9ab0f5d396a7ce46ba1049b6579afb7866b1e715475224b",
+                    "15e6be4e990f03ce4ea50b3b42df2eb5cb181d8f84965a3957add4fa95af01b2b665027efec01c7704b456be69c8b604",
+                ]),
+                y_den: hexes(&[
+                    "16112c4c3a9c98b252181140fad0eae9601a6de578980be6eec3232b5be72e7a07f3688ef60c206d01479253b03663c1",
+                    "1962d75c2381201e1a0cbd6c43c348b885c84ff731c4d59ca4a10356f453e01f78a4260763529e3532f6102c2e49a03d",
+                    "58df3306640da276faaae7d6e8eb15778c4855551ae7f310c35a5dd279cd2eca6757cd636f96f891e2538b53dbf67f2",
+                    "16b7d288798e5395f20d23bf89edb4d1d115c5dbddbcd30e123da489e726af41727364f2c28297ada8d26d98445f5416",
+                    "be0e079545f43e4b00cc912f8228ddcc6d19c9f0f69bbb0542eda0fc9dec916a20b15dc0fd2ededda39142311a5001d",
+                    "8d9e5297186db2d9fb266eaac783182b70152c65550d881c5ecd87b6f0f5a6449f38db9dfa9cce202c6477faaf9b7ac",
+                    "166007c08a99db2fc3ba8734ace9824b5eecfdfa8d0cf8ef5dd365bc400a0051d5fa9c01a58b1fb93d1a1399126a775c",
+                    "16a3ef08be3ea7ea03bcddfabba6ff6ee5a4375efa1f4fd7feb34fd206357132b920f5b00801dee460ee415a15812ed9",
+                    "1866c8ed336c61231a1be54fd1d74cc4f9fb0ce4c6af5920abc5750c4bf39b4852cfe2f7bb9248836b233d9d55535d4a",
+                    "167a55cda70a6e1cea820597d94a84903216f763e13d87bb5308592e7ea7d4fbc7385ea3d529b35e346ef48bb8913f55",
+                    "4d2f259eea405bd48f010a01ad2911d9c6dd039bb61a6290e591b36e636a5c871a5c29f4f83060400f8b49cba8f6aa8",
+                    "accbb67481d033ff5852c1e48c50c477f94ff8aefce42d28c0f9a88cea7913516f968986f7ebbea9684b529e2561092",
+                    "ad6b9514c767fe3c3613144b45f1496543346d98adf02267d5ceef9a00d9b8693000763e3b90ac11e99b138573345cc",
+                    "2660400eb2e4f3b628bdd0d53cd76f2bf565b94e72927c1cb748df27942480e420517bd8714cc80d1fadc1326ed06f7",
+                    "e0fa1d816ddc03e6b24255e0d7819c171c40f65e273b853324efcd6356caa205ca2f570f13497804415473a1d634b8f",
+                    "1",
+                ]),
+            }),
+            h_eff: hex("d201000000010001"),
+            l: 64,
+            curve: CurveParams::bls12_381(),
+        }
+    }
+
+    /// Reduces `count` uniform strings expanded from `msg` to field elements (`hash_to_field`).
+    pub fn hash_to_field(&self, msg: &[u8], dst: &[u8], count: usize) -> Vec<BigInt> {
+        native_expand_message_xmd(msg, dst, count * self.l)
+            .chunks(self.l)
+            .map(|bytes| BigInt::from_bytes_be(Sign::Plus, bytes).mod_floor(&self.curve.p))
+            .collect()
+    }
+
+    /// The simplified SWU map of `u` to the curve `y^2 = x^3 + a*x + b`.
+    pub fn map_to_curve_simple_swu(&self, u: &BigInt) -> (BigInt, BigInt) {
+        let p = &self.curve.p;
+        let g = |x: &BigInt| (x * x * x + &self.a * x + &self.b).mod_floor(p);
+        let zu2 = (&self.z * u * u).mod_floor(p);
+        let tv1 = (&zu2 * &zu2 + &zu2).mod_floor(p);
+        let x1 = if tv1 == BigInt::from(0) {
+            // The exceptional case: x1 = b / (z a)
+            &self.b * invert(&(&self.z * &self.a), p).unwrap()
+        } else {
+            -&self.b * invert(&self.a, p).unwrap() * (1 + invert(&tv1, p).unwrap())
+        }
+        .mod_floor(p);
+        let (x, y) = match sqrt(&g(&x1), p) {
+            Some(y) => (x1, y),
+            None => {
+                let x2 = (zu2 * x1).mod_floor(p);
+                let y = sqrt(&g(&x2), p).unwrap();
+                (x2, y)
+            }
+        };
+        let y = if y.is_odd() == u.mod_floor(p).is_odd() {
+            y
+        } else {
+            (p - y).mod_floor(p)
+        };
+        (x, y)
+    }
+
+    /// Maps a point of the curve the SWU map targets to the target curve.
+    pub fn iso_map(&self, (x, y): &(BigInt, BigInt)) -> NativePoint {
+        let p = &self.curve.p;
+        match &self.isogeny {
+            None => Some((x.clone(), y.clone())),
+            Some(iso) => {
+                let x_den = evaluate(&iso.x_den, x, p);
+                let y_den = evaluate(&iso.y_den, x, p);
+                if x_den == BigInt::from(0) || y_den == BigInt::from(0) {
+                    return None;
+                }
+                Some((
+                    (evaluate(&iso.x_num, x, p) * invert(&x_den, p).unwrap()).mod_floor(p),
+                    (y * evaluate(&iso.y_num, x, p) * invert(&y_den, p).unwrap()).mod_floor(p),
+                ))
+            }
+        }
+    }
+
+    /// Maps the field element `u` to the target curve (`map_to_curve`).
+    pub fn map_to_curve(&self, u: &BigInt) -> NativePoint {
+        self.iso_map(&self.map_to_curve_simple_swu(u))
+    }
+
+    pub fn clear_cofactor(&self, point: &NativePoint) -> NativePoint {
+        self.curve.mul(&self.h_eff, point)
+    }
+
+    /// Hashes `msg` to the curve with the random oracle encoding (`hash_to_curve`).
+    pub fn hash_to_curve(&self, msg: &[u8], dst: &[u8]) -> NativePoint {
+        let u = self.hash_to_field(msg, dst, 2);
+        let q = self
+            .curve
+            .add(&self.map_to_curve(&u[0]), &self.map_to_curve(&u[1]));
+        self.clear_cofactor(&q)
+    }
+
+    /// Hashes `msg` to the curve with the nonuniform encoding (`encode_to_curve`).
+    pub fn encode_to_curve(&self, msg: &[u8], dst: &[u8]) -> NativePoint {
+        let u = self.hash_to_field(msg, dst, 1);
+        self.clear_cofactor(&self.map_to_curve(&u[0]))
+    }
+}
+
+/// Reduces `count` uniform strings expanded from the message `msg` (given as the bits of its
+/// bytes, each byte's most significant bit first) to field elements (`hash_to_field`). The
+/// results are canonical.
+pub fn hash_to_field<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    count: usize,
+    params: &HashToCurveParams,
+) -> Result<Vec<BigNat<Scalar>>, SynthesisError> {
+    let curve = &params.curve;
+    let p = curve.modulus::<Scalar, CS>()?;
+    let n_bits = 8 * params.l;
+    let uniform = expand_message_xmd(cs.namespace(|| "expand"), msg, dst, count * params.l)?;
+    (0..count)
+        .map(|i| {
+            let bytes = to_bitvector::<Scalar, CS>(&uniform[i * n_bits..(i + 1) * n_bits]);
+            let n = BigNat::recompose(&be_bytes_to_bits(&bytes), curve.limb_width);
+            let u = residue(&mut cs, &format!("u {}", i), &[Term::Single(&n)], &p)?;
+            u.assert_canonical(cs.namespace(|| format!("u {} canonical", i)), &p)?;
+            Ok(u)
+        })
+        .collect()
+}
+
+/// Computes the value of the polynomial with the given constant coefficients at `x`, given the
+/// powers `[x, x^2, ...]`.
+fn polynomial<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: &mut CS,
+    name: &str,
+    coefficients: &[BigInt],
+    powers: &[BigNat<Scalar>],
+    params: &CurveParams,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    let p = params.modulus::<Scalar, CS>()?;
+    let constants = coefficients
+        .iter()
+        .map(|c| params.constant::<Scalar, CS>(c))
+        .collect::<Result<Vec<_>, _>>()?;
+    let terms: Vec<_> = constants
+        .iter()
+        .zip(coefficients)
+        .enumerate()
+        .map(|(i, (c, v))| match i {
+            0 => Term::Single(c),
+            _ if *v == BigInt::from(1) => Term::Single(&powers[i - 1]),
+            _ => Term::Product(c, &powers[i - 1]),
+        })
+        .collect();
+    residue(cs, name, &terms, &p)
+}
+
+/// Maps a point of the curve the SWU map targets to the target curve with the isogeny `iso`.
+/// The constraints are unsatisfiable for the points of the kernel, which map to the point at
+/// infinity.
+fn iso_map<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    x: &BigNat<Scalar>,
+    y: &BigNat<Scalar>,
+    iso: &IsogenyMap,
+    params: &CurveParams,
+) -> Result<EmulatedAffinePoint<Scalar>, SynthesisError> {
+    let p = params.modulus::<Scalar, CS>()?;
+    let mut powers = vec![x.clone()];
+    for i in 2..=iso.degree() {
+        let power = residue(
+            &mut cs,
+            &format!("x^{}", i),
+            &[Term::Product(powers.last().unwrap(), x)],
+            &p,
+        )?;
+        powers.push(power);
+    }
+    let x_num = polynomial(&mut cs, "x numerator", &iso.x_num, &powers, params)?;
+    let x_den = polynomial(&mut cs, "x denominator", &iso.x_den, &powers, params)?;
+    let y_num = polynomial(&mut cs, "y numerator", &iso.y_num, &powers, params)?;
+    let y_den = polynomial(&mut cs, "y denominator", &iso.y_den, &powers, params)?;
+    let x = x_num.div_mod(cs.namespace(|| "x"), &x_den, &p)?;
+    let y_ratio = y_num.div_mod(cs.namespace(|| "y ratio"), &y_den, &p)?;
+    let y = residue(&mut cs, "y", &[Term::Product(y, &y_ratio)], &p)?;
+    Ok(EmulatedAffinePoint::from_coords(x, y, params))
+}
+
+/// Maps the canonical field element `u` to the target curve (`map_to_curve`) with the simplified
+/// SWU map, followed by the isogeny if there is one.
+///
+/// The candidates `x1 = -b / a * (1 + 1 / (Z^2 u^4 + Z u^2))` and `x2 = Z u^2 x1` are computed,
+/// and the x-coordinate is constrained to be one of them. Since `g(x2) = (Z u^2)^3 g(x1)` for
+/// `g(x) = x^3 + a*x + b` and `Z` is not a square, exactly one of `g(x1)` and `g(x2)` is a square
+/// (the curve has no point of order two), so exhibiting a square root `y` of `g(x)` determines the
+/// choice. The sign of `y` is constrained to be that of `u`.
+///
+/// The exceptional cases are reached by hashing with negligible probability: it fails to
+/// synthesize if `Z^2 u^4 + Z u^2 = 0`, and the constraints are unsatisfiable for the points of
+/// the kernel of the isogeny.
+pub fn map_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    u: &BigNat<Scalar>,
+    params: &HashToCurveParams,
+) -> Result<EmulatedAffinePoint<Scalar>, SynthesisError> {
+    let curve = &params.curve;
+    let p = curve.modulus::<Scalar, CS>()?;
+    let a = curve.constant::<Scalar, CS>(&params.a)?;
+    let b = curve.constant::<Scalar, CS>(&params.b)?;
+    let z = curve.constant::<Scalar, CS>(&params.z)?;
+    let uu = residue(&mut cs, "u^2", &[Term::Product(u, u)], &p)?;
+    let zu2 = residue(&mut cs, "z u^2", &[Term::Product(&z, &uu)], &p)?;
+    let tv1 = residue(
+        &mut cs,
+        "tv1",
+        &[Term::Product(&zu2, &zu2), Term::Single(&zu2)],
+        &p,
+    )?;
+    let a_tv1 = residue(&mut cs, "a tv1", &[Term::Product(&a, &tv1)], &p)?;
+    // x1 a tv1 + b (tv1 + 1) = 0
+    let x1 = BigNat::alloc_residue(
+        cs.namespace(|| "x1"),
+        || {
+            let t = tv1.value.grab()?;
+            Ok(-&params.b * (t + 1u32) * invert(a_tv1.value.grab()?, &curve.p)?)
+        },
+        &p,
+    )?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "x1 check"),
+        &[
+            Term::Product(&x1, &a_tv1),
+            Term::Product(&b, &tv1),
+            Term::Single(&b),
+        ],
+        &[],
+        &p,
+    )?;
+    let x2 = residue(&mut cs, "x2", &[Term::Product(&zu2, &x1)], &p)?;
+
+    let point = u.value.as_ref().map(|u| params.map_to_curve_simple_swu(u));
+    // (x - x1) (x - x2) = 0
+    let x = BigNat::alloc_residue(cs.namespace(|| "x"), || Ok(point.grab()?.0.clone()), &p)?;
+    let x1_plus_x2 = x1.add::<CS>(&x2)?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "x choice"),
+        &[Term::Product(&x, &x), Term::Product(&x1, &x2)],
+        &[Term::Product(&x, &x1_plus_x2)],
+        &p,
+    )?;
+    // y^2 = x^3 + a x + b
+    let y = BigNat::alloc_residue(cs.namespace(|| "y"), || Ok(point.grab()?.1.clone()), &p)?;
+    y.assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+    let xx = residue(&mut cs, "x^2", &[Term::Product(&x, &x)], &p)?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "on curve"),
+        &[Term::Product(&y, &y)],
+        &[
+            Term::Product(&xx, &x),
+            Term::Product(&a, &x),
+            Term::Single(&b),
+        ],
+        &p,
+    )?;
+    // sgn0(u) = sgn0(y)
+    let odd = Bit::alloc(
+        cs.namespace(|| "sign"),
+        u.value.as_ref().map(|u| u.is_odd()),
+    )?;
+    assert_parity(cs.namespace(|| "u parity"), u, &odd)?;
+    assert_parity(cs.namespace(|| "y parity"), &y, &odd)?;
+
+    match &params.isogeny {
+        None => Ok(EmulatedAffinePoint::from_coords(x, y, curve)),
+        Some(iso) => iso_map(cs.namespace(|| "isogeny"), &x, &y, iso, curve),
+    }
+}
+
+/// Computes `h_eff * point`, which is in the subgroup of prime order.
+pub fn clear_cofactor<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: CS,
+    point: &EmulatedProjectivePoint<Scalar>,
+    params: &HashToCurveParams,
+) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+    if params.h_eff == BigInt::from(1) {
+        Ok(point.clone())
+    } else {
+        point.mul_by_constant(cs, &params.h_eff)
+    }
+}
+
+/// Hashes the message `msg`, given as the bits of its bytes, each byte's most significant bit
+/// first, to the curve with the random oracle encoding (`hash_to_curve`).
+pub fn hash_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    params: &HashToCurveParams,
+) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+    let u = hash_to_field(cs.namespace(|| "hash to field"), msg, dst, 2, params)?;
+    let q0 = map_to_curve(cs.namespace(|| "map u0"), &u[0], params)?;
+    let q1 = map_to_curve(cs.namespace(|| "map u1"), &u[1], params)?;
+    let q = EmulatedProjectivePoint::from_affine::<CS>(&q0)?.add(
+        cs.namespace(|| "add"),
+        &EmulatedProjectivePoint::from_affine::<CS>(&q1)?,
+    )?;
+    clear_cofactor(cs.namespace(|| "clear cofactor"), &q, params)
+}
+
+/// Hashes the message `msg`, given as the bits of its bytes, each byte's most significant bit
+/// first, to the curve with the nonuniform encoding (`encode_to_curve`).
+pub fn encode_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    params: &HashToCurveParams,
+) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+    let u = hash_to_field(cs.namespace(|| "hash to field"), msg, dst, 1, params)?;
+    let q = map_to_curve(cs.namespace(|| "map u0"), &u[0], params)?;
+    clear_cofactor(
+        cs.namespace(|| "clear cofactor"),
+        &EmulatedProjectivePoint::from_affine::<CS>(&q)?,
+        params,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    /// The domain separation tag of the test vectors of RFC 9380 for the suite `suite`.
+    fn dst(suite: &str) -> Vec<u8> {
+        format!("QUUX-V01-CS02-with-{}", suite).into_bytes()
+    }
+
+    /// Test vectors of RFC 9380, appendix J: the suite, the message and the coordinates.
+    const VECTORS: [(&str, &[u8], &str, &str); 7] = [
+        (
+            "secp256k1_XMD:SHA-256_SSWU_RO_",
+            b"",
+            "c1cae290e291aee617ebaef1be6d73861479c48b841eaba9b7b5852ddfeb1346",
+            "64fa678e07ae116126f08b022a94af6de15985c996c3a91b64c406a960e51067",
+        ),
+        (
+            "secp256k1_XMD:SHA-256_SSWU_RO_",
+            b"abc",
+            "3377e01eab42db296b512293120c6cee72b6ecf9f9205760bd9ff11fb3cb2c4b",
+            "7f95890f33efebd1044d382a01b1bee0900fb6116f94688d487c6c7b9c8371f6",
+        ),
+        (
+            "P256_XMD:SHA-256_SSWU_RO_",
+            b"",
+            "2c15230b26dbc6fc9a37051158c95b79656e17a1a920b11394ca91c44247d3e4",
+            "8a7a74985cc5c776cdfe4b1f19884970453912e9d31528c060be9ab5c43e8415",
+        ),
+        (
+            "P256_XMD:SHA-256_SSWU_RO_",
+            b"abc",
+            "0bb8b87485551aa43ed54f009230450b492fead5f1cc91658775dac4a3388a0f",
+            "5c41b3d0731a27a7b14bc0bf0ccded2d8751f83493404c84a88e71ffd424212e",
+        ),
+        (
+            "P256_XMD:SHA-256_SSWU_NU_",
+            b"",
+            "f871caad25ea3b59c16cf87c1894902f7e7b2c822c3d3f73596c5ace8ddd14d1",
+            "87b9ae23335bee057b99bac1e68588b18b5691af476234b8971bc4f011ddc99b",
+        ),
+        (
+            "BLS12381G1_XMD:SHA-256_SSWU_RO_",
+            b"",
+            "052926add2207b76ca4fa57a8734416c8dc95e24501772c814278700eed6d1e4e8cf62d9c09db0fac349612b759e79a1",
+            "08ba738453bfed09cb546dbb0783dbb3a5f1f566ed67bb6be0e8c67e2e81a4cc68ee29813bb7994998f3eae0c9c6a265",
+        ),
+        (
+            "BLS12381G1_XMD:SHA-256_SSWU_RO_",
+            b"abc",
+            "03567bc5ef9c690c2ab2ecdf6a96ef1c139cc0b2f284dca0a9a7943388a49a3aee664ba5379a7655d3c68900be2f6903",
+            "0b9c15f3fe6e5cf4211f346271d7b01c8f3b28be689c8429c85b67af215533311f0b8dfaaa154fa6b88176c229f2885d",
+        ),
+    ];
+
+    fn suite_params(suite: &str) -> HashToCurveParams {
+        match suite.split('_').next().unwrap() {
+            "secp256k1" => HashToCurveParams::secp256k1(),
+            "P256" => HashToCurveParams::p256(),
+            "BLS12381G1" => HashToCurveParams::bls12_381(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn hash_to_curve_vectors() {
+        for (suite, msg, x, y) in VECTORS {
+            let params = suite_params(suite);
+            let point = if suite.ends_with("RO_") {
+                params.hash_to_curve(msg, &dst(suite))
+            } else {
+                params.encode_to_curve(msg, &dst(suite))
+            };
+            assert_eq!(point, Some((hex(x), hex(y))), "{} {:?}", suite, msg);
+        }
+    }
+
+    #[test]
+    fn isogenies_map_to_curve() {
+        for params in [
+            HashToCurveParams::secp256k1(),
+            HashToCurveParams::bls12_381(),
+        ] {
+            for u in 1..20 {
+                let (x, y) = params.map_to_curve_simple_swu(&BigInt::from(u));
+                assert_eq!(
+                    (&y * &y - &x * &x * &x - &params.a * &x - &params.b)
+                        .mod_floor(&params.curve.p),
+                    BigInt::from(0)
+                );
+                assert_eq!(y.is_odd(), u % 2 == 1);
+                assert!(params
+                    .curve
+                    .is_on_curve(&params.map_to_curve(&BigInt::from(u))));
+            }
+        }
+    }
+
+    pub struct HashToCurve {
+        msg: Vec<u8>,
+        suite: &'static str,
+        expected: NativePoint,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for HashToCurve {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = suite_params(self.suite);
+            let dst = dst(self.suite);
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(&self.msg), self.msg.len())?;
+            let point = if self.suite.ends_with("RO_") {
+                hash_to_curve(cs.namespace(|| "hash"), &msg, &dst, &params)?
+            } else {
+                encode_to_curve(cs.namespace(|| "hash"), &msg, &dst, &params)?
+            };
+            let expected = EmulatedProjectivePoint::constant::<CS>(
+                &params.curve.to_projective(&self.expected),
+                &params.curve,
+            )?;
+            point.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    /// Hashes `msg` with `suite`, expecting the native hash of `expected_msg`.
+    fn hash(suite: &'static str, msg: &[u8], expected_msg: &[u8]) -> HashToCurve {
+        let params = suite_params(suite);
+        let expected = if suite.ends_with("RO_") {
+            params.hash_to_curve(expected_msg, &dst(suite))
+        } else {
+            params.encode_to_curve(expected_msg, &dst(suite))
+        };
+        HashToCurve {
+            msg: msg.to_vec(),
+            suite,
+            expected,
+        }
+    }
+
+    circuit_tests! {
+        hash_to_curve_secp256k1: (hash("secp256k1_XMD:SHA-256_SSWU_RO_", b"abc", b"abc"), true),
+        encode_to_curve_p256: (hash("P256_XMD:SHA-256_SSWU_NU_", b"abc", b"abc"), true),
+        hash_to_curve_secp256k1_wrong: (hash("secp256k1_XMD:SHA-256_SSWU_RO_", b"abc", b"abd"), false),
+    }
+
+    #[test]
+    fn hash_to_curve_bls12_381() {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        hash("BLS12381G1_XMD:SHA-256_SSWU_RO_", b"abc", b"abc")
+            .synthesize(&mut cs)
+            .unwrap();
+        println!(
+            "BLS12-381 G1 hash to curve: {} constraints",
+            cs.num_constraints()
+        );
+        assert_eq!(cs.which_is_unsatisfied(), None);
+    }
+}
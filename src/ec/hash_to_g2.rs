@@ -0,0 +1,630 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use super::compress::{assert_parity, be_bytes_to_bits};
+use super::fp2::{EmulatedFp2, Fp2Params, Fp2Term, NativeFp2};
+use super::g2::{EmulatedG2Point, G2Params, NativeG2Point};
+use super::{hex, residue};
+use crate::hash::to_bitvector;
+use crate::hash::xmd::{expand_message_xmd, native_expand_message_xmd};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::util::bit::Bit;
+use crate::OptionExt;
+
+fn fp2s(coefficients: &[(&str, &str)]) -> Vec<NativeFp2> {
+    coefficients
+        .iter()
+        .map(|(c0, c1)| (hex(c0), hex(c1)))
+        .collect()
+}
+
+/// The rational maps `(x, y) -> (x_num(x) / x_den(x), y * y_num(x) / y_den(x))` of an isogeny of
+/// curves over `Fp2`, each polynomial given by its coefficients, constant term first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fp2IsogenyMap {
+    pub x_num: Vec<NativeFp2>,
+    pub x_den: Vec<NativeFp2>,
+    pub y_num: Vec<NativeFp2>,
+    pub y_den: Vec<NativeFp2>,
+}
+
+impl Fp2IsogenyMap {
+    /// The largest degree of the polynomials.
+    fn degree(&self) -> usize {
+        [&self.x_num, &self.x_den, &self.y_num, &self.y_den]
+            .iter()
+            .map(|c| c.len() - 1)
+            .max()
+            .unwrap()
+    }
+}
+
+/// Evaluates the polynomial with the given coefficients at `x`.
+fn evaluate(f: &Fp2Params, coefficients: &[NativeFp2], x: &NativeFp2) -> NativeFp2 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(f.from_int(0), |acc, c| f.add(&f.mul(&acc, x), c))
+}
+
+/// The sign `sgn0(a)` of an element of `Fp2` (RFC 9380, section 4.1): the parity of `c0`, or of
+/// `c1` if `c0` is zero.
+fn sgn0(f: &Fp2Params, a: &NativeFp2) -> bool {
+    let (a0, a1) = f.reduce(a);
+    a0.is_odd() || (a0 == BigInt::from(0) && a1.is_odd())
+}
+
+/// Parameters of a hash-to-curve suite of RFC 9380 for G2 (see `G2Params`), with
+/// `expand_message_xmd` over SHA-256 and the simplified SWU map to a curve isogenous to the
+/// twist, e.g. `BLS12381G2_XMD:SHA-256_SSWU_RO_`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashToG2Params {
+    /// The target group
+    pub g2: G2Params,
+    /// The coefficients of the curve `y^2 = x^3 + a*x + b` over `Fp2`, with `a * b != 0`, which
+    /// the SWU map targets
+    pub a: NativeFp2,
+    pub b: NativeFp2,
+    /// The non-square `Z` of the SWU map
+    pub z: NativeFp2,
+    /// The isogeny to the twist
+    pub isogeny: Fp2IsogenyMap,
+    /// The length `L`, in bytes, of the uniform strings reduced to coefficients
+    pub l: usize,
+}
+
+impl HashToG2Params {
+    /// The suites for the BLS12-381 curve's group G2 (RFC 9380, section 8.8.2), through a
+    /// 3-isogeny.
+    pub fn bls12_381() -> Self {
+        let g2 = G2Params::bls12_381();
+        let p = &g2.fp2.p;
+        HashToG2Params {
+            a: (BigInt::from(0), BigInt::from(240)),
+            b: (BigInt::from(1012), BigInt::from(1012)),
+            // -(2 + u)
+            z: (p - 2u32, p - 1u32),
+            isogeny: Fp2IsogenyMap {
+                x_num: fp2s(&[
+                    (
+                        "5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97d6",
+                        "5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97d6",
+                    ),
+                    (
+                        "0",
+                        "11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71a",
+                    ),
+                    (
+                        "11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71e",
+                        "8ab05f8bdd54cde190937e76bc3e447cc27c3d6fbd7063fcd104635a790520c0a395554e5c6aaaa9354ffffffffe38d",
+                    ),
+                    (
+                        "171d6541fa38ccfaed6dea691f5fb614cb14b4e7f4e810aa22d6108f142b85757098e38d0f671c7188e2aaaaaaaa5ed1",
+                        "0",
+                    ),
+                ]),
+                x_den: fp2s(&[
+                    (
+                        "0",
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa63",
+                    ),
+                    (
+                        "c",
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa9f",
+                    ),
+                    ("1", "0"),
+                ]),
+                y_num: fp2s(&[
+                    (
+                        "1530477c7ab4113b59a4c18b076d11930f7da5d4a07f649bf54439d87d27e500fc8c25ebf8c92f6812cfc71c71c6d706",
+                        "1530477c7ab4113b59a4c18b076d11930f7da5d4a07f649bf54439d87d27e500fc8c25ebf8c92f6812cfc71c71c6d706",
+                    ),
+                    (
+                        "0",
+                        "5c759507e8e333ebb5b7a9a47d7ed8532c52d39fd3a042a88b58423c50ae15d5c2638e343d9c71c6238aaaaaaaa97be",
+                    ),
+                    (
+                        "11560bf17baa99bc32126fced787c88f984f87adf7ae0c7f9a208c6b4f20a4181472aaa9cb8d555526a9ffffffffc71c",
+                        "8ab05f8bdd54cde190937e76bc3e447cc27c3d6fbd7063fcd104635a790520c0a395554e5c6aaaa9354ffffffffe38f",
+                    ),
+                    (
+                        "124c9ad43b6cf79bfbf7043de3811ad0761b0f37a1e26286b0e977c69aa274524e79097a56dc4bd9e1b371c71c718b10",
+                        "0",
+                    ),
+                ]),
+                y_den: fp2s(&[
+                    (
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa8fb",
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa8fb",
+                    ),
+                    (
+                        "0",
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffa9d3",
+                    ),
+                    (
+                        "12",
+                        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaa99",
+                    ),
+                    ("1", "0"),
+                ]),
+            },
+            l: 64,
+            g2,
+        }
+    }
+
+    /// Reduces `count` pairs of uniform strings expanded from `msg` to elements of `Fp2`
+    /// (`hash_to_field`).
+    pub fn hash_to_field(&self, msg: &[u8], dst: &[u8], count: usize) -> Vec<NativeFp2> {
+        let p = &self.g2.fp2.p;
+        let coefficients: Vec<BigInt> = native_expand_message_xmd(msg, dst, 2 * count * self.l)
+            .chunks(self.l)
+            .map(|bytes| BigInt::from_bytes_be(Sign::Plus, bytes).mod_floor(p))
+            .collect();
+        coefficients
+            .chunks(2)
+            .map(|c| (c[0].clone(), c[1].clone()))
+            .collect()
+    }
+
+    /// The simplified SWU map of `u` to the curve `y^2 = x^3 + a*x + b`.
+    pub fn map_to_curve_simple_swu(&self, u: &NativeFp2) -> (NativeFp2, NativeFp2) {
+        let f = &self.g2.fp2;
+        let g = |x: &NativeFp2| f.add(&f.mul(&f.add(&f.square(x), &self.a), x), &self.b);
+        let zu2 = f.mul(&self.z, &f.square(u));
+        let tv1 = f.add(&f.square(&zu2), &zu2);
+        let x1 = match f.inverse(&tv1) {
+            // The exceptional case: x1 = b / (z a)
+            None => f.mul(&self.b, &f.inverse(&f.mul(&self.z, &self.a)).unwrap()),
+            Some(tv1_inv) => {
+                let b_over_a = f.mul(&self.b, &f.inverse(&self.a).unwrap());
+                f.mul(&f.neg(&b_over_a), &f.add(&f.from_int(1), &tv1_inv))
+            }
+        };
+        let (x, y) = match f.sqrt(&g(&x1)) {
+            Some(y) => (x1, y),
+            None => {
+                let x2 = f.mul(&zu2, &x1);
+                let y = f.sqrt(&g(&x2)).unwrap();
+                (x2, y)
+            }
+        };
+        let y = if sgn0(f, &y) == sgn0(f, u) {
+            y
+        } else {
+            f.neg(&y)
+        };
+        (x, y)
+    }
+
+    /// Maps a point of the curve the SWU map targets to the twist.
+    pub fn iso_map(&self, (x, y): &(NativeFp2, NativeFp2)) -> NativeG2Point {
+        let f = &self.g2.fp2;
+        let iso = &self.isogeny;
+        let x_den = f.inverse(&evaluate(f, &iso.x_den, x))?;
+        let y_den = f.inverse(&evaluate(f, &iso.y_den, x))?;
+        Some((
+            f.mul(&evaluate(f, &iso.x_num, x), &x_den),
+            f.mul(&f.mul(y, &evaluate(f, &iso.y_num, x)), &y_den),
+        ))
+    }
+
+    /// Maps the element `u` to the twist (`map_to_curve`).
+    pub fn map_to_curve(&self, u: &NativeFp2) -> NativeG2Point {
+        self.iso_map(&self.map_to_curve_simple_swu(u))
+    }
+
+    /// Computes `h_eff * point`, which is in G2, as
+    /// `(x^2 - x - 1) point + (x - 1) psi(point) + psi^2(2 point)` (RFC 9380, appendix G.3).
+    pub fn clear_cofactor(&self, point: &NativeG2Point) -> NativeG2Point {
+        let g2 = &self.g2;
+        let t1 = g2.mul(&g2.x, point);
+        let t2 = g2.psi(point);
+        let t3 = g2.psi(&g2.psi(&g2.double(point)));
+        let t3 = g2.add(&t3, &g2.negate(&t2));
+        let t2 = g2.mul(&g2.x, &g2.add(&t1, &t2));
+        let t3 = g2.add(&g2.add(&t3, &t2), &g2.negate(&t1));
+        g2.add(&t3, &g2.negate(point))
+    }
+
+    /// Hashes `msg` to G2 with the random oracle encoding (`hash_to_curve`).
+    pub fn hash_to_curve(&self, msg: &[u8], dst: &[u8]) -> NativeG2Point {
+        let u = self.hash_to_field(msg, dst, 2);
+        let q = self
+            .g2
+            .add(&self.map_to_curve(&u[0]), &self.map_to_curve(&u[1]));
+        self.clear_cofactor(&q)
+    }
+
+    /// Hashes `msg` to G2 with the nonuniform encoding (`encode_to_curve`).
+    pub fn encode_to_curve(&self, msg: &[u8], dst: &[u8]) -> NativeG2Point {
+        let u = self.hash_to_field(msg, dst, 1);
+        self.clear_cofactor(&self.map_to_curve(&u[0]))
+    }
+}
+
+/// Reduces `count` pairs of uniform strings expanded from the message `msg` (given as the bits of
+/// its bytes, each byte's most significant bit first) to elements of `Fp2` (`hash_to_field`). The
+/// coefficients of the results are canonical.
+pub fn hash_to_field<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    count: usize,
+    params: &HashToG2Params,
+) -> Result<Vec<EmulatedFp2<Scalar>>, SynthesisError> {
+    let f = &params.g2.fp2;
+    let p = f.modulus::<Scalar, CS>()?;
+    let n_bits = 8 * params.l;
+    let uniform = expand_message_xmd(cs.namespace(|| "expand"), msg, dst, 2 * count * params.l)?;
+    let coefficients = (0..2 * count)
+        .map(|i| {
+            let bytes = to_bitvector::<Scalar, CS>(&uniform[i * n_bits..(i + 1) * n_bits]);
+            let n = BigNat::recompose(&be_bytes_to_bits(&bytes), f.limb_width);
+            let e = residue(&mut cs, &format!("e {}", i), &[Term::Single(&n)], &p)?;
+            e.assert_canonical(cs.namespace(|| format!("e {} canonical", i)), &p)?;
+            Ok(e)
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+    Ok(coefficients
+        .chunks(2)
+        .map(|c| EmulatedFp2::from_coeffs(c[0].clone(), c[1].clone(), f))
+        .collect())
+}
+
+/// Constrain the sign `sgn0(a)` of the element `a`, whose coefficients must be canonical, to be
+/// `sign`: `sgn0(a) = sgn0(c0) + (c0 == 0) sgn0(c1)`, where the terms cannot both be set since
+/// zero is even.
+fn assert_sgn0<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    a: &EmulatedFp2<Scalar>,
+    sign: &Bit<Scalar>,
+) -> Result<(), SynthesisError> {
+    let (c0, c1) = (&a.c0, &a.c1);
+    let odd0 = Bit::alloc(
+        cs.namespace(|| "c0 odd"),
+        c0.value.as_ref().map(|c| c.is_odd()),
+    )?;
+    assert_parity(cs.namespace(|| "c0 parity"), c0, &odd0)?;
+    let odd1 = Bit::alloc(
+        cs.namespace(|| "c1 odd"),
+        c1.value.as_ref().map(|c| c.is_odd()),
+    )?;
+    assert_parity(cs.namespace(|| "c1 parity"), c1, &odd1)?;
+    // c0 is zero if and only if the sum of its range checked limbs is: c0_zero = 1 - sum * inv
+    // and sum * c0_zero = 0
+    let sum = c0
+        .limbs
+        .iter()
+        .fold(LinearCombination::zero(), |lc, limb| lc + limb);
+    let sum_value = c0
+        .limb_values
+        .as_ref()
+        .map(|limbs| limbs.iter().fold(Scalar::ZERO, |acc, limb| acc + limb));
+    let c0_zero = Bit::alloc(
+        cs.namespace(|| "c0 zero"),
+        sum_value.map(|s| bool::from(s.is_zero())),
+    )?;
+    let inv = cs.alloc(
+        || "sum inverse",
+        || Ok(sum_value.grab()?.invert().unwrap_or(Scalar::ZERO)),
+    )?;
+    cs.enforce(
+        || "c0 zero if sum is",
+        |lc| lc + &sum,
+        |lc| lc + inv,
+        |lc| lc + CS::one() - &c0_zero.bit,
+    );
+    cs.enforce(
+        || "sum zero if c0 zero",
+        |lc| lc + &sum,
+        |lc| lc + &c0_zero.bit,
+        |lc| lc,
+    );
+    cs.enforce(
+        || "sign",
+        |lc| lc + &c0_zero.bit,
+        |lc| lc + &odd1.bit,
+        |lc| lc + &sign.bit - &odd0.bit,
+    );
+    Ok(())
+}
+
+/// Computes the value of the polynomial with the given constant coefficients at `x`, given the
+/// powers `[x, x^2, ...]`.
+fn polynomial<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: &mut CS,
+    name: &str,
+    coefficients: &[NativeFp2],
+    powers: &[EmulatedFp2<Scalar>],
+    f: &Fp2Params,
+) -> Result<EmulatedFp2<Scalar>, SynthesisError> {
+    let constants = coefficients
+        .iter()
+        .map(|c| EmulatedFp2::constant::<CS>(c, f))
+        .collect::<Result<Vec<_>, _>>()?;
+    let terms: Vec<_> = constants
+        .iter()
+        .zip(coefficients)
+        .enumerate()
+        .map(|(i, (c, v))| match i {
+            0 => Fp2Term::Single(c),
+            _ if f.reduce(v) == f.from_int(1) => Fp2Term::Single(&powers[i - 1]),
+            _ => Fp2Term::Product(c, &powers[i - 1]),
+        })
+        .collect();
+    EmulatedFp2::residue(cs, name, &terms, f)
+}
+
+/// Maps a point of the curve the SWU map targets to the twist with the isogeny `iso`. The
+/// constraints are unsatisfiable for the points of the kernel, which map to the point at infinity.
+fn iso_map<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    x: &EmulatedFp2<Scalar>,
+    y: &EmulatedFp2<Scalar>,
+    iso: &Fp2IsogenyMap,
+    params: &G2Params,
+) -> Result<EmulatedG2Point<Scalar>, SynthesisError> {
+    let f = &params.fp2;
+    let mut powers = vec![x.clone()];
+    for i in 2..=iso.degree() {
+        let power = EmulatedFp2::residue(
+            &mut cs,
+            &format!("x^{}", i),
+            &[Fp2Term::Product(powers.last().unwrap(), x)],
+            f,
+        )?;
+        powers.push(power);
+    }
+    let x_num = polynomial(&mut cs, "x numerator", &iso.x_num, &powers, f)?;
+    let x_den = polynomial(&mut cs, "x denominator", &iso.x_den, &powers, f)?;
+    let y_num = polynomial(&mut cs, "y numerator", &iso.y_num, &powers, f)?;
+    let y_den = polynomial(&mut cs, "y denominator", &iso.y_den, &powers, f)?;
+    let x = x_num.div(cs.namespace(|| "x"), &x_den)?;
+    let y_ratio = y_num.div(cs.namespace(|| "y ratio"), &y_den)?;
+    let y = EmulatedFp2::residue(&mut cs, "y", &[Fp2Term::Product(y, &y_ratio)], f)?;
+    EmulatedG2Point::from_affine::<CS>(x, y, params)
+}
+
+/// Maps the element `u`, with canonical coefficients, to the twist (`map_to_curve`) with the
+/// simplified SWU map, followed by the isogeny.
+///
+/// As for `hash_to_curve::map_to_curve`, the candidates `x1` and `x2 = Z u^2 x1` are computed,
+/// and a square root `y` of `g(x)` for `x` one of them determines the choice, since `Z` is not a
+/// square in `Fp2`. The sign `sgn0` of `y` is constrained to be that of `u`.
+///
+/// The exceptional cases are reached by hashing with negligible probability: it fails to
+/// synthesize if `Z^2 u^4 + Z u^2 = 0`, and the constraints are unsatisfiable for the points of
+/// the kernel of the isogeny.
+pub fn map_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    u: &EmulatedFp2<Scalar>,
+    params: &HashToG2Params,
+) -> Result<EmulatedG2Point<Scalar>, SynthesisError> {
+    let f = &params.g2.fp2;
+    let residue = |cs: &mut CS, name: &str, terms: &[Fp2Term<Scalar>]| {
+        EmulatedFp2::residue(cs, name, terms, f)
+    };
+    let a = EmulatedFp2::constant::<CS>(&params.a, f)?;
+    let b = EmulatedFp2::constant::<CS>(&params.b, f)?;
+    let z = EmulatedFp2::constant::<CS>(&params.z, f)?;
+    let uu = residue(&mut cs, "u^2", &[Fp2Term::Product(u, u)])?;
+    let zu2 = residue(&mut cs, "z u^2", &[Fp2Term::Product(&z, &uu)])?;
+    let tv1 = residue(
+        &mut cs,
+        "tv1",
+        &[Fp2Term::Product(&zu2, &zu2), Fp2Term::Single(&zu2)],
+    )?;
+    let a_tv1 = residue(&mut cs, "a tv1", &[Fp2Term::Product(&a, &tv1)])?;
+    // x1 a tv1 + b (tv1 + 1) = 0
+    let x1 = EmulatedFp2::alloc_from_coeffs(
+        cs.namespace(|| "x1"),
+        || {
+            let t = tv1.value.grab()?;
+            let num = f.neg(&f.mul(&params.b, &f.add(t, &f.from_int(1))));
+            let inv = f.inverse(a_tv1.value.grab()?);
+            Ok(f.mul(&num, inv.grab()?))
+        },
+        f,
+    )?;
+    EmulatedFp2::assert_sums_congruent(
+        cs.namespace(|| "x1 check"),
+        &[
+            Fp2Term::Product(&x1, &a_tv1),
+            Fp2Term::Product(&b, &tv1),
+            Fp2Term::Single(&b),
+        ],
+        &[],
+        f,
+    )?;
+    let x2 = residue(&mut cs, "x2", &[Fp2Term::Product(&zu2, &x1)])?;
+
+    let point = u.value.as_ref().map(|u| params.map_to_curve_simple_swu(u));
+    // (x - x1) (x - x2) = 0
+    let x =
+        EmulatedFp2::alloc_from_coeffs(cs.namespace(|| "x"), || Ok(point.grab()?.0.clone()), f)?;
+    let x1_plus_x2 = residue(
+        &mut cs,
+        "x1 + x2",
+        &[Fp2Term::Single(&x1), Fp2Term::Single(&x2)],
+    )?;
+    EmulatedFp2::assert_sums_congruent(
+        cs.namespace(|| "x choice"),
+        &[Fp2Term::Product(&x, &x), Fp2Term::Product(&x1, &x2)],
+        &[Fp2Term::Product(&x, &x1_plus_x2)],
+        f,
+    )?;
+    // y^2 = x^3 + a x + b
+    let y =
+        EmulatedFp2::alloc_from_coeffs(cs.namespace(|| "y"), || Ok(point.grab()?.1.clone()), f)?;
+    let p = f.modulus::<Scalar, CS>()?;
+    y.c0.assert_canonical(cs.namespace(|| "y c0 canonical"), &p)?;
+    y.c1.assert_canonical(cs.namespace(|| "y c1 canonical"), &p)?;
+    let xx = residue(&mut cs, "x^2", &[Fp2Term::Product(&x, &x)])?;
+    EmulatedFp2::assert_sums_congruent(
+        cs.namespace(|| "on curve"),
+        &[Fp2Term::Product(&y, &y)],
+        &[
+            Fp2Term::Product(&xx, &x),
+            Fp2Term::Product(&a, &x),
+            Fp2Term::Single(&b),
+        ],
+        f,
+    )?;
+    // sgn0(u) = sgn0(y)
+    let sign = Bit::alloc(
+        cs.namespace(|| "sign"),
+        u.value.as_ref().map(|u| sgn0(f, u)),
+    )?;
+    assert_sgn0(cs.namespace(|| "u sign"), u, &sign)?;
+    assert_sgn0(cs.namespace(|| "y sign"), &y, &sign)?;
+
+    iso_map(
+        cs.namespace(|| "isogeny"),
+        &x,
+        &y,
+        &params.isogeny,
+        &params.g2,
+    )
+}
+
+/// Computes `h_eff * point`, which is in G2, with the endomorphism `psi` (see
+/// `HashToG2Params::clear_cofactor`).
+pub fn clear_cofactor<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    point: &EmulatedG2Point<Scalar>,
+    params: &HashToG2Params,
+) -> Result<EmulatedG2Point<Scalar>, SynthesisError> {
+    let x = &params.g2.x;
+    let t1 = point.mul_by_constant(cs.namespace(|| "x p"), x)?;
+    let t2 = point.psi(cs.namespace(|| "psi p"))?;
+    let t3 = point.double(cs.namespace(|| "2 p"))?;
+    let t3 = t3.psi(cs.namespace(|| "psi 2 p"))?;
+    let t3 = t3.psi(cs.namespace(|| "psi^2 2 p"))?;
+    let neg_t2 = t2.negate(cs.namespace(|| "-psi p"))?;
+    let t3 = t3.add(cs.namespace(|| "psi^2 2 p - psi p"), &neg_t2)?;
+    let t2 = t1.add(cs.namespace(|| "x p + psi p"), &t2)?;
+    let t2 = t2.mul_by_constant(cs.namespace(|| "x (x p + psi p)"), x)?;
+    let t3 = t3.add(cs.namespace(|| "add x (x p + psi p)"), &t2)?;
+    let neg_t1 = t1.negate(cs.namespace(|| "-x p"))?;
+    let t3 = t3.add(cs.namespace(|| "sub x p"), &neg_t1)?;
+    let neg_p = point.negate(cs.namespace(|| "-p"))?;
+    t3.add(cs.namespace(|| "sub p"), &neg_p)
+}
+
+/// Hashes the message `msg`, given as the bits of its bytes, each byte's most significant bit
+/// first, to G2 with the random oracle encoding (`hash_to_curve`).
+pub fn hash_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    params: &HashToG2Params,
+) -> Result<EmulatedG2Point<Scalar>, SynthesisError> {
+    let u = hash_to_field(cs.namespace(|| "hash to field"), msg, dst, 2, params)?;
+    let q0 = map_to_curve(cs.namespace(|| "map u0"), &u[0], params)?;
+    let q1 = map_to_curve(cs.namespace(|| "map u1"), &u[1], params)?;
+    let q = q0.add(cs.namespace(|| "add"), &q1)?;
+    clear_cofactor(cs.namespace(|| "clear cofactor"), &q, params)
+}
+
+/// Hashes the message `msg`, given as the bits of its bytes, each byte's most significant bit
+/// first, to G2 with the nonuniform encoding (`encode_to_curve`).
+pub fn encode_to_curve<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    params: &HashToG2Params,
+) -> Result<EmulatedG2Point<Scalar>, SynthesisError> {
+    let u = hash_to_field(cs.namespace(|| "hash to field"), msg, dst, 1, params)?;
+    let q = map_to_curve(cs.namespace(|| "map u0"), &u[0], params)?;
+    clear_cofactor(cs.namespace(|| "clear cofactor"), &q, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    /// The domain separation tag of the test vectors of RFC 9380 for
+    /// `BLS12381G2_XMD:SHA-256_SSWU_RO_`.
+    const DST: &[u8] = b"QUUX-V01-CS02-with-BLS12381G2_XMD:SHA-256_SSWU_RO_";
+
+    #[test]
+    fn hash_to_g2_vector() {
+        // RFC 9380, appendix J.10.1, for the empty message
+        let point = HashToG2Params::bls12_381().hash_to_curve(b"", DST);
+        let expected = fp2s(&[
+            (
+                "0141ebfbdca40eb85b87142e130ab689c673cf60f1a3e98d69335266f30d9b8d4ac44c1038e9dcdd5393faf5c41fb78a",
+                "05cb8437535e20ecffaef7752baddf98034139c38452458baeefab379ba13dff5bf5dd71b72418717047f5b0f37da03d",
+            ),
+            (
+                "0503921d7f6a12805e72940b963c0cf3471c7b2a524950ca195d11062ee75ec076daf2d4bc358c4b190c0c98064fdd92",
+                "12424ac32561493f3fe3c260708a12b7c620e7be00099a974e259ddc7d1f6395c3c811cdd19f1e8dbf3e9ecfdcbab8d6",
+            ),
+        ]);
+        assert_eq!(point, Some((expected[0].clone(), expected[1].clone())));
+    }
+
+    #[test]
+    fn isogeny_maps_to_twist() {
+        let params = HashToG2Params::bls12_381();
+        let f = &params.g2.fp2;
+        for k in 1..10 {
+            let u = (BigInt::from(k), BigInt::from(k * k + 1));
+            let (x, y) = params.map_to_curve_simple_swu(&u);
+            let rhs = f.add(&f.mul(&f.add(&f.square(&x), &params.a), &x), &params.b);
+            assert_eq!(f.square(&y), rhs);
+            assert_eq!(sgn0(f, &y), sgn0(f, &u));
+            let point = params.map_to_curve(&u);
+            assert!(params.g2.is_on_curve(&point));
+            let cleared = params.clear_cofactor(&point);
+            assert!(cleared.is_some() && params.g2.is_in_subgroup(&cleared));
+        }
+    }
+
+    pub struct HashToG2 {
+        msg: Vec<u8>,
+        expected: NativeG2Point,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for HashToG2 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = HashToG2Params::bls12_381();
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(&self.msg), self.msg.len())?;
+            let point = hash_to_curve(cs.namespace(|| "hash"), &msg, DST, &params)?;
+            let expected = EmulatedG2Point::constant::<CS>(
+                &params.g2.to_projective(&self.expected),
+                &params.g2,
+            )?;
+            point.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    #[test]
+    fn hash_to_g2_bls12_381() {
+        let params = HashToG2Params::bls12_381();
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        HashToG2 {
+            msg: b"abc".to_vec(),
+            expected: params.hash_to_curve(b"abc", DST),
+        }
+        .synthesize(&mut cs)
+        .unwrap();
+        println!(
+            "BLS12-381 G2 hash to curve: {} constraints",
+            cs.num_constraints()
+        );
+        assert_eq!(cs.which_is_unsatisfied(), None);
+    }
+}
@@ -0,0 +1,365 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::Num as BigNum;
+
+use std::fmt::{self, Debug, Formatter};
+
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, sqrt, Term};
+use crate::OptionExt;
+
+pub mod affine;
+pub mod bls;
+pub mod compress;
+pub mod ecdsa;
+pub mod ecvrf;
+pub mod eddsa;
+pub mod edwards;
+pub mod fixed_base;
+pub mod fp12;
+pub mod fp2;
+pub mod g2;
+pub mod glv;
+pub mod hash_to_curve;
+pub mod hash_to_g2;
+pub mod msm;
+pub mod pairing;
+pub mod projective;
+pub mod scalar_mul;
+pub mod schnorr;
+
+/// A point of a curve, computed natively. `None` is the point at infinity.
+pub type NativePoint = Option<(BigInt, BigInt)>;
+
+fn hex(s: &str) -> BigInt {
+    BigInt::from_str_radix(s, 16).unwrap()
+}
+
+/// Allocates a residue constrained to be congruent to the sum of `terms` modulo `modulus`.
+pub(crate) fn residue<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: &mut CS,
+    name: &str,
+    terms: &[Term<Scalar>],
+    modulus: &BigNat<Scalar>,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    let r = BigNat::alloc_residue(
+        cs.namespace(|| name.to_string()),
+        || {
+            let v: Option<BigInt> = terms.iter().map(Term::value).sum();
+            Ok(v.grab()?.clone())
+        },
+        modulus,
+    )?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| format!("{} check", name)),
+        terms,
+        &[Term::Single(&r)],
+        modulus,
+    )?;
+    Ok(r)
+}
+
+/// Allocates a residue constrained to be congruent to the sum of the `positive` terms minus the
+/// sum of the `negative` terms modulo `modulus`.
+pub(crate) fn signed_residue<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: &mut CS,
+    name: &str,
+    positive: &[Term<Scalar>],
+    negative: &[Term<Scalar>],
+    modulus: &BigNat<Scalar>,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    let r = BigNat::alloc_residue(
+        cs.namespace(|| name.to_string()),
+        || {
+            let pos: Option<BigInt> = positive.iter().map(Term::value).sum();
+            let neg: Option<BigInt> = negative.iter().map(Term::value).sum();
+            Ok(pos.grab()? - neg.grab()?)
+        },
+        modulus,
+    )?;
+    let mut lhs: Vec<Term<Scalar>> = negative.iter().map(Term::clone).collect();
+    lhs.push(Term::Single(&r));
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| format!("{} check", name)),
+        &lhs,
+        positive,
+        modulus,
+    )?;
+    Ok(r)
+}
+
+/// Parameters of a short Weierstrass curve `y^2 = x^3 + a*x + b` over an emulated prime field,
+/// together with the limb layout used to represent its coordinates.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CurveParams {
+    /// The modulus of the base field
+    pub p: BigInt,
+    pub a: BigInt,
+    pub b: BigInt,
+    /// The order of the group generated by `generator`
+    pub order: BigInt,
+    pub generator: (BigInt, BigInt),
+    pub limb_width: usize,
+    pub n_limbs: usize,
+}
+
+impl CurveParams {
+    /// The secp256k1 curve, with 4 limbs of 64 bits.
+    pub fn secp256k1() -> Self {
+        CurveParams {
+            p: hex("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"),
+            a: BigInt::from(0),
+            b: BigInt::from(7),
+            order: hex("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"),
+            generator: (
+                hex("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+                hex("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+            ),
+            limb_width: 64,
+            n_limbs: 4,
+        }
+    }
+
+    /// The NIST P-256 (secp256r1) curve, with 4 limbs of 64 bits.
+    pub fn p256() -> Self {
+        let p = hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff");
+        CurveParams {
+            a: &p - 3,
+            p,
+            b: hex("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b"),
+            order: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            generator: (
+                hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+                hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+            ),
+            limb_width: 64,
+            n_limbs: 4,
+        }
+    }
+
+    /// The BN254 (alt_bn128) curve's group G1, with 4 limbs of 64 bits.
+    pub fn bn254() -> Self {
+        CurveParams {
+            p: hex("30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47"),
+            a: BigInt::from(0),
+            b: BigInt::from(3),
+            order: hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"),
+            generator: (BigInt::from(1), BigInt::from(2)),
+            limb_width: 64,
+            n_limbs: 4,
+        }
+    }
+
+    /// The BLS12-381 curve's group G1, with 6 limbs of 64 bits.
+    pub fn bls12_381() -> Self {
+        CurveParams {
+            p: hex("1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab"),
+            a: BigInt::from(0),
+            b: BigInt::from(4),
+            order: hex("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001"),
+            generator: (
+                hex("17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb"),
+                hex("08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1"),
+            ),
+            limb_width: 64,
+            n_limbs: 6,
+        }
+    }
+
+    /// Curve25519 in short Weierstrass form ("Wei25519"), with 4 limbs of 64 bits. The
+    /// generator is the image of the Montgomery base point `u = 9` (and so of the ed25519 base
+    /// point, up to sign), which generates the subgroup of prime order `l`; the cofactor is 8.
+    pub fn curve25519() -> Self {
+        let p = (BigInt::from(1) << 255u32) - 19;
+        // From v^2 = u^3 + A u^2 + u via x = u + A/3
+        let m_a = BigInt::from(486662);
+        let third = invert(&BigInt::from(3), &p).unwrap();
+        let a = ((BigInt::from(3) - &m_a * &m_a) * &third).mod_floor(&p);
+        let b = ((&m_a * &m_a * &m_a * 2u64 - &m_a * 9u64) * third.pow(3)).mod_floor(&p);
+        let gx = (&m_a * &third + 9u64).mod_floor(&p);
+        let mut params = CurveParams {
+            p,
+            a,
+            b,
+            order: (BigInt::from(1) << 252u32)
+                + BigInt::from_str_radix("27742317777372353535851937790883648493", 10).unwrap(),
+            generator: (BigInt::from(0), BigInt::from(0)),
+            limb_width: 64,
+            n_limbs: 4,
+        };
+        params.generator = params.lift_x(&gx).unwrap();
+        params
+    }
+
+    /// The modulus of the base field, as a constant.
+    pub fn modulus<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&self.p, self.limb_width, self.n_limbs)
+    }
+
+    /// The order of the group, as a constant with the limb layout of the coordinates.
+    pub fn order_modulus<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&self.order, self.limb_width, self.n_limbs)
+    }
+
+    /// The base field element `v`, as a constant.
+    pub fn constant<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        &self,
+        v: &BigInt,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        BigNat::constant::<CS>(&v.mod_floor(&self.p), self.limb_width, self.n_limbs)
+    }
+
+    /// Whether `a = -3`, as on the NIST curves, for which doubling is cheaper.
+    pub fn a_is_minus_three(&self) -> bool {
+        (&self.a + 3u64).mod_floor(&self.p) == BigInt::from(0)
+    }
+
+    pub fn is_on_curve(&self, point: &NativePoint) -> bool {
+        match point {
+            None => true,
+            Some((x, y)) => {
+                (y * y - x * x * x - &self.a * x - &self.b).mod_floor(&self.p) == BigInt::from(0)
+            }
+        }
+    }
+
+    /// The point with x-coordinate `x` and the smaller of the two y-coordinates, if there is one.
+    pub fn lift_x(&self, x: &BigInt) -> NativePoint {
+        let x = x.mod_floor(&self.p);
+        let rhs = &x * &x * &x + &self.a * &x + &self.b;
+        sqrt(&rhs, &self.p).map(|y| (x, y))
+    }
+
+    /// A point with no known discrete logarithm relative to the generator: the point with the
+    /// least x-coordinate which is at least `2^128 + seed * 2^64`, so that distinct seeds give
+    /// distinct points. Used to offset accumulators away from the exceptional cases of incomplete
+    /// addition.
+    pub fn offset_point(&self, seed: u64) -> (BigInt, BigInt) {
+        let mut x = (BigInt::from(1) << 128u32) + (BigInt::from(seed) << 64u32);
+        loop {
+            if let Some(point) = self.lift_x(&x) {
+                return point;
+            }
+            x += 1;
+        }
+    }
+
+    /// The SEC1 compressed encoding of a point: `0x02` or `0x03` for an even or odd
+    /// y-coordinate, followed by the big-endian x-coordinate.
+    pub fn encode_sec1(&self, (x, y): &(BigInt, BigInt)) -> Vec<u8> {
+        let n_bytes = (self.p.bits() as usize).div_ceil(8);
+        let mut encoding = vec![if y.is_odd() { 3 } else { 2 }];
+        let x = x.to_bytes_be().1;
+        encoding.resize(1 + n_bytes - x.len(), 0);
+        encoding.extend(x);
+        encoding
+    }
+
+    pub fn negate(&self, point: &NativePoint) -> NativePoint {
+        point
+            .as_ref()
+            .map(|(x, y)| (x.clone(), (-y).mod_floor(&self.p)))
+    }
+
+    pub fn add(&self, a: &NativePoint, b: &NativePoint) -> NativePoint {
+        match (a, b) {
+            (None, _) => b.clone(),
+            (_, None) => a.clone(),
+            (Some((x1, y1)), Some((x2, y2))) => {
+                let p = &self.p;
+                let lambda = if (x1 - x2).mod_floor(p) == BigInt::from(0) {
+                    if (y1 + y2).mod_floor(p) == BigInt::from(0) {
+                        return None;
+                    }
+                    (3 * x1 * x1 + &self.a) * invert(&(2 * y1), p).unwrap()
+                } else {
+                    (y2 - y1) * invert(&(x2 - x1), p).unwrap()
+                };
+                let x3 = (&lambda * &lambda - x1 - x2).mod_floor(p);
+                let y3 = (lambda * (x1 - &x3) - y1).mod_floor(p);
+                Some((x3, y3))
+            }
+        }
+    }
+
+    pub fn double(&self, a: &NativePoint) -> NativePoint {
+        self.add(a, a)
+    }
+
+    /// Computes `k * point` by double-and-add, with `k` reduced modulo the order, for a point of
+    /// the subgroup of prime order.
+    pub fn mul(&self, k: &BigInt, point: &NativePoint) -> NativePoint {
+        self.mul_unreduced(&k.mod_floor(&self.order), point)
+    }
+
+    /// Computes `k * point` by double-and-add, for `k >= 0`, without reducing `k` modulo the
+    /// order, so the point may be out of the subgroup of prime order, as the offset points are on
+    /// curves with a cofactor.
+    pub fn mul_unreduced(&self, k: &BigInt, point: &NativePoint) -> NativePoint {
+        let mut acc = None;
+        for i in (0..k.bits()).rev() {
+            acc = self.double(&acc);
+            if k.bit(i) {
+                acc = self.add(&acc, point);
+            }
+        }
+        acc
+    }
+}
+
+impl Debug for CurveParams {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("CurveParams")
+            .field("p", &format_args!("{}", &self.p))
+            .field("a", &format_args!("{}", &self.a))
+            .field("b", &format_args!("{}", &self.b))
+            .field("limb_width", &self.limb_width)
+            .field("n_limbs", &self.n_limbs)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_generator(params: CurveParams) {
+        let g = Some(params.generator.clone());
+        assert!(params.is_on_curve(&g));
+        let mut n_minus_one = params.order.clone();
+        n_minus_one -= 1;
+        assert_eq!(params.mul(&n_minus_one, &g), params.negate(&g));
+        assert_eq!(params.add(&params.mul(&n_minus_one, &g), &g), None);
+    }
+
+    #[test]
+    fn secp256k1_generator_has_order_n() {
+        check_generator(CurveParams::secp256k1());
+    }
+
+    #[test]
+    fn p256_generator_has_order_n() {
+        check_generator(CurveParams::p256());
+    }
+
+    #[test]
+    fn bn254_generator_has_order_r() {
+        check_generator(CurveParams::bn254());
+    }
+
+    #[test]
+    fn bls12_381_generator_has_order_r() {
+        check_generator(CurveParams::bls12_381());
+    }
+
+    #[test]
+    fn curve25519_generator_has_order_l() {
+        check_generator(CurveParams::curve25519());
+    }
+}
@@ -0,0 +1,301 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+use super::affine::EmulatedAffinePoint;
+use super::fixed_base::{fixed_base_offsets, fixed_base_tables, scalar_windows, ConstantSelector};
+use super::projective::EmulatedProjectivePoint;
+use super::scalar_mul::window_offset_multiple;
+use super::{CurveParams, NativePoint};
+use crate::util::bit::{Bit, Bitvector};
+use crate::util::gadget::Gadget;
+
+/// The seed of the offset points used by multi-scalar multiplication. Term `j` uses the offset
+/// points with seeds `MSM_OFFSET_SEED + j * 2^16 + i`.
+const MSM_OFFSET_SEED: u64 = 2 << 32;
+
+/// The base of a term of a multi-scalar multiplication.
+pub enum Base<'a, Scalar: PrimeField> {
+    /// A constant point, e.g. a generator
+    Fixed(&'a (BigInt, BigInt)),
+    /// A witnessed point
+    Variable(&'a EmulatedAffinePoint<Scalar>),
+}
+
+/// Computes the sum of `scalar * base` over the `terms`, where each scalar is given by its
+/// (little-endian) bits, with fixed windows of `window` bits.
+///
+/// The variable bases are handled with Straus' method: their windows are aligned on the least
+/// significant bit and processed together from the most significant down, so that the `window`
+/// doublings of each window are shared between all of them, and each window costs one
+/// `mux_tree` lookup and addition per variable base. The fixed bases need no doublings; as in
+/// `fixed_base_mul`, each of their windows selects from a constant table and is added to the
+/// accumulator at the end. Every table is offset by a distinct point of unknown discrete
+/// logarithm, so the incomplete formulas can be used throughout, and the offsets are removed at
+/// the end with the complete projective formulas, so the result may be the identity.
+///
+/// The variable bases must not be the identity, and must have the curve's prime order.
+pub fn msm<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    terms: &[(Base<Scalar>, &Bitvector<Scalar>)],
+    window: usize,
+    params: &CurveParams,
+) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+    if terms.is_empty() || window == 0 || terms.iter().any(|(_, s)| s.bits.is_empty()) {
+        eprintln!("Cannot compute an MSM of {} terms", terms.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let seed = |j: usize| MSM_OFFSET_SEED + ((j as u64) << 16);
+    let mut offsets: NativePoint = None;
+
+    // The tables and (little-endian) windows of the variable bases
+    let mut variables = Vec::new();
+    for (j, (base, scalar)) in terms.iter().enumerate() {
+        if let Base::Variable(point) = base {
+            if point.params != *params {
+                eprintln!("Curves do not agree at msm");
+                return Err(SynthesisError::Unsatisfiable);
+            }
+            let table =
+                point.window_table(cs.namespace(|| format!("table {}", j)), window, seed(j))?;
+            let windows: Vec<Vec<Bit<Scalar>>> = scalar_windows(scalar, window).collect();
+            let offset = params.mul_unreduced(
+                &window_offset_multiple(scalar.bits.len(), window),
+                &Some(params.offset_point(seed(j))),
+            );
+            offsets = params.add(&offsets, &offset);
+            variables.push((table, windows));
+        }
+    }
+
+    let add = |cs: &mut CS,
+               name: String,
+               acc: Option<EmulatedAffinePoint<Scalar>>,
+               entry: EmulatedAffinePoint<Scalar>| match acc {
+        None => Ok(entry),
+        Some(acc) => acc.add(cs.namespace(|| name), &entry),
+    };
+
+    // Straus' method for the variable bases
+    let n_windows = variables.iter().map(|(_, w)| w.len()).max().unwrap_or(0);
+    let mut acc: Option<EmulatedAffinePoint<Scalar>> = None;
+    for i in (0..n_windows).rev() {
+        if let Some(mut a) = acc.take() {
+            for k in 0..window {
+                a = a.double(cs.namespace(|| format!("window {} double {}", i, k)))?;
+            }
+            acc = Some(a);
+        }
+        for (j, (table, windows)) in variables.iter().enumerate() {
+            if let Some(bits) = windows.get(i) {
+                let entry = Gadget::mux_tree(
+                    cs.namespace(|| format!("window {} lookup {}", i, j)),
+                    bits.iter().rev(),
+                    &table[..(1 << bits.len())],
+                )?;
+                acc = Some(add(&mut cs, format!("window {} add {}", i, j), acc, entry)?);
+            }
+        }
+    }
+
+    // The fixed bases, without doublings
+    for (j, (base, scalar)) in terms.iter().enumerate() {
+        if let Base::Fixed(base) = base {
+            let tables = fixed_base_tables(params, base, scalar.bits.len(), window, seed(j))?;
+            offsets = params.add(&offsets, &fixed_base_offsets(params, tables.len(), seed(j)));
+            for (i, (bits, table)) in scalar_windows(scalar, window).zip(&tables).enumerate() {
+                let selector = ConstantSelector::new(
+                    cs.namespace(|| format!("fixed {} window {} selector", j, i)),
+                    &bits,
+                )?;
+                let entry = EmulatedAffinePoint::select_constant(
+                    &selector,
+                    &table[..selector.len()],
+                    params,
+                )?;
+                acc = Some(add(
+                    &mut cs,
+                    format!("fixed {} window {} add", j, i),
+                    acc,
+                    entry,
+                )?);
+            }
+        }
+    }
+
+    let correction = EmulatedProjectivePoint::constant::<CS>(
+        &params.to_projective(&params.negate(&offsets)),
+        params,
+    )?;
+    let acc = EmulatedProjectivePoint::from_affine::<CS>(&acc.unwrap())?;
+    acc.add(cs.namespace(|| "remove offset"), &correction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp::bignat::BigNat;
+    use crate::util::test_helpers::*;
+    use crate::OptionExt;
+
+    pub struct MsmInputs {
+        /// The scalars, and the multiples of the generator giving the bases
+        pub terms: Vec<(BigInt, BigInt)>,
+        pub expected: NativePoint,
+    }
+
+    pub struct Msm {
+        inputs: Option<MsmInputs>,
+        /// Whether each base is fixed
+        fixed: Vec<bool>,
+        params: CurveParams,
+        n_bits: usize,
+        window: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Msm {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let g = Some(params.generator.clone());
+            let mut points = Vec::new();
+            let mut scalars = Vec::new();
+            for (j, (k, base)) in inputs.terms.iter().enumerate() {
+                let base = params.mul(base, &g).unwrap();
+                points.push(if self.fixed[j] {
+                    Err(base)
+                } else {
+                    Ok(EmulatedAffinePoint::alloc_from_coords(
+                        cs.namespace(|| format!("base {}", j)),
+                        || Ok(base.clone()),
+                        params,
+                    )?)
+                });
+                let k = BigNat::alloc_from_nat(
+                    cs.namespace(|| format!("scalar {}", j)),
+                    || Ok(k.clone()),
+                    params.limb_width,
+                    params.n_limbs,
+                )?;
+                scalars.push(
+                    k.decompose(cs.namespace(|| format!("decompose {}", j)))?
+                        .truncate(self.n_bits),
+                );
+            }
+            let terms: Vec<(Base<Scalar>, &Bitvector<Scalar>)> = points
+                .iter()
+                .zip(&scalars)
+                .map(|(p, k)| match p {
+                    Ok(p) => (Base::Variable(p), k),
+                    Err(p) => (Base::Fixed(p), k),
+                })
+                .collect();
+            let sum = msm(cs.namespace(|| "msm"), &terms, self.window, params)?;
+            let expected = EmulatedProjectivePoint::constant::<CS>(
+                &params.to_projective(&inputs.expected),
+                params,
+            )?;
+            sum.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    /// Terms `(k, b, fixed)` stand for `k * (b * G)`, with a fixed base if `fixed`.
+    fn msm_circuit(
+        params: CurveParams,
+        terms: &[(u64, i64, bool)],
+        n_bits: usize,
+        window: usize,
+        honest: bool,
+    ) -> Msm {
+        let terms: Vec<(BigInt, BigInt, bool)> = terms
+            .iter()
+            .map(|(k, b, f)| (BigInt::from(*k), BigInt::from(*b), *f))
+            .collect();
+        let expected = with_error(&params, &terms, honest);
+        Msm {
+            inputs: Some(MsmInputs {
+                terms: terms
+                    .iter()
+                    .map(|(k, b, _)| (k.clone(), b.clone()))
+                    .collect(),
+                expected,
+            }),
+            fixed: terms.iter().map(|(_, _, f)| *f).collect(),
+            params,
+            n_bits,
+            window,
+        }
+    }
+
+    /// The expected sum, plus the generator if not `honest`.
+    fn with_error(
+        params: &CurveParams,
+        terms: &[(BigInt, BigInt, bool)],
+        honest: bool,
+    ) -> NativePoint {
+        let g = Some(params.generator.clone());
+        let mut sum = terms.iter().fold(None, |acc, (k, b, _)| {
+            params.add(&acc, &params.mul(&(k * b), &g))
+        });
+        if !honest {
+            sum = params.add(&sum, &g);
+        }
+        sum
+    }
+
+    circuit_tests! {
+        msm_secp256k1_mixed: (msm_circuit(CurveParams::secp256k1(), &[(0xb3, 1, true), (0x5e, 7, false)], 8, 3, true), true),
+        msm_secp256k1_variable: (msm_circuit(CurveParams::secp256k1(), &[(0x2d, 3, false), (0x13, 5, false), (0x3, 11, false)], 6, 2, true), true),
+        msm_secp256k1_identity: (msm_circuit(CurveParams::secp256k1(), &[(0x1f, 2, false), (0x1f, -2, false), (0x7, 3, true), (0x7, -3, true)], 5, 2, true), true),
+        msm_p256_fixed: (msm_circuit(CurveParams::p256(), &[(0x35, 1, true), (0x0a, 9, true)], 6, 3, true), true),
+        msm_secp256k1_wrong: (msm_circuit(CurveParams::secp256k1(), &[(0xb3, 1, true), (0x5e, 7, false)], 8, 3, false), false),
+    }
+
+    #[test]
+    fn msm_curve25519_full_width() {
+        // The offset point is not in the subgroup of prime order, and the multiple of it removed
+        // from the accumulator exceeds the order
+        let params = CurveParams::curve25519();
+        let k = full_width_scalar(&params.order);
+        let circuit = Msm {
+            inputs: Some(MsmInputs {
+                expected: with_error(&params, &[(k.clone(), BigInt::from(5), false)], true),
+                terms: vec![(k, BigInt::from(5))],
+            }),
+            fixed: vec![false],
+            params,
+            n_bits: 256,
+            window: 4,
+        };
+        assert!(is_satisfied(circuit));
+    }
+
+    /// Reports the cost of a full-width `u1 * G + u2 * Q`, as in ECDSA verification.
+    #[test]
+    fn msm_secp256k1_ecdsa_cost() {
+        let params = CurveParams::secp256k1();
+        let u1 = full_width_scalar(&params.order);
+        let u2 = &params.order - BigInt::from(0x7654321u64);
+        let circuit = Msm {
+            inputs: Some(MsmInputs {
+                expected: with_error(
+                    &params,
+                    &[
+                        (u1.clone(), BigInt::from(1), true),
+                        (u2.clone(), BigInt::from(99), false),
+                    ],
+                    true,
+                ),
+                terms: vec![(u1, BigInt::from(1)), (u2, BigInt::from(99))],
+            }),
+            fixed: vec![true, false],
+            params,
+            n_bits: 256,
+            window: 4,
+        };
+        report_full_width("secp256k1 u1 * G + u2 * Q, window 4", circuit);
+    }
+}
@@ -0,0 +1,982 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::Signed;
+
+use super::affine::EmulatedAffinePoint;
+use super::fp12::{EmulatedFp12, Fp12Params, NativeFp12, SparseFp12};
+use super::fp2::{EmulatedFp2, Fp2Term, NativeFp2};
+use super::g2::{EmulatedG2Point, Family, G2Params, Twist};
+use super::glv::GlvParams;
+use super::projective::EmulatedProjectivePoint;
+use super::{residue, signed_residue, CurveParams, NativePoint};
+use crate::mp::modular::Term;
+use crate::OptionExt;
+
+/// A pair of affine points of G1 and G2, computed natively.
+pub type NativePairingInput = ((BigInt, BigInt), (NativeFp2, NativeFp2));
+
+/// The non-adjacent form of `n > 0`, most significant digit first.
+fn naf(n: &BigInt) -> Vec<i8> {
+    let mut n = n.clone();
+    let mut digits = Vec::new();
+    while n > BigInt::from(0) {
+        let digit = if n.is_odd() {
+            // The odd digit which makes `n - digit` a multiple of 4
+            let digit = if n.mod_floor(&BigInt::from(4)) == BigInt::from(1) {
+                1
+            } else {
+                -1
+            };
+            n -= digit;
+            digit
+        } else {
+            0
+        };
+        digits.push(digit);
+        n >>= 1;
+    }
+    digits.reverse();
+    digits
+}
+
+/// The inverse of `a` modulo `m`, which need not be prime.
+fn invert_mod(a: &BigInt, m: &BigInt) -> BigInt {
+    let gcd = a.mod_floor(m).extended_gcd(m);
+    assert_eq!(gcd.gcd, BigInt::from(1), "{} is not invertible", a);
+    gcd.x.mod_floor(m)
+}
+
+/// Parameters of the optimal ate pairing `e: G1 x G2 -> GT` of a pairing-friendly curve with
+/// embedding degree 12, where `G1` is on the curve over `Fp`, `G2` on its sextic twist over `Fp2`
+/// (see `G2Params`), and `GT` is the group of `r`-th roots of unity of `Fp12` (see
+/// `Fp12Params`).
+///
+/// The pairing is `f^((p^12 - 1) / r)`, the final exponentiation of the Miller loop `f`, which
+/// accumulates the lines `l` through the multiples `T` of `Q` in G2, evaluated at `P` in G1,
+/// while computing `[s] Q`, for `s = x` on BLS12 curves and `s = 6x + 2` on BN curves (followed
+/// by two more lines, through `pi(Q)` and `-pi^2(Q)`). The lines are scaled by constants and
+/// powers of `w` which lie in proper subfields of `Fp12`, and so are killed by the final
+/// exponentiation: an M-twist line is multiplied by `w^3`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PairingParams {
+    pub g1: CurveParams,
+    pub g2: G2Params,
+}
+
+impl PairingParams {
+    /// The optimal ate pairing on BLS12-381.
+    pub fn bls12_381() -> Self {
+        PairingParams {
+            g1: CurveParams::bls12_381(),
+            g2: G2Params::bls12_381(),
+        }
+    }
+
+    /// The optimal ate pairing on BN254 (alt_bn128).
+    pub fn bn254() -> Self {
+        PairingParams {
+            g1: CurveParams::bn254(),
+            g2: G2Params::bn254(),
+        }
+    }
+
+    pub fn fp12(&self) -> Fp12Params {
+        self.g2.fp12()
+    }
+
+    /// The scalar `s` of the Miller loop, which may be negative.
+    pub fn loop_scalar(&self) -> BigInt {
+        match self.g2.family {
+            Family::Bls12 => self.g2.x.clone(),
+            Family::Bn => &self.g2.x * 6u32 + 2u32,
+        }
+    }
+
+    /// The digits of `|s|` in non-adjacent form, most significant first.
+    pub fn loop_digits(&self) -> Vec<i8> {
+        naf(&self.loop_scalar().abs())
+    }
+
+    /// The Frobenius powers `(k, sign)` such that `|s| + sum(sign * p^k)` is a multiple of the
+    /// order `r` (see `assert_pairing_product_is_one`).
+    fn residue_frobenius(&self) -> Vec<(usize, Sign)> {
+        match self.g2.family {
+            // |x| + p = p - x = 0 mod r
+            Family::Bls12 => vec![(1, Sign::Plus)],
+            // 6x + 2 + p - p^2 + p^3 = 0 mod r
+            Family::Bn => vec![(1, Sign::Plus), (2, Sign::Minus), (3, Sign::Plus)],
+        }
+    }
+
+    /// The multiple `lambda = |s| + sum(sign * p^k)` of the order `r`.
+    pub fn residue_exponent(&self) -> BigInt {
+        let p = &self.g1.p;
+        self.residue_frobenius()
+            .iter()
+            .fold(self.loop_scalar().abs(), |acc, (k, sign)| {
+                let term = p.pow(*k as u32);
+                match sign {
+                    Sign::Minus => acc - term,
+                    _ => acc + term,
+                }
+            })
+    }
+
+    /// The line through `t` with slope `lambda`, evaluated at `p` and scaled: with
+    /// `alpha = lambda x_t - y_t`, it is `y_p - lambda x_p w + alpha w^3` for a D-twist, and
+    /// `alpha - lambda x_p w^2 + y_p w^3` for an M-twist.
+    fn line(
+        &self,
+        lambda: &NativeFp2,
+        (xt, yt): &(NativeFp2, NativeFp2),
+        (xp, yp): &(BigInt, BigInt),
+    ) -> NativeFp12 {
+        let f = &self.g2.fp2;
+        let fp12 = self.fp12();
+        let alpha = fp12.from_fp2(&f.sub(&f.mul(lambda, xt), yt));
+        let beta = fp12.from_fp2(&f.neg(&f.scale(lambda, xp)));
+        let monomial = |k: usize| {
+            let mut m = vec![BigInt::from(0); k + 1];
+            m[k] = BigInt::from(1);
+            m
+        };
+        let (alpha_k, beta_k, y_k) = line_powers(self.g2.twist);
+        let mut l = fp12.mul(&alpha, &monomial(alpha_k));
+        l = fp12.add(&l, &fp12.mul(&beta, &monomial(beta_k)));
+        fp12.add(&l, &fp12.mul(std::slice::from_ref(yp), &monomial(y_k)))
+    }
+
+    /// The slope of the tangent at `t`, or of the chord through `t` and `q`.
+    fn slope(
+        &self,
+        (xt, yt): &(NativeFp2, NativeFp2),
+        q: Option<&(NativeFp2, NativeFp2)>,
+    ) -> NativeFp2 {
+        let f = &self.g2.fp2;
+        match q {
+            None => {
+                let x2 = f.square(xt);
+                let num = f.add(&x2, &f.add(&x2, &x2));
+                f.mul(&num, &f.inverse(&f.add(yt, yt)).unwrap())
+            }
+            Some((xq, yq)) => f.mul(&f.sub(yq, yt), &f.inverse(&f.sub(xq, xt)).unwrap()),
+        }
+    }
+
+    /// The point `t + q` on the line through `t` with slope `lambda`, where `x_q` is the
+    /// x-coordinate of `q`.
+    fn next_point(
+        &self,
+        lambda: &NativeFp2,
+        (xt, yt): &(NativeFp2, NativeFp2),
+        xq: &NativeFp2,
+    ) -> (NativeFp2, NativeFp2) {
+        let f = &self.g2.fp2;
+        let x = f.sub(&f.sub(&f.square(lambda), xt), xq);
+        let y = f.sub(&f.mul(lambda, &f.sub(xt, &x)), yt);
+        (x, y)
+    }
+
+    /// The Miller loop over `|s|`, as computed by `miller_loop`, without the final conjugation
+    /// when `s` is negative.
+    pub fn miller_loop_unsigned(&self, pairs: &[NativePairingInput]) -> NativeFp12 {
+        let fp12 = self.fp12();
+        let g2 = &self.g2;
+        let mut f = fp12.from_int(1);
+        let mut ts: Vec<(NativeFp2, NativeFp2)> = pairs.iter().map(|(_, q)| q.clone()).collect();
+        for digit in self.loop_digits().into_iter().skip(1) {
+            f = fp12.square(&f);
+            for ((p, _), t) in pairs.iter().zip(ts.iter_mut()) {
+                let lambda = self.slope(t, None);
+                f = fp12.mul(&f, &self.line(&lambda, t, p));
+                *t = self.next_point(&lambda, t, &t.0.clone());
+            }
+            if digit != 0 {
+                for ((p, q), t) in pairs.iter().zip(ts.iter_mut()) {
+                    let q = if digit < 0 {
+                        g2.negate(&Some(q.clone())).unwrap()
+                    } else {
+                        q.clone()
+                    };
+                    let lambda = self.slope(t, Some(&q));
+                    f = fp12.mul(&f, &self.line(&lambda, t, p));
+                    *t = self.next_point(&lambda, t, &q.0);
+                }
+            }
+        }
+        if g2.family == Family::Bn {
+            for ((p, q), t) in pairs.iter().zip(ts.iter_mut()) {
+                let q1 = g2.psi(&Some(q.clone())).unwrap();
+                let q2 = g2.negate(&g2.psi(&Some(q1.clone()))).unwrap();
+                let lambda = self.slope(t, Some(&q1));
+                f = fp12.mul(&f, &self.line(&lambda, t, p));
+                *t = self.next_point(&lambda, t, &q1.0);
+                let lambda = self.slope(t, Some(&q2));
+                f = fp12.mul(&f, &self.line(&lambda, t, p));
+            }
+        }
+        f
+    }
+
+    /// The product of the Miller loops of the pairs (see `miller_loop`).
+    pub fn miller_loop(&self, pairs: &[NativePairingInput]) -> NativeFp12 {
+        let f = self.miller_loop_unsigned(pairs);
+        if self.loop_scalar().sign() == Sign::Minus {
+            self.fp12().conjugate(&f)
+        } else {
+            f
+        }
+    }
+
+    /// Raises `f` to `(p^12 - 1) / r`: the easy part `(p^6 - 1)(p^2 + 1)` with the Frobenius
+    /// map, and the hard part `(p^4 - p^2 + 1) / r` by square-and-multiply.
+    pub fn final_exponentiation(&self, f: &[BigInt]) -> NativeFp12 {
+        let fp12 = self.fp12();
+        let p = &self.g1.p;
+        let f = fp12.mul(&fp12.conjugate(f), &fp12.inverse(f).unwrap());
+        let f = fp12.mul(&fp12.frobenius(&f, 2), &f);
+        let hard = (p.pow(4) - p * p + 1u32) / &self.g1.order;
+        fp12.pow(&f, &hard)
+    }
+
+    /// The product of the pairings of the pairs.
+    pub fn multi_pairing(&self, pairs: &[NativePairingInput]) -> NativeFp12 {
+        self.final_exponentiation(&self.miller_loop(pairs))
+    }
+
+    pub fn pairing(&self, p: &(BigInt, BigInt), q: &(NativeFp2, NativeFp2)) -> NativeFp12 {
+        self.multi_pairing(&[(p.clone(), q.clone())])
+    }
+
+    /// The cube root of unity `beta` such that the endomorphism `phi(x, y) = (beta * x, y)` acts
+    /// on G1 as the multiplication by `-x^2`, for BLS12 curves.
+    fn g1_beta(&self) -> BigInt {
+        let glv = GlvParams::new(&self.g1).unwrap();
+        let minus_x2 = (-(&self.g2.x * &self.g2.x)).mod_floor(&self.g1.order);
+        if glv.lambda == minus_x2 {
+            glv.beta
+        } else {
+            (&glv.beta * &glv.beta).mod_floor(&self.g1.p)
+        }
+    }
+
+    /// Whether a point of the curve is in G1 (see `assert_in_g1`).
+    pub fn is_in_g1(&self, point: &NativePoint) -> bool {
+        let g1 = &self.g1;
+        match self.g2.family {
+            Family::Bls12 => {
+                let beta = self.g1_beta();
+                let phi = point
+                    .as_ref()
+                    .map(|(x, y)| ((&beta * x).mod_floor(&g1.p), y.clone()));
+                let x2 = &self.g2.x * &self.g2.x;
+                g1.is_on_curve(point) && phi == g1.negate(&g1.mul(&x2, point))
+            }
+            Family::Bn => g1.is_on_curve(point),
+        }
+    }
+
+    /// A residue `c` such that `g c^lambda` is in `Fp6`, where `lambda` is the multiple of `r`
+    /// of `residue_exponent`, if `g` has a trivial final exponentiation (and otherwise some
+    /// element).
+    ///
+    /// Let `n = p^12 - 1` and `s = n / r`, so that `g^s = 1`. Write `s = 3^k s'` with `s'` prime
+    /// to 3, and `g = g3 g'` with `g3` of order dividing `3^k` and `g'` of order dividing `s'`.
+    /// For these curves, the 3-Sylow subgroup of `Fp12*` is contained in `Fp6`, so `g3` is in
+    /// `Fp6`. The `lambda`-th powers of order dividing `s'` are the elements of order dividing
+    /// `t = s' / d`, where `d = gcd(lambda / r, s')`; with `y = g'^t` and `u = y^(1/t mod d)`,
+    /// which is in `Fp6` as `d` divides `p^6 - 1`, `u / g'` has order dividing `t`, and
+    /// `c = (u / g')^(1/lambda mod t)`.
+    pub fn residue_witness(&self, g: &[BigInt]) -> NativeFp12 {
+        let fp12 = self.fp12();
+        let p = &self.g1.p;
+        let r = &self.g1.order;
+        let s = (p.pow(12) - 1u32) / r;
+        let lambda = self.residue_exponent();
+        let mut three_k = BigInt::from(1);
+        while s.is_multiple_of(&(&three_k * 3u32)) {
+            three_k *= 3u32;
+        }
+        let s_prime = &s / &three_k;
+        let d = (&lambda / r).gcd(&s_prime);
+        let t = &s_prime / &d;
+        // The exponent of the projection `g -> g'`: 0 mod 3^k and 1 mod s'
+        let e = &three_k * invert_mod(&three_k, &s_prime);
+        let y = fp12.pow(g, &(&e * &t).mod_floor(&s));
+        let u = if d == BigInt::from(1) {
+            fp12.from_int(1)
+        } else {
+            fp12.pow(&y, &invert_mod(&t, &d))
+        };
+        let l = invert_mod(&lambda, &t);
+        let c = fp12.pow(g, &(-&e * &l).mod_floor(&s));
+        fp12.mul(&c, &fp12.pow(&u, &l.mod_floor(&d)))
+    }
+}
+
+/// The powers of `w` of the coefficients `alpha`, `-lambda x_p` and `y_p` of a line (see
+/// `PairingParams::line`).
+fn line_powers(twist: Twist) -> (usize, usize, usize) {
+    match twist {
+        Twist::M => (0, 2, 3),
+        Twist::D => (3, 1, 0),
+    }
+}
+
+/// A point of G2 in affine coordinates.
+type AffineG2<Scalar> = (EmulatedFp2<Scalar>, EmulatedFp2<Scalar>);
+
+/// Allocates the slope of the tangent at `t`, or of the chord through `t` and `q`, constrained by
+/// `2 y_t lambda = 3 x_t^2` or `(x_q - x_t) lambda = y_q - y_t`. The chord must not be vertical,
+/// nor the tangent (which holds for points of odd order).
+fn slope<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    t: &AffineG2<Scalar>,
+    q: Option<&AffineG2<Scalar>>,
+    params: &PairingParams,
+) -> Result<EmulatedFp2<Scalar>, SynthesisError> {
+    let f = &params.g2.fp2;
+    let (xt, yt) = t;
+    let lambda = EmulatedFp2::alloc_from_coeffs(
+        cs.namespace(|| "lambda"),
+        || {
+            let t = (xt.value.grab()?.clone(), yt.value.grab()?.clone());
+            let q = match q {
+                Some((xq, yq)) => Some((xq.value.grab()?.clone(), yq.value.grab()?.clone())),
+                None => None,
+            };
+            Ok(params.slope(&t, q.as_ref()))
+        },
+        f,
+    )?;
+    match q {
+        None => EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Fp2Term::Product(&lambda, &yt.scale::<CS>(2))],
+            &[Fp2Term::Product(&xt.scale::<CS>(3), xt)],
+            f,
+        )?,
+        Some((xq, yq)) => EmulatedFp2::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Fp2Term::Product(&lambda, xq), Fp2Term::Single(yt)],
+            &[Fp2Term::Product(&lambda, xt), Fp2Term::Single(yq)],
+            f,
+        )?,
+    }
+    Ok(lambda)
+}
+
+/// Computes the point `t + q` on the line through `t` with slope `lambda`:
+/// `x = lambda^2 - x_t - x_q` and `y = lambda (x_t - x) - y_t`.
+fn next_point<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    lambda: &EmulatedFp2<Scalar>,
+    (xt, yt): &AffineG2<Scalar>,
+    xq: &EmulatedFp2<Scalar>,
+    params: &PairingParams,
+) -> Result<AffineG2<Scalar>, SynthesisError> {
+    let f = &params.g2.fp2;
+    let x = EmulatedFp2::alloc_from_coeffs(
+        cs.namespace(|| "x"),
+        || {
+            let l2 = f.square(lambda.value.grab()?);
+            Ok(f.sub(&f.sub(&l2, xt.value.grab()?), xq.value.grab()?))
+        },
+        f,
+    )?;
+    EmulatedFp2::assert_sums_congruent(
+        cs.namespace(|| "x check"),
+        &[
+            Fp2Term::Single(&x),
+            Fp2Term::Single(xt),
+            Fp2Term::Single(xq),
+        ],
+        &[Fp2Term::Product(lambda, lambda)],
+        f,
+    )?;
+    let y = EmulatedFp2::alloc_from_coeffs(
+        cs.namespace(|| "y"),
+        || {
+            let dx = f.sub(xt.value.grab()?, x.value.grab()?);
+            Ok(f.sub(&f.mul(lambda.value.grab()?, &dx), yt.value.grab()?))
+        },
+        f,
+    )?;
+    EmulatedFp2::assert_sums_congruent(
+        cs.namespace(|| "y check"),
+        &[
+            Fp2Term::Single(&y),
+            Fp2Term::Single(yt),
+            Fp2Term::Product(lambda, &x),
+        ],
+        &[Fp2Term::Product(lambda, xt)],
+        f,
+    )?;
+    Ok((x, y))
+}
+
+/// Computes the line through `t` with slope `lambda` evaluated at `p` (see
+/// `PairingParams::line`). An element `a0 + a1 u` of `Fp2` at `w^k` has the coefficients
+/// `a0 - xi0 a1` at `w^k` and `a1` at `w^(k + 6)`, so with `alpha = lambda x_t - y_t`:
+/// `alpha0 - xi0 alpha1 = l0 x0 - l1 x1 - y0 - xi0 (l0 x1 + l1 x0 - y1)`,
+/// `alpha1 = l0 x1 + l1 x0 - y1`, and for `-lambda x_p`: `(xi0 l1 - l0) x_p` and `-l1 x_p`.
+fn line<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    lambda: &EmulatedFp2<Scalar>,
+    (xt, yt): &AffineG2<Scalar>,
+    p: &EmulatedAffinePoint<Scalar>,
+    params: &PairingParams,
+) -> Result<SparseFp12<Scalar>, SynthesisError> {
+    let modulus = params.g1.modulus::<Scalar, CS>()?;
+    let xi0 = Scalar::from(params.g2.xi.0.to_string().parse::<u64>().unwrap());
+    let (l0, l1) = (&lambda.c0, &lambda.c1);
+    let (x0, x1) = (&xt.c0, &xt.c1);
+    let (y0, y1) = (&yt.c0, &yt.c1);
+    let (xi_l0, xi_l1) = (l0.scale::<CS>(xi0), l1.scale::<CS>(xi0));
+    let xi_y1 = y1.scale::<CS>(xi0);
+    let alpha_lo = signed_residue(
+        &mut cs,
+        "alpha lo",
+        &[Term::Product(l0, x0), Term::Single(&xi_y1)],
+        &[
+            Term::Product(l1, x1),
+            Term::Single(y0),
+            Term::Product(&xi_l0, x1),
+            Term::Product(&xi_l1, x0),
+        ],
+        &modulus,
+    )?;
+    let alpha_hi = signed_residue(
+        &mut cs,
+        "alpha hi",
+        &[Term::Product(l0, x1), Term::Product(l1, x0)],
+        &[Term::Single(y1)],
+        &modulus,
+    )?;
+    let beta_lo = signed_residue(
+        &mut cs,
+        "beta lo",
+        &[Term::Product(&xi_l1, &p.x)],
+        &[Term::Product(l0, &p.x)],
+        &modulus,
+    )?;
+    let beta_hi = signed_residue(
+        &mut cs,
+        "beta hi",
+        &[],
+        &[Term::Product(l1, &p.x)],
+        &modulus,
+    )?;
+    let (alpha_k, beta_k, y_k) = line_powers(params.g2.twist);
+    Ok(SparseFp12::new(
+        vec![
+            (alpha_k, alpha_lo),
+            (alpha_k + 6, alpha_hi),
+            (beta_k, beta_lo),
+            (beta_k + 6, beta_hi),
+            (y_k, p.y.clone()),
+        ],
+        &params.fp12(),
+    ))
+}
+
+/// Computes `psi(q)` on affine coordinates (see `G2Params::psi_coefficients`).
+fn psi<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    (x, y): &AffineG2<Scalar>,
+    params: &PairingParams,
+) -> Result<AffineG2<Scalar>, SynthesisError> {
+    let (cx, cy) = params.g2.psi_coefficients();
+    let x = x.conjugate(cs.namespace(|| "conj x"))?;
+    let x = x.mul_by_constant(cs.namespace(|| "cx conj x"), &cx)?;
+    let y = y.conjugate(cs.namespace(|| "conj y"))?;
+    let y = y.mul_by_constant(cs.namespace(|| "cy conj y"), &cy)?;
+    Ok((x, y))
+}
+
+/// Multiplies the accumulator by a line, or starts it.
+fn accumulate<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: CS,
+    f: Option<EmulatedFp12<Scalar>>,
+    line: &SparseFp12<Scalar>,
+) -> Result<EmulatedFp12<Scalar>, SynthesisError> {
+    match f {
+        Some(f) => f.mul_by_sparse(cs, line),
+        None => EmulatedFp12::from_sparse::<CS>(line),
+    }
+}
+
+/// The Miller loop over `|s|` of `miller_loop`, where `residue`, if any, is `(c, 1 / c)`, and
+/// is multiplied into the accumulator along with each digit of `|s|`, so that the result is
+/// multiplied by `c^|s|`.
+fn miller_loop_with_residue<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    pairs: &[(&EmulatedAffinePoint<Scalar>, &EmulatedG2Point<Scalar>)],
+    residue: Option<(&EmulatedFp12<Scalar>, &EmulatedFp12<Scalar>)>,
+    params: &PairingParams,
+) -> Result<EmulatedFp12<Scalar>, SynthesisError> {
+    let digits = params.loop_digits();
+    if pairs.is_empty() {
+        eprintln!("Cannot compute a Miller loop of no pairs");
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    // The affine points of G2, and their negations if needed
+    let mut qs = Vec::new();
+    for (j, (p, q)) in pairs.iter().enumerate() {
+        if p.params != params.g1 || q.params != params.g2 {
+            eprintln!("Curves do not agree at miller_loop");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let (x, y) = q.to_affine(cs.namespace(|| format!("q {} affine", j)))?;
+        let neg_y = if digits.contains(&-1) {
+            Some(y.neg(cs.namespace(|| format!("q {} negate", j)))?)
+        } else {
+            None
+        };
+        qs.push(((x, y), neg_y));
+    }
+    let mut ts: Vec<AffineG2<Scalar>> = qs.iter().map(|(q, _)| q.clone()).collect();
+    let mut f: Option<EmulatedFp12<Scalar>> = residue.map(|(c, _)| c.clone());
+
+    for (i, digit) in digits.iter().enumerate().skip(1) {
+        let mut cs = cs.namespace(|| format!("digit {}", i));
+        if let Some(acc) = f {
+            f = Some(acc.square(cs.namespace(|| "square"))?);
+        }
+        for (j, (p, _)) in pairs.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("double {}", j));
+            let t = &ts[j];
+            let lambda = slope(cs.namespace(|| "slope"), t, None, params)?;
+            let l = line(cs.namespace(|| "line"), &lambda, t, p, params)?;
+            f = Some(accumulate(cs.namespace(|| "accumulate"), f, &l)?);
+            ts[j] = next_point(cs.namespace(|| "next"), &lambda, t, &t.0, params)?;
+        }
+        if *digit != 0 {
+            for (j, (p, _)) in pairs.iter().enumerate() {
+                let mut cs = cs.namespace(|| format!("add {}", j));
+                let ((xq, yq), neg_yq) = &qs[j];
+                let q = if *digit < 0 {
+                    (xq.clone(), neg_yq.clone().unwrap())
+                } else {
+                    (xq.clone(), yq.clone())
+                };
+                let t = &ts[j];
+                let lambda = slope(cs.namespace(|| "slope"), t, Some(&q), params)?;
+                let l = line(cs.namespace(|| "line"), &lambda, t, p, params)?;
+                f = Some(accumulate(cs.namespace(|| "accumulate"), f, &l)?);
+                ts[j] = next_point(cs.namespace(|| "next"), &lambda, t, xq, params)?;
+            }
+            if let Some((c, c_inv)) = residue {
+                let factor = if *digit < 0 { c_inv } else { c };
+                f = Some(f.grab()?.mul(cs.namespace(|| "residue"), factor)?);
+            }
+        }
+    }
+    if params.g2.family == Family::Bn {
+        for (j, (p, _)) in pairs.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("frobenius lines {}", j));
+            let (q, _) = &qs[j];
+            let q1 = psi(cs.namespace(|| "psi q"), q, params)?;
+            let (x2, y2) = psi(cs.namespace(|| "psi^2 q"), &q1, params)?;
+            let q2 = (x2, y2.neg(cs.namespace(|| "-psi^2 q"))?);
+            let t = &ts[j];
+            let lambda = slope(cs.namespace(|| "slope 1"), t, Some(&q1), params)?;
+            let l = line(cs.namespace(|| "line 1"), &lambda, t, p, params)?;
+            f = Some(accumulate(cs.namespace(|| "accumulate 1"), f, &l)?);
+            let t = next_point(cs.namespace(|| "next"), &lambda, t, &q1.0, params)?;
+            let lambda = slope(cs.namespace(|| "slope 2"), &t, Some(&q2), params)?;
+            let l = line(cs.namespace(|| "line 2"), &lambda, &t, p, params)?;
+            f = Some(accumulate(cs.namespace(|| "accumulate 2"), f, &l)?);
+        }
+    }
+    f.ok_or_else(|| {
+        eprintln!("The Miller loop has no lines");
+        SynthesisError::Unsatisfiable
+    })
+}
+
+/// Computes the product of the Miller loops `f_{s,Q}(P)` of the pairs `(P, Q)` (see
+/// `PairingParams`), sharing the squarings.
+///
+/// The lines are computed in affine coordinates with witnessed slopes, which are sound as long as
+/// no line is vertical: each `P` must be a point of G1 (which cannot be the identity in affine
+/// coordinates), and each `Q` a point of G2 other than the identity (see
+/// `EmulatedG2Point::assert_in_subgroup`).
+pub fn miller_loop<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    pairs: &[(&EmulatedAffinePoint<Scalar>, &EmulatedG2Point<Scalar>)],
+    params: &PairingParams,
+) -> Result<EmulatedFp12<Scalar>, SynthesisError> {
+    let f = miller_loop_with_residue(cs.namespace(|| "loop"), pairs, None, params)?;
+    if params.loop_scalar().sign() == Sign::Minus {
+        f.conjugate(cs.namespace(|| "conjugate"))
+    } else {
+        Ok(f)
+    }
+}
+
+/// Raises `f` to `(p^12 - 1) / r`. The easy part `(p^6 - 1)(p^2 + 1)` uses the Frobenius map and
+/// maps `f` into the cyclotomic subgroup, where inverses are conjugates. The hard part
+/// `(p^4 - p^2 + 1) / r` is written in base `p` with coefficients polynomial in `x`, as
+/// `((x - 1)^2 / 3)(x + p)(x^2 + p^2 - 1) + 1` for BLS12 curves (Hayashida, Hayasaka and Teruya,
+/// 2020), and `l0 + l1 p + l2 p^2 + p^3` with `l0 = -36x^3 - 30x^2 - 18x - 2`,
+/// `l1 = -36x^3 - 18x^2 - 12x + 1` and `l2 = 6x^2 + 1` for BN curves (Scott et al., 2009), so
+/// that it only takes exponentiations by `x`.
+pub fn final_exponentiation<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    f: &EmulatedFp12<Scalar>,
+    params: &PairingParams,
+) -> Result<EmulatedFp12<Scalar>, SynthesisError> {
+    let x = &params.g2.x;
+    let inverse = f.inverse(cs.namespace(|| "inverse"))?;
+    let conj = f.conjugate(cs.namespace(|| "conjugate"))?;
+    let t = conj.mul(cs.namespace(|| "p^6 - 1"), &inverse)?;
+    let t = t
+        .frobenius(cs.namespace(|| "frobenius 2"), 2)?
+        .mul(cs.namespace(|| "p^2 + 1"), &t)?;
+
+    let mut cs = cs.namespace(|| "hard part");
+    match params.g2.family {
+        Family::Bls12 => {
+            let x_minus_one = x - 1u32;
+            let y = t.cyclotomic_pow(cs.namespace(|| "t^(x - 1)"), &x_minus_one)?;
+            let y = y.cyclotomic_pow(cs.namespace(|| "y"), &(&x_minus_one / 3u32))?;
+            let y1 = y.cyclotomic_pow(cs.namespace(|| "y^x"), x)?;
+            let y2 = y1.cyclotomic_pow(cs.namespace(|| "y^(x^2)"), x)?;
+            let y3 = y2.cyclotomic_pow(cs.namespace(|| "y^(x^3)"), x)?;
+            // l0 = y^(x^3 - x) t, l1 = y^(x^2 - 1), l2 = y^x, l3 = y
+            let l0 = y1.conjugate(cs.namespace(|| "y^-x"))?;
+            let l0 = l0.mul(cs.namespace(|| "y^(x^3 - x)"), &y3)?;
+            let l0 = l0.mul(cs.namespace(|| "l0"), &t)?;
+            let l1 = y.conjugate(cs.namespace(|| "y^-1"))?;
+            let l1 = l1.mul(cs.namespace(|| "l1"), &y2)?;
+            let l1 = l1.frobenius(cs.namespace(|| "l1 frobenius"), 1)?;
+            let l2 = y1.frobenius(cs.namespace(|| "l2 frobenius"), 2)?;
+            let l3 = y.frobenius(cs.namespace(|| "l3 frobenius"), 3)?;
+            let acc = l0.mul(cs.namespace(|| "l0 l1"), &l1)?;
+            let acc = acc.mul(cs.namespace(|| "l0 l1 l2"), &l2)?;
+            acc.mul(cs.namespace(|| "l0 l1 l2 l3"), &l3)
+        }
+        Family::Bn => {
+            let a = t.cyclotomic_pow(cs.namespace(|| "t^x"), x)?;
+            let b = a.cyclotomic_pow(cs.namespace(|| "t^(x^2)"), x)?;
+            let c = b.cyclotomic_pow(cs.namespace(|| "t^(x^3)"), x)?;
+            let c36 = c.cyclotomic_pow(cs.namespace(|| "c^36"), &BigInt::from(36))?;
+            let b6 = b.cyclotomic_pow(cs.namespace(|| "b^6"), &BigInt::from(6))?;
+            let b18 = b6.cyclotomic_pow(cs.namespace(|| "b^18"), &BigInt::from(3))?;
+            let b30 = b18.mul(cs.namespace(|| "b^24"), &b6)?;
+            let b30 = b30.mul(cs.namespace(|| "b^30"), &b6)?;
+            let a6 = a.cyclotomic_pow(cs.namespace(|| "a^6"), &BigInt::from(6))?;
+            let a12 = a6.square(cs.namespace(|| "a^12"))?;
+            let a18 = a12.mul(cs.namespace(|| "a^18"), &a6)?;
+            let t2 = t.square(cs.namespace(|| "t^2"))?;
+            // l0 = (c^36 b^30 a^18 t^2)^-1
+            let l0 = c36.mul(cs.namespace(|| "c^36 b^30"), &b30)?;
+            let l0 = l0.mul(cs.namespace(|| "c^36 b^30 a^18"), &a18)?;
+            let l0 = l0.mul(cs.namespace(|| "c^36 b^30 a^18 t^2"), &t2)?;
+            let l0 = l0.conjugate(cs.namespace(|| "l0"))?;
+            // l1 = (c^36 b^18 a^12)^-1 t
+            let l1 = c36.mul(cs.namespace(|| "c^36 b^18"), &b18)?;
+            let l1 = l1.mul(cs.namespace(|| "c^36 b^18 a^12"), &a12)?;
+            let l1 = l1.conjugate(cs.namespace(|| "(c^36 b^18 a^12)^-1"))?;
+            let l1 = l1.mul(cs.namespace(|| "l1"), &t)?;
+            let l1 = l1.frobenius(cs.namespace(|| "l1 frobenius"), 1)?;
+            // l2 = b^6 t, l3 = t
+            let l2 = b6.mul(cs.namespace(|| "l2"), &t)?;
+            let l2 = l2.frobenius(cs.namespace(|| "l2 frobenius"), 2)?;
+            let l3 = t.frobenius(cs.namespace(|| "l3 frobenius"), 3)?;
+            let acc = l0.mul(cs.namespace(|| "l0 l1"), &l1)?;
+            let acc = acc.mul(cs.namespace(|| "l0 l1 l2"), &l2)?;
+            acc.mul(cs.namespace(|| "l0 l1 l2 l3"), &l3)
+        }
+    }
+}
+
+/// Computes the pairing `e(P, Q)`, with the requirements of `miller_loop`.
+pub fn pairing<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    p: &EmulatedAffinePoint<Scalar>,
+    q: &EmulatedG2Point<Scalar>,
+    params: &PairingParams,
+) -> Result<EmulatedFp12<Scalar>, SynthesisError> {
+    let f = miller_loop(cs.namespace(|| "miller loop"), &[(p, q)], params)?;
+    final_exponentiation(cs.namespace(|| "final exponentiation"), &f, params)
+}
+
+/// Constrain the product of the pairings `e(P, Q)` of the pairs to be one, with the
+/// requirements of `miller_loop`, without a final exponentiation (Novakovic and Eagen, "On
+/// proving pairings", 2024).
+///
+/// The product is one if and only if the product `g` of the Miller loops over `|s|` has a trivial
+/// final exponentiation, i.e. if and only if `g` is an `r`-th power up to an element of `Fp6`
+/// (any element of a proper subfield has a trivial final exponentiation). A residue `c` is
+/// witnessed such that `g c^lambda` is in `Fp6`, for a multiple
+/// `lambda = |s| + sum(+-p^k)` of `r` (see `PairingParams::residue_witness`): `c^|s|` is
+/// accumulated along the Miller loop, sharing its squarings, and `c^(p^k)` are Frobenius maps.
+pub fn assert_pairing_product_is_one<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    pairs: &[(&EmulatedAffinePoint<Scalar>, &EmulatedG2Point<Scalar>)],
+    params: &PairingParams,
+) -> Result<(), SynthesisError> {
+    let fp12 = params.fp12();
+    let c = EmulatedFp12::alloc_from_coeffs(
+        cs.namespace(|| "residue"),
+        || {
+            let pairs = pairs
+                .iter()
+                .map(|(p, q)| {
+                    let q = q.affine_value().grab()?.clone();
+                    Ok((p.value.grab()?.clone(), q.grab()?.clone()))
+                })
+                .collect::<Result<Vec<_>, SynthesisError>>()?;
+            Ok(params.residue_witness(&params.miller_loop_unsigned(&pairs)))
+        },
+        &fp12,
+    )?;
+    let c_inv = c.inverse(cs.namespace(|| "residue inverse"))?;
+    let mut acc =
+        miller_loop_with_residue(cs.namespace(|| "loop"), pairs, Some((&c, &c_inv)), params)?;
+    for (k, sign) in params.residue_frobenius() {
+        let base = if sign == Sign::Minus { &c_inv } else { &c };
+        let frobenius = base.frobenius(cs.namespace(|| format!("frobenius {}", k)), k)?;
+        acc = acc.mul(cs.namespace(|| format!("mul frobenius {}", k)), &frobenius)?;
+    }
+    acc.assert_in_fp6(cs.namespace(|| "in fp6"))
+}
+
+/// Constrain the point `p` of the curve to be in G1. BN curves have a trivial cofactor, so this
+/// only constrains anything on BLS12 curves, with the test of Scott ("A note on group membership
+/// tests for G1, G2 and GT on BLS pairing-friendly curves", 2021): `phi(P) = -[x^2] P`, for the
+/// endomorphism `phi(x, y) = (beta * x, y)` which acts as `-x^2` on G1.
+pub fn assert_in_g1<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    p: &EmulatedAffinePoint<Scalar>,
+    params: &PairingParams,
+) -> Result<(), SynthesisError> {
+    if p.params != params.g1 {
+        eprintln!("Curves do not agree at assert_in_g1");
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    if params.g2.family == Family::Bn {
+        return Ok(());
+    }
+    let g1 = &params.g1;
+    let modulus = g1.modulus::<Scalar, CS>()?;
+    let beta = g1.constant::<Scalar, CS>(&params.g1_beta())?;
+    let phi_x = residue(&mut cs, "phi x", &[Term::Product(&beta, &p.x)], &modulus)?;
+    let neg = p.negate(cs.namespace(|| "negate"))?;
+    let neg_phi = EmulatedAffinePoint::from_coords(phi_x, neg.y, g1);
+    let x2 = &params.g2.x * &params.g2.x;
+    let x2_p = EmulatedProjectivePoint::from_affine::<CS>(p)?
+        .mul_by_constant(cs.namespace(|| "x^2 p"), &x2)?;
+    x2_p.assert_equivalent(
+        cs.namespace(|| "check"),
+        &EmulatedProjectivePoint::from_affine::<CS>(&neg_phi)?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    fn g1(params: &PairingParams, k: u64) -> (BigInt, BigInt) {
+        let g = Some(params.g1.generator.clone());
+        params.g1.mul(&BigInt::from(k), &g).unwrap()
+    }
+
+    fn g2(params: &PairingParams, k: i64) -> (NativeFp2, NativeFp2) {
+        let g = Some(params.g2.generator.clone());
+        params.g2.mul(&BigInt::from(k), &g).unwrap()
+    }
+
+    fn check_bilinear(params: PairingParams) {
+        let fp12 = params.fp12();
+        let r = &params.g1.order;
+        assert!(params.residue_exponent().is_multiple_of(r));
+        assert_eq!(params.loop_digits()[0], 1);
+        let e = params.pairing(&g1(&params, 1), &g2(&params, 1));
+        assert_ne!(e, fp12.from_int(1));
+        assert_eq!(fp12.pow(&e, r), fp12.from_int(1));
+        let e_ab = params.pairing(&g1(&params, 6), &g2(&params, 7));
+        assert_eq!(e_ab, fp12.pow(&e, &BigInt::from(42)));
+        // The final exponentiation is the power by (p^12 - 1) / r
+        let f = params.miller_loop(&[(g1(&params, 6), g2(&params, 7))]);
+        let exponent = (params.g1.p.pow(12) - 1u32) / r;
+        assert_eq!(fp12.pow(&f, &exponent), e_ab);
+    }
+
+    #[test]
+    fn pairing_bilinear_bls12_381() {
+        check_bilinear(PairingParams::bls12_381());
+    }
+
+    #[test]
+    fn pairing_bilinear_bn254() {
+        check_bilinear(PairingParams::bn254());
+    }
+
+    fn check_residue_witness(params: PairingParams) {
+        let fp12 = params.fp12();
+        let lambda = params.residue_exponent();
+        // e(6 P, Q) e(-2 P, 3 Q) = 1
+        let neg = params.g1.negate(&Some(g1(&params, 2))).unwrap();
+        let pairs = [(g1(&params, 6), g2(&params, 1)), (neg, g2(&params, 3))];
+        let g = params.miller_loop_unsigned(&pairs);
+        let c = params.residue_witness(&g);
+        assert!(fp12.is_in_fp6(&fp12.mul(&g, &fp12.pow(&c, &lambda))));
+        // e(6 P, Q) e(-2 P, 2 Q) != 1
+        let pairs = [pairs[0].clone(), (pairs[1].0.clone(), g2(&params, 2))];
+        let g = params.miller_loop_unsigned(&pairs);
+        let c = params.residue_witness(&g);
+        assert!(!fp12.is_in_fp6(&fp12.mul(&g, &fp12.pow(&c, &lambda))));
+    }
+
+    #[test]
+    fn pairing_residue_witness_bls12_381() {
+        check_residue_witness(PairingParams::bls12_381());
+    }
+
+    #[test]
+    fn pairing_residue_witness_bn254() {
+        check_residue_witness(PairingParams::bn254());
+    }
+
+    /// Allocates the pairs of points.
+    fn alloc_pairs<CS: ConstraintSystem<Fr>>(
+        cs: &mut CS,
+        params: &PairingParams,
+        pairs: &[NativePairingInput],
+    ) -> Vec<(EmulatedAffinePoint<Fr>, EmulatedG2Point<Fr>)> {
+        pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (p, q))| {
+                let p = EmulatedAffinePoint::alloc_from_coords(
+                    cs.namespace(|| format!("p {}", i)),
+                    || Ok(p.clone()),
+                    &params.g1,
+                )
+                .unwrap();
+                let q = EmulatedG2Point::alloc_affine(
+                    cs.namespace(|| format!("q {}", i)),
+                    || Ok(q.clone()),
+                    &params.g2,
+                )
+                .unwrap();
+                (p, q)
+            })
+            .collect()
+    }
+
+    /// Checks that the product of the pairings is one with a witnessed residue, and reports the
+    /// cost.
+    fn product_check(name: &str, params: &PairingParams, pairs: &[NativePairingInput]) -> bool {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let points = alloc_pairs(&mut cs, params, pairs);
+        let refs: Vec<_> = points.iter().map(|(p, q)| (p, q)).collect();
+        assert_pairing_product_is_one(cs.namespace(|| "check"), &refs, params).unwrap();
+        println!(
+            "{} product of {} pairings is one: {} constraints",
+            name,
+            pairs.len(),
+            cs.num_constraints()
+        );
+        cs.is_satisfied()
+    }
+
+    /// The pairs `(6 P, Q), (-2 P, k Q)`, whose pairings multiply to one if `k = 3`.
+    fn product_pairs(params: &PairingParams, k: i64) -> Vec<NativePairingInput> {
+        let neg = params.g1.negate(&Some(g1(params, 2))).unwrap();
+        vec![(g1(params, 6), g2(params, 1)), (neg, g2(params, k))]
+    }
+
+    #[test]
+    fn pairing_product_bls12_381() {
+        let params = PairingParams::bls12_381();
+        assert!(product_check(
+            "BLS12-381",
+            &params,
+            &product_pairs(&params, 3)
+        ));
+    }
+
+    #[test]
+    fn pairing_product_bls12_381_wrong() {
+        let params = PairingParams::bls12_381();
+        assert!(!product_check(
+            "BLS12-381",
+            &params,
+            &product_pairs(&params, 2)
+        ));
+    }
+
+    #[test]
+    fn pairing_product_bn254() {
+        let params = PairingParams::bn254();
+        assert!(product_check("BN254", &params, &product_pairs(&params, 3)));
+    }
+
+    #[test]
+    fn pairing_product_bn254_wrong() {
+        let params = PairingParams::bn254();
+        assert!(!product_check("BN254", &params, &product_pairs(&params, 2)));
+    }
+
+    /// Checks that a point is in G1, and reports the cost.
+    fn g1_check(params: &PairingParams, point: (BigInt, BigInt)) -> bool {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let p =
+            EmulatedAffinePoint::alloc_from_coords(cs.namespace(|| "p"), || Ok(point), &params.g1)
+                .unwrap();
+        assert_in_g1(cs.namespace(|| "check"), &p, params).unwrap();
+        println!("G1 membership: {} constraints", cs.num_constraints());
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn g1_membership_bls12_381() {
+        let params = PairingParams::bls12_381();
+        let outside = (1..)
+            .find_map(|x| {
+                params
+                    .g1
+                    .lift_x(&BigInt::from(x))
+                    .filter(|p| !params.is_in_g1(&Some(p.clone())))
+            })
+            .unwrap();
+        assert!(params.is_in_g1(&Some(g1(&params, 5))));
+        assert!(g1_check(&params, g1(&params, 5)));
+        assert!(!g1_check(&params, outside));
+    }
+
+    /// Computes a pairing, checks it against the native one, and reports the cost.
+    fn check_pairing(name: &str, params: PairingParams) {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let pair = (g1(&params, 3), g2(&params, 5));
+        let points = alloc_pairs(&mut cs, &params, std::slice::from_ref(&pair));
+        let (p, q) = &points[0];
+        let e = pairing(cs.namespace(|| "pairing"), p, q, &params).unwrap();
+        let expected = EmulatedFp12::constant::<ValueConstraintSystem<Fr>>(
+            &params.pairing(&pair.0, &pair.1),
+            &params.fp12(),
+        )
+        .unwrap();
+        e.assert_equal(cs.namespace(|| "check"), &expected).unwrap();
+        println!("{} pairing: {} constraints", name, cs.num_constraints());
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn pairing_bn254() {
+        check_pairing("BN254", PairingParams::bn254());
+    }
+
+    #[test]
+    fn pairing_bls12_381() {
+        check_pairing("BLS12-381", PairingParams::bls12_381());
+    }
+}
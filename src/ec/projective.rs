@@ -0,0 +1,693 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use super::affine::EmulatedAffinePoint;
+use super::{residue, CurveParams, NativePoint};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::{invert, Term};
+use crate::util::gadget::Gadget;
+use crate::OptionExt;
+
+/// Projective coordinates `(X, Y, Z)` of a point, computed natively.
+pub type NativeProjectivePoint = (BigInt, BigInt, BigInt);
+
+/// Constants larger than this are multiplied in with a product rather than by scaling limbs.
+const MAX_SCALE: u64 = 1 << 16;
+
+impl CurveParams {
+    /// Adds two points in projective coordinates with the complete formulas of Renes, Costello
+    /// and Batina ("Complete addition formulas for prime order elliptic curves", 2015).
+    pub fn projective_add(
+        &self,
+        (x1, y1, z1): &NativeProjectivePoint,
+        (x2, y2, z2): &NativeProjectivePoint,
+    ) -> NativeProjectivePoint {
+        let (a, p) = (&self.a, &self.p);
+        let b3 = &(&self.b * 3u64);
+        let t0 = x1 * x2;
+        let t1 = y1 * y2;
+        let t2 = z1 * z2;
+        let t3 = x1 * y2 + x2 * y1;
+        let t4 = y1 * z2 + y2 * z1;
+        let t5 = x1 * z2 + x2 * z1;
+        let c_a = &t1 - a * &t5 - b3 * &t2;
+        let c_b = &t1 + a * &t5 + b3 * &t2;
+        let c_c = a * &t0 + b3 * &t5 - a * a * &t2;
+        let c_d = &t0 * 3u64 + a * &t2;
+        (
+            (&t3 * &c_a - &t4 * &c_c).mod_floor(p),
+            (&c_b * &c_a + &c_d * &c_c).mod_floor(p),
+            (&t4 * &c_b + &t3 * &c_d).mod_floor(p),
+        )
+    }
+
+    pub fn to_projective(&self, point: &NativePoint) -> NativeProjectivePoint {
+        match point {
+            None => (BigInt::from(0), BigInt::from(1), BigInt::from(0)),
+            Some((x, y)) => (x.clone(), y.clone(), BigInt::from(1)),
+        }
+    }
+
+    pub fn to_affine(&self, (x, y, z): &NativeProjectivePoint) -> NativePoint {
+        if z.mod_floor(&self.p) == BigInt::from(0) {
+            None
+        } else {
+            let z_inv = invert(z, &self.p).unwrap();
+            Some((
+                (x * &z_inv).mod_floor(&self.p),
+                (y * &z_inv).mod_floor(&self.p),
+            ))
+        }
+    }
+}
+
+/// A point, in projective coordinates, of a prime-order short Weierstrass curve over an emulated
+/// prime field.
+///
+/// Addition uses the complete formulas of Renes, Costello and Batina, so it has no exceptional
+/// cases: the identity `(0 : 1 : 0)` and doubling are handled like any other input.
+#[derive(Clone)]
+pub struct EmulatedProjectivePoint<Scalar: PrimeField> {
+    pub x: BigNat<Scalar>,
+    pub y: BigNat<Scalar>,
+    pub z: BigNat<Scalar>,
+    /// The reduced coordinates of the point (filled at witness-time)
+    pub value: Option<NativeProjectivePoint>,
+    /// Parameters
+    pub params: CurveParams,
+}
+
+impl<Scalar: PrimeField> EmulatedProjectivePoint<Scalar> {
+    fn from_coords(
+        x: BigNat<Scalar>,
+        y: BigNat<Scalar>,
+        z: BigNat<Scalar>,
+        params: &CurveParams,
+    ) -> Self {
+        let p = &params.p;
+        let value = x.value.as_ref().and_then(|x| {
+            y.value.as_ref().and_then(|y| {
+                z.value
+                    .as_ref()
+                    .map(|z| (x.mod_floor(p), y.mod_floor(p), z.mod_floor(p)))
+            })
+        });
+        Self {
+            x,
+            y,
+            z,
+            value,
+            params: params.clone(),
+        }
+    }
+
+    /// The identity `(0 : 1 : 0)`, as a constant.
+    pub fn identity<CS: ConstraintSystem<Scalar>>(
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant::<CS>(&params.to_projective(&None), params)
+    }
+
+    /// Creates a constant point.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        (x, y, z): &NativeProjectivePoint,
+        params: &CurveParams,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self::from_coords(
+            params.constant::<Scalar, CS>(x)?,
+            params.constant::<Scalar, CS>(y)?,
+            params.constant::<Scalar, CS>(z)?,
+            params,
+        ))
+    }
+
+    /// Lifts an affine point to projective coordinates `(x : y : 1)`. Costs no constraints.
+    pub fn from_affine<CS: ConstraintSystem<Scalar>>(
+        point: &EmulatedAffinePoint<Scalar>,
+    ) -> Result<Self, SynthesisError> {
+        let params = &point.params;
+        Ok(Self::from_coords(
+            point.x.clone(),
+            point.y.clone(),
+            params.constant::<Scalar, CS>(&BigInt::from(1))?,
+            params,
+        ))
+    }
+
+    /// The affine point represented by `self` (filled at witness-time). `Some(None)` is the point
+    /// at infinity.
+    pub fn affine_value(&self) -> Option<NativePoint> {
+        self.value.as_ref().map(|v| self.params.to_affine(v))
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!("Curves do not agree at {}", location);
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Computes `self + other` with the complete formulas.
+    ///
+    /// The six cross products of the coordinates are witnessed first; each output coordinate is
+    /// then a sum of two products of linear combinations of those, and is checked with a single
+    /// modular sum-of-products congruence.
+    pub fn add<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let a_is_zero = params.a == BigInt::from(0);
+        let a = params.constant::<Scalar, CS>(&params.a)?;
+        let b3 = (&params.b * 3u64).mod_floor(&params.p);
+
+        let (x1, y1, z1) = (&self.x, &self.y, &self.z);
+        let (x2, y2, z2) = (&other.x, &other.y, &other.z);
+        let t0 = residue(&mut cs, "x1x2", &[Term::Product(x1, x2)], &p)?;
+        let t1 = residue(&mut cs, "y1y2", &[Term::Product(y1, y2)], &p)?;
+        let t2 = residue(&mut cs, "z1z2", &[Term::Product(z1, z2)], &p)?;
+        let t3 = residue(
+            &mut cs,
+            "x1y2 + x2y1",
+            &[Term::Product(x1, y2), Term::Product(x2, y1)],
+            &p,
+        )?;
+        let t4 = residue(
+            &mut cs,
+            "y1z2 + y2z1",
+            &[Term::Product(y1, z2), Term::Product(y2, z1)],
+            &p,
+        )?;
+        let t5 = residue(
+            &mut cs,
+            "x1z2 + x2z1",
+            &[Term::Product(x1, z2), Term::Product(x2, z1)],
+            &p,
+        )?;
+
+        // 3b * t2 and 3b * t5, by scaling the limbs when 3b is small
+        let (b3_t2, b3_t5) = match u64::try_from(&b3) {
+            Ok(s) if s < MAX_SCALE => (
+                t2.scale::<CS>(Scalar::from(s)),
+                t5.scale::<CS>(Scalar::from(s)),
+            ),
+            _ => {
+                let b3 = params.constant::<Scalar, CS>(&b3)?;
+                (
+                    residue(&mut cs, "3b z1z2", &[Term::Product(&b3, &t2)], &p)?,
+                    residue(&mut cs, "3b t5", &[Term::Product(&b3, &t5)], &p)?,
+                )
+            }
+        };
+
+        // A = t1 - a t5 - 3b t2, B = t1 + a t5 + 3b t2
+        let (c_a, c_b, c_c, c_d) = if a_is_zero {
+            // A + 3b t2 = t1
+            let c_a = BigNat::alloc_residue(
+                cs.namespace(|| "A"),
+                || Ok(t1.value.grab()? - b3_t2.value.grab()?),
+                &p,
+            )?;
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "A check"),
+                &[Term::Single(&c_a), Term::Single(&b3_t2)],
+                &[Term::Single(&t1)],
+                &p,
+            )?;
+            let c_b = t1.add::<CS>(&b3_t2)?;
+            // C = 3b t5, D = 3 t0
+            (c_a, c_b, b3_t5, t0.scale::<CS>(Scalar::from(3)))
+        } else {
+            let a2 = params.constant::<Scalar, CS>(&(&params.a * &params.a))?;
+            // A + a t5 + 3b t2 = t1
+            let c_a = BigNat::alloc_residue(
+                cs.namespace(|| "A"),
+                || Ok(t1.value.grab()? - &params.a * t5.value.grab()? - b3_t2.value.grab()?),
+                &p,
+            )?;
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "A check"),
+                &[
+                    Term::Single(&c_a),
+                    Term::Product(&a, &t5),
+                    Term::Single(&b3_t2),
+                ],
+                &[Term::Single(&t1)],
+                &p,
+            )?;
+            let c_b = residue(
+                &mut cs,
+                "B",
+                &[
+                    Term::Single(&t1),
+                    Term::Product(&a, &t5),
+                    Term::Single(&b3_t2),
+                ],
+                &p,
+            )?;
+            // C + a^2 t2 = a t0 + 3b t5
+            let c_c = BigNat::alloc_residue(
+                cs.namespace(|| "C"),
+                || {
+                    Ok(&params.a * t0.value.grab()? + b3_t5.value.grab()?
+                        - &params.a * &params.a * t2.value.grab()?)
+                },
+                &p,
+            )?;
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "C check"),
+                &[Term::Single(&c_c), Term::Product(&a2, &t2)],
+                &[Term::Product(&a, &t0), Term::Single(&b3_t5)],
+                &p,
+            )?;
+            let three_t0 = t0.scale::<CS>(Scalar::from(3));
+            let c_d = residue(
+                &mut cs,
+                "D",
+                &[Term::Single(&three_t0), Term::Product(&a, &t2)],
+                &p,
+            )?;
+            (c_a, c_b, c_c, c_d)
+        };
+
+        // X3 = t3 A - t4 C
+        let x3 = BigNat::alloc_residue(
+            cs.namespace(|| "x3"),
+            || Ok(t3.value.grab()? * c_a.value.grab()? - t4.value.grab()? * c_c.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x3 check"),
+            &[Term::Single(&x3), Term::Product(&t4, &c_c)],
+            &[Term::Product(&t3, &c_a)],
+            &p,
+        )?;
+        // Y3 = B A + D C
+        let y3 = residue(
+            &mut cs,
+            "y3",
+            &[Term::Product(&c_b, &c_a), Term::Product(&c_d, &c_c)],
+            &p,
+        )?;
+        // Z3 = t4 B + t3 D
+        let z3 = residue(
+            &mut cs,
+            "z3",
+            &[Term::Product(&t4, &c_b), Term::Product(&t3, &c_d)],
+            &p,
+        )?;
+        Ok(Self::from_coords(x3, y3, z3, params))
+    }
+
+    /// Computes `2 * self` with the complete formulas.
+    ///
+    /// When `a = -3`, as on the NIST curves, the formulas are specialised to doubling: the cross
+    /// products become the squares and products of the coordinates of `self`, and the products
+    /// by `a` become scalings of limbs.
+    pub fn double<CS: ConstraintSystem<Scalar>>(&self, cs: CS) -> Result<Self, SynthesisError> {
+        if self.params.a_is_minus_three() {
+            self.double_a_minus_3(cs)
+        } else {
+            self.add(cs, self)
+        }
+    }
+
+    /// Doubling for `a = -3`. With `t0 = X^2`, `t1 = Y^2`, `t2 = Z^2`, `u = XY`, `v = YZ` and
+    /// `w = XZ`, the addition formulas give
+    /// `A = t1 + 6w - 3b t2`, `B = 2 t1 - A`, `C = 6b w - 3 t0 - 9 t2`, `D = 3 t0 - 3 t2` and
+    /// `(X3, Y3, Z3) = (2u A - 2v C, B A + D C, 2v B + 2u D)`.
+    fn double_a_minus_3<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let params = &self.params;
+        let p = params.modulus::<Scalar, CS>()?;
+        let b3_value = &(&params.b * 3u64).mod_floor(&params.p);
+        let b3 = params.constant::<Scalar, CS>(b3_value)?;
+
+        let (x, y, z) = (&self.x, &self.y, &self.z);
+        let t0 = residue(&mut cs, "xx", &[Term::Product(x, x)], &p)?;
+        let t1 = residue(&mut cs, "yy", &[Term::Product(y, y)], &p)?;
+        let t2 = residue(&mut cs, "zz", &[Term::Product(z, z)], &p)?;
+        let u = residue(&mut cs, "xy", &[Term::Product(x, y)], &p)?;
+        let v = residue(&mut cs, "yz", &[Term::Product(y, z)], &p)?;
+        let w = residue(&mut cs, "xz", &[Term::Product(x, z)], &p)?;
+        let scale = |n: &BigNat<Scalar>, k: u64| n.scale::<CS>(Scalar::from(k));
+
+        // A + 3b t2 = t1 + 6w
+        let c_a = BigNat::alloc_residue(
+            cs.namespace(|| "A"),
+            || Ok(t1.value.grab()? + 6 * w.value.grab()? - b3_value * t2.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "A check"),
+            &[Term::Single(&c_a), Term::Product(&b3, &t2)],
+            &[Term::Single(&t1), Term::Single(&scale(&w, 6))],
+            &p,
+        )?;
+        // B + A = 2 t1
+        let c_b = BigNat::alloc_residue(
+            cs.namespace(|| "B"),
+            || Ok(2 * t1.value.grab()? - c_a.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "B check"),
+            &[Term::Single(&c_b), Term::Single(&c_a)],
+            &[Term::Single(&scale(&t1, 2))],
+            &p,
+        )?;
+        // C + 3 t0 + 9 t2 = 3b 2w
+        let c_c = BigNat::alloc_residue(
+            cs.namespace(|| "C"),
+            || Ok(2 * b3_value * w.value.grab()? - 3 * t0.value.grab()? - 9 * t2.value.grab()?),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "C check"),
+            &[
+                Term::Single(&c_c),
+                Term::Single(&scale(&t0, 3)),
+                Term::Single(&scale(&t2, 9)),
+            ],
+            &[Term::Product(&b3, &scale(&w, 2))],
+            &p,
+        )?;
+        // D + 3 t2 = 3 t0
+        let c_d = BigNat::alloc_residue(
+            cs.namespace(|| "D"),
+            || Ok(3 * (t0.value.grab()? - t2.value.grab()?)),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "D check"),
+            &[Term::Single(&c_d), Term::Single(&scale(&t2, 3))],
+            &[Term::Single(&scale(&t0, 3))],
+            &p,
+        )?;
+
+        let (u2, v2) = (scale(&u, 2), scale(&v, 2));
+        // X3 = 2u A - 2v C
+        let x3 = BigNat::alloc_residue(
+            cs.namespace(|| "x3"),
+            || Ok(2 * (u.value.grab()? * c_a.value.grab()? - v.value.grab()? * c_c.value.grab()?)),
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x3 check"),
+            &[Term::Single(&x3), Term::Product(&v2, &c_c)],
+            &[Term::Product(&u2, &c_a)],
+            &p,
+        )?;
+        // Y3 = B A + D C
+        let y3 = residue(
+            &mut cs,
+            "y3",
+            &[Term::Product(&c_b, &c_a), Term::Product(&c_d, &c_c)],
+            &p,
+        )?;
+        // Z3 = 2v B + 2u D
+        let z3 = residue(
+            &mut cs,
+            "z3",
+            &[Term::Product(&v2, &c_b), Term::Product(&u2, &c_d)],
+            &p,
+        )?;
+        Ok(Self::from_coords(x3, y3, z3, params))
+    }
+
+    /// Computes `-self`.
+    pub fn negate<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let y = self.y.neg_mod(cs.namespace(|| "y"), &p)?;
+        Ok(Self::from_coords(
+            self.x.clone(),
+            y,
+            self.z.clone(),
+            &self.params,
+        ))
+    }
+
+    /// Computes `k * self` for a constant `k`, which may be negative, by double-and-add.
+    pub fn mul_by_constant<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        k: &BigInt,
+    ) -> Result<Self, SynthesisError> {
+        let bits = k.bits();
+        if bits == 0 {
+            return Self::identity::<CS>(&self.params);
+        }
+        let mut acc = self.clone();
+        for i in (0..(bits - 1)).rev() {
+            acc = acc.double(cs.namespace(|| format!("double {}", i)))?;
+            if k.magnitude().bit(i) {
+                acc = acc.add(cs.namespace(|| format!("add {}", i)), self)?;
+            }
+        }
+        if k.sign() == Sign::Minus {
+            acc = acc.negate(cs.namespace(|| "negate"))?;
+        }
+        Ok(acc)
+    }
+
+    /// Computes the affine coordinates `(X/Z, Y/Z)`. This also proves that `self` is not the
+    /// identity.
+    pub fn to_affine<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<EmulatedAffinePoint<Scalar>, SynthesisError> {
+        let p = self.params.modulus::<Scalar, CS>()?;
+        let z_inv = self.z.inverse_mod(cs.namespace(|| "z inverse"), &p)?;
+        let x = residue(&mut cs, "x", &[Term::Product(&self.x, &z_inv)], &p)?;
+        let y = residue(&mut cs, "y", &[Term::Product(&self.y, &z_inv)], &p)?;
+        Ok(EmulatedAffinePoint::from_coords(x, y, &self.params))
+    }
+
+    /// Constrain `self` and `other` to represent the same point, i.e. `X1 Z2 = X2 Z1` and
+    /// `Y1 Z2 = Y2 Z1`.
+    pub fn assert_equivalent<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_params_agreement(other, "assert_equivalent")?;
+        let p = self.params.modulus::<Scalar, CS>()?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "x"),
+            &[Term::Product(&self.x, &other.z)],
+            &[Term::Product(&other.x, &self.z)],
+            &p,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "y"),
+            &[Term::Product(&self.y, &other.z)],
+            &[Term::Product(&other.y, &self.z)],
+            &p,
+        )
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for EmulatedProjectivePoint<Scalar> {
+    type Scalar = Scalar;
+    type Value = NativeProjectivePoint;
+    type Params = CurveParams;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        let x = BigNat::alloc_from_nat(
+            cs.namespace(|| "x"),
+            || Ok(value.grab()?.0.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        x.assert_well_formed(cs.namespace(|| "x rangecheck"))?;
+        let y = BigNat::alloc_from_nat(
+            cs.namespace(|| "y"),
+            || Ok(value.grab()?.1.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        y.assert_well_formed(cs.namespace(|| "y rangecheck"))?;
+        let z = BigNat::alloc_from_nat(
+            cs.namespace(|| "z"),
+            || Ok(value.grab()?.2.clone()),
+            params.limb_width,
+            params.n_limbs,
+        )?;
+        z.assert_well_formed(cs.namespace(|| "z rangecheck"))?;
+        Ok(Self::from_coords(x, y, z, params))
+    }
+    fn value(&self) -> Option<&NativeProjectivePoint> {
+        self.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        let mut values = self.x.limb_values.clone()?;
+        values.extend(self.y.limb_values.as_ref()?.iter().cloned());
+        values.extend(self.z.limb_values.as_ref()?.iter().cloned());
+        Some(values)
+    }
+    fn params(&self) -> &CurveParams {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        let mut wires = self.x.limbs.clone();
+        wires.extend(self.y.limbs.iter().cloned());
+        wires.extend(self.z.limbs.iter().cloned());
+        wires
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+}
+
+impl<Scalar: PrimeField> Display for EmulatedProjectivePoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.value.as_ref() {
+            Some((x, y, z)) => write!(f, "EmulatedProjectivePoint({} : {} : {})", x, y, z),
+            None => write!(f, "EmulatedProjectivePoint(empty)"),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> Debug for EmulatedProjectivePoint<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EmulatedProjectivePoint")
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("z", &self.z)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    pub struct ProjectiveOpsInputs {
+        pub p: NativePoint,
+        pub q: NativePoint,
+        /// Whether the sum is claimed to be `p + q` or `p + q + G`
+        pub honest: bool,
+    }
+
+    pub struct ProjectiveOps {
+        inputs: Option<ProjectiveOpsInputs>,
+        params: CurveParams,
+    }
+
+    fn assert_coords<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        point: &EmulatedProjectivePoint<Scalar>,
+        expected: &NativeProjectivePoint,
+    ) -> Result<(), SynthesisError> {
+        let expected = EmulatedProjectivePoint::constant::<CS>(expected, &point.params)?;
+        point.x.equal(cs.namespace(|| "x"), &expected.x)?;
+        point.y.equal(cs.namespace(|| "y"), &expected.y)?;
+        point.z.equal(cs.namespace(|| "z"), &expected.z)
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for ProjectiveOps {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let (np, nq) = (
+                params.to_projective(&inputs.p),
+                params.to_projective(&inputs.q),
+            );
+            let p = EmulatedProjectivePoint::alloc(cs.namespace(|| "p"), Some(&np), (), params)?;
+            let q = EmulatedProjectivePoint::alloc(cs.namespace(|| "q"), Some(&nq), (), params)?;
+
+            let sum = p.add(cs.namespace(|| "p + q"), &q)?;
+            assert_coords(
+                cs.namespace(|| "sum"),
+                &sum,
+                &params.projective_add(&np, &nq),
+            )?;
+
+            let double = p.double(cs.namespace(|| "2p"))?;
+            assert_coords(
+                cs.namespace(|| "double"),
+                &double,
+                &params.projective_add(&np, &np),
+            )?;
+
+            let neg = q.negate(cs.namespace(|| "-q"))?;
+            let zero = neg.add(cs.namespace(|| "q - q"), &q)?;
+            let identity = EmulatedProjectivePoint::identity::<CS>(params)?;
+            zero.assert_equivalent(cs.namespace(|| "q - q = O"), &identity)?;
+
+            let mut expected = params.add(&inputs.p, &inputs.q);
+            if !inputs.honest {
+                expected = params.add(&expected, &Some(params.generator.clone()));
+            }
+            if let Some((x, y)) = expected {
+                let affine = sum.to_affine(cs.namespace(|| "affine"))?;
+                let expected = EmulatedAffinePoint::constant::<CS>(&x, &y, params)?;
+                affine.x.equal(cs.namespace(|| "x"), &expected.x)?;
+                affine.y.equal(cs.namespace(|| "y"), &expected.y)?;
+            } else {
+                sum.assert_equivalent(cs.namespace(|| "p + q = O"), &identity)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn projective_ops(params: CurveParams, p: i64, q: i64, honest: bool) -> ProjectiveOps {
+        let g = Some(params.generator.clone());
+        let p = params.mul(&BigInt::from(p), &g);
+        let q = params.mul(&BigInt::from(q), &g);
+        ProjectiveOps {
+            inputs: Some(ProjectiveOpsInputs { p, q, honest }),
+            params,
+        }
+    }
+
+    #[test]
+    fn projective_add_matches_affine() {
+        for params in [CurveParams::secp256k1(), CurveParams::p256()] {
+            let g = Some(params.generator.clone());
+            let points: Vec<NativePoint> = [0, 1, 2, 3, -3, 1234567]
+                .iter()
+                .map(|k| params.mul(&BigInt::from(*k), &g))
+                .collect();
+            for p in &points {
+                for q in &points {
+                    let sum =
+                        params.projective_add(&params.to_projective(p), &params.to_projective(q));
+                    assert_eq!(params.to_affine(&sum), params.add(p, q));
+                }
+            }
+        }
+    }
+
+    circuit_tests! {
+        projective_secp256k1_ops: (projective_ops(CurveParams::secp256k1(), 1, 2, true), true),
+        projective_secp256k1_equal: (projective_ops(CurveParams::secp256k1(), 3, 3, true), true),
+        projective_secp256k1_inverse: (projective_ops(CurveParams::secp256k1(), 5, -5, true), true),
+        projective_secp256k1_identity: (projective_ops(CurveParams::secp256k1(), 0, 7, true), true),
+        projective_p256_ops: (projective_ops(CurveParams::p256(), 7, 3, true), true),
+        projective_p256_identity: (projective_ops(CurveParams::p256(), 4, 0, true), true),
+        projective_p256_double_identity: (projective_ops(CurveParams::p256(), 0, 5, true), true),
+        projective_secp256k1_wrong: (projective_ops(CurveParams::secp256k1(), 1, 2, false), false),
+    }
+}
@@ -0,0 +1,240 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+use super::affine::EmulatedAffinePoint;
+use super::projective::EmulatedProjectivePoint;
+use crate::util::bit::{Bit, Bitvector};
+use crate::util::gadget::Gadget;
+
+/// The seed of the offset point used by variable-base scalar multiplication.
+const VARIABLE_BASE_OFFSET_SEED: u64 = 0;
+
+/// Splits the (little-endian) bits of `scalar` into windows of `window` bits, returning the
+/// windows from the most significant down, each with its most significant bit first, as expected
+/// by `Gadget::mux_tree`. Only the most significant window may be shorter.
+pub(crate) fn windows<Scalar: PrimeField>(
+    scalar: &Bitvector<Scalar>,
+    window: usize,
+) -> Result<Vec<Vec<Bit<Scalar>>>, SynthesisError> {
+    if window == 0 || scalar.bits.is_empty() {
+        eprintln!(
+            "Cannot split {} bits into windows of {}",
+            scalar.bits.len(),
+            window
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let bits: Vec<Bit<Scalar>> = (0..scalar.bits.len())
+        .map(|i| scalar.get(i).unwrap())
+        .collect();
+    Ok(bits
+        .chunks(window)
+        .rev()
+        .map(|chunk| chunk.iter().rev().cloned().collect())
+        .collect())
+}
+
+/// The sum `sum_i 2^(window * i)` over the windows `i` of an `n_bits`-bit scalar.
+pub(crate) fn window_offset_multiple(n_bits: usize, window: usize) -> BigInt {
+    let n_windows = n_bits.div_ceil(window);
+    (0..n_windows).fold(BigInt::from(0), |acc, i| {
+        acc + (BigInt::from(1) << (window * i) as u32)
+    })
+}
+
+impl<Scalar: PrimeField> EmulatedAffinePoint<Scalar> {
+    /// The table of the points `d * self + R`, for each digit `d < 2^window`, where `R` is the
+    /// offset point with seed `seed`.
+    pub(crate) fn window_table<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        window: usize,
+        seed: u64,
+    ) -> Result<Vec<Self>, SynthesisError> {
+        let (rx, ry) = self.params.offset_point(seed);
+        let mut table = vec![EmulatedAffinePoint::constant::<CS>(&rx, &ry, &self.params)?];
+        for d in 1..(1usize << window) {
+            let entry = table[d - 1].add(cs.namespace(|| format!("{}", d)), self)?;
+            table.push(entry);
+        }
+        Ok(table)
+    }
+
+    /// Computes `scalar * self`, where `scalar` is given by its (little-endian) bits, with fixed
+    /// windows of `window` bits.
+    ///
+    /// The multiples `d * self + R`, for each digit `d < 2^window` and a fixed offset point `R`
+    /// of unknown discrete logarithm, are tabulated; each window then costs `window` doublings of
+    /// the accumulator, a `mux_tree` lookup in the table, and one addition. Offsetting every
+    /// table entry by `R` keeps the identity out of the accumulator, so the cheap incomplete
+    /// formulas can be used: they constrain their inputs to be non-exceptional, so a dishonest
+    /// prover cannot exploit them, and an honest prover only hits an exceptional case if it knows
+    /// the discrete logarithm of `R`. The accumulated multiple of `R` is removed at the end with
+    /// the complete projective formulas, so the result may be the identity (e.g. if the scalar
+    /// is zero).
+    ///
+    /// `self` must not be the identity, and must have the curve's prime order.
+    pub fn scalar_mul<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        scalar: &Bitvector<Scalar>,
+        window: usize,
+    ) -> Result<EmulatedProjectivePoint<Scalar>, SynthesisError> {
+        let params = &self.params;
+        let windows = windows(scalar, window)?;
+        let table =
+            self.window_table(cs.namespace(|| "table"), window, VARIABLE_BASE_OFFSET_SEED)?;
+
+        let mut acc: Option<Self> = None;
+        for (i, bits) in windows.iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("window {}", i));
+            let entry = Gadget::mux_tree(
+                cs.namespace(|| "lookup"),
+                bits.iter(),
+                &table[..(1 << bits.len())],
+            )?;
+            acc = Some(match acc {
+                None => entry,
+                Some(mut acc) => {
+                    for j in 0..window {
+                        acc = acc.double(cs.namespace(|| format!("double {}", j)))?;
+                    }
+                    acc.add(cs.namespace(|| "add"), &entry)?
+                }
+            });
+        }
+
+        // acc = scalar * self + c * R
+        let c = window_offset_multiple(scalar.bits.len(), window);
+        let offset = params.offset_point(VARIABLE_BASE_OFFSET_SEED);
+        let correction = params.negate(&params.mul_unreduced(&c, &Some(offset)));
+        let correction =
+            EmulatedProjectivePoint::constant::<CS>(&params.to_projective(&correction), params)?;
+        let acc = EmulatedProjectivePoint::from_affine::<CS>(&acc.unwrap())?;
+        acc.add(cs.namespace(|| "remove offset"), &correction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::{CurveParams, NativePoint};
+    use crate::mp::bignat::BigNat;
+    use crate::util::test_helpers::*;
+    use crate::OptionExt;
+
+    pub struct ScalarMulInputs {
+        pub k: BigInt,
+        pub p: (BigInt, BigInt),
+        pub expected: NativePoint,
+    }
+
+    pub struct ScalarMul {
+        inputs: Option<ScalarMulInputs>,
+        params: CurveParams,
+        n_bits: usize,
+        window: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for ScalarMul {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let params = &self.params;
+            let inputs = self.inputs.grab()?;
+            let p = EmulatedAffinePoint::alloc_from_coords(
+                cs.namespace(|| "p"),
+                || Ok(inputs.p.clone()),
+                params,
+            )?;
+            p.assert_on_curve(cs.namespace(|| "p on curve"))?;
+            let k = BigNat::alloc_from_nat(
+                cs.namespace(|| "k"),
+                || Ok(inputs.k.clone()),
+                params.limb_width,
+                params.n_limbs,
+            )?;
+            let bits = k
+                .decompose(cs.namespace(|| "decompose"))?
+                .truncate(self.n_bits);
+            let product = p.scalar_mul(cs.namespace(|| "k * p"), &bits, self.window)?;
+            let expected = EmulatedProjectivePoint::constant::<CS>(
+                &params.to_projective(&inputs.expected),
+                params,
+            )?;
+            product.assert_equivalent(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    fn scalar_mul(
+        params: CurveParams,
+        k: u64,
+        p: u64,
+        n_bits: usize,
+        window: usize,
+        honest: bool,
+    ) -> ScalarMul {
+        let g = Some(params.generator.clone());
+        let k = BigInt::from(k);
+        let p = params.mul(&BigInt::from(p), &g);
+        let mut expected = params.mul(&k, &p);
+        if !honest {
+            expected = params.add(&expected, &g);
+        }
+        ScalarMul {
+            inputs: Some(ScalarMulInputs {
+                k,
+                p: p.unwrap(),
+                expected,
+            }),
+            params,
+            n_bits,
+            window,
+        }
+    }
+
+    circuit_tests! {
+        scalar_mul_secp256k1: (scalar_mul(CurveParams::secp256k1(), 0xb3c5, 3, 16, 4, true), true),
+        scalar_mul_secp256k1_short_window: (scalar_mul(CurveParams::secp256k1(), 0x1d, 5, 5, 3, true), true),
+        scalar_mul_secp256k1_zero: (scalar_mul(CurveParams::secp256k1(), 0, 7, 6, 2, true), true),
+        scalar_mul_p256: (scalar_mul(CurveParams::p256(), 0x9e, 11, 8, 3, true), true),
+        scalar_mul_secp256k1_wrong: (scalar_mul(CurveParams::secp256k1(), 0x2b, 3, 6, 2, false), false),
+    }
+
+    /// A scalar multiplication of the generator by a full-width scalar.
+    fn full_width(params: CurveParams, window: usize) -> ScalarMul {
+        let k = full_width_scalar(&params.order);
+        ScalarMul {
+            inputs: Some(ScalarMulInputs {
+                expected: params.mul(&k, &Some(params.generator.clone())),
+                p: params.generator.clone(),
+                k,
+            }),
+            n_bits: 256,
+            window,
+            params,
+        }
+    }
+
+    #[test]
+    fn scalar_mul_curve25519_full_width() {
+        // The offset point is not in the subgroup of prime order, and the multiple of it removed
+        // from the accumulator exceeds the order
+        assert!(is_satisfied(full_width(CurveParams::curve25519(), 4)));
+    }
+
+    #[test]
+    fn scalar_mul_secp256k1_cost() {
+        report_full_width(
+            "secp256k1 window 4",
+            full_width(CurveParams::secp256k1(), 4),
+        );
+    }
+
+    #[test]
+    fn scalar_mul_p256_cost() {
+        report_full_width("P-256 window 4", full_width(CurveParams::p256(), 4));
+    }
+}
@@ -0,0 +1,359 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use super::affine::EmulatedAffinePoint;
+use super::compress::{assert_parity, be_bytes_to_bits};
+use super::msm::{msm, Base};
+use super::{CurveParams, NativePoint};
+use crate::hash::sha256::sha256;
+use crate::hash::{constant_bits, to_bitvector};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::util::bit::Bit;
+
+/// The window of the multi-scalar multiplication `s G - e P`.
+const WINDOW: usize = 4;
+
+/// The lengths of an x-only public key and of a signature, in bytes.
+const POINT_BYTES: usize = 32;
+const SIGNATURE_BYTES: usize = 64;
+
+/// The tags of the hashes of BIP-340.
+const AUX_TAG: &[u8] = b"BIP0340/aux";
+const NONCE_TAG: &[u8] = b"BIP0340/nonce";
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+/// The tagged hash `SHA-256(SHA-256(tag) || SHA-256(tag) || message)` of BIP-340, computed
+/// natively.
+pub fn native_tagged_hash(tag: &[u8], message: &[u8]) -> [u8; 32] {
+    let tag = sha256(tag);
+    sha256(&[&tag[..], &tag, message].concat())
+}
+
+/// The tagged hash `SHA-256(SHA-256(tag) || SHA-256(tag) || message)` of BIP-340, of the message
+/// `message`, given as the bits of its bytes, each byte's most significant bit first. The digest
+/// is given in the same way.
+pub fn tagged_hash<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    cs: CS,
+    tag: &[u8],
+    message: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let tag = sha256(tag);
+    let mut input = constant_bits(&[tag, tag].concat());
+    input.extend_from_slice(message);
+    sha256_circuit(cs, &input)
+}
+
+/// The 32 big-endian bytes of `v`.
+fn bytes32(v: &BigInt) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    let v = v.to_bytes_be().1;
+    bytes[32 - v.len()..].copy_from_slice(&v);
+    bytes
+}
+
+impl CurveParams {
+    /// The point with x-coordinate `x` and an even y-coordinate, if there is one.
+    fn lift_x_even(&self, x: &BigInt) -> NativePoint {
+        self.lift_x(x).map(|(x, y)| {
+            let y = if y.is_odd() { &self.p - y } else { y };
+            (x, y)
+        })
+    }
+
+    /// The x-only public key of the secret key `secret` for BIP-340, computed natively. Returns
+    /// `None` if the secret key is out of range.
+    pub fn bip340_public_key(&self, secret: &BigInt) -> Option<[u8; 32]> {
+        if *secret <= BigInt::from(0) || *secret >= self.order {
+            return None;
+        }
+        let (x, _) = self.mul(secret, &Some(self.generator.clone()))?;
+        Some(bytes32(&x))
+    }
+
+    /// Signs `message` with the secret key `secret` and the auxiliary randomness `aux` as in
+    /// BIP-340, natively. Returns `None` if the secret key is out of range, or if the nonce is
+    /// zero.
+    pub fn bip340_sign(&self, secret: &BigInt, message: &[u8], aux: &[u8; 32]) -> Option<[u8; 64]> {
+        let n = &self.order;
+        if *secret <= BigInt::from(0) || secret >= n {
+            return None;
+        }
+        let (px, py) = self.mul(secret, &Some(self.generator.clone()))?;
+        let d = if py.is_odd() {
+            n - secret
+        } else {
+            secret.clone()
+        };
+        let t: Vec<u8> = bytes32(&d)
+            .iter()
+            .zip(native_tagged_hash(AUX_TAG, aux))
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let rand = native_tagged_hash(NONCE_TAG, &[&t[..], &bytes32(&px), message].concat());
+        let k = BigInt::from_bytes_be(Sign::Plus, &rand).mod_floor(n);
+        if k == BigInt::from(0) {
+            return None;
+        }
+        let (rx, ry) = self.mul(&k, &Some(self.generator.clone()))?;
+        let k = if ry.is_odd() { n - k } else { k };
+        let e = native_tagged_hash(
+            CHALLENGE_TAG,
+            &[&bytes32(&rx)[..], &bytes32(&px), message].concat(),
+        );
+        let e = BigInt::from_bytes_be(Sign::Plus, &e).mod_floor(n);
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&bytes32(&rx));
+        signature[32..].copy_from_slice(&bytes32(&(k + e * d).mod_floor(n)));
+        Some(signature)
+    }
+
+    /// Verifies the BIP-340 signature `signature` of `message` under the x-only public key
+    /// `public_key`, natively.
+    pub fn bip340_verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        if public_key.len() != POINT_BYTES || signature.len() != SIGNATURE_BYTES {
+            return false;
+        }
+        let px = BigInt::from_bytes_be(Sign::Plus, public_key);
+        let r = BigInt::from_bytes_be(Sign::Plus, &signature[..32]);
+        let s = BigInt::from_bytes_be(Sign::Plus, &signature[32..]);
+        if px >= self.p || r >= self.p || s >= self.order {
+            return false;
+        }
+        let point = self.lift_x_even(&px);
+        if point.is_none() {
+            return false;
+        }
+        let e = native_tagged_hash(
+            CHALLENGE_TAG,
+            &[&signature[..32], public_key, message].concat(),
+        );
+        let e = BigInt::from_bytes_be(Sign::Plus, &e).mod_floor(&self.order);
+        let sg = self.mul(&s, &Some(self.generator.clone()));
+        match self.add(&sg, &self.negate(&self.mul(&e, &point))) {
+            None => false,
+            Some((x, y)) => y.is_even() && x == r,
+        }
+    }
+}
+
+/// Constrain `signature` to be a valid BIP-340 Schnorr signature over secp256k1 of `message`
+/// under the x-only public key `public_key`. All are given as the bits of their bytes, each
+/// byte's most significant bit first.
+///
+/// The public key is lifted to `-P`, the point with an odd y-coordinate, which rejects
+/// x-coordinates which are not canonical or not on the curve, and `r < p` and `s < n` are
+/// enforced. The challenge `e` is the tagged hash of `r || x(P) || m`, computed in the circuit;
+/// it need not be reduced, as `P` has order `n`. Then `R = s G + e (-P)` is computed with a
+/// multi-scalar multiplication, and constrained not to be the identity, with an even
+/// y-coordinate and `x(R) = r`.
+pub fn verify_bip340<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    public_key: &[Boolean],
+    message: &[Boolean],
+    signature: &[Boolean],
+) -> Result<(), SynthesisError> {
+    if public_key.len() != 8 * POINT_BYTES || signature.len() != 8 * SIGNATURE_BYTES {
+        eprintln!(
+            "A BIP-340 public key has {} bits and a signature {}, not {} and {}",
+            public_key.len(),
+            signature.len(),
+            8 * POINT_BYTES,
+            8 * SIGNATURE_BYTES
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    if !message.len().is_multiple_of(8) {
+        eprintln!("The message has {} bits, not whole bytes", message.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let params = CurveParams::secp256k1();
+    let p = params.modulus::<Scalar, CS>()?;
+    let n = params.order_modulus::<Scalar, CS>()?;
+    let (r_bytes, s_bytes) = signature.split_at(8 * POINT_BYTES);
+    let minus_p = EmulatedAffinePoint::decompress(
+        cs.namespace(|| "-P"),
+        &be_bytes_to_bits(&to_bitvector::<Scalar, CS>(public_key)),
+        &Bit::new_true::<CS>(),
+        &params,
+    )?;
+
+    // r < p and s < n
+    let r = BigNat::recompose(
+        &be_bytes_to_bits(&to_bitvector::<Scalar, CS>(r_bytes)),
+        params.limb_width,
+    );
+    r.assert_canonical(cs.namespace(|| "r < p"), &p)?;
+    let s_bits = be_bytes_to_bits(&to_bitvector::<Scalar, CS>(s_bytes));
+    let s = BigNat::recompose(&s_bits, params.limb_width);
+    s.assert_canonical(cs.namespace(|| "s < n"), &n)?;
+
+    let digest = tagged_hash(
+        cs.namespace(|| "challenge"),
+        CHALLENGE_TAG,
+        &[r_bytes, public_key, message].concat(),
+    )?;
+    let e_bits = be_bytes_to_bits(&to_bitvector::<Scalar, CS>(&digest));
+    let point = msm(
+        cs.namespace(|| "s G - e P"),
+        &[
+            (Base::Fixed(&params.generator), &s_bits),
+            (Base::Variable(&minus_p), &e_bits),
+        ],
+        WINDOW,
+        &params,
+    )?;
+
+    // y(R) even and x(R) == r, with canonical coordinates
+    let point = point.to_affine(cs.namespace(|| "affine"))?;
+    point
+        .x
+        .assert_canonical(cs.namespace(|| "x canonical"), &p)?;
+    point
+        .y
+        .assert_canonical(cs.namespace(|| "y canonical"), &p)?;
+    assert_parity(cs.namespace(|| "y even"), &point.y, &Bit::new_false::<CS>())?;
+    BigNat::assert_sums_congruent(
+        cs.namespace(|| "x == r"),
+        &[Term::Single(&point.x)],
+        &[Term::Single(&r)],
+        &p,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::alloc_bits;
+    use crate::util::test_helpers::*;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// BIP-340 test vectors 0 and 1: the secret key, public key, auxiliary randomness, message
+    /// and signature.
+    const VECTORS: [[&str; 5]; 2] = [
+        [
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+        ],
+        [
+            "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+            "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+        ],
+    ];
+
+    #[test]
+    fn bip340_vectors() {
+        let params = CurveParams::secp256k1();
+        for [secret, public_key, aux, message, signature] in VECTORS {
+            let secret = BigInt::from_bytes_be(Sign::Plus, &from_hex(secret));
+            let aux: [u8; 32] = from_hex(aux).try_into().unwrap();
+            let message = from_hex(message);
+            let public_key = from_hex(public_key);
+            let signature = from_hex(signature);
+            assert_eq!(
+                params.bip340_public_key(&secret).unwrap().to_vec(),
+                public_key
+            );
+            let signed = params.bip340_sign(&secret, &message, &aux).unwrap();
+            assert_eq!(signed.to_vec(), signature);
+            assert!(params.bip340_verify(&public_key, &message, &signature));
+            assert!(!params.bip340_verify(&public_key, b"x", &signature));
+        }
+    }
+
+    pub struct Bip340 {
+        public_key: Vec<u8>,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Bip340 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let mut alloc = |name: &str, bytes: &[u8]| {
+                alloc_bits(cs.namespace(|| name.to_string()), Some(bytes), bytes.len())
+            };
+            let public_key = alloc("public key", &self.public_key)?;
+            let message = alloc("message", &self.message)?;
+            let signature = alloc("signature", &self.signature)?;
+            verify_bip340(cs.namespace(|| "verify"), &public_key, &message, &signature)
+        }
+    }
+
+    /// Test vector 1, with the message checked by the circuit replaced by `message` if given.
+    fn bip340(message: Option<&[u8]>) -> Bip340 {
+        let [_, public_key, _, signed, signature] = VECTORS[1];
+        Bip340 {
+            public_key: from_hex(public_key),
+            message: message.map_or(from_hex(signed), |m| m.to_vec()),
+            signature: from_hex(signature),
+        }
+    }
+
+    /// A signature which satisfies `x(R) = r` with `R = s G - e P`, but where `R` has an odd
+    /// y-coordinate: the nonce is not negated.
+    fn odd_nonce() -> Bip340 {
+        let params = CurveParams::secp256k1();
+        let n = &params.order;
+        let secret = BigInt::from(0x5ec7e7u64) << 200u32;
+        let public_key = params.bip340_public_key(&secret).unwrap();
+        let (_, py) = params
+            .mul(&secret, &Some(params.generator.clone()))
+            .unwrap();
+        let d = if py.is_odd() { n - &secret } else { secret };
+        let message = b"odd nonce".to_vec();
+        let mut k = BigInt::from(0x4e0ce5u64) << 220u32;
+        let (rx, _) = loop {
+            let (rx, ry) = params.mul(&k, &Some(params.generator.clone())).unwrap();
+            if ry.is_odd() {
+                break (rx, ry);
+            }
+            k += 1;
+        };
+        let e = native_tagged_hash(
+            CHALLENGE_TAG,
+            &[&bytes32(&rx)[..], &public_key, &message].concat(),
+        );
+        let e = BigInt::from_bytes_be(Sign::Plus, &e).mod_floor(n);
+        let signature = [bytes32(&rx), bytes32(&(k + e * d).mod_floor(n))].concat();
+        assert!(!params.bip340_verify(&public_key, &message, &signature));
+        Bip340 {
+            public_key: public_key.to_vec(),
+            message,
+            signature,
+        }
+    }
+
+    #[test]
+    fn bip340_secp256k1() {
+        assert!(is_satisfied(bip340(None)));
+    }
+
+    #[test]
+    fn bip340_secp256k1_wrong_message() {
+        assert!(!is_satisfied(bip340(Some(b"hello"))));
+    }
+
+    #[test]
+    fn bip340_secp256k1_odd_nonce() {
+        assert!(!is_satisfied(odd_nonce()));
+    }
+}
@@ -0,0 +1,438 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::One;
+
+use super::sha256::sha256;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::util::bit::{Bit, Bitvector};
+use crate::OptionExt;
+
+/// The prime `2^32 - 5` at the root of the certificate chains.
+const BASE_PRIME: u64 = 0xfffffffb;
+const BASE_PRIME_BITS: usize = 32;
+
+/// The number of bits of the nonce of each link, searched by the prover for the link to be prime.
+const NONCE_BITS: usize = 16;
+
+/// The largest base searched for a Pocklington certificate.
+const MAX_BASE: u64 = 1 << 8;
+
+/// The bases of the Miller-Rabin test used to search for the nonces.
+const MILLER_RABIN_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// The shape of a link of a certificate chain, from a prime `p` to the prime `p * r + 1`, for
+/// `r = 2 * (1 || hash || nonce)` with the next `hash_bits` bits of the hash. The next prime is
+/// in `[2^low, 2^high)`.
+struct Link {
+    hash_bits: usize,
+    low: usize,
+    high: usize,
+}
+
+/// The links of the chain consuming `entropy` bits of the hash. The multiplier `r` of each link
+/// is smaller than its prime `p`, as required by Pocklington's criterion.
+fn chain(entropy: usize) -> Result<Vec<Link>, SynthesisError> {
+    if entropy == 0 || entropy > 256 {
+        eprintln!(
+            "Cannot hash to a prime with {} bits of a SHA-256 digest",
+            entropy
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let (mut low, mut high) = (BASE_PRIME_BITS - 1, BASE_PRIME_BITS);
+    let mut remaining = entropy;
+    let mut links = Vec::new();
+    while remaining > 0 {
+        // r < 2^len <= 2^low <= p
+        let hash_bits = std::cmp::min(low - 2 - NONCE_BITS, remaining);
+        let len = hash_bits + NONCE_BITS + 2;
+        low += len - 1;
+        high += len;
+        remaining -= hash_bits;
+        links.push(Link {
+            hash_bits,
+            low,
+            high,
+        });
+    }
+    Ok(links)
+}
+
+/// The multiplier `r = 2 * (1 || hash || nonce)` of a link.
+fn multiplier(hash: &[bool], nonce: u64) -> BigInt {
+    let r = hash
+        .iter()
+        .fold(BigInt::one(), |r, b| (r << 1u32) + *b as u8);
+    ((r << NONCE_BITS as u32) + nonce) << 1u32
+}
+
+/// Whether `n` is a probable prime, by the Miller-Rabin test with the bases `MILLER_RABIN_BASES`.
+fn is_probable_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for b in MILLER_RABIN_BASES {
+        if n.is_multiple_of(&BigInt::from(b)) {
+            return *n == BigInt::from(b);
+        }
+    }
+    let n_1: BigInt = n - 1;
+    let s = n_1.trailing_zeros().unwrap();
+    let d = &n_1 >> s;
+    MILLER_RABIN_BASES.iter().all(|b| {
+        let mut x = BigInt::from(*b).modpow(&d, n);
+        if x.is_one() || x == n_1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = (&x * &x).mod_floor(n);
+            if x == n_1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// The witnesses of a link to the prime `n = p * r + 1`: its nonce, and a Pocklington base `a`,
+/// with the inverse of `a^r - 1` modulo `n`.
+struct Certificate {
+    nonce: u64,
+    base: u64,
+    inverse: BigInt,
+}
+
+/// Searches for the nonce of the link from the prime `p` with the hash bits `hash`, and for its
+/// certificate, returning the next prime.
+fn certify(p: &BigInt, hash: &[bool]) -> Option<(BigInt, Certificate)> {
+    (0..(1u64 << NONCE_BITS)).find_map(|nonce| {
+        let r = multiplier(hash, nonce);
+        let n = p * &r + 1;
+        if !is_probable_prime(&n) {
+            return None;
+        }
+        (2..MAX_BASE).find_map(|base| {
+            let t = BigInt::from(base).modpow(&r, &n);
+            let gcd = (t - 1u8).extended_gcd(&n);
+            gcd.gcd.is_one().then(|| {
+                (
+                    n.clone(),
+                    Certificate {
+                        nonce,
+                        base,
+                        inverse: gcd.x.mod_floor(&n),
+                    },
+                )
+            })
+        })
+    })
+}
+
+/// The prime at the end of the chain `links` for the hash bits `hash`, and the certificates of
+/// its links.
+fn certify_chain(
+    hash: &[bool],
+    links: &[Link],
+) -> Result<(BigInt, Vec<Certificate>), SynthesisError> {
+    let mut p = BigInt::from(BASE_PRIME);
+    let mut hash = hash;
+    let mut certificates = Vec::new();
+    for (i, link) in links.iter().enumerate() {
+        let (link_hash, rest) = hash.split_at(link.hash_bits);
+        let (next, certificate) = certify(&p, link_hash).ok_or_else(|| {
+            eprintln!("No prime found for link {} of the chain", i);
+            SynthesisError::Unsatisfiable
+        })?;
+        certificates.push(certificate);
+        p = next;
+        hash = rest;
+    }
+    Ok((p, certificates))
+}
+
+/// The bits of `bytes`, each byte's most significant bit first.
+fn bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+        .collect()
+}
+
+/// Hashes `input` to a prime, natively (see `hash_to_prime`). Panics if `entropy` is not in
+/// `1..=256`.
+pub fn native_hash_to_prime(input: &[u8], entropy: usize) -> BigInt {
+    let links = chain(entropy).expect("invalid entropy");
+    let hash = bits(&sha256(input));
+    certify_chain(&hash[..entropy], &links)
+        .expect("no prime found")
+        .0
+}
+
+/// Hashes `input`, given as the bits of its bytes, each byte's most significant bit first, to a
+/// prime, returned with limbs of `limb_width` bits and its proven `min_bits`.
+///
+/// The first `entropy` bits of the SHA-256 digest of the input are split among the links of a
+/// chain of primes from `2^32 - 5`: each link goes from a prime `p` to the prime
+/// `n = p * r + 1`, where `r = 2 * (1 || hash || nonce) < p`, for the next bits of the digest
+/// and a nonce witnessed by the prover. The primality of `n` is proven by Pocklington's
+/// criterion, with a witnessed base `a`: `a^(n - 1) = 1 mod n` and `gcd(a^r - 1, n) = 1`.
+///
+/// The nonces are not constrained to be the smallest ones giving primes, so the prime is not
+/// unique: the prover may choose among the primes of the valid nonces, of which
+/// `native_hash_to_prime` returns the one of the smallest nonces. The hash only binds the input
+/// to `entropy` bits of the prime.
+pub fn hash_to_prime<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    input: &[Boolean],
+    entropy: usize,
+    limb_width: usize,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    if !input.len().is_multiple_of(8) {
+        eprintln!("The input has {} bits, not whole bytes", input.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let links = chain(entropy)?;
+    let digest = sha256_circuit(cs.namespace(|| "digest"), input)?;
+    let hash: Option<Vec<bool>> = digest[..entropy].iter().map(|b| b.get_value()).collect();
+    let certificates = match hash {
+        Some(hash) => Some(certify_chain(&hash, &links)?.1),
+        None => None,
+    };
+    certified_prime(
+        cs.namespace(|| "chain"),
+        &digest[..entropy],
+        &links,
+        certificates.as_deref(),
+        limb_width,
+    )
+}
+
+/// The prime at the end of the chain `links` for the hash bits `hash`, with limbs of
+/// `limb_width` bits, constrained by the witnessed `certificates` of its links (see
+/// `hash_to_prime`).
+fn certified_prime<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    hash: &[Boolean],
+    links: &[Link],
+    certificates: Option<&[Certificate]>,
+    limb_width: usize,
+) -> Result<BigNat<Scalar>, SynthesisError> {
+    let mut prime = BigNat::constant::<CS>(
+        &BigInt::from(BASE_PRIME),
+        limb_width,
+        BASE_PRIME_BITS.div_ceil(limb_width),
+    )?;
+    let mut prime_bits = Bitvector::from_bits(
+        (0..BASE_PRIME_BITS)
+            .map(|i| Bit::new_value::<CS>((BASE_PRIME >> i) & 1 == 1))
+            .collect(),
+    );
+    let mut hash = hash;
+    for (i, link) in links.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("link {}", i));
+        let certificate = certificates.map(|cs| &cs[i]);
+        let (link_hash, rest) = hash.split_at(link.hash_bits);
+        hash = rest;
+
+        // r = 2 * (1 || hash || nonce), little-endian
+        let mut r_bits = vec![Bit::new_false::<CS>()];
+        for j in 0..NONCE_BITS {
+            r_bits.push(Bit::alloc(
+                cs.namespace(|| format!("nonce {}", j)),
+                certificate.map(|c| (c.nonce >> j) & 1 == 1),
+            )?);
+        }
+        r_bits.extend(
+            link_hash
+                .iter()
+                .rev()
+                .map(|b| Bit::from_sapling::<CS>(b.clone())),
+        );
+        r_bits.push(Bit::new_true::<CS>());
+        let r_bits = Bitvector::from_bits(r_bits);
+        let r = BigNat::recompose(&r_bits, limb_width);
+
+        // n = p * r + 1
+        let product = prime.mult(cs.namespace(|| "p r"), &r)?;
+        let mut next = BigNat::alloc_from_nat(
+            cs.namespace(|| "n"),
+            || Ok(product.value.grab()? + 1),
+            limb_width,
+            link.high.div_ceil(limb_width),
+        )?;
+        next.assert_well_formed(cs.namespace(|| "n rangecheck"))?;
+        let one = BigNat::one::<CS>(limb_width);
+        product
+            .add::<CS>(&one)?
+            .equal_when_carried_regroup(cs.namespace(|| "n == p r + 1"), &next)?;
+        next.params.min_bits = link.low + 1;
+
+        // a^(n - 1) = (a^r)^p = 1 mod n
+        let a = BigNat::alloc_from_nat(
+            cs.namespace(|| "a"),
+            || Ok(BigInt::from(certificate.grab()?.base)),
+            limb_width,
+            1,
+        )?;
+        a.assert_well_formed(cs.namespace(|| "a rangecheck"))?;
+        let t = a.pow_mod_bits(cs.namespace(|| "a^r"), &r_bits, &next)?;
+        let t_p = t.pow_mod_bits(cs.namespace(|| "a^(n - 1)"), &prime_bits, &next)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "a^(n - 1) == 1"),
+            &[Term::Single(&t_p)],
+            &[Term::Single(&one)],
+            &next,
+        )?;
+
+        // gcd(a^r - 1, n) = 1, with y (a^r - 1) = 1 mod n
+        let y = BigNat::alloc_from_nat(
+            cs.namespace(|| "y"),
+            || Ok(certificate.grab()?.inverse.clone()),
+            limb_width,
+            next.params.n_limbs,
+        )?;
+        y.assert_well_formed(cs.namespace(|| "y rangecheck"))?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "a^r y == 1 + y"),
+            &[Term::Product(&t, &y)],
+            &[Term::Single(&one), Term::Single(&y)],
+            &next,
+        )?;
+
+        if i + 1 < links.len() {
+            prime_bits = next
+                .decompose(cs.namespace(|| "n bits"))?
+                .truncate(link.high);
+        }
+        prime = next;
+    }
+    Ok(prime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::constant_bits;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn hash_to_prime_chain() {
+        // The multipliers are smaller than the primes, and the digest bits are all consumed
+        for entropy in 1..=256 {
+            let links = chain(entropy).unwrap();
+            let mut low = BASE_PRIME_BITS - 1;
+            for link in &links {
+                assert!(link.hash_bits + NONCE_BITS + 2 <= low);
+                low = link.low;
+            }
+            assert_eq!(links.iter().map(|l| l.hash_bits).sum::<usize>(), entropy);
+        }
+        assert_eq!(chain(128).unwrap().last().unwrap().low, 210);
+        assert!(chain(0).is_err());
+        assert!(chain(257).is_err());
+    }
+
+    #[test]
+    fn hash_to_prime_native() {
+        let p = native_hash_to_prime(b"abc", 128);
+        assert!(is_probable_prime(&p));
+        assert!(p.bits() > 210 && p.bits() <= 214);
+        assert_ne!(p, native_hash_to_prime(b"abd", 128));
+        assert!(is_probable_prime(&BigInt::from(BASE_PRIME)));
+        assert!(!is_probable_prime(&BigInt::from(561)));
+        assert!(!is_probable_prime(&(BigInt::from(BASE_PRIME) * 3)));
+    }
+
+    /// Hashes `input` to a prime in a circuit, returning whether it is satisfied and whether the
+    /// prime is `expected`.
+    fn hashes_to(input: &[u8], entropy: usize, expected: &BigInt) -> bool {
+        let mut prime = None;
+        let satisfied = is_satisfied_by(|cs| {
+            let input = constant_bits(input);
+            prime = Some(hash_to_prime(cs.namespace(|| "hash"), &input, entropy, 32)?);
+            Ok(())
+        });
+        let prime = prime.unwrap();
+        assert!(prime.params.min_bits > 0);
+        assert!(*prime.value.as_ref().unwrap() >= BigInt::one() << (prime.params.min_bits - 1));
+        satisfied && prime.value.as_ref() == Some(expected)
+    }
+
+    #[test]
+    fn hash_to_prime_abc() {
+        let expected = native_hash_to_prime(b"abc", 128);
+        assert!(hashes_to(b"abc", 128, &expected));
+    }
+
+    #[test]
+    fn hash_to_prime_short() {
+        let expected = native_hash_to_prime(b"", 20);
+        assert!(hashes_to(b"", 20, &expected));
+    }
+
+    /// Constrains the chain of the first 20 bits of the digest of the empty input, with the
+    /// certificate of its first link modified by `tamper`, given the hash bits of the link,
+    /// returning whether the circuit is satisfied.
+    fn is_certified_with<F: FnOnce(&[bool], &mut Certificate)>(tamper: F) -> bool {
+        let links = chain(20).unwrap();
+        let hash = bits(&sha256(b""))[..20].to_vec();
+        let mut certificates = certify_chain(&hash, &links).unwrap().1;
+        tamper(&hash[..links[0].hash_bits], &mut certificates[0]);
+        is_satisfied_by(|cs| {
+            let hash: Vec<_> = hash.iter().map(|b| Boolean::constant(*b)).collect();
+            certified_prime(
+                cs.namespace(|| "chain"),
+                &hash,
+                &links,
+                Some(&certificates),
+                32,
+            )?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn hash_to_prime_certified() {
+        assert!(is_certified_with(|_, _| ()));
+    }
+
+    #[test]
+    fn hash_to_prime_wrong_base() {
+        assert!(!is_certified_with(|_, c| c.base += 1));
+    }
+
+    #[test]
+    fn hash_to_prime_wrong_inverse() {
+        assert!(!is_certified_with(|_, c| c.inverse += 1));
+    }
+
+    #[test]
+    fn hash_to_prime_wrong_nonce() {
+        assert!(!is_certified_with(|_, c| c.nonce ^= 1));
+    }
+
+    #[test]
+    fn hash_to_prime_composite() {
+        // A nonce for which n is composite, with a consistent inverse: only the Fermat test fails
+        assert!(!is_certified_with(|hash, c| {
+            let p = BigInt::from(BASE_PRIME);
+            let base = BigInt::from(c.base);
+            let (nonce, inverse) = (0..(1u64 << NONCE_BITS))
+                .find_map(|nonce| {
+                    let r = multiplier(hash, nonce);
+                    let n = &p * &r + 1;
+                    let gcd = (base.modpow(&r, &n) - 1u8).extended_gcd(&n);
+                    (!is_probable_prime(&n) && gcd.gcd.is_one())
+                        .then(|| (nonce, gcd.x.mod_floor(&n)))
+                })
+                .unwrap();
+            c.nonce = nonce;
+            c.inverse = inverse;
+        }));
+    }
+}
@@ -0,0 +1,84 @@
+use bellpepper::gadgets::boolean::{AllocatedBit, Boolean};
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+
+use crate::util::bit::{Bit, Bitvector};
+
+pub mod hash_to_prime;
+pub mod sha256;
+pub mod sha512;
+pub mod xmd;
+
+/// The bits of `bytes`, as constants, each byte's most significant bit first.
+pub fn constant_bits(bytes: &[u8]) -> Vec<Boolean> {
+    bytes
+        .iter()
+        .flat_map(|b| {
+            (0..8)
+                .rev()
+                .map(move |i| Boolean::constant((b >> i) & 1 == 1))
+        })
+        .collect()
+}
+
+/// Allocates the bits of `n_bytes` bytes, in order, each byte's most significant bit first.
+pub fn alloc_bits<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    bytes: Option<&[u8]>,
+    n_bytes: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    (0..(8 * n_bytes))
+        .map(|i| {
+            AllocatedBit::alloc(
+                cs.namespace(|| format!("bit {}", i)),
+                bytes.map(|bs| (bs[i / 8] >> (7 - i % 8)) & 1 == 1),
+            )
+            .map(Boolean::from)
+        })
+        .collect()
+}
+
+/// The bytes encoded by `bits`, each byte's most significant bit first (filled at witness-time).
+pub fn bytes_value(bits: &[Boolean]) -> Option<Vec<u8>> {
+    bits.chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .try_fold(0u8, |acc, b| b.get_value().map(|b| (acc << 1) | b as u8))
+        })
+        .collect()
+}
+
+/// The bits as a `Bitvector`, in the same order.
+pub fn to_bitvector<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    bits: &[Boolean],
+) -> Bitvector<Scalar> {
+    Bitvector::from_bits(
+        bits.iter()
+            .map(|b| Bit::from_sapling::<CS>(b.clone()))
+            .collect(),
+    )
+}
+
+/// Allocates booleans constrained to be equal to the bits of `bits`, in the same order.
+pub fn to_booleans<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    bits: &Bitvector<Scalar>,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    bits.bits
+        .iter()
+        .enumerate()
+        .map(|(i, bit)| {
+            let b = AllocatedBit::alloc(
+                cs.namespace(|| format!("bit {}", i)),
+                bits.values.as_ref().map(|vs| vs[i]),
+            )?;
+            cs.enforce(
+                || format!("bit {} equal", i),
+                |lc| lc + bit,
+                |lc| lc + CS::one(),
+                |lc| lc + b.get_variable(),
+            );
+            Ok(Boolean::from(b))
+        })
+        .collect()
+}
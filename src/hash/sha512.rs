@@ -0,0 +1,430 @@
+use bellpepper::gadgets::boolean::{AllocatedBit, Boolean};
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+
+const ROUND_CONSTANTS: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+/// The SHA-512 (FIPS 180-4) digest of `message`, computed natively.
+pub fn native_sha512(message: &[u8]) -> [u8; 64] {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 128 != 112 {
+        padded.push(0);
+    }
+    padded.extend((8 * message.len() as u128).to_be_bytes());
+
+    let mut state = IV;
+    for block in padded.chunks(128) {
+        let mut w = [0u64; 80];
+        for (i, word) in block.chunks(8).enumerate() {
+            w[i] = u64::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..80 {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(ROUND_CONSTANTS[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 64];
+    for (i, s) in state.iter().enumerate() {
+        digest[8 * i..8 * i + 8].copy_from_slice(&s.to_be_bytes());
+    }
+    digest
+}
+
+/// A 64-bit word, as its bits, least significant first.
+type Word = Vec<Boolean>;
+
+fn constant_word(v: u64) -> Word {
+    (0..64)
+        .map(|i| Boolean::constant((v >> i) & 1 == 1))
+        .collect()
+}
+
+fn word_value(w: &[Boolean]) -> Option<u64> {
+    w.iter()
+        .rev()
+        .try_fold(0u64, |acc, b| b.get_value().map(|b| (acc << 1) | b as u64))
+}
+
+/// The big-endian word with bits `bits`, most significant first.
+fn be_word(bits: &[Boolean]) -> Word {
+    bits.iter().rev().cloned().collect()
+}
+
+fn rotr(w: &[Boolean], n: usize) -> Word {
+    (0..64).map(|i| w[(i + n) % 64].clone()).collect()
+}
+
+fn shr(w: &[Boolean], n: usize) -> Word {
+    (0..64)
+        .map(|i| w.get(i + n).cloned().unwrap_or(Boolean::constant(false)))
+        .collect()
+}
+
+/// `a ^ b ^ c`, bitwise.
+fn xor3<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    a: &[Boolean],
+    b: &[Boolean],
+    c: &[Boolean],
+) -> Result<Word, SynthesisError> {
+    (0..64)
+        .map(|i| {
+            let ab = Boolean::xor(cs.namespace(|| format!("xor {} ab", i)), &a[i], &b[i])?;
+            Boolean::xor(cs.namespace(|| format!("xor {} c", i)), &ab, &c[i])
+        })
+        .collect()
+}
+
+/// `(e & f) ^ (!e & g)`, bitwise.
+fn ch<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    e: &[Boolean],
+    f: &[Boolean],
+    g: &[Boolean],
+) -> Result<Word, SynthesisError> {
+    (0..64)
+        .map(|i| Boolean::sha256_ch(cs.namespace(|| format!("ch {}", i)), &e[i], &f[i], &g[i]))
+        .collect()
+}
+
+/// `(a & b) ^ (a & c) ^ (b & c)`, bitwise.
+fn maj<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    a: &[Boolean],
+    b: &[Boolean],
+    c: &[Boolean],
+) -> Result<Word, SynthesisError> {
+    (0..64)
+        .map(|i| Boolean::sha256_maj(cs.namespace(|| format!("maj {}", i)), &a[i], &b[i], &c[i]))
+        .collect()
+}
+
+/// The sum of `words` and `constant` modulo `2^64`. The full sum is allocated as bits, with as
+/// many carry bits as needed, and checked against the weighted sum of the input bits with a
+/// single constraint.
+fn add_many<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    words: &[&Word],
+    constant: u64,
+) -> Result<Word, SynthesisError> {
+    let n_bits = 64 + (words.len() + 1).next_power_of_two().trailing_zeros() as usize;
+    let value = words.iter().try_fold(constant as u128, |acc, w| {
+        word_value(w).map(|v| acc + v as u128)
+    });
+    let mut lc = LinearCombination::zero() + (Scalar::from(constant), CS::one());
+    for word in words {
+        let mut coeff = Scalar::ONE;
+        for bit in word.iter() {
+            lc = lc + &bit.lc(CS::one(), coeff);
+            coeff = coeff.double();
+        }
+    }
+    let mut sum = Vec::with_capacity(n_bits);
+    let mut coeff = Scalar::ONE;
+    for i in 0..n_bits {
+        let bit = AllocatedBit::alloc(
+            cs.namespace(|| format!("bit {}", i)),
+            value.map(|v| (v >> i) & 1 == 1),
+        )?;
+        lc = lc - (coeff, bit.get_variable());
+        coeff = coeff.double();
+        sum.push(Boolean::from(bit));
+    }
+    cs.enforce(|| "sum", |_| lc, |lc| lc + CS::one(), |lc| lc);
+    sum.truncate(64);
+    Ok(sum)
+}
+
+/// The SHA-512 compression function, applied to the state `state` and the 1024-bit block
+/// `block`, most significant bit first.
+fn compress<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    block: &[Boolean],
+    state: &[Word],
+) -> Result<Vec<Word>, SynthesisError> {
+    let mut w: Vec<Word> = block.chunks(64).map(be_word).collect();
+    for i in 16..80 {
+        let mut cs = cs.namespace(|| format!("schedule {}", i));
+        let (w15, w2) = (&w[i - 15], &w[i - 2]);
+        let s0 = xor3(
+            cs.namespace(|| "s0"),
+            &rotr(w15, 1),
+            &rotr(w15, 8),
+            &shr(w15, 7),
+        )?;
+        let s1 = xor3(
+            cs.namespace(|| "s1"),
+            &rotr(w2, 19),
+            &rotr(w2, 61),
+            &shr(w2, 6),
+        )?;
+        let wi = add_many(cs.namespace(|| "w"), &[&w[i - 16], &s0, &w[i - 7], &s1], 0)?;
+        w.push(wi);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] =
+        <[Word; 8]>::try_from(state.to_vec()).unwrap();
+    for i in 0..80 {
+        let mut cs = cs.namespace(|| format!("round {}", i));
+        let s1 = xor3(
+            cs.namespace(|| "s1"),
+            &rotr(&e, 14),
+            &rotr(&e, 18),
+            &rotr(&e, 41),
+        )?;
+        let ch = ch(cs.namespace(|| "ch"), &e, &f, &g)?;
+        let s0 = xor3(
+            cs.namespace(|| "s0"),
+            &rotr(&a, 28),
+            &rotr(&a, 34),
+            &rotr(&a, 39),
+        )?;
+        let maj = maj(cs.namespace(|| "maj"), &a, &b, &c)?;
+        // e = d + t1 and a = t1 + t2, without allocating t1
+        let t1 = [&h, &s1, &ch, &w[i]];
+        let new_e = add_many(
+            cs.namespace(|| "e"),
+            &[t1.as_slice(), &[&d]].concat(),
+            ROUND_CONSTANTS[i],
+        )?;
+        let new_a = add_many(
+            cs.namespace(|| "a"),
+            &[t1.as_slice(), &[&s0, &maj]].concat(),
+            ROUND_CONSTANTS[i],
+        )?;
+        h = g;
+        g = f;
+        f = e;
+        e = new_e;
+        d = c;
+        c = b;
+        b = a;
+        a = new_a;
+    }
+    state
+        .iter()
+        .zip([a, b, c, d, e, f, g, h])
+        .enumerate()
+        .map(|(i, (s, v))| add_many(cs.namespace(|| format!("state {}", i)), &[s, &v], 0))
+        .collect()
+}
+
+/// Computes the SHA-512 (FIPS 180-4) digest of the message `message`, given as the bits of its
+/// bytes, each byte's most significant bit first. The digest is given in the same way.
+pub fn sha512<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    message: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    if !message.len().is_multiple_of(8) {
+        eprintln!("The message has {} bits, not whole bytes", message.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let mut padded = message.to_vec();
+    padded.push(Boolean::constant(true));
+    while padded.len() % 1024 != 896 {
+        padded.push(Boolean::constant(false));
+    }
+    let length = message.len() as u128;
+    padded.extend(
+        (0..128)
+            .rev()
+            .map(|i| Boolean::constant((length >> i) & 1 == 1)),
+    );
+
+    let mut state: Vec<Word> = IV.iter().map(|v| constant_word(*v)).collect();
+    for (i, block) in padded.chunks(1024).enumerate() {
+        state = compress(cs.namespace(|| format!("block {}", i)), block, &state)?;
+    }
+    Ok(state.iter().flat_map(|w| w.iter().rev().cloned()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{alloc_bits, bytes_value, constant_bits};
+    use crate::util::test_helpers::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The two-block message of FIPS 180-4's examples
+    const TWO_BLOCKS: &[u8] = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+        hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    #[test]
+    fn sha512_vectors() {
+        assert_eq!(
+            hex(&native_sha512(b"")),
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+             47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"
+        );
+        assert_eq!(
+            hex(&native_sha512(b"abc")),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(
+            hex(&native_sha512(TWO_BLOCKS)),
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+             501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        );
+    }
+
+    pub struct Sha512 {
+        message: Vec<u8>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Sha512 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let message = alloc_bits(
+                cs.namespace(|| "message"),
+                Some(&self.message),
+                self.message.len(),
+            )?;
+            let digest = sha512(cs.namespace(|| "sha512"), &message)?;
+            let expected = native_sha512(&self.message);
+            assert_eq!(bytes_value(&digest), Some(expected.to_vec()));
+            for (i, (bit, expected)) in digest.iter().zip(constant_bits(&expected)).enumerate() {
+                Boolean::enforce_equal(cs.namespace(|| format!("bit {}", i)), bit, &expected)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn sha(message: &[u8]) -> Sha512 {
+        Sha512 {
+            message: message.to_vec(),
+        }
+    }
+
+    circuit_tests! {
+        sha512_abc: (sha(b"abc"), true),
+        sha512_two_blocks: (sha(TWO_BLOCKS), true),
+    }
+}
@@ -0,0 +1,171 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+
+use super::constant_bits;
+use super::sha256::sha256;
+
+/// The output and block sizes of SHA-256, in bytes.
+const B_IN_BYTES: usize = 32;
+const S_IN_BYTES: usize = 64;
+
+/// The number of hash outputs `ell` needed to expand to `len_in_bytes` bytes with the domain
+/// separation tag `dst`, if the lengths are within the limits of RFC 9380.
+fn n_blocks(dst: &[u8], len_in_bytes: usize) -> Result<usize, SynthesisError> {
+    let ell = len_in_bytes.div_ceil(B_IN_BYTES);
+    if ell > 255 || len_in_bytes > 65535 || dst.len() > 255 {
+        eprintln!(
+            "Cannot expand to {} bytes with a tag of {} bytes",
+            len_in_bytes,
+            dst.len()
+        );
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    Ok(ell)
+}
+
+/// `DST_prime = DST || I2OSP(len(DST), 1)`
+fn dst_prime(dst: &[u8]) -> Vec<u8> {
+    let mut dst = dst.to_vec();
+    dst.push(dst.len() as u8);
+    dst
+}
+
+/// Computes `expand_message_xmd` with SHA-256 (RFC 9380, section 5.3.1) natively. Panics if
+/// `len_in_bytes` exceeds `255 * 32` or `dst` exceeds 255 bytes.
+pub fn native_expand_message_xmd(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+    let ell = n_blocks(dst, len_in_bytes).expect("invalid lengths");
+    let dst = dst_prime(dst);
+    let mut msg_prime = vec![0u8; S_IN_BYTES];
+    msg_prime.extend(msg);
+    msg_prime.extend((len_in_bytes as u16).to_be_bytes());
+    msg_prime.push(0);
+    msg_prime.extend(&dst);
+    let b_0 = sha256(&msg_prime);
+
+    let mut uniform = Vec::new();
+    let mut b_i = [0u8; B_IN_BYTES];
+    for i in 1..=ell {
+        let mut input: Vec<u8> = b_0.iter().zip(&b_i).map(|(a, b)| a ^ b).collect();
+        input.push(i as u8);
+        input.extend(&dst);
+        b_i = sha256(&input);
+        uniform.extend(b_i);
+    }
+    uniform.truncate(len_in_bytes);
+    uniform
+}
+
+/// Computes `expand_message_xmd` with SHA-256 (RFC 9380, section 5.3.1) of the message `msg`,
+/// given as the bits of its bytes, each byte's most significant bit first. The output bytes are
+/// given in the same way.
+pub fn expand_message_xmd<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    msg: &[Boolean],
+    dst: &[u8],
+    len_in_bytes: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    if !msg.len().is_multiple_of(8) {
+        eprintln!("The message has {} bits, not whole bytes", msg.len());
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let ell = n_blocks(dst, len_in_bytes)?;
+    let dst = constant_bits(&dst_prime(dst));
+    // b_0 = H(Z_pad || msg || I2OSP(len_in_bytes, 2) || I2OSP(0, 1) || DST_prime)
+    let mut msg_prime = constant_bits(&[0u8; S_IN_BYTES]);
+    msg_prime.extend_from_slice(msg);
+    msg_prime.extend(constant_bits(&(len_in_bytes as u16).to_be_bytes()));
+    msg_prime.extend(constant_bits(&[0]));
+    msg_prime.extend_from_slice(&dst);
+    let b_0 = sha256_circuit(cs.namespace(|| "b 0"), &msg_prime)?;
+
+    // b_i = H(strxor(b_0, b_(i - 1)) || I2OSP(i, 1) || DST_prime)
+    let mut uniform: Vec<Boolean> = Vec::new();
+    let mut b_i: Option<Vec<Boolean>> = None;
+    for i in 1..=ell {
+        let mut cs = cs.namespace(|| format!("b {}", i));
+        let mut input = match &b_i {
+            None => b_0.clone(),
+            Some(b_i) => b_0
+                .iter()
+                .zip(b_i)
+                .enumerate()
+                .map(|(j, (a, b))| Boolean::xor(cs.namespace(|| format!("xor {}", j)), a, b))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        input.extend(constant_bits(&[i as u8]));
+        input.extend_from_slice(&dst);
+        let b = sha256_circuit(cs.namespace(|| "hash"), &input)?;
+        uniform.extend_from_slice(&b);
+        b_i = Some(b);
+    }
+    uniform.truncate(8 * len_in_bytes);
+    Ok(uniform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{alloc_bits, bytes_value};
+    use crate::util::test_helpers::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    const DST: &[u8] = b"QUUX-V01-CS02-with-expander-SHA256-128";
+
+    #[test]
+    fn expand_message_xmd_vectors() {
+        // RFC 9380, appendix K.1
+        assert_eq!(
+            hex(&native_expand_message_xmd(b"", DST, 0x20)),
+            "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235"
+        );
+        assert_eq!(
+            hex(&native_expand_message_xmd(b"abc", DST, 0x20)),
+            "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615"
+        );
+    }
+
+    pub struct Expand {
+        msg: Vec<u8>,
+        len_in_bytes: usize,
+        expected: Vec<u8>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Expand {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let msg = alloc_bits(cs.namespace(|| "msg"), Some(&self.msg), self.msg.len())?;
+            let uniform =
+                expand_message_xmd(cs.namespace(|| "expand"), &msg, DST, self.len_in_bytes)?;
+            assert_eq!(bytes_value(&uniform), Some(self.expected.clone()));
+            for (i, (bit, expected)) in uniform
+                .iter()
+                .zip(constant_bits(&self.expected))
+                .enumerate()
+            {
+                Boolean::enforce_equal(cs.namespace(|| format!("bit {}", i)), bit, &expected)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Expands `msg`, checking the result against the native computation.
+    fn expand(msg: &[u8], len_in_bytes: usize) -> Expand {
+        Expand {
+            msg: msg.to_vec(),
+            len_in_bytes,
+            expected: native_expand_message_xmd(msg, DST, len_in_bytes),
+        }
+    }
+
+    circuit_tests! {
+        expand_message_xmd_empty: (expand(b"", 0x20), true),
+        expand_message_xmd_partial_block: (expand(b"abc", 0x50), true),
+    }
+}
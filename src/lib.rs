@@ -6,7 +6,10 @@ extern crate quickcheck_macros;
 
 #[macro_use]
 pub mod util;
+pub mod ec;
+pub mod hash;
 pub mod mp;
+pub mod rsa;
 
 use bellpepper_core::SynthesisError;
 use ff::PrimeField;
@@ -14,6 +14,7 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::ops::Deref;
 use std::rc::Rc;
 
+use super::modular::pad;
 use super::poly::Polynomial;
 use crate::util::bit::{Bit, Bitvector};
 use crate::util::convert::{f_to_nat, nat_to_f};
@@ -1000,7 +1001,9 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
             cs.namespace(|| "poly product"),
             &Polynomial::from(other.clone()),
         )?;
-        BigNat::from_poly(poly_prod, other.params.limb_width, max_word)
+        // Pad the product to the limbs of `prod`, so that the last carry reaches its top limb
+        let n_coeffs = max(poly_prod.coefficients.len(), prod.limbs.len());
+        BigNat::from_poly(pad(poly_prod, n_coeffs), other.params.limb_width, max_word)
             .equal_when_carried_regroup(cs.namespace(|| "equal"), prod)?;
         Ok(())
     }
@@ -1072,8 +1075,9 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
     ) -> Result<(BigNat<Scalar>, BigNat<Scalar>), SynthesisError> {
         self.enforce_limb_width_agreement(other, "mult_mod")?;
         let limb_width = self.params.limb_width;
+        // The modulus is at least 2^(min_bits - 1)
         let quotient_bits =
-            (self.n_bits() + other.n_bits()).saturating_sub(modulus.params.min_bits);
+            (self.n_bits() + other.n_bits() + 1).saturating_sub(modulus.params.min_bits);
         let quotient_limbs = quotient_bits.saturating_sub(1) / limb_width + 1;
         let quotient = BigNat::alloc_from_nat(
             cs.namespace(|| "quotient"),
@@ -1135,6 +1139,66 @@ impl<Scalar: PrimeField> BigNat<Scalar> {
         Ok((quotient, remainder))
     }
 
+    /// Compute a `BigNat` constrained to be congruent to `self ^ exponent` modulo `modulus`, for
+    /// a positive constant `exponent`, by square-and-multiply with `mult_mod`. The result is well
+    /// formed if `self` is, but is not necessarily reduced.
+    pub fn pow_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        exponent: &BigInt,
+        modulus: &Self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        if exponent <= &BigInt::from(0) {
+            eprintln!("Cannot raise to the power {}", exponent);
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut acc = self.clone();
+        for i in (0..(exponent.bits() - 1)).rev() {
+            acc = acc
+                .mult_mod(cs.namespace(|| format!("square {}", i)), &acc, modulus)?
+                .1;
+            if exponent.bit(i) {
+                acc = acc
+                    .mult_mod(cs.namespace(|| format!("multiply {}", i)), self, modulus)?
+                    .1;
+            }
+        }
+        Ok(acc)
+    }
+
+    /// Compute a `BigNat` constrained to be congruent to `self ^ exponent` modulo `modulus`, for
+    /// an exponent given by its (little-endian) bits, by square-and-multiply with `mult_mod`,
+    /// selecting each product with a `mux`. The result is well formed if `self` is, but is not
+    /// necessarily reduced.
+    pub fn pow_mod_bits<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        exponent: &Bitvector<Scalar>,
+        modulus: &Self,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        let mut acc = BigNat::one::<CS>(self.params.limb_width);
+        let n_bits = exponent.bits.len();
+        for i in (0..n_bits).rev() {
+            // The accumulator is one until the most significant bit
+            let product = if i + 1 == n_bits {
+                self.clone()
+            } else {
+                acc = acc
+                    .mult_mod(cs.namespace(|| format!("square {}", i)), &acc, modulus)?
+                    .1;
+                acc.mult_mod(cs.namespace(|| format!("multiply {}", i)), self, modulus)?
+                    .1
+            };
+            acc = BigNat::mux(
+                cs.namespace(|| format!("select {}", i)),
+                &exponent.get(i).unwrap(),
+                &acc,
+                &product,
+            )?;
+        }
+        Ok(acc)
+    }
+
     /// Compute a `BigNat` contrained to be equal to `self * other % modulus`.
     pub fn red_mod<CS: ConstraintSystem<Scalar>>(
         &self,
@@ -1563,7 +1627,9 @@ mod tests {
                 cs.namespace(|| "q"),
                 || Ok(self.inputs.grab()?.q.clone()),
                 self.params.limb_width,
-                self.params.n_limbs_a + self.params.n_limbs_b - self.params.n_limbs_m,
+                // The quotient may need a bit more than the difference of the sizes, as the
+                // modulus may be as small as `2^(min_bits - 1)`
+                self.params.n_limbs_a + self.params.n_limbs_b - self.params.n_limbs_m + 1,
             )?;
             let r = BigNat::alloc_from_nat(
                 cs.namespace(|| "r"),
@@ -1739,6 +1805,22 @@ mod tests {
                 r: BigInt::from(120usize),
             }),
         }, true),
+        mult_mod_255_by_255_mod_128: ( MultMod {
+            params: MultModParameters {
+                limb_width: 4,
+                n_limbs_a: 2,
+                n_limbs_b: 2,
+                n_limbs_m: 2,
+                full_m: true,
+            },
+            inputs: Some(MultModInputs {
+                a: BigInt::from(255usize),
+                b: BigInt::from(255usize),
+                m: BigInt::from(128usize),
+                q: BigInt::from(508usize),
+                r: BigInt::from(1usize),
+            }),
+        }, true),
         mult_mod_pallas: ( MultMod {
           params: MultModParameters {
             limb_width: 32,
@@ -1757,6 +1839,61 @@ mod tests {
         }, true),
     }
 
+    pub struct Mult {
+        a: BigInt,
+        b: BigInt,
+        limb_width: usize,
+        n_limbs_a: usize,
+        n_limbs_b: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Mult {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let expected = &self.a * &self.b;
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(self.a),
+                self.limb_width,
+                self.n_limbs_a,
+            )?;
+            let b = BigNat::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(self.b),
+                self.limb_width,
+                self.n_limbs_b,
+            )?;
+            let product = a.mult(cs.namespace(|| "product"), &b)?;
+            let expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "expected"),
+                || Ok(expected),
+                self.limb_width,
+                self.n_limbs_a + self.n_limbs_b,
+            )?;
+            product.equal(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    circuit_tests! {
+        mult_2w_by_2w: ( Mult {
+            a: BigInt::from(0xfedcba9876543210u64),
+            b: BigInt::from(0x0123456789abcdefu64),
+            limb_width: 32,
+            n_limbs_a: 2,
+            n_limbs_b: 2,
+        }, true),
+        // The top limb of the product is not in the group of the last coefficient
+        mult_4w_by_3w: ( Mult {
+            a: (BigInt::from(1) << 128u32) - 1,
+            b: (BigInt::from(1) << 96u32) - 1,
+            limb_width: 32,
+            n_limbs_a: 4,
+            n_limbs_b: 3,
+        }, true),
+    }
+
     #[derive(Debug)]
     pub struct NumberBitDecompInputs {
         pub n: BigInt,
@@ -1887,6 +2024,52 @@ mod tests {
         circuit.synthesize(&mut cs).expect("synthesis failed");
         TestResult::from_bool(cs.is_satisfied())
     }
+
+    pub struct PowModBits {
+        base: BigInt,
+        exponent: BigInt,
+        modulus: BigInt,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for PowModBits {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let expected = self.base.modpow(&self.exponent, &self.modulus);
+            let base = BigNat::alloc_from_nat(cs.namespace(|| "base"), || Ok(self.base), 32, 2)?;
+            let exponent =
+                BigNat::alloc_from_nat(cs.namespace(|| "exponent"), || Ok(self.exponent), 32, 1)?;
+            let mut modulus =
+                BigNat::alloc_from_nat(cs.namespace(|| "modulus"), || Ok(self.modulus), 32, 2)?;
+            modulus.enforce_full_bits(cs.namespace(|| "modulus is full"))?;
+            let bits = exponent.decompose(cs.namespace(|| "bits"))?;
+            let power = base.pow_mod_bits(cs.namespace(|| "power"), &bits, &modulus)?;
+            let expected =
+                BigNat::alloc_from_nat(cs.namespace(|| "expected"), || Ok(expected), 32, 2)?;
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "check"),
+                &[crate::mp::modular::Term::Single(&power)],
+                &[crate::mp::modular::Term::Single(&expected)],
+                &modulus,
+            )
+        }
+    }
+
+    #[quickcheck]
+    fn big_nat_pow_mod_bits(base: u64, exponent: u32) -> TestResult {
+        use crate::util::scalar::Fr;
+        // The prime 2^64 - 59
+        let modulus = BigInt::from(0xffffffffffffffc5u64);
+        let circuit = PowModBits {
+            base: BigInt::from(base),
+            exponent: BigInt::from(exponent),
+            modulus,
+        };
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        TestResult::from_bool(cs.is_satisfied())
+    }
 }
 
 impl<Scalar: PrimeField> Display for BigNat<Scalar> {
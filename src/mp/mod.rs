@@ -1,2 +1,4 @@
 pub mod bignat;
+pub mod modular;
 mod poly;
+pub mod smallfield;
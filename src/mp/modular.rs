@@ -0,0 +1,540 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use std::cmp::max;
+
+use super::bignat::{nat_to_limbs, BigNat, BigNatParams};
+use super::poly::Polynomial;
+use crate::OptionExt;
+
+/// A term of a sum whose residue is checked by `BigNat::assert_sums_congruent`.
+#[derive(Clone)]
+pub enum Term<'a, Scalar: PrimeField> {
+    /// A single number
+    Single(&'a BigNat<Scalar>),
+    /// The product of two numbers
+    Product(&'a BigNat<Scalar>, &'a BigNat<Scalar>),
+}
+
+impl<'a, Scalar: PrimeField> Term<'a, Scalar> {
+    /// The value of the term (filled at witness-time)
+    pub fn value(&self) -> Option<BigInt> {
+        match self {
+            Term::Single(a) => a.value.clone(),
+            Term::Product(a, b) => a
+                .value
+                .as_ref()
+                .and_then(|a| b.value.as_ref().map(|b| a * b)),
+        }
+    }
+
+    /// An exclusive upper bound on the value of the term.
+    fn bound(&self) -> BigInt {
+        match self {
+            Term::Single(a) => BigInt::from(1) << a.n_bits() as u32,
+            Term::Product(a, b) => BigInt::from(1) << (a.n_bits() + b.n_bits()) as u32,
+        }
+    }
+
+    /// The term as a polynomial in the limb base, along with the maximum value of a coefficient.
+    fn poly<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+    ) -> Result<(Polynomial<Scalar>, BigInt), SynthesisError> {
+        match self {
+            Term::Single(a) => Ok((Polynomial::from((*a).clone()), a.params.max_word.clone())),
+            Term::Product(a, b) => {
+                a.enforce_limb_width_agreement(b, "Term::poly")?;
+                let max_word = BigInt::from(std::cmp::min(a.limbs.len(), b.limbs.len()))
+                    * &a.params.max_word
+                    * &b.params.max_word;
+                let prod = Polynomial::from((*a).clone())
+                    .alloc_product(cs, &Polynomial::from((*b).clone()))?;
+                Ok((prod, max_word))
+            }
+        }
+    }
+}
+
+/// Sums the polynomials of the terms, returning the sum and the maximum value of a coefficient.
+fn sum_terms<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    terms: &[Term<Scalar>],
+) -> Result<(Polynomial<Scalar>, BigInt), SynthesisError> {
+    let mut sum = Polynomial {
+        coefficients: Vec::new(),
+        values: Some(Vec::new()),
+    };
+    let mut max_word = BigInt::from(0);
+    for (i, term) in terms.iter().enumerate() {
+        let (poly, term_max_word) = term.poly(cs.namespace(|| format!("term {}", i)))?;
+        sum = sum.sum(&poly);
+        max_word += term_max_word;
+    }
+    Ok((sum, max_word))
+}
+
+/// Pads the polynomial with zero coefficients up to `n_coeffs` coefficients.
+pub(crate) fn pad<Scalar: PrimeField>(
+    poly: Polynomial<Scalar>,
+    n_coeffs: usize,
+) -> Polynomial<Scalar> {
+    let zero = Polynomial {
+        coefficients: vec![LinearCombination::zero(); n_coeffs],
+        values: Some(vec![Scalar::ZERO; n_coeffs]),
+    };
+    poly.sum(&zero)
+}
+
+impl<Scalar: PrimeField> BigNat<Scalar> {
+    /// Creates a constant `BigNat` with `n_limbs` limbs of width `limb_width` each.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        value: &BigInt,
+        limb_width: usize,
+        n_limbs: usize,
+    ) -> Result<Self, SynthesisError> {
+        let limb_values = nat_to_limbs::<Scalar>(value, limb_width, n_limbs)?;
+        let mut params = BigNatParams::new(limb_width, n_limbs);
+        params.min_bits = value.bits() as usize;
+        Ok(BigNat {
+            limbs: limb_values
+                .iter()
+                .map(|v| LinearCombination::zero() + (*v, CS::one()))
+                .collect(),
+            limb_values: Some(limb_values),
+            value: Some(value.clone()),
+            params,
+        })
+    }
+
+    /// Constrain the sum of the `lhs` terms to be congruent to the sum of the `rhs` terms, modulo
+    /// `modulus`.
+    ///
+    /// A multiple of the modulus `k * modulus`, for a constant `k` large enough for it to exceed
+    /// the right-hand side, is added to the left, so that the witnessed quotient
+    /// `q = (lhs + k * modulus - rhs) / modulus` is a natural number and the identity
+    /// `lhs + k * modulus == rhs + q * modulus` can be checked over the naturals.
+    pub fn assert_sums_congruent<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        lhs: &[Term<Scalar>],
+        rhs: &[Term<Scalar>],
+        modulus: &Self,
+    ) -> Result<(), SynthesisError> {
+        let limb_width = modulus.params.limb_width;
+        if modulus.params.min_bits == 0 {
+            eprintln!("The modulus must have a lower bound (BigNat::assert_sums_congruent)");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let min_modulus = BigInt::from(1) << (modulus.params.min_bits - 1) as u32;
+        let max_modulus = BigInt::from(1) << modulus.n_bits() as u32;
+        let lhs_bound: BigInt = lhs.iter().map(Term::bound).sum();
+        let rhs_bound: BigInt = rhs.iter().map(Term::bound).sum();
+
+        // The smallest `k` such that `k * modulus >= rhs` is guaranteed.
+        let k = rhs_bound.div_ceil(&min_modulus);
+        let k_limbs = (k.bits() as usize).saturating_sub(1) / limb_width + 1;
+        let k = BigNat::constant::<CS>(&k, limb_width, k_limbs)?;
+
+        let quotient_bound = (lhs_bound + k.value.grab()? * &max_modulus).div_ceil(&min_modulus);
+        let quotient_limbs = (quotient_bound.bits() as usize).saturating_sub(1) / limb_width + 1;
+        let quotient = BigNat::alloc_from_nat(
+            cs.namespace(|| "quotient"),
+            || {
+                let l: Option<BigInt> = lhs.iter().map(Term::value).sum();
+                let r: Option<BigInt> = rhs.iter().map(Term::value).sum();
+                let m = modulus.value.grab()?;
+                Ok((l.grab()? + k.value.grab()? * m - r.grab()?).div_floor(m))
+            },
+            limb_width,
+            quotient_limbs,
+        )?;
+        quotient.assert_well_formed(cs.namespace(|| "quotient rangecheck"))?;
+
+        let (left, left_max_word) = sum_terms(cs.namespace(|| "lhs"), lhs)?;
+        let (offset, offset_max_word) =
+            Term::Product(&k, modulus).poly(cs.namespace(|| "offset"))?;
+        let left = left.sum(&offset);
+        let left_max_word = left_max_word + offset_max_word;
+
+        let (right, right_max_word) = sum_terms(cs.namespace(|| "rhs"), rhs)?;
+        let (right_product, right_product_max_word) =
+            Term::Product(&quotient, modulus).poly(cs.namespace(|| "quotient product"))?;
+        let right = right.sum(&right_product);
+        let right_max_word = right_max_word + right_product_max_word;
+
+        let n_coeffs = max(left.coefficients.len(), right.coefficients.len());
+        let left_int = BigNat::from_poly(pad(left, n_coeffs), limb_width, left_max_word);
+        let right_int = BigNat::from_poly(pad(right, n_coeffs), limb_width, right_max_word);
+        left_int.equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int)
+    }
+
+    /// Allocates the residue `f() mod modulus` with as many limbs as the modulus, range checking
+    /// each limb.
+    pub fn alloc_residue<CS, F>(mut cs: CS, f: F, modulus: &Self) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let residue = BigNat::alloc_from_nat(
+            cs.namespace(|| "residue"),
+            || Ok(f()?.mod_floor(modulus.value.grab()?)),
+            modulus.params.limb_width,
+            modulus.params.n_limbs,
+        )?;
+        residue.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        Ok(residue)
+    }
+
+    /// Compute a `BigNat` constrained to be congruent to `self + other` modulo `modulus`.
+    pub fn add_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+        modulus: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let sum = BigNat::alloc_residue(
+            cs.namespace(|| "sum"),
+            || Ok(self.value.grab()? + other.value.grab()?),
+            modulus,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Term::Single(self), Term::Single(other)],
+            &[Term::Single(&sum)],
+            modulus,
+        )?;
+        Ok(sum)
+    }
+
+    /// Compute a `BigNat` constrained to be congruent to `self - other` modulo `modulus`.
+    pub fn sub_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+        modulus: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let diff = BigNat::alloc_residue(
+            cs.namespace(|| "diff"),
+            || Ok(self.value.grab()? - other.value.grab()?),
+            modulus,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Term::Single(&diff), Term::Single(other)],
+            &[Term::Single(self)],
+            modulus,
+        )?;
+        Ok(diff)
+    }
+
+    /// Compute a `BigNat` constrained to be congruent to `-self` modulo `modulus`.
+    pub fn neg_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let neg = BigNat::alloc_residue(
+            cs.namespace(|| "neg"),
+            || Ok(-self.value.grab()?.clone()),
+            modulus,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Term::Single(&neg), Term::Single(self)],
+            &[],
+            modulus,
+        )?;
+        Ok(neg)
+    }
+
+    /// Compute a `BigNat` constrained to be congruent to `self / other` modulo the prime
+    /// `modulus`, as `self` times the inverse of `other` (see `inverse_mod`). The constraints are
+    /// unsatisfiable if `other` is zero modulo `modulus`.
+    pub fn div_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+        modulus: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let inverse = other.inverse_mod(cs.namespace(|| "inverse"), modulus)?;
+        let quotient = BigNat::alloc_residue(
+            cs.namespace(|| "quotient"),
+            || Ok(self.value.grab()? * inverse.value.grab()?),
+            modulus,
+        )?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Term::Product(self, &inverse)],
+            &[Term::Single(&quotient)],
+            modulus,
+        )?;
+        Ok(quotient)
+    }
+
+    /// Compute a `BigNat` constrained to be the inverse of `self` modulo the prime `modulus`.
+    /// This also proves that `self` is non-zero modulo `modulus`: the constraints are
+    /// unsatisfiable otherwise.
+    pub fn inverse_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let inverse = BigNat::alloc_residue(
+            cs.namespace(|| "inverse"),
+            || {
+                let m = modulus.value.grab()?;
+                let value = self.value.grab()?;
+                if value.is_multiple_of(m) {
+                    // There is no inverse: any witness fails the check
+                    return Ok(BigInt::from(0));
+                }
+                invert(value, m)
+            },
+            modulus,
+        )?;
+        let one = BigNat::one::<CS>(self.params.limb_width);
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "check"),
+            &[Term::Product(self, &inverse)],
+            &[Term::Single(&one)],
+            modulus,
+        )?;
+        Ok(inverse)
+    }
+
+    /// Constrain `self` to be less than `modulus`, i.e. to be the canonical representative of its
+    /// residue class. `self` must be well formed.
+    pub fn assert_canonical<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+    ) -> Result<(), SynthesisError> {
+        // modulus - 1 - self is a natural number
+        let gap = BigNat::alloc_from_nat(
+            cs.namespace(|| "gap"),
+            || {
+                let gap: BigInt = modulus.value.grab()? - 1u32 - self.value.grab()?;
+                Ok(gap.max(BigInt::from(0)))
+            },
+            modulus.params.limb_width,
+            modulus.params.n_limbs,
+        )?;
+        gap.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        let one = BigNat::one::<CS>(self.params.limb_width);
+        let sum = self.add::<CS>(&gap)?.add::<CS>(&one)?;
+        sum.equal_when_carried_regroup(cs.namespace(|| "sum"), modulus)
+    }
+}
+
+/// Inverts `a` modulo the prime `m`.
+pub fn invert(a: &BigInt, m: &BigInt) -> Result<BigInt, SynthesisError> {
+    let a = a.mod_floor(m);
+    if a == BigInt::from(0) {
+        eprintln!("Cannot invert zero");
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    Ok(a.modpow(&(m - 2), m))
+}
+
+/// Computes a square root of `a` modulo the odd prime `m` with the Tonelli-Shanks algorithm, if
+/// `a` is a square. The root returned is the smaller of the two.
+pub fn sqrt(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    let zero = BigInt::from(0);
+    let one = BigInt::from(1);
+    let a = a.mod_floor(m);
+    if a == zero {
+        return Some(zero);
+    }
+    let m1 = m - &one;
+    if a.modpow(&(&m1 >> 1u32), m) != one {
+        return None;
+    }
+    // m - 1 = q * 2^s, with q odd
+    let s = m1.trailing_zeros().unwrap();
+    let q = &m1 >> s;
+    let mut z = BigInt::from(2);
+    while z.modpow(&(&m1 >> 1u32), m) == one {
+        z += 1;
+    }
+    let mut c = z.modpow(&q, m);
+    let mut t = a.modpow(&q, m);
+    let mut r = a.modpow(&((&q + &one) >> 1u32), m);
+    let mut s = s;
+    while t != one {
+        // Find the least i such that t^(2^i) = 1
+        let mut i = 0;
+        let mut t2 = t.clone();
+        while t2 != one {
+            t2 = (&t2 * &t2).mod_floor(m);
+            i += 1;
+        }
+        let b = c.modpow(&(BigInt::from(1) << (s - i - 1)), m);
+        r = (r * &b).mod_floor(m);
+        c = (&b * &b).mod_floor(m);
+        t = (t * &c).mod_floor(m);
+        s = i;
+    }
+    let neg = m - &r;
+    Some(if neg < r { neg } else { r })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_helpers::*;
+    use num_traits::Num as BigNum;
+
+    pub struct ModularOpsInputs {
+        pub a: BigInt,
+        pub b: BigInt,
+        pub m: BigInt,
+        pub sum: BigInt,
+        pub diff: BigInt,
+        pub quotient: BigInt,
+    }
+
+    pub struct ModularOps {
+        inputs: Option<ModularOpsInputs>,
+        limb_width: usize,
+        n_limbs: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for ModularOps {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let alloc = |cs: &mut CS, name: &str, f: fn(&ModularOpsInputs) -> BigInt| {
+                BigNat::alloc_from_nat(
+                    cs.namespace(|| name.to_string()),
+                    || Ok(f(self.inputs.grab()?)),
+                    self.limb_width,
+                    self.n_limbs,
+                )
+            };
+            let a = alloc(cs, "a", |i| i.a.clone())?;
+            let b = alloc(cs, "b", |i| i.b.clone())?;
+            let sum = alloc(cs, "sum", |i| i.sum.clone())?;
+            let diff = alloc(cs, "diff", |i| i.diff.clone())?;
+            let quotient = alloc(cs, "quotient", |i| i.quotient.clone())?;
+            let m = BigNat::constant::<CS>(&self.inputs.grab()?.m, self.limb_width, self.n_limbs)?;
+            a.add_mod(cs.namespace(|| "add"), &b, &m)?
+                .equal(cs.namespace(|| "sum eq"), &sum)?;
+            a.sub_mod(cs.namespace(|| "sub"), &b, &m)?
+                .equal(cs.namespace(|| "diff eq"), &diff)?;
+            a.div_mod(cs.namespace(|| "div"), &b, &m)?
+                .equal(cs.namespace(|| "quotient eq"), &quotient)?;
+            let neg = a.neg_mod(cs.namespace(|| "neg"), &m)?;
+            BigNat::assert_sums_congruent(
+                cs.namespace(|| "a - a"),
+                &[Term::Single(&a), Term::Single(&neg)],
+                &[],
+                &m,
+            )?;
+            Ok(())
+        }
+    }
+
+    fn modular_ops(a: &str, b: &str, m: &str, wrong: bool) -> ModularOps {
+        let a = BigInt::from_str_radix(a, 16).unwrap();
+        let b = BigInt::from_str_radix(b, 16).unwrap();
+        let m = BigInt::from_str_radix(m, 16).unwrap();
+        let sum = (&a + &b) % &m;
+        let mut diff = (&a - &b).mod_floor(&m);
+        if wrong {
+            diff += 1;
+        }
+        let quotient = (&a * invert(&b, &m).unwrap()) % &m;
+        ModularOps {
+            inputs: Some(ModularOpsInputs {
+                a,
+                b,
+                m,
+                sum,
+                diff,
+                quotient,
+            }),
+            limb_width: 64,
+            n_limbs: 4,
+        }
+    }
+
+    const P: &str = "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f";
+
+    /// Divides `a` by `b` modulo `P`, with limbs of 64 bits, checking the claimed `quotient`.
+    pub struct DivMod {
+        a: BigInt,
+        b: BigInt,
+        quotient: BigInt,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for DivMod {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let alloc = |cs: &mut CS, name: &str, v: BigInt| {
+                BigNat::alloc_from_nat(cs.namespace(|| name.to_string()), || Ok(v), 64, 4)
+            };
+            let a = alloc(cs, "a", self.a)?;
+            let b = alloc(cs, "b", self.b)?;
+            let quotient = alloc(cs, "quotient", self.quotient)?;
+            let m = BigNat::constant::<CS>(&BigInt::from_str_radix(P, 16).unwrap(), 64, 4)?;
+            a.div_mod(cs.namespace(|| "div"), &b, &m)?
+                .equal(cs.namespace(|| "quotient eq"), &quotient)
+        }
+    }
+
+    fn div_mod(a: &str, b: &str, quotient: &str) -> DivMod {
+        DivMod {
+            a: BigInt::from_str_radix(a, 16).unwrap(),
+            b: BigInt::from_str_radix(b, 16).unwrap(),
+            quotient: BigInt::from_str_radix(quotient, 16).unwrap(),
+        }
+    }
+
+    #[test]
+    fn sqrt_roots_squares() {
+        // s = 1, s = 2 and s = 27 in p - 1 = q * 2^s
+        for m in [
+            BigInt::from_str_radix(P, 16).unwrap(),
+            (BigInt::from(1) << 255u32) - 19,
+            BigInt::from(15u64 * (1 << 27) + 1),
+        ] {
+            for a in [0u64, 1, 2, 3, 5, 1 << 40, 123456789] {
+                let a = BigInt::from(a);
+                let square = (&a * &a).mod_floor(&m);
+                let root = sqrt(&square, &m).unwrap();
+                assert!(root == a.mod_floor(&m) || root == (-&a).mod_floor(&m));
+                assert!(&root * 2 <= m);
+            }
+            // The least non-residue is not a square
+            let mut z = BigInt::from(2);
+            while z.modpow(&((&m - 1) >> 1u32), &m) == BigInt::from(1) {
+                z += 1;
+            }
+            assert_eq!(sqrt(&z, &m), None);
+        }
+    }
+
+    circuit_tests! {
+        modular_ops_small: (modular_ops("5", "7", P, false), true),
+        modular_ops_wrap: (
+            modular_ops(
+                "1234567890abcdef",
+                "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+                P,
+                false,
+            ),
+            true
+        ),
+        modular_ops_wrong: (modular_ops("5", "7", P, true), false),
+        div_mod_exact: (div_mod("e", "7", "2"), true),
+        // 0 = q * 0 for any q: the divisor must be proven non-zero
+        div_mod_zero_by_zero: (div_mod("0", "0", "0"), false),
+        div_mod_zero_by_zero_any_quotient: (div_mod("0", "0", "5"), false),
+        div_mod_by_modulus: (div_mod("5", P, "0"), false),
+    }
+}
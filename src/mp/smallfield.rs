@@ -0,0 +1,572 @@
+use bellpepper_core::{ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use std::cmp::max;
+use std::fmt::{self, Debug, Display, Formatter};
+
+use crate::util::bit::Bit;
+use crate::util::convert::{f_to_nat, nat_to_f};
+use crate::util::gadget::Gadget;
+use crate::util::num::Num;
+use crate::OptionExt;
+
+/// Parameters of an emulated prime field whose elements fit in a single native field element.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SmallFieldParams {
+    pub modulus: BigInt,
+    pub modulus_bits: usize,
+}
+
+impl SmallFieldParams {
+    pub fn new(modulus: BigInt) -> Self {
+        let modulus_bits = modulus.bits() as usize;
+        SmallFieldParams {
+            modulus,
+            modulus_bits,
+        }
+    }
+
+    /// The Goldilocks field, `2^64 - 2^32 + 1`.
+    pub fn goldilocks() -> Self {
+        Self::new((BigInt::from(1) << 64u32) - (BigInt::from(1) << 32u32) + 1)
+    }
+
+    /// The BabyBear field, `2^31 - 2^27 + 1`.
+    pub fn baby_bear() -> Self {
+        Self::new((BigInt::from(1) << 31u32) - (BigInt::from(1) << 27u32) + 1)
+    }
+}
+
+/// An element of a small emulated prime field, represented by a single native number.
+///
+/// The number is an integer representative of the element which need not be reduced: additions,
+/// subtractions and (lazy) multiplications only grow `max_value`, and a reduction is needed only
+/// once the bound approaches the capacity of the native field.
+#[derive(Clone)]
+pub struct SmallFieldElement<Scalar: PrimeField> {
+    /// The native number holding the integer representative
+    pub num: Num<Scalar>,
+    /// An upper bound on the integer representative
+    pub max_value: BigInt,
+    /// Parameters
+    pub params: SmallFieldParams,
+}
+
+/// Checks that integers up to `bound` can be represented without wrapping in the native field.
+fn check_bound<Scalar: PrimeField>(bound: &BigInt, location: &str) -> Result<(), SynthesisError> {
+    if bound.bits() as u32 > Scalar::CAPACITY {
+        eprintln!(
+            "Bound of {} bits exceeds the native capacity at {}",
+            bound.bits(),
+            location
+        );
+        Err(SynthesisError::Unsatisfiable)
+    } else {
+        Ok(())
+    }
+}
+
+impl<Scalar: PrimeField> SmallFieldElement<Scalar> {
+    /// Allocates an element, range checked to the bit-width of the modulus.
+    /// The value is provided by a closure returning a natural number.
+    pub fn alloc_from_nat<CS, F>(
+        mut cs: CS,
+        f: F,
+        params: &SmallFieldParams,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let num = Num::alloc(cs.namespace(|| "num"), || {
+            nat_to_f(&f()?).ok_or(SynthesisError::Unsatisfiable)
+        })?;
+        num.fits_in_bits(cs.namespace(|| "rangecheck"), params.modulus_bits)?;
+        Ok(Self {
+            num,
+            max_value: (BigInt::from(1) << params.modulus_bits as u32) - 1,
+            params: params.clone(),
+        })
+    }
+
+    /// Creates a constant element.
+    pub fn constant<CS: ConstraintSystem<Scalar>>(
+        value: &BigInt,
+        params: &SmallFieldParams,
+    ) -> Result<Self, SynthesisError> {
+        let value = value.mod_floor(&params.modulus);
+        let f: Scalar = nat_to_f(&value).ok_or(SynthesisError::Unsatisfiable)?;
+        Ok(Self {
+            num: Num::new(Some(f), LinearCombination::zero() + (f, CS::one())),
+            max_value: value,
+            params: params.clone(),
+        })
+    }
+
+    /// The integer representative of this element (filled at witness-time).
+    pub fn nat_value(&self) -> Option<BigInt> {
+        self.num.value.as_ref().map(f_to_nat)
+    }
+
+    fn enforce_params_agreement(&self, other: &Self, location: &str) -> Result<(), SynthesisError> {
+        if self.params == other.params {
+            Ok(())
+        } else {
+            eprintln!(
+                "Moduli {}, {}, do not agree at {}",
+                self.params.modulus, other.params.modulus, location
+            );
+            Err(SynthesisError::Unsatisfiable)
+        }
+    }
+
+    /// Computes `self + other` without reducing. Costs no constraints.
+    pub fn add(&self, other: &Self) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "add")?;
+        let max_value = &self.max_value + &other.max_value;
+        check_bound::<Scalar>(&max_value, "add")?;
+        Ok(Self {
+            num: Num::new(
+                self.num.value.and_then(|a| other.num.value.map(|b| a + b)),
+                self.num.num.clone() + &other.num.num,
+            ),
+            max_value,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes a representative of `self - other` without reducing. Costs no constraints.
+    ///
+    /// A multiple of the modulus exceeding `other.max_value` is added so that the result stays a
+    /// natural number.
+    pub fn sub<CS: ConstraintSystem<Scalar>>(&self, other: &Self) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "sub")?;
+        let offset = other.max_value.div_ceil(&self.params.modulus) * &self.params.modulus;
+        let max_value = &self.max_value + &offset;
+        check_bound::<Scalar>(&max_value, "sub")?;
+        let offset_f: Scalar = nat_to_f(&offset).ok_or(SynthesisError::Unsatisfiable)?;
+        Ok(Self {
+            num: Num::new(
+                self.num
+                    .value
+                    .and_then(|a| other.num.value.map(|b| a + offset_f - b)),
+                self.num.num.clone() + (offset_f, CS::one()) - &other.num.num,
+            ),
+            max_value,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes a representative of `-self` without reducing. Costs no constraints.
+    pub fn neg<CS: ConstraintSystem<Scalar>>(&self) -> Result<Self, SynthesisError> {
+        let zero = Self {
+            num: Num::new(Some(Scalar::ZERO), LinearCombination::zero()),
+            max_value: BigInt::from(0),
+            params: self.params.clone(),
+        };
+        zero.sub::<CS>(self)
+    }
+
+    /// Computes `constant * self` without reducing. Costs no constraints.
+    pub fn scale(&self, constant: u64) -> Result<Self, SynthesisError> {
+        let max_value = &self.max_value * constant;
+        check_bound::<Scalar>(&max_value, "scale")?;
+        let c = Scalar::from(constant);
+        Ok(Self {
+            num: Num::new(
+                self.num.value.map(|v| v * c),
+                LinearCombination::zero() + (c, &self.num.num),
+            ),
+            max_value,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes `self * other` without reducing, deferring the reduction to a later `reduce` or
+    /// `mult_mod`. Costs one constraint.
+    pub fn mult<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "mult")?;
+        let max_value = &self.max_value * &other.max_value;
+        check_bound::<Scalar>(&max_value, "mult")?;
+        let prod = Num::alloc(cs.namespace(|| "product"), || {
+            Ok(*self.num.value.grab()? * other.num.value.grab()?)
+        })?;
+        cs.enforce(
+            || "multiplication",
+            |lc| lc + &self.num.num,
+            |lc| lc + &other.num.num,
+            |lc| lc + &prod.num,
+        );
+        Ok(Self {
+            num: prod,
+            max_value,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Allocates a quotient and a remainder of `value` by the modulus, where `value` is at most
+    /// `max_value`, and range checks both. Returns the quotient and the remainder.
+    fn alloc_quotient_remainder<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        value: Option<BigInt>,
+        max_value: &BigInt,
+    ) -> Result<(Num<Scalar>, Num<Scalar>), SynthesisError> {
+        let modulus = &self.params.modulus;
+        let quotient_bits = (max_value / modulus).bits() as usize;
+        // `quotient * modulus + remainder` must not wrap around the native modulus.
+        let max_rhs = ((BigInt::from(1) << quotient_bits as u32) - 1) * modulus
+            + (BigInt::from(1) << self.params.modulus_bits as u32);
+        check_bound::<Scalar>(&max_rhs, "quotient")?;
+        let quotient = Num::alloc(cs.namespace(|| "quotient"), || {
+            nat_to_f(&(value.grab()? / modulus)).ok_or(SynthesisError::Unsatisfiable)
+        })?;
+        let remainder = Num::alloc(cs.namespace(|| "remainder"), || {
+            nat_to_f(&value.grab()?.mod_floor(modulus)).ok_or(SynthesisError::Unsatisfiable)
+        })?;
+        if quotient_bits > 0 {
+            quotient.fits_in_bits(cs.namespace(|| "quotient rangecheck"), quotient_bits)?;
+        } else {
+            cs.enforce(
+                || "quotient is zero",
+                |lc| lc,
+                |lc| lc,
+                |lc| lc + &quotient.num,
+            );
+        }
+        remainder.fits_in_bits(
+            cs.namespace(|| "remainder rangecheck"),
+            self.params.modulus_bits,
+        )?;
+        Ok((quotient, remainder))
+    }
+
+    /// Computes a reduced representative of `self * other`: the product is checked against a
+    /// witnessed quotient and remainder in a single constraint.
+    pub fn mult_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        self.enforce_params_agreement(other, "mult_mod")?;
+        let max_value = &self.max_value * &other.max_value;
+        check_bound::<Scalar>(&max_value, "mult_mod")?;
+        let value = self
+            .nat_value()
+            .and_then(|a| other.nat_value().map(|b| a * b));
+        let (quotient, remainder) =
+            self.alloc_quotient_remainder(cs.namespace(|| "divide"), value, &max_value)?;
+        let modulus = nat_to_f::<Scalar>(&self.params.modulus).unwrap();
+        cs.enforce(
+            || "product",
+            |lc| lc + &self.num.num,
+            |lc| lc + &other.num.num,
+            |lc| lc + (modulus, &quotient.num) + &remainder.num,
+        );
+        Ok(Self {
+            num: remainder,
+            max_value: (BigInt::from(1) << self.params.modulus_bits as u32) - 1,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes a representative of `self` which fits in the bit-width of the modulus.
+    pub fn reduce<CS: ConstraintSystem<Scalar>>(&self, mut cs: CS) -> Result<Self, SynthesisError> {
+        let (quotient, remainder) = self.alloc_quotient_remainder(
+            cs.namespace(|| "divide"),
+            self.nat_value(),
+            &self.max_value,
+        )?;
+        let modulus = nat_to_f::<Scalar>(&self.params.modulus).unwrap();
+        cs.enforce(
+            || "reduction",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + &self.num.num - (modulus, &quotient.num) - &remainder.num,
+        );
+        Ok(Self {
+            num: remainder,
+            max_value: (BigInt::from(1) << self.params.modulus_bits as u32) - 1,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Computes the canonical representative of `self`, i.e. the one in `[0, modulus)`.
+    pub fn canonicalize<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let mut reduced = self.reduce(cs.namespace(|| "reduce"))?;
+        // `modulus - 1 - reduced` is non-negative iff `reduced < modulus`
+        let max_canonical = &self.params.modulus - 1;
+        let max_canonical_f: Scalar = nat_to_f(&max_canonical).unwrap();
+        let gap = Num::new(
+            reduced.num.value.map(|v| max_canonical_f - v),
+            LinearCombination::zero() + (max_canonical_f, CS::one()) - &reduced.num.num,
+        );
+        gap.fits_in_bits(cs.namespace(|| "gap"), self.params.modulus_bits)?;
+        reduced.max_value = max_canonical;
+        Ok(reduced)
+    }
+
+    /// Constrains `self` and `other` to represent the same element.
+    pub fn assert_equal_mod<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        let diff = self.sub::<CS>(other)?;
+        let modulus = &self.params.modulus;
+        let quotient_bits = (&diff.max_value / modulus).bits() as usize;
+        check_bound::<Scalar>(
+            &(((BigInt::from(1) << quotient_bits as u32) - 1) * modulus),
+            "assert_equal_mod",
+        )?;
+        let quotient = Num::alloc(cs.namespace(|| "quotient"), || {
+            nat_to_f(&(diff.nat_value().grab()? / modulus)).ok_or(SynthesisError::Unsatisfiable)
+        })?;
+        if quotient_bits > 0 {
+            quotient.fits_in_bits(cs.namespace(|| "quotient rangecheck"), quotient_bits)?;
+        }
+        let modulus_f = nat_to_f::<Scalar>(modulus).unwrap();
+        cs.enforce(
+            || "congruence",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + &diff.num.num - (modulus_f, &quotient.num),
+        );
+        Ok(())
+    }
+
+    /// Computes the inverse of `self`, which must be non-zero.
+    pub fn inverse<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Self, SynthesisError> {
+        let modulus = &self.params.modulus;
+        let inverse = Self::alloc_from_nat(
+            cs.namespace(|| "inverse"),
+            || {
+                let v = self.nat_value().grab()?.mod_floor(modulus);
+                if v == BigInt::from(0) {
+                    eprintln!("Cannot invert zero");
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+                Ok(v.modpow(&(modulus - 2), modulus))
+            },
+            &self.params,
+        )?;
+        let product = self.mult(cs.namespace(|| "product"), &inverse)?;
+        let one = Self::constant::<CS>(&BigInt::from(1), &self.params)?;
+        product.assert_equal_mod(cs.namespace(|| "is one"), &one)?;
+        Ok(inverse)
+    }
+}
+
+impl<Scalar: PrimeField> Gadget for SmallFieldElement<Scalar> {
+    type Scalar = Scalar;
+    type Value = Scalar;
+    type Params = SmallFieldParams;
+    type Access = ();
+    fn alloc<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        value: Option<&Self::Value>,
+        _access: (),
+        params: &Self::Params,
+    ) -> Result<Self, SynthesisError> {
+        let num = Num::alloc(cs.namespace(|| "num"), || Ok(**value.grab()?))?;
+        num.fits_in_bits(cs.namespace(|| "rangecheck"), params.modulus_bits)?;
+        Ok(Self {
+            num,
+            max_value: (BigInt::from(1) << params.modulus_bits as u32) - 1,
+            params: params.clone(),
+        })
+    }
+    fn value(&self) -> Option<&Scalar> {
+        self.num.value.as_ref()
+    }
+    fn wire_values(&self) -> Option<Vec<Scalar>> {
+        self.num.value.map(|v| vec![v])
+    }
+    fn params(&self) -> &SmallFieldParams {
+        &self.params
+    }
+    fn wires(&self) -> Vec<LinearCombination<Scalar>> {
+        vec![self.num.num.clone()]
+    }
+    fn access(&self) -> &() {
+        &()
+    }
+    fn mux<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        s: &Bit<Scalar>,
+        i0: &Self,
+        i1: &Self,
+    ) -> Result<Self, SynthesisError> {
+        i0.enforce_params_agreement(i1, "mux")?;
+        let value = s
+            .value
+            .and_then(|b| if b { i1.num.value } else { i0.num.value });
+        let out = Num::alloc(cs.namespace(|| "out"), || Ok(*value.grab()?))?;
+        cs.enforce(
+            || "select",
+            |lc| lc + &s.bit,
+            |lc| lc + &i1.num.num - &i0.num.num,
+            |lc| lc + &out.num - &i0.num.num,
+        );
+        Ok(Self {
+            num: out,
+            max_value: max(i0.max_value.clone(), i1.max_value.clone()),
+            params: i0.params.clone(),
+        })
+    }
+}
+
+impl<Scalar: PrimeField> Display for SmallFieldElement<Scalar> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.nat_value() {
+            Some(n) => write!(f, "SmallFieldElement({})", n),
+            None => write!(f, "SmallFieldElement(empty)"),
+        }
+    }
+}
+
+impl Debug for SmallFieldParams {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("SmallFieldParams")
+            .field("modulus", &format_args!("{}", &self.modulus))
+            .field("modulus_bits", &self.modulus_bits)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    pub struct SmallFieldMultInputs {
+        pub a: BigInt,
+        pub b: BigInt,
+        pub c: BigInt,
+        /// `a * b * c`, reduced
+        pub abc: BigInt,
+    }
+
+    pub struct SmallFieldMult {
+        inputs: Option<SmallFieldMultInputs>,
+        params: SmallFieldParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for SmallFieldMult {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = SmallFieldElement::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(self.inputs.grab()?.a.clone()),
+                &self.params,
+            )?;
+            let b = SmallFieldElement::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(self.inputs.grab()?.b.clone()),
+                &self.params,
+            )?;
+            let c = SmallFieldElement::alloc_from_nat(
+                cs.namespace(|| "c"),
+                || Ok(self.inputs.grab()?.c.clone()),
+                &self.params,
+            )?;
+            let abc = SmallFieldElement::alloc_from_nat(
+                cs.namespace(|| "abc"),
+                || Ok(self.inputs.grab()?.abc.clone()),
+                &self.params,
+            )?;
+            // Reduce eagerly
+            let ab = a.mult_mod(cs.namespace(|| "ab"), &b)?;
+            let eager = ab.mult_mod(cs.namespace(|| "ab * c"), &c)?;
+            eager.assert_equal_mod(cs.namespace(|| "eager"), &abc)?;
+            // Defer the reduction
+            let lazy = a
+                .mult(cs.namespace(|| "lazy ab"), &b)?
+                .mult(cs.namespace(|| "lazy ab * c"), &c)?
+                .reduce(cs.namespace(|| "reduce"))?;
+            lazy.assert_equal_mod(cs.namespace(|| "lazy"), &abc)?;
+            // (a - b) + b == a
+            let round_trip = a.sub::<CS>(&b)?.add(&b)?;
+            round_trip.assert_equal_mod(cs.namespace(|| "round trip"), &a)?;
+            // a * a^-1 == 1, canonically
+            let inv = a.inverse(cs.namespace(|| "inverse"))?;
+            let one = a
+                .mult_mod(cs.namespace(|| "a * inv"), &inv)?
+                .canonicalize(cs.namespace(|| "canonical"))?;
+            one.assert_equal_mod(
+                cs.namespace(|| "one"),
+                &SmallFieldElement::constant::<CS>(&BigInt::from(1), &self.params)?,
+            )?;
+            Ok(())
+        }
+    }
+
+    fn mult_case(params: SmallFieldParams, a: u64, b: u64, c: u64, wrong: bool) -> SmallFieldMult {
+        let (a, b, c) = (BigInt::from(a), BigInt::from(b), BigInt::from(c));
+        let mut abc = (&a * &b * &c) % &params.modulus;
+        if wrong {
+            abc += 1;
+        }
+        SmallFieldMult {
+            inputs: Some(SmallFieldMultInputs { a, b, c, abc }),
+            params,
+        }
+    }
+
+    pub struct SmallFieldAlloc {
+        value: BigInt,
+        params: SmallFieldParams,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for SmallFieldAlloc {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let value: Scalar = nat_to_f(&self.value).unwrap();
+            SmallFieldElement::alloc(cs.namespace(|| "a"), Some(&value), (), &self.params)?;
+            Ok(())
+        }
+    }
+
+    circuit_tests! {
+        small_field_alloc_baby_bear: (
+            SmallFieldAlloc { value: BigInt::from(0x7fff_ffffu64), params: SmallFieldParams::baby_bear() },
+            true
+        ),
+        small_field_alloc_baby_bear_too_large: (
+            SmallFieldAlloc { value: BigInt::from(0x8000_0000u64), params: SmallFieldParams::baby_bear() },
+            false
+        ),
+        small_field_goldilocks_mult: (
+            mult_case(SmallFieldParams::goldilocks(), 0xffff_ffff_0000_0000, 0xdead_beef_1234_5678, 0x1234_5678_9abc_def0, false),
+            true
+        ),
+        small_field_goldilocks_mult_wrong: (
+            mult_case(SmallFieldParams::goldilocks(), 0xffff_ffff_0000_0000, 0xdead_beef_1234_5678, 0x1234_5678_9abc_def0, true),
+            false
+        ),
+        small_field_baby_bear_mult: (
+            mult_case(SmallFieldParams::baby_bear(), 2013265920, 1234567, 7654321, false),
+            true
+        ),
+        small_field_baby_bear_mult_wrong: (
+            mult_case(SmallFieldParams::baby_bear(), 2013265920, 1234567, 7654321, true),
+            false
+        ),
+    }
+}
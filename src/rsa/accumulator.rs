@@ -0,0 +1,420 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::Zero;
+
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::OptionExt;
+
+/// The value `g^(x_1 ... x_k) mod n` of the accumulator of the elements `x_i`, computed natively.
+pub fn accumulate(n: &BigInt, g: &BigInt, elements: &[BigInt]) -> BigInt {
+    let x: BigInt = elements.iter().product();
+    g.modpow(&x, n)
+}
+
+/// A non-membership witness `(w, b)` of `x` for the accumulator of the elements `x_i`, computed
+/// natively: with `u = x_1 ... x_k` and Bezout coefficients `a x + b u = 1`, where `b` is reduced
+/// modulo `x`, `w = g^a mod n`, so that `w^x * A^b = g`. Returns `None` if `x` is not coprime to
+/// the elements, or if `g` is not invertible.
+pub fn non_membership_witness(
+    n: &BigInt,
+    g: &BigInt,
+    elements: &[BigInt],
+    x: &BigInt,
+) -> Option<(BigInt, BigInt)> {
+    let u: BigInt = elements.iter().product();
+    let gcd = u.extended_gcd(x);
+    if gcd.gcd != BigInt::from(1) {
+        return None;
+    }
+    let b = gcd.x.mod_floor(x);
+    let a = (BigInt::from(1) - &b * &u) / x;
+    let w = if a < BigInt::from(0) {
+        let inverse = g.extended_gcd(n);
+        if inverse.gcd != BigInt::from(1) {
+            return None;
+        }
+        inverse.x.mod_floor(n).modpow(&-a, n)
+    } else {
+        g.modpow(&a, n)
+    };
+    Some((w, b))
+}
+
+/// The proof `Q = u^(x / l) mod n` of Wesolowski's proof of exponentiation that
+/// `w = u^x mod n`, for `x = x_1 ... x_k` and the prime challenge `l`, computed natively.
+pub fn prove_exponentiation(n: &BigInt, u: &BigInt, factors: &[BigInt], l: &BigInt) -> BigInt {
+    let x: BigInt = factors.iter().product();
+    u.modpow(&(x / l), n)
+}
+
+/// The public parameters of an RSA accumulator: an allocated modulus `n` of unknown
+/// factorization, and a generator `g`. The accumulator of the elements `x_1, ..., x_k` is
+/// `A = g^(x_1 ... x_k) mod n`; the elements must be primes, e.g. hashed to primes, for the
+/// (non-)membership proofs to be sound.
+///
+/// Group elements have the limb layout of the modulus, with range checked limbs, and need not be
+/// reduced; elements and other exponents are well formed numbers with the same limb width.
+#[derive(Clone)]
+pub struct RsaAccumulator<Scalar: PrimeField> {
+    /// The modulus, with exactly `n_bits` bits
+    pub n: BigNat<Scalar>,
+    /// The generator
+    pub g: BigNat<Scalar>,
+    pub n_bits: usize,
+}
+
+impl<Scalar: PrimeField> RsaAccumulator<Scalar> {
+    /// Allocates the parameters with a modulus of `n_bits` bits, which must be a multiple of
+    /// `limb_width`. The modulus is range checked, with its most significant bit constrained to
+    /// be set, and the generator is range checked.
+    /// The value is provided by a closure returning the modulus and the generator.
+    pub fn alloc<CS, F>(
+        mut cs: CS,
+        f: F,
+        n_bits: usize,
+        limb_width: usize,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<(BigInt, BigInt), SynthesisError>,
+    {
+        if n_bits == 0 || !n_bits.is_multiple_of(limb_width) {
+            eprintln!(
+                "A modulus of {} bits does not fill limbs of {} bits",
+                n_bits, limb_width
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let value = f().ok();
+        let n_limbs = n_bits / limb_width;
+        let mut n = BigNat::alloc_from_nat(
+            cs.namespace(|| "n"),
+            || Ok(value.grab()?.0.clone()),
+            limb_width,
+            n_limbs,
+        )?;
+        n.assert_well_formed(cs.namespace(|| "n rangecheck"))?;
+        n.enforce_full_bits(cs.namespace(|| "n full"))?;
+        let g = BigNat::alloc_from_nat(
+            cs.namespace(|| "g"),
+            || Ok(value.grab()?.1.clone()),
+            limb_width,
+            n_limbs,
+        )?;
+        g.assert_well_formed(cs.namespace(|| "g rangecheck"))?;
+        Ok(Self { n, g, n_bits })
+    }
+
+    /// Allocates a group element with range checked limbs, with the limb layout of the modulus.
+    /// The value is provided by a closure returning the element as an integer.
+    pub fn alloc_element<CS, F>(&self, mut cs: CS, f: F) -> Result<BigNat<Scalar>, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let element = BigNat::alloc_from_nat(
+            cs.namespace(|| "element"),
+            f,
+            self.n.params.limb_width,
+            self.n.params.n_limbs,
+        )?;
+        element.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        Ok(element)
+    }
+
+    /// Constrain `witness` to be a membership witness of `element` for the accumulator `acc`,
+    /// i.e. `witness^element = acc mod n`.
+    pub fn verify_membership<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        acc: &BigNat<Scalar>,
+        element: &BigNat<Scalar>,
+        witness: &BigNat<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        let x = element.decompose(cs.namespace(|| "x bits"))?;
+        let power = witness.pow_mod_bits(cs.namespace(|| "w^x"), &x, &self.n)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "w^x == A"),
+            &[Term::Single(&power)],
+            &[Term::Single(acc)],
+            &self.n,
+        )
+    }
+
+    /// Constrain `(witness, b)` to be a non-membership witness of `element` for the accumulator
+    /// `acc`, i.e. `witness^element * acc^b = g mod n`. Since this gives Bezout coefficients of
+    /// the element and of the product of the accumulated elements, they are coprime. The prover
+    /// can reduce `b` modulo the element, so it is short (see `non_membership_witness`).
+    pub fn verify_non_membership<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        acc: &BigNat<Scalar>,
+        element: &BigNat<Scalar>,
+        witness: &BigNat<Scalar>,
+        b: &BigNat<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        let x = element.decompose(cs.namespace(|| "x bits"))?;
+        let b = b.decompose(cs.namespace(|| "b bits"))?;
+        let w_x = witness.pow_mod_bits(cs.namespace(|| "w^x"), &x, &self.n)?;
+        let a_b = acc.pow_mod_bits(cs.namespace(|| "A^b"), &b, &self.n)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "w^x A^b == g"),
+            &[Term::Product(&w_x, &a_b)],
+            &[Term::Single(&self.g)],
+            &self.n,
+        )
+    }
+
+    /// Constrain `result = base^(x_1 ... x_k) mod n` for the `factors` `x_i`, with Wesolowski's
+    /// proof of exponentiation `proof` for the prime `challenge` (see `prove_exponentiation`).
+    ///
+    /// The product of the factors is not computed: only its residue `r` modulo the challenge
+    /// `l`, from which `proof^l * base^r = result mod n` is checked. The challenge must be
+    /// derived from the statement, e.g. by hashing it to a prime, for the proof to be sound.
+    ///
+    /// This is a proof of exponentiation (PoE), in which the exponent is public, rather than a
+    /// proof of knowledge of the exponent (PoKE): the factors are inputs of the circuit, and the
+    /// proof only saves the computation of their product.
+    pub fn verify_exponentiation<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        base: &BigNat<Scalar>,
+        factors: &[BigNat<Scalar>],
+        result: &BigNat<Scalar>,
+        proof: &BigNat<Scalar>,
+        challenge: &BigNat<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        if factors.is_empty() {
+            eprintln!("Cannot prove an exponentiation by an empty product");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        if challenge.value.as_ref().is_some_and(|l| l.is_zero()) {
+            eprintln!("The challenge of a proof of exponentiation must be non-zero");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut r = BigNat::one::<CS>(challenge.params.limb_width);
+        for (i, factor) in factors.iter().enumerate() {
+            r = r
+                .mult_mod(cs.namespace(|| format!("r {}", i)), factor, challenge)?
+                .1;
+        }
+        let l = challenge.decompose(cs.namespace(|| "l bits"))?;
+        let r = r.decompose(cs.namespace(|| "r bits"))?;
+        let q_l = proof.pow_mod_bits(cs.namespace(|| "Q^l"), &l, &self.n)?;
+        let u_r = base.pow_mod_bits(cs.namespace(|| "u^r"), &r, &self.n)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "Q^l u^r == w"),
+            &[Term::Product(&q_l, &u_r)],
+            &[Term::Single(result)],
+            &self.n,
+        )
+    }
+
+    /// Constrain `witness` to be a membership witness of all the `elements` for the accumulator
+    /// `acc`, i.e. `witness^(x_1 ... x_k) = acc mod n`, with a proof of exponentiation (see
+    /// `verify_exponentiation`).
+    pub fn verify_batch_membership<CS: ConstraintSystem<Scalar>>(
+        &self,
+        cs: CS,
+        acc: &BigNat<Scalar>,
+        elements: &[BigNat<Scalar>],
+        witness: &BigNat<Scalar>,
+        proof: &BigNat<Scalar>,
+        challenge: &BigNat<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        self.verify_exponentiation(cs, witness, elements, acc, proof, challenge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsa::tests::*;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use crate::util::value_cs::ValueConstraintSystem;
+
+    /// Primes of 64, 61 and 30 bits, and a prime which is not accumulated.
+    const ELEMENTS: [u64; 3] = [0xffffffffffffffc5, 0x1fffffffffffffff, 1000000007];
+    const NON_ELEMENT: u64 = 998244353;
+
+    /// The modulus and the generator of the tests.
+    fn group() -> (BigInt, BigInt) {
+        (test_key(KEY_1024, 3).0, BigInt::from(3))
+    }
+
+    fn elements() -> Vec<BigInt> {
+        ELEMENTS.iter().map(|x| BigInt::from(*x)).collect()
+    }
+
+    /// The prime challenge `2^127 - 1`.
+    fn challenge() -> BigInt {
+        (BigInt::from(1) << 127u32) - 1
+    }
+
+    /// Allocates the well formed number `v`, with limbs of 64 bits.
+    fn alloc_number<CS: ConstraintSystem<Fr>>(
+        mut cs: CS,
+        v: &BigInt,
+    ) -> Result<BigNat<Fr>, SynthesisError> {
+        let n_limbs = (v.bits() as usize).div_ceil(64).max(1);
+        let v = BigNat::alloc_from_nat(cs.namespace(|| "value"), || Ok(v.clone()), 64, n_limbs)?;
+        v.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        Ok(v)
+    }
+
+    /// Runs `f` with the allocated parameters in a circuit, returning whether it is satisfied.
+    fn is_satisfied_with_params<F>(f: F) -> bool
+    where
+        F: FnOnce(
+            &mut ValueConstraintSystem<Fr>,
+            &RsaAccumulator<Fr>,
+        ) -> Result<(), SynthesisError>,
+    {
+        is_satisfied_by(|cs| {
+            let params =
+                RsaAccumulator::alloc(cs.namespace(|| "params"), || Ok(group()), 1024, 64)?;
+            f(cs, &params)
+        })
+    }
+
+    #[test]
+    fn accumulator_native() {
+        let (n, g) = group();
+        let acc = accumulate(&n, &g, &elements());
+        let witness = accumulate(&n, &g, &elements()[1..]);
+        assert_eq!(witness.modpow(&elements()[0], &n), acc);
+
+        let x = BigInt::from(NON_ELEMENT);
+        let (w, b) = non_membership_witness(&n, &g, &elements(), &x).unwrap();
+        assert!(b < x);
+        assert_eq!((w.modpow(&x, &n) * acc.modpow(&b, &n)).mod_floor(&n), g);
+        assert_eq!(
+            non_membership_witness(&n, &g, &elements(), &elements()[2]),
+            None
+        );
+
+        let l = challenge();
+        let q = prove_exponentiation(&n, &g, &elements(), &l);
+        let r = elements().iter().product::<BigInt>().mod_floor(&l);
+        assert_eq!((q.modpow(&l, &n) * g.modpow(&r, &n)).mod_floor(&n), acc);
+    }
+
+    /// Checks the membership witness of the first element for `x`.
+    fn membership(x: u64) -> bool {
+        let (n, g) = group();
+        let acc = accumulate(&n, &g, &elements());
+        let witness = accumulate(&n, &g, &elements()[1..]);
+        is_satisfied_with_params(|cs, params| {
+            let acc = params.alloc_element(cs.namespace(|| "A"), || Ok(acc))?;
+            let witness = params.alloc_element(cs.namespace(|| "w"), || Ok(witness))?;
+            let x = alloc_number(cs.namespace(|| "x"), &BigInt::from(x))?;
+            params.verify_membership(cs.namespace(|| "verify"), &acc, &x, &witness)
+        })
+    }
+
+    /// Checks the non-membership witness of `NON_ELEMENT`, with `tweak` added to `b`.
+    fn non_membership(tweak: u64) -> bool {
+        let (n, g) = group();
+        let acc = accumulate(&n, &g, &elements());
+        let x = BigInt::from(NON_ELEMENT);
+        let (witness, b) = non_membership_witness(&n, &g, &elements(), &x).unwrap();
+        is_satisfied_with_params(|cs, params| {
+            let acc = params.alloc_element(cs.namespace(|| "A"), || Ok(acc))?;
+            let witness = params.alloc_element(cs.namespace(|| "w"), || Ok(witness))?;
+            let x = alloc_number(cs.namespace(|| "x"), &x)?;
+            let b = alloc_number(cs.namespace(|| "b"), &(b + tweak))?;
+            params.verify_non_membership(cs.namespace(|| "verify"), &acc, &x, &witness, &b)
+        })
+    }
+
+    /// Checks the membership of all the elements in the accumulator of the elements and of
+    /// `NON_ELEMENT`, with `tweak` added to the proof.
+    fn batch_membership(tweak: u64) -> bool {
+        let (n, g) = group();
+        let all = [elements(), vec![BigInt::from(NON_ELEMENT)]].concat();
+        let acc = accumulate(&n, &g, &all);
+        let witness = accumulate(&n, &g, &all[3..]);
+        let proof = prove_exponentiation(&n, &witness, &elements(), &challenge()) + tweak;
+        is_satisfied_with_params(|cs, params| {
+            let acc = params.alloc_element(cs.namespace(|| "A"), || Ok(acc))?;
+            let witness = params.alloc_element(cs.namespace(|| "w"), || Ok(witness))?;
+            let proof = params.alloc_element(cs.namespace(|| "Q"), || Ok(proof))?;
+            let elements = elements()
+                .iter()
+                .enumerate()
+                .map(|(i, x)| alloc_number(cs.namespace(|| format!("x {}", i)), x))
+                .collect::<Result<Vec<_>, _>>()?;
+            let l = alloc_number(cs.namespace(|| "l"), &challenge())?;
+            params.verify_batch_membership(
+                cs.namespace(|| "verify"),
+                &acc,
+                &elements,
+                &witness,
+                &proof,
+                &l,
+            )
+        })
+    }
+
+    #[test]
+    fn accumulator_membership() {
+        assert!(membership(ELEMENTS[0]));
+    }
+
+    #[test]
+    fn accumulator_membership_wrong_element() {
+        assert!(!membership(NON_ELEMENT));
+    }
+
+    #[test]
+    fn accumulator_non_membership() {
+        assert!(non_membership(0));
+    }
+
+    #[test]
+    fn accumulator_non_membership_wrong_coefficient() {
+        assert!(!non_membership(1));
+    }
+
+    #[test]
+    fn accumulator_batch_membership() {
+        assert!(batch_membership(0));
+    }
+
+    #[test]
+    fn accumulator_batch_membership_wrong_proof() {
+        assert!(!batch_membership(1));
+    }
+
+    #[test]
+    fn accumulator_exponentiation_zero_challenge() {
+        let (n, g) = group();
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let params =
+            RsaAccumulator::alloc(cs.namespace(|| "params"), || Ok(group()), 1024, 64).unwrap();
+        let base = params
+            .alloc_element(cs.namespace(|| "u"), || Ok(g.clone()))
+            .unwrap();
+        let result = params
+            .alloc_element(cs.namespace(|| "w"), || Ok(g))
+            .unwrap();
+        let proof = params
+            .alloc_element(cs.namespace(|| "Q"), || Ok(n))
+            .unwrap();
+        let x = alloc_number(cs.namespace(|| "x"), &BigInt::from(1)).unwrap();
+        let l = alloc_number(cs.namespace(|| "l"), &BigInt::from(0)).unwrap();
+        let verified = params.verify_exponentiation(
+            cs.namespace(|| "verify"),
+            &base,
+            &[x],
+            &result,
+            &proof,
+            &l,
+        );
+        assert!(verified.is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+
+use crate::mp::bignat::BigNat;
+
+pub mod accumulator;
+pub mod pkcs1;
+pub mod pss;
+pub mod vdf;
+
+/// An RSA public key `(n, e)`, with an allocated modulus and a constant public exponent.
+#[derive(Clone)]
+pub struct RsaPublicKey<Scalar: PrimeField> {
+    /// The modulus, with exactly `n_bits` bits
+    pub n: BigNat<Scalar>,
+    /// The public exponent
+    pub e: BigInt,
+    pub n_bits: usize,
+}
+
+impl<Scalar: PrimeField> RsaPublicKey<Scalar> {
+    /// Allocates a key with a modulus of `n_bits` bits, which must be a multiple of `limb_width`,
+    /// and the public exponent `e`. The modulus is range checked, with its most significant bit
+    /// constrained to be set.
+    /// The value is provided by a closure returning the modulus.
+    pub fn alloc<CS, F>(
+        mut cs: CS,
+        f: F,
+        e: &BigInt,
+        n_bits: usize,
+        limb_width: usize,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        if n_bits == 0 || !n_bits.is_multiple_of(limb_width) {
+            eprintln!(
+                "A modulus of {} bits does not fill limbs of {} bits",
+                n_bits, limb_width
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut n =
+            BigNat::alloc_from_nat(cs.namespace(|| "n"), f, limb_width, n_bits / limb_width)?;
+        n.assert_well_formed(cs.namespace(|| "n rangecheck"))?;
+        n.enforce_full_bits(cs.namespace(|| "n full"))?;
+        Ok(Self {
+            n,
+            e: e.clone(),
+            n_bits,
+        })
+    }
+
+    /// The length `k` of the modulus in bytes.
+    pub fn n_bytes(&self) -> usize {
+        self.n_bits.div_ceil(8)
+    }
+
+    /// Allocates a signature representative with range checked limbs, with the limb layout of
+    /// the modulus; `s < n` is enforced by `rsavp1`.
+    /// The value is provided by a closure returning the signature as an integer.
+    pub fn alloc_signature<CS, F>(&self, mut cs: CS, f: F) -> Result<BigNat<Scalar>, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let s = BigNat::alloc_from_nat(
+            cs.namespace(|| "s"),
+            f,
+            self.n.params.limb_width,
+            self.n.params.n_limbs,
+        )?;
+        s.assert_well_formed(cs.namespace(|| "s rangecheck"))?;
+        Ok(s)
+    }
+
+    /// Computes the message representative `m = s^e mod n` of the signature representative `s`
+    /// (RSAVP1, RFC 8017, section 5.2.2). `s < n` is enforced, and `m` is constrained to be
+    /// reduced.
+    pub fn rsavp1<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        signature: &BigNat<Scalar>,
+    ) -> Result<BigNat<Scalar>, SynthesisError> {
+        signature.assert_canonical(cs.namespace(|| "s < n"), &self.n)?;
+        let m = signature.pow_mod(cs.namespace(|| "s^e"), &self.e, &self.n)?;
+        m.assert_canonical(cs.namespace(|| "m < n"), &self.n)?;
+        Ok(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::scalar::Fr;
+    use crate::util::value_cs::ValueConstraintSystem;
+    use num_integer::Integer;
+    use num_traits::Num;
+
+    /// Test keys of 1024 and 2048 bits, as their prime factors. All the primes are `2 mod 3`, so
+    /// that the exponents 3 and 65537 are valid.
+    pub(super) const KEY_1024: [&str; 2] = [
+        "c1c690918d10d79fd61645f6610f8b4836bc696f1320d12373044b1bedb97c96d4d268218c84bba768a2733f6a8df002e0f0b785b3c146689737f25f9d5a113f",
+        "f179c5b2a60bcbbf0644e833433baa2d6a544c989ff19f15841b3b60f163dad1fa2553a3e93816b873a3be004b089db5e8d94e990c6d254e3ea9dcec64a2cb65",
+    ];
+    pub(super) const KEY_2048: [&str; 2] = [
+        "ed3cf627799bf7fa532db200200e08d82a248192d11ccd1ad9d81b74493b8d453dcfef48ba0609928b4f863ac1bd1db2829ce592d05db8b253457009a94084373d3a35f22e01a570580eb4e7b80217dbe79fc5c48fdc6d645a6652fb182e735be3d549cc5b3e9944d10fd9a1657a0477c6b9b0112168728413afd3e3594ed705",
+        "cb1c836ebac6302915146aa0e9b88efcd1f31f9b410c14e2ee454fc569f1fde64369f38ee959e54019c4970650d5a4555e0b5ea19d447ab1e1cde32e67c30b4c923c5ee03ef068e116a97cb86c9da4fe58d09de3a6e71a035ee749e86643e4a542f31acf131c7df47a39af375eb910a244244536f2e90ecf5084d4c32537808d",
+    ];
+
+    /// The modulus `n`, and the public and private exponents `e` and `d`, of the key with the
+    /// prime factors `key`.
+    pub(super) fn test_key(key: [&str; 2], e: u64) -> (BigInt, BigInt, BigInt) {
+        let [p, q] = key.map(|f| BigInt::from_str_radix(f, 16).unwrap());
+        let n = &p * &q;
+        let e = BigInt::from(e);
+        let lambda = (p - 1u32).lcm(&(q - 1u32));
+        let d = e.extended_gcd(&lambda).x.mod_floor(&lambda);
+        (n, e, d)
+    }
+
+    /// Computes `s^e mod n` in a circuit, returning the result if the circuit is satisfied.
+    fn rsavp1(n: &BigInt, e: &BigInt, s: &BigInt) -> Option<BigInt> {
+        let mut cs = ValueConstraintSystem::<Fr>::new();
+        let m = RsaPublicKey::alloc(cs.namespace(|| "key"), || Ok(n.clone()), e, 1024, 64)
+            .and_then(|key| {
+                let s = key.alloc_signature(cs.namespace(|| "s"), || Ok(s.clone()))?;
+                key.rsavp1(cs.namespace(|| "rsavp1"), &s)
+            })
+            .ok()?;
+        cs.is_satisfied().then_some(m.value?)
+    }
+
+    #[test]
+    fn rsavp1_e3() {
+        let (n, e, _) = test_key(KEY_1024, 3);
+        let s = BigInt::from(0x1234567890abcdefu64) << 900;
+        assert_eq!(rsavp1(&n, &e, &s), Some(s.modpow(&e, &n)));
+    }
+
+    #[test]
+    fn rsavp1_unreduced_signature() {
+        let (n, e, _) = test_key(KEY_1024, 3);
+        assert_eq!(rsavp1(&n, &e, &(&n + 2u32)), None);
+    }
+}
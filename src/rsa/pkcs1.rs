@@ -0,0 +1,162 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::{BigInt, Sign};
+
+use super::RsaPublicKey;
+use crate::ec::compress::be_bytes_to_bits;
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::util::bit::Bitvector;
+
+/// The DER encoding of the `DigestInfo` of a SHA-256 digest, up to the digest (RFC 8017,
+/// section 9.2, note 1).
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// The length of a SHA-256 digest, in bytes.
+const DIGEST_BYTES: usize = 32;
+
+/// The EMSA-PKCS1-v1_5 encoding in `k` bytes of the SHA-256 digest `digest` (RFC 8017,
+/// section 9.2), `0x00 0x01 FF..FF 0x00 || DigestInfo || digest`, computed natively. Returns
+/// `None` if `k` is too short.
+pub fn emsa_pkcs1_v1_5(digest: &[u8], k: usize) -> Option<Vec<u8>> {
+    let t_len = SHA256_DIGEST_INFO.len() + digest.len();
+    if k < t_len + 11 {
+        return None;
+    }
+    let mut em = vec![0x00, 0x01];
+    em.resize(k - t_len - 1, 0xff);
+    em.push(0x00);
+    em.extend(SHA256_DIGEST_INFO);
+    em.extend(digest);
+    Some(em)
+}
+
+impl<Scalar: PrimeField> RsaPublicKey<Scalar> {
+    /// Constrain `signature` to be a valid RSASSA-PKCS1-v1_5 signature with SHA-256 (RFC 8017,
+    /// section 8.2.2) of a message with the digest `digest`, given as the bits of its 32 bytes,
+    /// each byte's most significant bit first.
+    ///
+    /// The encoding is the sum of a constant, for the padding and the `DigestInfo`, and of the
+    /// digest as an integer. Since the message representative is reduced and the encoding is
+    /// less than `n`, they are equal if they are congruent modulo `n`.
+    pub fn verify_pkcs1_v1_5<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        signature: &BigNat<Scalar>,
+        digest: &Bitvector<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        if digest.bits.len() != 8 * DIGEST_BYTES {
+            eprintln!(
+                "A SHA-256 digest has {} bits, not {}",
+                digest.bits.len(),
+                8 * DIGEST_BYTES
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let Some(padding) = emsa_pkcs1_v1_5(&[0; DIGEST_BYTES], self.n_bytes()) else {
+            eprintln!("A modulus of {} bits is too short", self.n_bits);
+            return Err(SynthesisError::Unsatisfiable);
+        };
+        let (limb_width, n_limbs) = (self.n.params.limb_width, self.n.params.n_limbs);
+        let padding = BigNat::constant::<CS>(
+            &BigInt::from_bytes_be(Sign::Plus, &padding),
+            limb_width,
+            n_limbs,
+        )?;
+        let digest = BigNat::recompose(&be_bytes_to_bits(digest), limb_width);
+        let m = self.rsavp1(cs.namespace(|| "rsavp1"), signature)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "encoding"),
+            &[Term::Single(&m)],
+            &[Term::Single(&padding), Term::Single(&digest)],
+            &self.n,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::sha256::sha256;
+    use crate::rsa::tests::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn emsa_pkcs1_v1_5_layout() {
+        let digest = sha256(b"abc");
+        let em = emsa_pkcs1_v1_5(&digest, 256).unwrap();
+        assert_eq!(em.len(), 256);
+        assert_eq!(&em[..2], &[0x00, 0x01]);
+        assert!(em[2..204].iter().all(|b| *b == 0xff));
+        assert_eq!(em[204], 0x00);
+        assert_eq!(&em[205..224], &SHA256_DIGEST_INFO);
+        assert_eq!(&em[224..], &digest);
+        assert_eq!(emsa_pkcs1_v1_5(&digest, 61), None);
+    }
+
+    pub struct Pkcs1 {
+        n: BigInt,
+        e: BigInt,
+        n_bits: usize,
+        signature: BigInt,
+        digest: Vec<u8>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Pkcs1 {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let key = RsaPublicKey::alloc(
+                cs.namespace(|| "key"),
+                || Ok(self.n.clone()),
+                &self.e,
+                self.n_bits,
+                64,
+            )?;
+            let signature =
+                key.alloc_signature(cs.namespace(|| "signature"), || Ok(self.signature.clone()))?;
+            let digest = Bitvector::alloc_bytes(
+                cs.namespace(|| "digest"),
+                Some(&self.digest),
+                DIGEST_BYTES,
+            )?;
+            key.verify_pkcs1_v1_5(cs.namespace(|| "verify"), &signature, &digest)
+        }
+    }
+
+    /// A signature of `message` with the key of factors `key` and the exponent `e`, checked
+    /// against the digest of `checked`.
+    fn pkcs1(key: [&str; 2], e: u64, message: &[u8], checked: &[u8]) -> Pkcs1 {
+        let (n, e, d) = test_key(key, e);
+        let n_bits = n.bits() as usize;
+        let em = emsa_pkcs1_v1_5(&sha256(message), n_bits / 8).unwrap();
+        let signature = BigInt::from_bytes_be(Sign::Plus, &em).modpow(&d, &n);
+        assert_eq!(signature.modpow(&e, &n).to_bytes_be().1, em[1..]);
+        Pkcs1 {
+            n,
+            e,
+            n_bits,
+            signature,
+            digest: sha256(checked).to_vec(),
+        }
+    }
+
+    #[test]
+    fn rsa_pkcs1_v1_5_2048() {
+        assert!(is_satisfied(pkcs1(KEY_2048, 65537, b"hello", b"hello")));
+    }
+
+    #[test]
+    fn rsa_pkcs1_v1_5_2048_wrong_digest() {
+        assert!(!is_satisfied(pkcs1(KEY_2048, 65537, b"hello", b"hellp")));
+    }
+
+    #[test]
+    fn rsa_pkcs1_v1_5_1024_e3() {
+        assert!(is_satisfied(pkcs1(KEY_1024, 3, b"hello", b"hello")));
+    }
+}
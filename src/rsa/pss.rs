@@ -0,0 +1,313 @@
+use bellpepper::gadgets::boolean::Boolean;
+use bellpepper::gadgets::sha256::sha256 as sha256_circuit;
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+
+use super::RsaPublicKey;
+use crate::ec::compress::be_bytes_to_bits;
+use crate::hash::sha256::sha256;
+use crate::hash::{alloc_bits, constant_bits, to_bitvector, to_booleans};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+use crate::util::bit::Bitvector;
+
+/// The length of a SHA-256 digest, in bytes.
+const H_LEN: usize = 32;
+
+/// The last byte of an EMSA-PSS encoding.
+const TRAILER: u8 = 0xbc;
+
+/// Computes MGF1 with SHA-256 (RFC 8017, appendix B.2.1) of `seed` natively, with an output of
+/// `mask_len` bytes.
+pub fn native_mgf1(seed: &[u8], mask_len: usize) -> Vec<u8> {
+    let mut mask = Vec::new();
+    for counter in 0..mask_len.div_ceil(H_LEN) as u32 {
+        let mut input = seed.to_vec();
+        input.extend(counter.to_be_bytes());
+        mask.extend(sha256(&input));
+    }
+    mask.truncate(mask_len);
+    mask
+}
+
+/// Computes MGF1 with SHA-256 (RFC 8017, appendix B.2.1) of `seed`, given as the bits of its
+/// bytes, each byte's most significant bit first, with an output of `mask_len` bytes, given in
+/// the same way.
+pub fn mgf1<Scalar: PrimeField, CS: ConstraintSystem<Scalar>>(
+    mut cs: CS,
+    seed: &[Boolean],
+    mask_len: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut mask = Vec::new();
+    for counter in 0..mask_len.div_ceil(H_LEN) as u32 {
+        let mut input = seed.to_vec();
+        input.extend(constant_bits(&counter.to_be_bytes()));
+        mask.extend(sha256_circuit(
+            cs.namespace(|| format!("block {}", counter)),
+            &input,
+        )?);
+    }
+    mask.truncate(8 * mask_len);
+    Ok(mask)
+}
+
+/// The EMSA-PSS encoding in `em_bits` bits of the SHA-256 digest `digest` with the salt `salt`
+/// (RFC 8017, section 9.1.1), `maskedDB || H || 0xbc`, computed natively. Returns `None` if
+/// `em_bits` is too short.
+pub fn emsa_pss_encode(digest: &[u8], salt: &[u8], em_bits: usize) -> Option<Vec<u8>> {
+    let em_len = em_bits.div_ceil(8);
+    if em_len < digest.len() + salt.len() + 2 {
+        return None;
+    }
+    let mut m_prime = vec![0u8; 8];
+    m_prime.extend(digest);
+    m_prime.extend(salt);
+    let h = sha256(&m_prime);
+    let db_len = em_len - H_LEN - 1;
+    let mut db = vec![0u8; db_len - salt.len() - 1];
+    db.push(0x01);
+    db.extend(salt);
+    let mut em: Vec<u8> = db
+        .iter()
+        .zip(native_mgf1(&h, db_len))
+        .map(|(d, m)| d ^ m)
+        .collect();
+    em[0] &= 0xff >> (8 * em_len - em_bits);
+    em.extend(h);
+    em.push(TRAILER);
+    Some(em)
+}
+
+impl<Scalar: PrimeField> RsaPublicKey<Scalar> {
+    /// Constrain `signature` to be a valid RSASSA-PSS signature with SHA-256 and MGF1 with
+    /// SHA-256 (RFC 8017, section 8.1.2) of a message with the digest `digest`, given as the bits
+    /// of its 32 bytes, each byte's most significant bit first, for salts of `salt_len` bytes.
+    /// Returns the salt, given in the same way.
+    ///
+    /// The encoded message is allocated as bits, which are tied to the message representative
+    /// by recomposition: with its leftmost bit zero, the encoding is less than `n`, so it is
+    /// equal to the message representative if they are congruent modulo `n`.
+    pub fn verify_pss<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        signature: &BigNat<Scalar>,
+        digest: &Bitvector<Scalar>,
+        salt_len: usize,
+    ) -> Result<Bitvector<Scalar>, SynthesisError> {
+        if digest.bits.len() != 8 * H_LEN {
+            eprintln!(
+                "A SHA-256 digest has {} bits, not {}",
+                digest.bits.len(),
+                8 * H_LEN
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let em_bits = self.n_bits - 1;
+        let em_len = em_bits.div_ceil(8);
+        if em_len < H_LEN + salt_len + 2 {
+            eprintln!(
+                "A modulus of {} bits is too short for a salt of {} bytes",
+                self.n_bits, salt_len
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let m = self.rsavp1(cs.namespace(|| "rsavp1"), signature)?;
+
+        // EM = I2OSP(m, emLen), with its leftmost 8 * emLen - emBits bits zero
+        let em_value = m.value.as_ref().map(|m| {
+            let mut bytes = m.to_bytes_be().1;
+            if bytes.len() < em_len {
+                bytes.splice(0..0, vec![0; em_len - bytes.len()]);
+            }
+            bytes.split_off(bytes.len() - em_len)
+        });
+        let em = alloc_bits(cs.namespace(|| "em bits"), em_value.as_deref(), em_len)?;
+        let zero_bits = 8 * em_len - em_bits;
+        for (i, bit) in em[..zero_bits].iter().enumerate() {
+            Boolean::enforce_equal(
+                cs.namespace(|| format!("em bit {} zero", i)),
+                bit,
+                &Boolean::constant(false),
+            )?;
+        }
+        let em_nat = BigNat::recompose(
+            &be_bytes_to_bits(&to_bitvector::<Scalar, CS>(&em)),
+            self.n.params.limb_width,
+        );
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "em congruent"),
+            &[Term::Single(&m)],
+            &[Term::Single(&em_nat)],
+            &self.n,
+        )?;
+
+        // EM = maskedDB || H || 0xbc
+        let db_len = em_len - H_LEN - 1;
+        let (masked_db, rest) = em.split_at(8 * db_len);
+        let (h, trailer) = rest.split_at(8 * H_LEN);
+        for (i, (bit, expected)) in trailer.iter().zip(constant_bits(&[TRAILER])).enumerate() {
+            Boolean::enforce_equal(
+                cs.namespace(|| format!("trailer bit {}", i)),
+                bit,
+                &expected,
+            )?;
+        }
+
+        // DB = maskedDB xor MGF1(H) = PS || 0x01 || salt, where the leftmost bits of DB, which
+        // are those of EM, are ignored. Each bit of maskedDB before the salt is the mask bit,
+        // flipped where DB has a one.
+        let db_mask = mgf1(cs.namespace(|| "mgf1"), h, db_len)?;
+        let ps_len = db_len - salt_len - 1;
+        let mut padding = vec![0u8; ps_len];
+        padding.push(0x01);
+        for (i, expected) in constant_bits(&padding)
+            .into_iter()
+            .enumerate()
+            .skip(zero_bits)
+        {
+            let mask_bit = if expected.get_value() == Some(true) {
+                db_mask[i].not()
+            } else {
+                db_mask[i].clone()
+            };
+            Boolean::enforce_equal(
+                cs.namespace(|| format!("padding bit {}", i)),
+                &masked_db[i],
+                &mask_bit,
+            )?;
+        }
+        let salt = (8 * (ps_len + 1)..8 * db_len)
+            .map(|i| {
+                Boolean::xor(
+                    cs.namespace(|| format!("salt bit {}", i)),
+                    &masked_db[i],
+                    &db_mask[i],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // H = Hash(0x00 * 8 || mHash || salt)
+        let mut m_prime = constant_bits(&[0; 8]);
+        m_prime.extend(to_booleans(cs.namespace(|| "digest"), digest)?);
+        m_prime.extend_from_slice(&salt);
+        let h_prime = sha256_circuit(cs.namespace(|| "m prime"), &m_prime)?;
+        for (i, (a, b)) in h.iter().zip(&h_prime).enumerate() {
+            Boolean::enforce_equal(cs.namespace(|| format!("hash bit {}", i)), a, b)?;
+        }
+        Ok(to_bitvector::<Scalar, CS>(&salt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsa::tests::*;
+    use crate::util::test_helpers::*;
+    use num_bigint::{BigInt, Sign};
+
+    #[test]
+    fn mgf1_lengths() {
+        let mask = native_mgf1(b"seed", 100);
+        assert_eq!(mask.len(), 100);
+        assert_eq!(&mask[..32], &sha256(b"seed\x00\x00\x00\x00"));
+        assert_eq!(&mask[96..], &sha256(b"seed\x00\x00\x00\x03")[..4]);
+    }
+
+    pub struct Pss {
+        n: BigInt,
+        e: BigInt,
+        n_bits: usize,
+        signature: BigInt,
+        digest: Vec<u8>,
+        salt: Vec<u8>,
+        salt_len: usize,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for Pss {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let key = RsaPublicKey::alloc(
+                cs.namespace(|| "key"),
+                || Ok(self.n.clone()),
+                &self.e,
+                self.n_bits,
+                64,
+            )?;
+            let signature =
+                key.alloc_signature(cs.namespace(|| "signature"), || Ok(self.signature.clone()))?;
+            let digest =
+                Bitvector::alloc_bytes(cs.namespace(|| "digest"), Some(&self.digest), H_LEN)?;
+            let salt = key.verify_pss(
+                cs.namespace(|| "verify"),
+                &signature,
+                &digest,
+                self.salt_len,
+            )?;
+            let salt_bytes: Option<Vec<u8>> = salt.values.map(|bits| {
+                bits.chunks(8)
+                    .map(|byte| byte.iter().fold(0, |acc, b| (acc << 1) | *b as u8))
+                    .collect()
+            });
+            if self.salt_len == self.salt.len() {
+                assert_eq!(salt_bytes, Some(self.salt));
+            }
+            Ok(())
+        }
+    }
+
+    /// A signature of `message` with the salt `salt`, the key of factors `key` and the exponent
+    /// `e`, checked against the digest of `checked` for salts of `salt_len` bytes.
+    fn pss(
+        key: [&str; 2],
+        e: u64,
+        message: &[u8],
+        salt: &[u8],
+        checked: &[u8],
+        salt_len: usize,
+    ) -> Pss {
+        let (n, e, d) = test_key(key, e);
+        let n_bits = n.bits() as usize;
+        let em = emsa_pss_encode(&sha256(message), salt, n_bits - 1).unwrap();
+        let signature = BigInt::from_bytes_be(Sign::Plus, &em).modpow(&d, &n);
+        Pss {
+            n,
+            e,
+            n_bits,
+            signature,
+            digest: sha256(checked).to_vec(),
+            salt: salt.to_vec(),
+            salt_len,
+        }
+    }
+
+    #[test]
+    fn rsa_pss_2048() {
+        let salt: Vec<u8> = (0..32).collect();
+        assert!(is_satisfied(pss(
+            KEY_2048, 65537, b"hello", &salt, b"hello", 32
+        )));
+    }
+
+    #[test]
+    fn rsa_pss_2048_wrong_digest() {
+        let salt: Vec<u8> = (0..32).collect();
+        assert!(!is_satisfied(pss(
+            KEY_2048, 65537, b"hello", &salt, b"hellp", 32
+        )));
+    }
+
+    #[test]
+    fn rsa_pss_1024_empty_salt() {
+        assert!(is_satisfied(pss(KEY_1024, 3, b"hello", &[], b"hello", 0)));
+    }
+
+    #[test]
+    fn rsa_pss_1024_wrong_salt_length() {
+        let salt: Vec<u8> = (0..20).collect();
+        assert!(!is_satisfied(pss(
+            KEY_1024, 3, b"hello", &salt, b"hello", 32
+        )));
+    }
+}
@@ -0,0 +1,246 @@
+use bellpepper_core::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::One;
+
+use crate::hash::hash_to_prime::{hash_to_prime, native_hash_to_prime};
+use crate::hash::{constant_bits, to_booleans};
+use crate::mp::bignat::BigNat;
+use crate::mp::modular::Term;
+
+/// The number of bits of the digest of the statement hashed to the challenge prime.
+const CHALLENGE_ENTROPY: usize = 128;
+
+/// The big-endian bytes of `v`, left-padded to `n_bytes` bytes.
+fn to_bytes(v: &BigInt, n_bytes: usize) -> Vec<u8> {
+    let bytes = v.to_bytes_be().1;
+    [vec![0u8; n_bytes.saturating_sub(bytes.len())], bytes].concat()
+}
+
+/// The input `n || x || y || T` hashed to the challenge prime, for a modulus of `n_bits` bits.
+fn challenge_input(n: &BigInt, n_bits: usize, x: &BigInt, y: &BigInt, t: u64) -> Vec<u8> {
+    let n_bytes = n_bits / 8;
+    [
+        to_bytes(n, n_bytes),
+        to_bytes(x, n_bytes),
+        to_bytes(y, n_bytes),
+        t.to_be_bytes().to_vec(),
+    ]
+    .concat()
+}
+
+/// The challenge prime `l` of the statement `y = x^(2^T) mod n`, for a modulus of `n_bits` bits,
+/// computed natively.
+pub fn challenge(n: &BigInt, n_bits: usize, x: &BigInt, y: &BigInt, t: u64) -> BigInt {
+    native_hash_to_prime(&challenge_input(n, n_bits, x, y, t), CHALLENGE_ENTROPY)
+}
+
+/// Evaluates the VDF, `y = x^(2^T) mod n`, natively, by `T` squarings.
+pub fn evaluate(n: &BigInt, x: &BigInt, t: u64) -> BigInt {
+    (0..t).fold(x.mod_floor(n), |y, _| (&y * &y).mod_floor(n))
+}
+
+/// The output `y` and Wesolowski's proof `pi = x^(2^T / l) mod n` of the VDF for `x`, with a
+/// modulus of `n_bits` bits, computed natively. The proof is computed by long division of `2^T`
+/// by the challenge `l`, one bit at a time.
+pub fn prove(n: &BigInt, n_bits: usize, x: &BigInt, t: u64) -> (BigInt, BigInt) {
+    let y = evaluate(n, x, t);
+    let l = challenge(n, n_bits, x, &y, t);
+    let mut pi = BigInt::one();
+    let mut r = BigInt::one();
+    for _ in 0..t {
+        let (b, rest) = (&r << 1u32).div_rem(&l);
+        pi = (&pi * &pi).mod_floor(n);
+        if b.is_one() {
+            pi = (pi * x).mod_floor(n);
+        }
+        r = rest;
+    }
+    (y, pi)
+}
+
+/// Verifies Wesolowski's proof `pi` of the VDF output `y` for `x`, natively.
+pub fn native_verify(
+    n: &BigInt,
+    n_bits: usize,
+    x: &BigInt,
+    y: &BigInt,
+    t: u64,
+    pi: &BigInt,
+) -> bool {
+    if x >= n || y >= n {
+        return false;
+    }
+    let l = challenge(n, n_bits, x, y, t);
+    let r = BigInt::from(2).modpow(&BigInt::from(t), &l);
+    (pi.modpow(&l, n) * x.modpow(&r, n)).mod_floor(n) == *y
+}
+
+/// The public parameters of a Wesolowski VDF in the RSA group of an allocated modulus `n` of
+/// unknown factorization, with `T` squarings: the output for the input `x` is
+/// `y = x^(2^T) mod n`.
+///
+/// Group elements have the limb layout of the modulus, with range checked limbs.
+#[derive(Clone)]
+pub struct WesolowskiVdf<Scalar: PrimeField> {
+    /// The modulus, with exactly `n_bits` bits
+    pub n: BigNat<Scalar>,
+    pub n_bits: usize,
+    /// The number of squarings `T`
+    pub t: u64,
+}
+
+impl<Scalar: PrimeField> WesolowskiVdf<Scalar> {
+    /// Allocates the parameters with a modulus of `n_bits` bits, which must be a multiple of
+    /// `limb_width` and of 8, and `t` squarings. The modulus is range checked, with its most
+    /// significant bit constrained to be set.
+    /// The value is provided by a closure returning the modulus.
+    pub fn alloc<CS, F>(
+        mut cs: CS,
+        f: F,
+        n_bits: usize,
+        limb_width: usize,
+        t: u64,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        if n_bits == 0 || !n_bits.is_multiple_of(limb_width) || !n_bits.is_multiple_of(8) {
+            eprintln!(
+                "A modulus of {} bits does not fill bytes and limbs of {} bits",
+                n_bits, limb_width
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        if t == 0 {
+            eprintln!("A VDF needs at least one squaring");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut n =
+            BigNat::alloc_from_nat(cs.namespace(|| "n"), f, limb_width, n_bits / limb_width)?;
+        n.assert_well_formed(cs.namespace(|| "n rangecheck"))?;
+        n.enforce_full_bits(cs.namespace(|| "n full"))?;
+        Ok(Self { n, n_bits, t })
+    }
+
+    /// Allocates a group element with range checked limbs, with the limb layout of the modulus.
+    /// The value is provided by a closure returning the element as an integer.
+    pub fn alloc_element<CS, F>(&self, mut cs: CS, f: F) -> Result<BigNat<Scalar>, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+        F: FnOnce() -> Result<BigInt, SynthesisError>,
+    {
+        let element = BigNat::alloc_from_nat(
+            cs.namespace(|| "element"),
+            f,
+            self.n.params.limb_width,
+            self.n.params.n_limbs,
+        )?;
+        element.assert_well_formed(cs.namespace(|| "rangecheck"))?;
+        Ok(element)
+    }
+
+    /// Constrain `proof` to be Wesolowski's proof that `y = x^(2^T) mod n` (see `prove`).
+    ///
+    /// The input and the output are constrained to be reduced, and hashed with the modulus and
+    /// `T` to the challenge prime `l` (see `hash_to_prime`), from which `r = 2^T mod l` is
+    /// computed and `proof^l * x^r = y mod n` is checked.
+    pub fn verify<CS: ConstraintSystem<Scalar>>(
+        &self,
+        mut cs: CS,
+        x: &BigNat<Scalar>,
+        y: &BigNat<Scalar>,
+        proof: &BigNat<Scalar>,
+    ) -> Result<(), SynthesisError> {
+        let limb_width = self.n.params.limb_width;
+        x.assert_canonical(cs.namespace(|| "x canonical"), &self.n)?;
+        y.assert_canonical(cs.namespace(|| "y canonical"), &self.n)?;
+        let modulus_bits = self.n.decompose(cs.namespace(|| "n bits"))?;
+        let x_bits = x.decompose(cs.namespace(|| "x bits"))?;
+        let y_bits = y.decompose(cs.namespace(|| "y bits"))?;
+
+        // l = H(n || x || y || T), as the modulus is witnessed by the prover
+        let mut input = to_booleans(cs.namespace(|| "n bytes"), &modulus_bits.reversed())?;
+        input.extend(to_booleans(cs.namespace(|| "x bytes"), &x_bits.reversed())?);
+        input.extend(to_booleans(cs.namespace(|| "y bytes"), &y_bits.reversed())?);
+        input.extend(constant_bits(&self.t.to_be_bytes()));
+        let l = hash_to_prime(cs.namespace(|| "l"), &input, CHALLENGE_ENTROPY, limb_width)?;
+
+        let two = BigNat::constant::<CS>(&BigInt::from(2), limb_width, 1)?;
+        let r = two.pow_mod(cs.namespace(|| "2^T"), &BigInt::from(self.t), &l)?;
+        let l_bits = l.decompose(cs.namespace(|| "l bits"))?;
+        let r_bits = r.decompose(cs.namespace(|| "r bits"))?;
+        let proof_l = proof.pow_mod_bits(cs.namespace(|| "pi^l"), &l_bits, &self.n)?;
+        let x_r = x.pow_mod_bits(cs.namespace(|| "x^r"), &r_bits, &self.n)?;
+        BigNat::assert_sums_congruent(
+            cs.namespace(|| "pi^l x^r == y"),
+            &[Term::Product(&proof_l, &x_r)],
+            &[Term::Single(y)],
+            &self.n,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsa::tests::*;
+    use crate::util::test_helpers::*;
+
+    /// The number of squarings of the tests.
+    const T: u64 = 1000;
+
+    fn modulus() -> BigInt {
+        test_key(KEY_1024, 3).0
+    }
+
+    fn input() -> BigInt {
+        BigInt::from(0x1234567890abcdefu64) << 900
+    }
+
+    #[test]
+    fn vdf_native() {
+        let n = modulus();
+        let x = input();
+        let (y, pi) = prove(&n, 1024, &x, T);
+        assert_eq!(y, x.modpow(&(BigInt::one() << T), &n));
+        let l = challenge(&n, 1024, &x, &y, T);
+        assert_eq!(pi, x.modpow(&((BigInt::one() << T) / &l), &n));
+        assert!(native_verify(&n, 1024, &x, &y, T, &pi));
+        assert!(!native_verify(&n, 1024, &x, &y, T + 1, &pi));
+        assert!(!native_verify(&n, 1024, &x, &(&y + 1), T, &pi));
+        assert_ne!(l, challenge(&(&n + 2), 1024, &x, &y, T));
+    }
+
+    /// Verifies the proof of the VDF for `x` with `T` squarings, with `tweak` added to the
+    /// output, in a circuit with `t` squarings, returning whether it is satisfied.
+    fn verifies(t: u64, tweak: u64) -> bool {
+        let n = modulus();
+        let x = input();
+        let (y, pi) = prove(&n, 1024, &x, T);
+        is_satisfied_by(|cs| {
+            let vdf = WesolowskiVdf::alloc(cs.namespace(|| "params"), || Ok(n), 1024, 64, t)?;
+            let x = vdf.alloc_element(cs.namespace(|| "x"), || Ok(x))?;
+            let y = vdf.alloc_element(cs.namespace(|| "y"), || Ok(y + tweak))?;
+            let pi = vdf.alloc_element(cs.namespace(|| "pi"), || Ok(pi))?;
+            vdf.verify(cs.namespace(|| "verify"), &x, &y, &pi)
+        })
+    }
+
+    #[test]
+    fn vdf_wesolowski() {
+        assert!(verifies(T, 0));
+    }
+
+    #[test]
+    fn vdf_wesolowski_wrong_output() {
+        assert!(!verifies(T, 1));
+    }
+
+    #[test]
+    fn vdf_wesolowski_wrong_squarings() {
+        assert!(!verifies(T + 1, 0));
+    }
+}
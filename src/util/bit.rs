@@ -110,6 +110,23 @@ impl<Scalar: PrimeField> Bitvector<Scalar> {
             .collect()
     }
 
+    /// Allocates the bits of `n_bytes` bytes, in order, each byte's most significant bit first.
+    pub fn alloc_bytes<CS: ConstraintSystem<Scalar>>(
+        mut cs: CS,
+        bytes: Option<&[u8]>,
+        n_bytes: usize,
+    ) -> Result<Self, SynthesisError> {
+        let bits = (0..(8 * n_bytes))
+            .map(|i| {
+                Bit::alloc(
+                    cs.namespace(|| format!("bit {}", i)),
+                    bytes.map(|bs| (bs[i / 8] >> (7 - i % 8)) & 1 == 1),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_bits(bits))
+    }
+
     pub fn from_bits(bs: Vec<Bit<Scalar>>) -> Self {
         let mut bits = Vec::new();
         let mut values = Some(Vec::new());
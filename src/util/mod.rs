@@ -4,6 +4,7 @@ pub mod gadget;
 pub mod lazy;
 pub mod num;
 pub mod scalar;
+pub mod value_cs;
 
 #[cfg(test)]
 #[macro_use]
@@ -7,6 +7,7 @@ use crate::BitAccess;
 use crate::OptionExt;
 use std::convert::From;
 
+#[derive(Clone)]
 pub struct Num<Scalar: PrimeField> {
     pub num: LinearCombination<Scalar>,
     pub value: Option<Scalar>,
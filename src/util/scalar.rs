@@ -1,7 +1,21 @@
 use ff::PrimeField;
 
+/// The scalar field of BLS12-381.
 #[derive(PrimeField)]
 #[PrimeFieldModulus = "52435875175126190479447740508185965837690552500527637822603658699938581184513"]
 #[PrimeFieldGenerator = "7"]
 #[PrimeFieldReprEndianness = "little"]
 pub struct Fr([u64; 4]);
+
+pub use bn254::Bn254Fr;
+
+mod bn254 {
+    use ff::PrimeField;
+
+    /// The scalar field of BN254.
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
+    #[PrimeFieldGenerator = "5"]
+    #[PrimeFieldReprEndianness = "little"]
+    pub struct Bn254Fr([u64; 4]);
+}
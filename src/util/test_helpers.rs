@@ -1,7 +1,13 @@
 pub use bellpepper_core::test_cs::TestConstraintSystem;
 pub use bellpepper_core::Circuit;
+use bellpepper_core::SynthesisError;
 pub use ff::PrimeField;
 
+use num_bigint::BigInt;
+
+use crate::util::scalar::Fr;
+use crate::util::value_cs::ValueConstraintSystem;
+
 macro_rules! circuit_tests {
     ($($name:ident: $value:expr,)*) => {
         $(
@@ -21,3 +27,41 @@ macro_rules! circuit_tests {
         )*
     }
 }
+
+/// A full-width scalar for a group of order `order`: the order minus a small constant.
+pub fn full_width_scalar(order: &BigInt) -> BigInt {
+    order - BigInt::from(0x1234567u64)
+}
+
+/// Runs a scalar multiplication circuit with a 256-bit scalar natively, asserting that it is
+/// satisfied, and reports its cost. Returns the number of constraints.
+pub fn report_full_width<C: Circuit<Fr>>(name: &str, circuit: C) -> usize {
+    let mut cs = ValueConstraintSystem::<Fr>::new();
+    circuit.synthesize(&mut cs).expect("synthesis failed");
+    assert_eq!(cs.which_is_unsatisfied(), None);
+    let n_constraints = cs.num_constraints();
+    println!(
+        "{}: {} constraints, {:.0} per scalar bit",
+        name,
+        n_constraints,
+        n_constraints as f64 / 256.0
+    );
+    n_constraints
+}
+
+/// Synthesizes `circuit` natively, asserting that the synthesis succeeds, and returns whether the
+/// constraints are satisfied.
+pub fn is_satisfied<C: Circuit<Fr>>(circuit: C) -> bool {
+    is_satisfied_by(|cs| circuit.synthesize(cs))
+}
+
+/// Runs `synthesize` on a `ValueConstraintSystem`, asserting that it succeeds, and returns whether
+/// the constraints are satisfied.
+pub fn is_satisfied_by<F>(synthesize: F) -> bool
+where
+    F: FnOnce(&mut ValueConstraintSystem<Fr>) -> Result<(), SynthesisError>,
+{
+    let mut cs = ValueConstraintSystem::<Fr>::new();
+    synthesize(&mut cs).expect("synthesis failed");
+    cs.is_satisfied()
+}
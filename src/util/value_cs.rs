@@ -0,0 +1,224 @@
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use ff::PrimeField;
+
+/// A constraint system which only computes values.
+///
+/// Synthesizing a gadget against this system runs all of its witness computations (so every
+/// `value` field of the produced gadgets is filled) without recording any constraints. Each
+/// constraint is instead checked against the assignment as soon as it is enforced, which makes
+/// this a cheap way to precompute witnesses, compute expected outputs, and check that a witness
+/// is satisfying without building a `TestConstraintSystem`.
+pub struct ValueConstraintSystem<Scalar: PrimeField> {
+    inputs: Vec<Scalar>,
+    aux: Vec<Scalar>,
+    namespace: Vec<String>,
+    num_constraints: usize,
+    unsatisfied: Option<String>,
+}
+
+impl<Scalar: PrimeField> ValueConstraintSystem<Scalar> {
+    pub fn new() -> Self {
+        Self {
+            inputs: vec![Scalar::ONE],
+            aux: Vec::new(),
+            namespace: Vec::new(),
+            num_constraints: 0,
+            unsatisfied: None,
+        }
+    }
+
+    /// Whether every constraint enforced so far is satisfied.
+    pub fn is_satisfied(&self) -> bool {
+        self.unsatisfied.is_none()
+    }
+
+    /// The path of the first unsatisfied constraint, if any.
+    pub fn which_is_unsatisfied(&self) -> Option<&str> {
+        self.unsatisfied.as_deref()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.num_constraints
+    }
+
+    /// The number of inputs, including the constant one.
+    pub fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.aux.len()
+    }
+
+    /// The values of the inputs, including the constant one.
+    pub fn inputs(&self) -> &[Scalar] {
+        &self.inputs
+    }
+
+    pub fn aux(&self) -> &[Scalar] {
+        &self.aux
+    }
+
+    /// The value of the variable.
+    pub fn get(&self, var: Variable) -> Scalar {
+        match var.get_unchecked() {
+            Index::Input(i) => self.inputs[i],
+            Index::Aux(i) => self.aux[i],
+        }
+    }
+
+    /// The value of the linear combination.
+    pub fn eval(&self, lc: &LinearCombination<Scalar>) -> Scalar {
+        lc.eval(&self.inputs, &self.aux)
+    }
+
+    fn path(&self, name: String) -> String {
+        let mut path = self.namespace.join("/");
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(&name);
+        path
+    }
+}
+
+impl<Scalar: PrimeField> Default for ValueConstraintSystem<Scalar> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ValueConstraintSystem<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux.push(f()?);
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inputs.push(f()?);
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.num_constraints += 1;
+        let a = self.eval(&a(LinearCombination::zero()));
+        let b = self.eval(&b(LinearCombination::zero()));
+        let c = self.eval(&c(LinearCombination::zero()));
+        if self.unsatisfied.is_none() && a * b != c {
+            self.unsatisfied = Some(self.path(annotation().into()));
+        }
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.namespace.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        self.namespace.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp::bignat::BigNat;
+    use crate::util::scalar::Fr;
+    use crate::util::test_helpers::*;
+    use num_bigint::BigInt;
+
+    /// Computes `a * b mod m` and exposes the remainder.
+    struct MultMod {
+        a: BigInt,
+        b: BigInt,
+        m: BigInt,
+        r: BigInt,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for MultMod {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(self.a.clone()), 32, 4)?;
+            let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(self.b.clone()), 32, 4)?;
+            let m = BigNat::alloc_from_nat(cs.namespace(|| "m"), || Ok(self.m.clone()), 32, 4)?;
+            let r = BigNat::alloc_from_nat(cs.namespace(|| "r"), || Ok(self.r.clone()), 32, 4)?;
+            let (_, ra) = a.mult_mod(cs.namespace(|| "mult_mod"), &b, &m)?;
+            ra.equal(cs.namespace(|| "check"), &r)?;
+            ra.inputize(cs.namespace(|| "out"))?;
+            Ok(())
+        }
+    }
+
+    fn mult_mod(r: usize) -> MultMod {
+        MultMod {
+            a: BigInt::from(123456789usize),
+            b: BigInt::from(987654321usize),
+            m: BigInt::from(1000000007usize),
+            r: BigInt::from(r),
+        }
+    }
+
+    #[test]
+    fn value_cs_matches_test_cs() {
+        let mut tcs = TestConstraintSystem::<Fr>::new();
+        mult_mod(259106859).synthesize(&mut tcs).unwrap();
+        let mut vcs = ValueConstraintSystem::<Fr>::new();
+        mult_mod(259106859).synthesize(&mut vcs).unwrap();
+        assert!(tcs.is_satisfied());
+        assert!(vcs.is_satisfied());
+        assert_eq!(tcs.num_constraints(), vcs.num_constraints());
+        assert_eq!(tcs.num_inputs(), vcs.num_inputs());
+        assert_eq!(vcs.inputs()[1], Fr::from(259106859));
+    }
+
+    #[test]
+    fn value_cs_finds_unsatisfied() {
+        let mut vcs = ValueConstraintSystem::<Fr>::new();
+        mult_mod(259106860).synthesize(&mut vcs).unwrap();
+        assert!(!vcs.is_satisfied());
+        assert_eq!(vcs.which_is_unsatisfied(), Some("check/equal 0"));
+    }
+
+    #[test]
+    fn value_cs_fills_values() {
+        let mut vcs = ValueConstraintSystem::<Fr>::new();
+        let a = BigNat::alloc_from_nat(
+            vcs.namespace(|| "a"),
+            || Ok(BigInt::from(1usize) << 100u32),
+            32,
+            4,
+        )
+        .unwrap();
+        let sq = a.mult(vcs.namespace(|| "square"), &a).unwrap();
+        assert_eq!(sq.value, Some(BigInt::from(1usize) << 200u32));
+        assert!(vcs.is_satisfied());
+    }
+}